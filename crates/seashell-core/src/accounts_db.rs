@@ -2,11 +2,13 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use agave_feature_set::FeatureSet;
-use agave_syscalls::create_program_runtime_environment_v1;
+use indexmap::IndexMap;
 use parking_lot::RwLock;
 use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
 use solana_compute_budget::compute_budget::ComputeBudget;
 use solana_instruction::Instruction;
+use solana_loader_v3_interface::get_program_data_address;
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
 use solana_program_runtime::loaded_programs::{
     LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch,
 };
@@ -14,6 +16,9 @@ use solana_program_runtime::sysvar_cache::SysvarCache;
 use solana_pubkey::Pubkey;
 use solana_transaction_context::TransactionAccount;
 
+use crate::error::SeashellError;
+use crate::metrics::{self, Metrics};
+use crate::patch::{apply_patches, FieldPatch};
 use crate::scenario::Scenario;
 use crate::sysvar::{SysvarInstructions, Sysvars};
 
@@ -21,31 +26,129 @@ pub fn mock_account_shared_data(pubkey: Pubkey) -> AccountSharedData {
     AccountSharedData::new(0, 0, &pubkey)
 }
 
+/// The deployment slot and ELF held by the data of a programdata account.
+pub(crate) fn parse_programdata(data: &[u8]) -> Option<(u64, &[u8])> {
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    match bincode::deserialize(data.get(..metadata_len)?) {
+        Ok(UpgradeableLoaderState::ProgramData { slot, .. }) => Some((slot, &data[metadata_len..])),
+        _ => None,
+    }
+}
+
 #[derive(Default)]
 pub struct AccountsDb {
     pub scenario: Scenario,
     pub accounts: RwLock<HashMap<Pubkey, AccountSharedData>>,
-    pub programs: ProgramCacheForTxBatch,
+    pub programs: RwLock<ProgramCacheForTxBatch>,
     pub sysvars: Sysvars,
+    pub metrics: Metrics,
+    /// Addresses whose reads and writes are redirected to another account, see
+    /// `Seashell::override_address`.
+    pub address_overrides: RwLock<HashMap<Pubkey, Pubkey>>,
+}
+
+/// The accounts, programs and sysvars of an `AccountsDb` at one point in time. Account data and
+/// program entries are shared with the live state until either side changes them.
+#[derive(Clone)]
+pub struct AccountsDbCheckpoint {
+    pub(crate) accounts: HashMap<Pubkey, AccountSharedData>,
+    scenario_writes: HashMap<Pubkey, AccountSharedData>,
+    programs: ProgramCacheForTxBatch,
+    pub(crate) sysvars: Sysvars,
+}
+
+impl AccountsDbCheckpoint {
+    /// The accounts written locally as of the checkpoint, see [`AccountsDb::written_accounts`].
+    pub(crate) fn written_accounts(&self) -> HashMap<Pubkey, AccountSharedData> {
+        let mut accounts = self.accounts.clone();
+        accounts.extend(self.scenario_writes.clone());
+        accounts
+    }
 }
 
 impl AccountsDb {
+    /// An independent copy sharing account data and program entries until either side writes.
+    pub fn fork(&self) -> AccountsDb {
+        AccountsDb {
+            scenario: self.scenario.fork(),
+            accounts: RwLock::new(self.accounts.read().clone()),
+            programs: RwLock::new(self.programs.read().clone()),
+            sysvars: self.sysvars.clone(),
+            metrics: self.metrics.clone(),
+            address_overrides: RwLock::new(self.address_overrides.read().clone()),
+        }
+    }
+
+    pub fn checkpoint(&self) -> AccountsDbCheckpoint {
+        AccountsDbCheckpoint {
+            accounts: self.accounts.read().clone(),
+            scenario_writes: self.scenario.writes(),
+            programs: self.programs.read().clone(),
+            sysvars: self.sysvars.clone(),
+        }
+    }
+
+    /// Puts back the state of `checkpoint`. Writes over scenario accounts are undone, but
+    /// accounts fetched since the checkpoint stay cached.
+    pub fn restore(&mut self, checkpoint: &AccountsDbCheckpoint) {
+        *self.accounts.get_mut() = checkpoint.accounts.clone();
+        self.scenario.set_writes(checkpoint.scenario_writes.clone());
+        *self.programs.get_mut() = checkpoint.programs.clone();
+        self.sysvars = checkpoint.sysvars.clone();
+    }
+
     pub fn clear_non_program_accounts(&self) {
-        self.accounts.write().retain(|_, account| account.executable());
+        self.accounts
+            .write()
+            .retain(|_, account| account.executable());
+        self.scenario.set_writes(HashMap::new());
+    }
+
+    /// Drops every account not owned by a loader and resets the sysvars, keeping programs, their
+    /// programdata and the program cache. The scenario's accounts go back to their recorded
+    /// state.
+    pub fn reset_to_genesis(&mut self) {
+        let loaders = [
+            solana_sdk_ids::native_loader::id(),
+            solana_sdk_ids::bpf_loader::id(),
+            solana_sdk_ids::bpf_loader_deprecated::id(),
+            solana_sdk_ids::bpf_loader_upgradeable::id(),
+            solana_sdk_ids::loader_v4::id(),
+        ];
+        self.accounts
+            .get_mut()
+            .retain(|_, account| account.executable() || loaders.contains(account.owner()));
+        self.address_overrides.get_mut().clear();
+        self.scenario.set_writes(HashMap::new());
+        self.sysvars = Sysvars::default();
     }
 
     pub fn warp(&self, slot: u64, timestamp: i64) {
         self.sysvars.warp(slot, timestamp);
     }
 
+    pub fn warp_to_slot(&self, slot: u64) {
+        self.sysvars.warp_to_slot(slot);
+    }
+
+    /// The account that stands in for `pubkey`: its override if one is registered, else itself.
+    pub fn resolve_address(&self, pubkey: &Pubkey) -> Pubkey {
+        self.address_overrides
+            .read()
+            .get(pubkey)
+            .copied()
+            .unwrap_or(*pubkey)
+    }
+
     pub fn account_maybe(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        let pubkey = &self.resolve_address(pubkey);
         if self.sysvars.is_sysvar(pubkey) {
             return Some(self.sysvars.get(pubkey));
         }
 
         // 1. Check scenario overrides
         if let Some(account) = self.scenario.get(pubkey) {
-            return Some(account.clone());
+            return Some(account);
         }
 
         // 2. Check regular accounts
@@ -56,12 +159,53 @@ impl AccountsDb {
         None
     }
 
+    /// The account from the local cache or the scenario, else fetched from the RPC if one is
+    /// configured. Fails with `SeashellError::AccountNotFound` where `account_must` panics.
+    pub fn try_account(&self, pubkey: &Pubkey) -> Result<AccountSharedData, SeashellError> {
+        let pubkey = &self.resolve_address(pubkey);
+        self.account_maybe(pubkey)
+            .or_else(|| {
+                self.scenario
+                    .rpc_enabled()
+                    .then(|| self.fetch_from_rpc(pubkey))
+                    .flatten()
+            })
+            .ok_or(SeashellError::AccountNotFound(*pubkey))
+    }
+
     pub fn account_must(&self, pubkey: &Pubkey) -> AccountSharedData {
-        self.account_maybe(pubkey).unwrap_or_else(|| {
-            self.scenario
-                .try_fetch_from_rpc(pubkey)
-                .expect("Account not found")
-        })
+        let pubkey = &self.resolve_address(pubkey);
+        self.account_maybe(pubkey)
+            .unwrap_or_else(|| self.fetch_from_rpc(pubkey).expect("Account not found"))
+    }
+
+    /// `Scenario::try_fetch_from_rpc`, recording the fetch in `metrics`.
+    pub fn fetch_from_rpc(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        let start = std::time::Instant::now();
+        let account = self.scenario.try_fetch_from_rpc(pubkey);
+        self.metrics
+            .observe(metrics::RPC_FETCH_SECONDS, start.elapsed().as_secs_f64());
+        self.metrics.increment(metrics::RPC_FETCHES, 1);
+        if account.is_none() {
+            self.metrics.increment(metrics::RPC_FETCH_FAILURES, 1);
+        }
+        account
+    }
+
+    /// `Scenario::try_fetch_many_from_rpc`, recording the fetches in `metrics`.
+    pub fn fetch_many_from_rpc(&self, pubkeys: &[Pubkey]) -> HashMap<Pubkey, AccountSharedData> {
+        let start = std::time::Instant::now();
+        let accounts = self.scenario.try_fetch_many_from_rpc(pubkeys);
+        self.metrics
+            .observe(metrics::RPC_FETCH_SECONDS, start.elapsed().as_secs_f64());
+        self.metrics
+            .increment(metrics::RPC_FETCHES, pubkeys.len() as u64);
+        let failures = pubkeys.len().saturating_sub(accounts.len());
+        if failures > 0 {
+            self.metrics
+                .increment(metrics::RPC_FETCH_FAILURES, failures as u64);
+        }
+        accounts
     }
 
     /// Panics if unable to find any account.
@@ -70,42 +214,131 @@ impl AccountsDb {
         allow_uninitialized_accounts: bool,
         instruction: &Instruction,
     ) -> Vec<TransactionAccount> {
-        // always insert the program_id of the instruction as the first account.
-        let mut accounts =
-            vec![(instruction.program_id, self.account_must(&instruction.program_id))];
-        instruction.accounts.iter().for_each(|meta| {
-            if meta.pubkey == solana_sdk_ids::sysvar::instructions::id() {
-                // sysvar instructions needs to be handled specially
-                let account = SysvarInstructions::construct_instructions_account(instruction);
-                accounts.push((meta.pubkey, account));
-                return;
+        self.accounts_for_instruction_with_overlay(
+            allow_uninitialized_accounts,
+            instruction,
+            &IndexMap::new(),
+            &[],
+        )
+    }
+
+    /// Like `accounts_for_instruction`, but fails with `SeashellError::AccountNotFound` instead of
+    /// panicking.
+    pub fn try_accounts_for_instruction(
+        &self,
+        allow_uninitialized_accounts: bool,
+        instruction: &Instruction,
+    ) -> Result<Vec<TransactionAccount>, SeashellError> {
+        self.try_accounts_for_instruction_with_overlay(
+            allow_uninitialized_accounts,
+            instruction,
+            &IndexMap::new(),
+            &[],
+        )
+    }
+
+    /// Like `accounts_for_instruction`, but accounts in `overlay` take precedence over the
+    /// `AccountsDb` and are never looked up in it. The instructions sysvar lists the top-level
+    /// instructions `processed` earlier in the chain before `instruction`.
+    ///
+    /// `AccountSharedData` keeps its data behind an `Arc`, so the accounts returned here share
+    /// their data with the `AccountsDb`; it is only copied if the program writes to it. Each
+    /// distinct key is looked up once, however many times the instruction references it.
+    pub fn accounts_for_instruction_with_overlay(
+        &self,
+        allow_uninitialized_accounts: bool,
+        instruction: &Instruction,
+        overlay: &IndexMap<Pubkey, AccountSharedData>,
+        processed: &[Instruction],
+    ) -> Vec<TransactionAccount> {
+        self.try_accounts_for_instruction_with_overlay(
+            allow_uninitialized_accounts,
+            instruction,
+            overlay,
+            processed,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `accounts_for_instruction_with_overlay`, but fails with
+    /// `SeashellError::AccountNotFound` on the first account that is neither local, in the
+    /// scenario nor on the RPC, instead of panicking.
+    pub fn try_accounts_for_instruction_with_overlay(
+        &self,
+        allow_uninitialized_accounts: bool,
+        instruction: &Instruction,
+        overlay: &IndexMap<Pubkey, AccountSharedData>,
+        processed: &[Instruction],
+    ) -> Result<Vec<TransactionAccount>, SeashellError> {
+        let mut loaded: HashMap<Pubkey, AccountSharedData> =
+            HashMap::with_capacity(instruction.accounts.len() + 1);
+        if self.scenario.rpc_enabled() {
+            // Fetch every missing account in one round trip instead of one per account.
+            let mut missing = Vec::new();
+            let pubkeys = std::iter::once(&instruction.program_id)
+                .chain(instruction.accounts.iter().map(|meta| &meta.pubkey));
+            for pubkey in pubkeys {
+                if overlay.contains_key(pubkey)
+                    || *pubkey == solana_sdk_ids::sysvar::instructions::id()
+                    || loaded.contains_key(pubkey)
+                    || missing.contains(pubkey)
+                {
+                    continue;
+                }
+                match self.account_maybe(pubkey) {
+                    Some(account) => {
+                        loaded.insert(*pubkey, account);
+                    }
+                    // Overridden addresses are fetched one by one under their replacement.
+                    None if self.resolve_address(pubkey) == *pubkey => missing.push(*pubkey),
+                    None => {}
+                }
             }
+            if !missing.is_empty() {
+                loaded.extend(self.fetch_many_from_rpc(&missing));
+            }
+        }
+        let mut load = |pubkey: Pubkey, load_account: &dyn Fn() -> Option<AccountSharedData>| {
+            let account = match overlay.get(&pubkey).or_else(|| loaded.get(&pubkey)) {
+                Some(account) => account.clone(),
+                None => {
+                    let account = load_account().ok_or(SeashellError::AccountNotFound(pubkey))?;
+                    loaded.insert(pubkey, account.clone());
+                    account
+                }
+            };
+            Ok::<_, SeashellError>((pubkey, account))
+        };
+        let fetch = |pubkey: &Pubkey| self.try_account(pubkey).ok();
 
+        // always insert the program_id of the instruction as the first account.
+        let mut accounts = Vec::with_capacity(instruction.accounts.len() + 1);
+        accounts.push(load(instruction.program_id, &|| fetch(&instruction.program_id))?);
+        for meta in &instruction.accounts {
             let pubkey = meta.pubkey;
-            // first, check local cache
-            if let Some(account) = self.account_maybe(&pubkey) {
+            if pubkey == solana_sdk_ids::sysvar::instructions::id() {
+                // sysvar instructions needs to be handled specially
+                let account =
+                    SysvarInstructions::construct_instructions_account(processed, instruction);
                 accounts.push((pubkey, account));
-                return;
+                continue;
             }
 
-            // if account is not present in local cache, attempt to fetch from rpc
-            if self.scenario.rpc_enabled() {
-                if let Some(account) = self.scenario.try_fetch_from_rpc(&pubkey) {
-                    accounts.push((pubkey, account));
-                    return;
+            accounts.push(load(pubkey, &|| {
+                // check the local cache, then the rpc
+                if let Some(account) = fetch(&pubkey) {
+                    return Some(account);
                 }
-            }
-
-            // finally, if still not found, handle according to allow_uninitialized_accounts
-            if allow_uninitialized_accounts {
-                log::debug!("Creating uninitialized account for {pubkey}");
-                accounts.push((pubkey, AccountSharedData::default()));
-                return;
-            }
 
-            panic!("Account not found for {pubkey}");
-        });
-        accounts
+                // if still not found, handle according to allow_uninitialized_accounts
+                if allow_uninitialized_accounts {
+                    log::debug!("Creating uninitialized account for {pubkey}");
+                    return Some(AccountSharedData::default());
+                }
+                None
+            })?);
+        }
+        Ok(accounts)
     }
 
     pub fn sysvars_for_instruction(&self, accounts: &[TransactionAccount]) -> SysvarCache {
@@ -118,10 +351,10 @@ impl AccountsDb {
             } else {
                 // If not, check our AccountsDb (which will always contain the sysvar as a fallback)
                 // Optionality here is to avoid supporting Fees sysvar, which is deprecated but expected by SysvarCache
-                let account = self.account_maybe(sysvar);
-                let data = account.map(|a| a.data().to_owned()).unwrap_or_default();
-
-                set_sysvar(&data);
+                match self.account_maybe(sysvar) {
+                    Some(account) => set_sysvar(account.data()),
+                    None => set_sysvar(&[]),
+                }
             }
         });
 
@@ -135,13 +368,47 @@ impl AccountsDb {
     }
 
     pub fn set_account(&self, pubkey: Pubkey, account: AccountSharedData) {
+        let pubkey = self.resolve_address(&pubkey);
         if self.sysvars.is_sysvar(&pubkey) {
             self.sysvars.set(&pubkey, account)
+        } else if self.scenario.get(&pubkey).is_some() {
+            // Reads prefer the scenario, so the write has to land there to be seen.
+            self.scenario.write(pubkey, account);
         } else {
             self.accounts.write().insert(pubkey, account);
         }
     }
 
+    /// Every account written locally, including writes over accounts the scenario supplies.
+    pub(crate) fn written_accounts(&self) -> HashMap<Pubkey, AccountSharedData> {
+        let mut accounts = self.accounts.read().clone();
+        accounts.extend(self.scenario.writes());
+        accounts
+    }
+
+    /// Applies `patches` to the account's data. Accounts supplied by the scenario are patched in
+    /// the scenario, so the change is persisted along with it.
+    pub fn patch_account(
+        &mut self,
+        pubkey: &Pubkey,
+        patches: &[FieldPatch],
+    ) -> Result<(), SeashellError> {
+        let mut account = self.account_must(&self.resolve_address(pubkey));
+        apply_patches(account.data_as_mut_slice(), patches)?;
+        self.replace_account(*pubkey, account);
+        Ok(())
+    }
+
+    /// Writes `account` over an existing one, in the scenario if the scenario supplies it.
+    pub fn replace_account(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        let pubkey = self.resolve_address(&pubkey);
+        if self.scenario.get(&pubkey).is_some() {
+            self.scenario.insert(pubkey, account);
+        } else {
+            self.set_account(pubkey, account);
+        }
+    }
+
     pub fn set_account_mock(&mut self, pubkey: Pubkey) {
         let account = mock_account_shared_data(pubkey);
         self.set_account(pubkey, account);
@@ -158,6 +425,7 @@ impl AccountsDb {
                 let builtin_program =
                     ProgramCacheEntry::new_builtin(0, builtin.name.len(), builtin.entrypoint);
                 self.programs
+                    .get_mut()
                     .replenish(builtin.program_id, Arc::new(builtin_program));
                 let mut account_shared_data =
                     AccountSharedData::new(1, 0, &solana_sdk_ids::native_loader::id());
@@ -181,15 +449,8 @@ impl AccountsDb {
         let mut program_account_shared_data =
             AccountSharedData::new(minimum_balance_for_rent_exemption, account_size, &loader);
         program_account_shared_data.set_executable(true);
-        let program_runtime_environment = Arc::new(
-            create_program_runtime_environment_v1(
-                &feature_set.runtime_features(),
-                &compute_budget.to_budget(),
-                false,
-                false,
-            )
-            .expect("Failed to create program runtime environment"),
-        );
+        let program_runtime_environment =
+            crate::agave::program_runtime_environment(feature_set, compute_budget);
         let program_cache_entry = ProgramCacheEntry::new(
             &loader,
             program_runtime_environment,
@@ -202,6 +463,134 @@ impl AccountsDb {
         .expect(&format!("Failed to load program {program_id} from bytes"));
         self.set_account(program_id, program_account_shared_data);
         self.programs
+            .get_mut()
             .replenish(program_id, Arc::new(program_cache_entry));
     }
+
+    /// Sets the environment the loaders verify newly deployed programs against.
+    pub fn configure_program_runtime_environment(
+        &mut self,
+        feature_set: &FeatureSet,
+        compute_budget: &ComputeBudget,
+    ) {
+        crate::agave::set_program_runtime_environment(
+            self.programs.get_mut(),
+            crate::agave::program_runtime_environment(feature_set, compute_budget),
+        );
+    }
+
+    /// Deploys `bytes` under `bpf_loader_upgradeable` as of the current slot, creating the program
+    /// account and its programdata account at the canonical address.
+    pub fn load_upgradeable_program_from_bytes(
+        &mut self,
+        program_id: Pubkey,
+        bytes: &[u8],
+        upgrade_authority: Option<Pubkey>,
+        feature_set: &FeatureSet,
+        compute_budget: &ComputeBudget,
+    ) {
+        let loader = solana_sdk_ids::bpf_loader_upgradeable::id();
+        let current_slot = self.sysvars.clock().slot;
+        let rent = self.sysvars.rent();
+        let programdata_address = get_program_data_address(&program_id);
+
+        let program_state = UpgradeableLoaderState::Program { programdata_address };
+        let program_len = UpgradeableLoaderState::size_of_program();
+        let mut program_account =
+            AccountSharedData::new(rent.minimum_balance(program_len), program_len, &loader);
+        bincode::serialize_into(program_account.data_as_mut_slice(), &program_state)
+            .expect("Failed to serialize program account");
+        program_account.set_executable(true);
+
+        let programdata_state = UpgradeableLoaderState::ProgramData {
+            slot: current_slot,
+            upgrade_authority_address: upgrade_authority,
+        };
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        let programdata_len = metadata_len + bytes.len();
+        let mut programdata_account =
+            AccountSharedData::new(rent.minimum_balance(programdata_len), programdata_len, &loader);
+        bincode::serialize_into(programdata_account.data_as_mut_slice(), &programdata_state)
+            .expect("Failed to serialize programdata account");
+        programdata_account.data_as_mut_slice()[metadata_len..].copy_from_slice(bytes);
+
+        self.load_upgradeable_program_from_accounts(
+            program_id,
+            program_account,
+            programdata_address,
+            programdata_account,
+            feature_set,
+            compute_budget,
+        )
+        .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Loads an upgradeable program from existing program and programdata accounts, e.g. ones
+    /// cloned from a cluster. The ELF is everything after the programdata metadata.
+    pub fn load_upgradeable_program_from_accounts(
+        &mut self,
+        program_id: Pubkey,
+        program_account: AccountSharedData,
+        programdata_address: Pubkey,
+        programdata_account: AccountSharedData,
+        feature_set: &FeatureSet,
+        compute_budget: &ComputeBudget,
+    ) -> Result<(), SeashellError> {
+        let loader = solana_sdk_ids::bpf_loader_upgradeable::id();
+        let Some((deployment_slot, elf)) = parse_programdata(programdata_account.data()) else {
+            return Err(SeashellError::Custom(format!(
+                "Account {programdata_address} is not a programdata account"
+            )));
+        };
+        let current_slot = self.sysvars.clock().slot;
+
+        let program_cache_entry = ProgramCacheEntry::new(
+            &loader,
+            crate::agave::program_runtime_environment(feature_set, compute_budget),
+            deployment_slot,
+            current_slot.max(deployment_slot),
+            elf,
+            program_account.data().len() + programdata_account.data().len(),
+            &mut LoadProgramMetrics::default(),
+        )
+        .map_err(|err| {
+            SeashellError::Custom(format!("Failed to load program {program_id}: {err}"))
+        })?;
+        self.set_account(program_id, program_account);
+        self.set_account(programdata_address, programdata_account);
+        self.programs
+            .get_mut()
+            .replenish(program_id, Arc::new(program_cache_entry));
+        Ok(())
+    }
+
+    /// Creates a `bpf_loader_upgradeable` buffer holding `bytes`, ready to deploy or upgrade from.
+    pub fn set_program_buffer(&self, buffer: Pubkey, authority: Option<Pubkey>, bytes: &[u8]) {
+        let loader = solana_sdk_ids::bpf_loader_upgradeable::id();
+        let metadata_len = UpgradeableLoaderState::size_of_buffer_metadata();
+        let buffer_len = metadata_len + bytes.len();
+        let mut buffer_account = AccountSharedData::new(
+            self.sysvars.rent().minimum_balance(buffer_len),
+            buffer_len,
+            &loader,
+        );
+        bincode::serialize_into(
+            buffer_account.data_as_mut_slice(),
+            &UpgradeableLoaderState::Buffer { authority_address: authority },
+        )
+        .expect("Failed to serialize buffer account");
+        buffer_account.data_as_mut_slice()[metadata_len..].copy_from_slice(bytes);
+        self.set_account(buffer, buffer_account);
+    }
+
+    /// Makes programs deployed, upgraded or closed by an instruction visible to later ones.
+    pub fn commit_programs(
+        &self,
+        programs: impl IntoIterator<Item = (Pubkey, Arc<ProgramCacheEntry>)>,
+    ) {
+        let mut cache = self.programs.write();
+        for (program_id, entry) in programs {
+            cache.replenish(program_id, entry);
+        }
+    }
 }
@@ -0,0 +1,320 @@
+//! The points where seashell talks to agave's transaction context, invoke context callback and
+//! program runtime environment, kept in one place so upgrading agave touches as little of the
+//! crate as possible. Only the agave release in the workspace manifest is supported; there is no
+//! backend per release.
+
+use std::cell::Cell;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use agave_feature_set::FeatureSet;
+use agave_syscalls::create_program_runtime_environment_v1;
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_precompile_error::PrecompileError;
+use solana_program_runtime::loaded_programs::{ProgramCacheForTxBatch, ProgramRuntimeEnvironment};
+use solana_program_runtime::solana_sbpf::program::{BuiltinFunction, BuiltinProgram};
+use solana_program_runtime::solana_sbpf::vm::{get_runtime_environment_key, ContextObject, EbpfVm};
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_svm_callback::InvokeContextCallback;
+use solana_svm_timings::ExecuteTimings;
+use solana_transaction_context::{
+    IndexOfAccount, InstructionAccount, TransactionAccount, TransactionContext,
+};
+
+use crate::compile::{compile_accounts_for_instruction, INSTRUCTION_PROGRAM_ID_INDEX};
+use crate::trace::{ExecutionTimings, TracedInstruction};
+use crate::vote::EpochStakes;
+
+pub(crate) struct SeashellInvokeContextCallback<'a> {
+    pub feature_set: &'a FeatureSet,
+    pub epoch_stakes: &'a EpochStakes,
+}
+
+impl InvokeContextCallback for SeashellInvokeContextCallback<'_> {
+    fn get_epoch_stake(&self) -> u64 {
+        self.epoch_stakes.total()
+    }
+
+    fn get_epoch_stake_for_vote_account(&self, vote_address: &Pubkey) -> u64 {
+        self.epoch_stakes.get(vote_address)
+    }
+
+    fn is_precompile(&self, program_id: &Pubkey) -> bool {
+        agave_precompiles::is_precompile(program_id, |feature| self.feature_set.is_active(feature))
+    }
+
+    fn process_precompile(
+        &self,
+        program_id: &Pubkey,
+        data: &[u8],
+        instruction_datas: Vec<&[u8]>,
+    ) -> Result<(), PrecompileError> {
+        if let Some(precompile) = agave_precompiles::get_precompile(program_id, |feature_id| {
+            self.feature_set.is_active(feature_id)
+        }) {
+            precompile.verify(data, &instruction_datas, self.feature_set)
+        } else {
+            Err(PrecompileError::InvalidPublicKey)
+        }
+    }
+}
+
+pub(crate) fn new_transaction_context(
+    transaction_accounts: Vec<TransactionAccount>,
+    rent: Rent,
+    compute_budget: &ComputeBudget,
+) -> TransactionContext {
+    TransactionContext::new(
+        transaction_accounts,
+        rent,
+        compute_budget.max_instruction_stack_depth,
+        compute_budget.max_instruction_trace_length,
+    )
+}
+
+/// Configures `ixn` as the next top-level instruction, with its program id at transaction index 0.
+pub(crate) fn configure_instruction(
+    transaction_context: &mut TransactionContext,
+    ixn: &Instruction,
+) {
+    let instruction_accounts = compile_accounts_for_instruction(ixn);
+
+    let mut dedup_map = vec![u8::MAX; solana_transaction_context::MAX_ACCOUNTS_PER_TRANSACTION];
+    for (idx, account) in instruction_accounts.iter().enumerate() {
+        let index_in_instruction = dedup_map
+            .get_mut(account.index_in_transaction as usize)
+            .unwrap();
+        if *index_in_instruction == u8::MAX {
+            *index_in_instruction = idx as u8;
+        }
+    }
+
+    transaction_context
+        .configure_next_instruction(
+            INSTRUCTION_PROGRAM_ID_INDEX as IndexOfAccount,
+            instruction_accounts,
+            dedup_map,
+            &ixn.data,
+        )
+        .expect("Failed to configure instruction");
+}
+
+/// Records `processed` in the instruction trace as top-level instructions that already ran, so
+/// `sol_get_processed_sibling_instruction` returns them. Every account they reference must be in
+/// the transaction context.
+pub(crate) fn record_processed_instructions(
+    transaction_context: &mut TransactionContext,
+    processed: &[Instruction],
+) {
+    for ixn in processed {
+        let index_of = |pubkey: &Pubkey| {
+            transaction_context
+                .find_index_of_account(pubkey)
+                .expect("Processed instruction accounts are in the transaction context")
+        };
+        let program_index = index_of(&ixn.program_id);
+        let mut dedup_map = vec![u8::MAX; solana_transaction_context::MAX_ACCOUNTS_PER_TRANSACTION];
+        let instruction_accounts = ixn
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(idx, meta)| {
+                let index_in_transaction = index_of(&meta.pubkey);
+                let index_in_instruction = &mut dedup_map[index_in_transaction as usize];
+                if *index_in_instruction == u8::MAX {
+                    *index_in_instruction = idx as u8;
+                }
+                InstructionAccount::new(index_in_transaction, meta.is_signer, meta.is_writable)
+            })
+            .collect();
+
+        transaction_context
+            .configure_next_instruction(program_index, instruction_accounts, dedup_map, &ixn.data)
+            .expect("Failed to configure processed instruction");
+        transaction_context
+            .push()
+            .expect("Failed to record processed instruction");
+        transaction_context
+            .pop()
+            .expect("Failed to record processed instruction");
+    }
+}
+
+pub(crate) fn program_runtime_environment(
+    feature_set: &FeatureSet,
+    compute_budget: &ComputeBudget,
+) -> ProgramRuntimeEnvironment {
+    let environment = create_program_runtime_environment_v1(
+        &feature_set.runtime_features(),
+        &compute_budget.to_budget(),
+        false,
+        false,
+    )
+    .expect("Failed to create program runtime environment");
+    Arc::new(interruptible_environment(&environment))
+}
+
+/// The context object of the program runtime environment.
+type Context = <ProgramRuntimeEnvironment as Environment>::Context;
+
+trait Environment {
+    type Context: ContextObject;
+}
+
+impl<C: ContextObject> Environment for Arc<BuiltinProgram<C>> {
+    type Context = C;
+}
+
+/// More syscalls than any feature set registers.
+const MAX_SYSCALLS: usize = 64;
+
+/// The syscalls behind [`interruptible`], by index.
+static SYSCALLS: [OnceLock<BuiltinFunction<Context>>; MAX_SYSCALLS] =
+    [const { OnceLock::new() }; MAX_SYSCALLS];
+
+thread_local! {
+    /// When the instruction running on this thread is stopped, see [`with_deadline`].
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    /// Whether a syscall was interrupted since the deadline was set.
+    static INTERRUPTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with programs stopped at their first syscall after `deadline`: the compute meter is
+/// exhausted, so the syscall fails and the VM aborts. Also returns whether that happened.
+/// Programs that make no syscalls are bounded by their compute budget alone.
+pub(crate) fn with_deadline<T>(deadline: Instant, f: impl FnOnce() -> T) -> (T, bool) {
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            DEADLINE.set(None);
+            INTERRUPTED.set(false);
+        }
+    }
+
+    DEADLINE.set(Some(deadline));
+    INTERRUPTED.set(false);
+    let _reset = Reset;
+    let output = f();
+    (output, INTERRUPTED.get())
+}
+
+/// `environment` with every syscall called through [`interruptible`].
+fn interruptible_environment(environment: &BuiltinProgram<Context>) -> BuiltinProgram<Context> {
+    static REGISTER: Mutex<()> = Mutex::new(());
+    let _guard = REGISTER.lock().unwrap();
+
+    let mut interruptible_environment =
+        BuiltinProgram::new_loader(environment.get_config().clone());
+    for (_, (name, syscall)) in environment.get_function_registry().iter() {
+        let index = SYSCALLS
+            .iter()
+            .position(|slot| {
+                slot.get()
+                    .is_none_or(|recorded| std::ptr::fn_addr_eq(*recorded, syscall))
+            })
+            .expect("More syscalls than MAX_SYSCALLS");
+        SYSCALLS[index].get_or_init(|| syscall);
+        let name = std::str::from_utf8(name).expect("Syscall names are UTF-8");
+        interruptible_environment
+            .register_function(name, INTERRUPTIBLE[index])
+            .expect("Failed to register syscall");
+    }
+    interruptible_environment
+}
+
+/// Calls the syscall at `INDEX`, exhausting the compute meter first once the deadline passed.
+fn interruptible<const INDEX: usize>(
+    vm: *mut EbpfVm<Context>,
+    arg_a: u64,
+    arg_b: u64,
+    arg_c: u64,
+    arg_d: u64,
+    arg_e: u64,
+) {
+    if DEADLINE
+        .get()
+        .is_some_and(|deadline| Instant::now() >= deadline)
+    {
+        // SAFETY: `vm` is offset by the runtime environment key, as syscalls receive it.
+        let vm = unsafe {
+            &mut *vm
+                .cast::<u64>()
+                .offset(-(get_runtime_environment_key() as isize))
+                .cast::<EbpfVm<Context>>()
+        };
+        let remaining = vm.context_object_pointer.get_remaining();
+        vm.context_object_pointer.consume(remaining);
+        INTERRUPTED.set(true);
+    }
+    let syscall = SYSCALLS[INDEX]
+        .get()
+        .expect("Syscalls are recorded before they're called");
+    syscall(vm, arg_a, arg_b, arg_c, arg_d, arg_e)
+}
+
+macro_rules! interruptible {
+    ($($index:literal)*) => {
+        [$(interruptible::<$index> as BuiltinFunction<Context>),*]
+    };
+}
+
+const INTERRUPTIBLE: [BuiltinFunction<Context>; MAX_SYSCALLS] = interruptible!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32 33
+    34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63
+);
+
+pub(crate) fn set_program_runtime_environment(
+    programs: &mut ProgramCacheForTxBatch,
+    environment: ProgramRuntimeEnvironment,
+) {
+    programs.environments.program_runtime_v1 = environment;
+}
+
+/// Reads every instruction the runtime recorded from `start` in the trace on, in execution order.
+pub(crate) fn instruction_trace(
+    transaction_context: &TransactionContext,
+    start: usize,
+) -> Vec<TracedInstruction> {
+    (start..transaction_context.get_instruction_trace_length())
+        .filter_map(|index_in_trace| {
+            let instruction_context = transaction_context
+                .get_instruction_context_at_index_in_trace(index_in_trace)
+                .ok()?;
+            let accounts = (0..instruction_context.get_number_of_instruction_accounts())
+                .filter_map(|index| {
+                    Some(AccountMeta {
+                        pubkey: *instruction_context
+                            .get_key_of_instruction_account(index)
+                            .ok()?,
+                        is_signer: instruction_context
+                            .is_instruction_account_signer(index)
+                            .ok()?,
+                        is_writable: instruction_context
+                            .is_instruction_account_writable(index)
+                            .ok()?,
+                    })
+                })
+                .collect();
+            Some(TracedInstruction {
+                program_id: *instruction_context.get_program_key().ok()?,
+                accounts,
+                data: instruction_context.get_instruction_data().to_vec(),
+                stack_height: instruction_context.get_stack_height(),
+            })
+        })
+        .collect()
+}
+
+/// Copies the runtime's timing details into seashell's version-independent struct.
+pub(crate) fn execution_timings(timings: &ExecuteTimings, total_us: u64) -> ExecutionTimings {
+    ExecutionTimings {
+        load_accounts_us: 0,
+        serialize_us: timings.details.serialize_us,
+        create_vm_us: timings.details.create_vm_us,
+        execute_us: timings.details.execute_us,
+        deserialize_us: timings.details.deserialize_us,
+        total_us,
+    }
+}
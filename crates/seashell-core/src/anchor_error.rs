@@ -0,0 +1,172 @@
+//! Names the custom errors and decodes the events of programs with an IDL registered through
+//! [`Seashell::load_idl`], so tests read `InsufficientCollateral` and `{"amount": 5}` rather
+//! than `Custom(6003)` and base64:
+//!
+//! ```ignore
+//! let result = seashell.process_instruction(ixn);
+//! assert_eq!(result.anchor_error.unwrap().name, "InsufficientCollateral");
+//! assert_eq!(result.decoded_events[0].fields["amount"], 5);
+//! ```
+//!
+//! Errors of programs without an IDL, like Anchor's own framework errors, are still named when
+//! the program logged them as an `AnchorError`.
+
+use solana_instruction::error::InstructionError;
+use solana_pubkey::Pubkey;
+
+use crate::idl::Idl;
+use crate::seashell::{InstructionProcessingError, InstructionProcessingResult, Seashell};
+
+/// A custom program error, named after the IDL of the program that returned it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorError {
+    pub program_id: Pubkey,
+    pub code: u32,
+    pub name: String,
+    pub msg: Option<String>,
+}
+
+impl std::fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}) of {}", self.name, self.code, self.program_id)?;
+        if let Some(msg) = &self.msg {
+            write!(f, ": {msg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The program that failed with custom error `code`: the innermost one logging the failure.
+fn failing_program(logs: &[String], code: u32) -> Option<Pubkey> {
+    let failure = format!(" failed: custom program error: {code:#x}");
+    logs.iter().find_map(|log| {
+        log.strip_prefix("Program ")?
+            .strip_suffix(&failure)?
+            .parse()
+            .ok()
+    })
+}
+
+/// The name and message of `code` from an `AnchorError ... Error Code: <name>. Error Number:
+/// <code>. Error Message: <msg>.` log line.
+fn logged_anchor_error(logs: &[String], code: u32) -> Option<(String, String)> {
+    logs.iter().rev().find_map(|log| {
+        let rest = log.strip_prefix("Program log: AnchorError ")?;
+        let (_, rest) = rest.split_once("Error Code: ")?;
+        let (name, rest) = rest.split_once(". Error Number: ")?;
+        let (number, msg) = rest.split_once(". Error Message: ")?;
+        (number.parse() == Ok(code)).then(|| {
+            let msg = msg.strip_suffix('.').unwrap_or(msg);
+            (name.to_string(), msg.to_string())
+        })
+    })
+}
+
+/// Names the custom error `result` failed with, through the IDL `idl_of` finds for the failing
+/// program, or else the program's `AnchorError` log.
+pub fn anchor_error<'a>(
+    result: &InstructionProcessingResult,
+    idl_of: impl Fn(&Pubkey) -> Option<&'a Idl>,
+) -> Option<AnchorError> {
+    let Some(InstructionProcessingError::InstructionError(InstructionError::Custom(code))) =
+        result.error
+    else {
+        return None;
+    };
+    let program_id = failing_program(&result.logs, code)?;
+    let (name, msg) = match idl_of(&program_id).and_then(|idl| idl.error_by_code(code)) {
+        Some(error) => (error.name.clone(), error.msg.clone()),
+        None => {
+            let (name, msg) = logged_anchor_error(&result.logs, code)?;
+            (name, Some(msg))
+        }
+    };
+    Some(AnchorError { program_id, code, name, msg })
+}
+
+impl Seashell {
+    /// Fills in the events and error of `result` named through the registered IDLs. Runs before
+    /// log filtering, which may drop the logs both are read from.
+    pub(crate) fn decode_with_idls(&self, result: &mut InstructionProcessingResult) {
+        if self.idls.is_empty() && result.error.is_none() {
+            return;
+        }
+        result.decoded_events = result
+            .events()
+            .iter()
+            .filter_map(|event| event.decode(self.idls.get(&event.program_id)?))
+            .collect();
+        result.anchor_error = anchor_error(result, |program_id| self.idls.get(program_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::event::EventSource;
+
+    #[test]
+    fn test_decode_with_idls() {
+        let program_id = Pubkey::new_unique();
+        let idl = Idl::from_json(
+            r#"{
+                "errors": [{"code": 6003, "name": "InsufficientCollateral", "msg": "Too little"}],
+                "events": [{"name": "Deposited", "fields": [
+                    {"name": "amount", "type": "u64", "index": false}
+                ]}]
+            }"#,
+        )
+        .unwrap();
+        let mut seashell = Seashell::new();
+        seashell.idls.insert(program_id, idl);
+
+        let event = [&Sha256::digest("event:Deposited")[..8], &5u64.to_le_bytes()].concat();
+        let mut result = InstructionProcessingResult {
+            logs: vec![
+                format!("Program {program_id} invoke [1]"),
+                format!(
+                    "Program data: {}",
+                    base64::engine::general_purpose::STANDARD.encode(event)
+                ),
+                format!("Program {program_id} failed: custom program error: 0x1773"),
+            ],
+            ..InstructionProcessingResult::from_error(InstructionProcessingError::InstructionError(
+                InstructionError::Custom(6003),
+            ))
+        };
+        seashell.decode_with_idls(&mut result);
+        assert_eq!(result.decoded_events.len(), 1);
+        assert_eq!(result.decoded_events[0].name, "Deposited");
+        assert_eq!(result.decoded_events[0].fields, serde_json::json!({"amount": 5}));
+        assert_eq!(result.decoded_events[0].source, EventSource::Log);
+        let error = result.anchor_error.unwrap();
+        assert_eq!((error.code, error.name.as_str()), (6003, "InsufficientCollateral"));
+        assert_eq!(error.msg.as_deref(), Some("Too little"));
+
+        // Framework errors aren't in the IDL, but Anchor logs them.
+        let other = Pubkey::new_unique();
+        let result = InstructionProcessingResult {
+            logs: vec![
+                format!("Program {other} invoke [1]"),
+                "Program log: AnchorError caused by account: vault. Error Code: \
+                 AccountNotInitialized. Error Number: 3012. Error Message: The program expected \
+                 this account to be already initialized."
+                    .to_string(),
+                format!("Program {other} failed: custom program error: 0xbc4"),
+            ],
+            ..InstructionProcessingResult::from_error(InstructionProcessingError::InstructionError(
+                InstructionError::Custom(3012),
+            ))
+        };
+        let error = anchor_error(&result, |program_id| seashell.idls.get(program_id)).unwrap();
+        assert_eq!(error.program_id, other);
+        assert_eq!(error.name, "AccountNotInitialized");
+        assert_eq!(
+            error.msg.as_deref(),
+            Some("The program expected this account to be already initialized")
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Packs processed transactions into simulated blocks, to estimate how many blocks a workload
+//! (e.g. a crank running every slot) would take on mainnet.
+//!
+//! Transactions are packed in the order they are processed. One that would push its block over
+//! [`BlockLimits::max_block_units`], or any account it writes over
+//! [`BlockLimits::max_writable_account_units`], starts a new block.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use solana_pubkey::Pubkey;
+
+/// Cost of verifying one signature, as charged by the cost model.
+pub const SIGNATURE_COST: u64 = 720;
+/// Cost of one write lock, as charged by the cost model.
+pub const WRITE_LOCK_UNITS: u64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLimits {
+    pub max_block_units: u64,
+    /// Units the transactions writing one account may use per block. This is what serializes
+    /// transactions contending for the same writable account.
+    pub max_writable_account_units: u64,
+}
+
+impl BlockLimits {
+    pub fn mainnet() -> Self {
+        BlockLimits { max_block_units: 60_000_000, max_writable_account_units: 12_000_000 }
+    }
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        BlockLimits::mainnet()
+    }
+}
+
+/// What the block builder needs to know about a processed transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionCost {
+    pub compute_units: u64,
+    pub signatures: u64,
+    pub instructions: usize,
+    pub writable_accounts: Vec<Pubkey>,
+}
+
+impl TransactionCost {
+    /// Execution units plus the signature and write lock costs, leaving out the smaller
+    /// instruction data and loaded account data costs.
+    pub fn units(&self) -> u64 {
+        self.compute_units
+            + self.signatures * SIGNATURE_COST
+            + self.writable_accounts.len() as u64 * WRITE_LOCK_UNITS
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimulatedBlock {
+    pub transactions: usize,
+    pub instructions: usize,
+    pub units: u64,
+    pub writable_account_units: HashMap<Pubkey, u64>,
+}
+
+impl SimulatedBlock {
+    fn fits(&self, cost: &TransactionCost, units: u64, limits: &BlockLimits) -> bool {
+        self.units + units <= limits.max_block_units
+            && cost.writable_accounts.iter().all(|pubkey| {
+                self.writable_account_units
+                    .get(pubkey)
+                    .copied()
+                    .unwrap_or(0)
+                    + units
+                    <= limits.max_writable_account_units
+            })
+    }
+
+    /// The account with the most units written in this block.
+    pub fn hottest_account(&self) -> Option<(Pubkey, u64)> {
+        self.writable_account_units
+            .iter()
+            .max_by_key(|(_, units)| **units)
+            .map(|(pubkey, units)| (*pubkey, *units))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockBuilder {
+    pub limits: BlockLimits,
+    blocks: Vec<SimulatedBlock>,
+}
+
+impl BlockBuilder {
+    pub fn new(limits: BlockLimits) -> Self {
+        BlockBuilder { limits, blocks: Vec::new() }
+    }
+
+    /// Packs a transaction, returning the index of the block it landed in.
+    pub fn add(&mut self, cost: &TransactionCost) -> usize {
+        let units = cost.units();
+        let fits = self
+            .blocks
+            .last()
+            .is_some_and(|block| block.fits(cost, units, &self.limits));
+        if !fits {
+            self.blocks.push(SimulatedBlock::default());
+        }
+
+        let block = self.blocks.last_mut().unwrap();
+        block.transactions += 1;
+        block.instructions += cost.instructions;
+        block.units += units;
+        for pubkey in &cost.writable_accounts {
+            *block.writable_account_units.entry(*pubkey).or_default() += units;
+        }
+        self.blocks.len() - 1
+    }
+
+    pub fn blocks(&self) -> &[SimulatedBlock] {
+        &self.blocks
+    }
+}
+
+impl fmt::Display for BlockBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} blocks", self.blocks.len())?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            write!(
+                f,
+                "  block {index}: {} transactions, {} instructions, {} units",
+                block.transactions, block.instructions, block.units
+            )?;
+            if let Some((pubkey, units)) = block.hottest_account() {
+                write!(f, ", hottest account {pubkey} ({units} units)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_builder() {
+        let limits = BlockLimits { max_block_units: 10_000, max_writable_account_units: 4_000 };
+        let mut builder = BlockBuilder::new(limits);
+        let hot = Pubkey::new_unique();
+        let cost = |writable_accounts| TransactionCost {
+            compute_units: 1_000,
+            signatures: 1,
+            instructions: 2,
+            writable_accounts,
+        };
+
+        // 2_020 units each: only one transaction writing `hot` fits per block.
+        assert_eq!(builder.add(&cost(vec![hot])), 0);
+        assert_eq!(builder.add(&cost(vec![Pubkey::new_unique()])), 0);
+        assert_eq!(builder.add(&cost(vec![hot])), 1);
+        for _ in 0..3 {
+            builder.add(&cost(vec![Pubkey::new_unique()]));
+        }
+
+        let blocks = builder.blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].transactions, 4);
+        assert_eq!(blocks[1].instructions, 8);
+        assert_eq!(blocks[0].writable_account_units[&hot], 2_020);
+    }
+}
@@ -0,0 +1,184 @@
+//! The recent blockhashes of the simulated cluster: the blockhash the runtime hands to the
+//! system program (which durable nonces store), and the deprecated RecentBlockhashes sysvar.
+//!
+//! A new blockhash is registered for every slot the clock warps through, or explicitly with
+//! [`crate::Seashell::expire_blockhash`]. Blockhashes are chained SHA-256 hashes, so a run is
+//! reproducible.
+
+use std::collections::VecDeque;
+
+use sha2::{Digest, Sha256};
+use solana_account::{Account, AccountSharedData};
+use solana_hash::Hash;
+use solana_slot_hashes::MAX_ENTRIES;
+
+/// Entries in the RecentBlockhashes sysvar.
+pub const MAX_RECENT_BLOCKHASHES: usize = 150;
+/// Age past which the runtime rejects a transaction's recent blockhash.
+pub const MAX_PROCESSING_AGE: usize = 150;
+pub const DEFAULT_LAMPORTS_PER_SIGNATURE: u64 = 5000;
+/// Expired blockhashes remembered, so that together with the valid ones the queue spans as many
+/// slots as SlotHashes.
+const MAX_EXPIRED_BLOCKHASHES: usize = MAX_ENTRIES - (MAX_PROCESSING_AGE + 1);
+
+/// Size of the RecentBlockhashes sysvar account: a length prefix and 150 entries of a hash and
+/// a fee calculator.
+const RECENT_BLOCKHASHES_ACCOUNT_SIZE: usize = 8 + MAX_RECENT_BLOCKHASHES * (32 + 8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockhashQueue {
+    /// `(blockhash, lamports per signature)`, newest first.
+    entries: VecDeque<(Hash, u64)>,
+    /// Blockhashes past [`MAX_PROCESSING_AGE`], newest first.
+    expired: VecDeque<Hash>,
+    lamports_per_signature: u64,
+}
+
+impl Default for BlockhashQueue {
+    fn default() -> Self {
+        let genesis = Hash::new_from_array(Sha256::digest(b"seashell genesis").into());
+        BlockhashQueue {
+            entries: VecDeque::from([(genesis, DEFAULT_LAMPORTS_PER_SIGNATURE)]),
+            expired: VecDeque::new(),
+            lamports_per_signature: DEFAULT_LAMPORTS_PER_SIGNATURE,
+        }
+    }
+}
+
+impl BlockhashQueue {
+    pub fn latest(&self) -> Hash {
+        self.entries[0].0
+    }
+
+    /// Fee rate blockhashes registered from now on are stamped with.
+    pub fn lamports_per_signature(&self) -> u64 {
+        self.lamports_per_signature
+    }
+
+    pub fn set_lamports_per_signature(&mut self, lamports_per_signature: u64) {
+        self.lamports_per_signature = lamports_per_signature;
+    }
+
+    /// Registers `count` new blockhashes and returns the latest. Only the ones still within
+    /// [`MAX_PROCESSING_AGE`] are kept.
+    pub fn advance(&mut self, count: u64) -> Hash {
+        for _ in 0..count.min(MAX_ENTRIES as u64) {
+            let next = Hash::new_from_array(Sha256::digest(self.latest().as_ref()).into());
+            self.register(next);
+        }
+        self.latest()
+    }
+
+    /// Makes `blockhash` the latest blockhash.
+    pub fn register(&mut self, blockhash: Hash) {
+        self.entries
+            .push_front((blockhash, self.lamports_per_signature));
+        if self.entries.len() > MAX_PROCESSING_AGE + 1 {
+            if let Some((expired, _)) = self.entries.pop_back() {
+                self.expired.push_front(expired);
+                self.expired.truncate(MAX_EXPIRED_BLOCKHASHES);
+            }
+        }
+    }
+
+    /// How many blockhashes were registered after `blockhash`, if it is still in the queue.
+    pub fn age(&self, blockhash: &Hash) -> Option<usize> {
+        self.entries.iter().position(|(hash, _)| hash == blockhash)
+    }
+
+    /// Whether a transaction using `blockhash` would still be accepted.
+    pub fn is_valid(&self, blockhash: &Hash) -> bool {
+        self.age(blockhash)
+            .is_some_and(|age| age <= MAX_PROCESSING_AGE)
+    }
+
+    /// Whether `blockhash` was valid once but is now too old, as opposed to never registered.
+    /// Blockhashes are forgotten once SlotHashes would have forgotten their slot.
+    pub fn is_expired(&self, blockhash: &Hash) -> bool {
+        self.expired.contains(blockhash)
+    }
+
+    /// The fee rate `blockhash` was registered with.
+    pub fn lamports_per_signature_of(&self, blockhash: &Hash) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(hash, _)| hash == blockhash)
+            .map(|(_, lamports_per_signature)| *lamports_per_signature)
+    }
+
+    /// The RecentBlockhashes sysvar account, as the runtime lays it out.
+    pub fn recent_blockhashes_account(&self) -> AccountSharedData {
+        let entries: Vec<_> = self.entries.iter().take(MAX_RECENT_BLOCKHASHES).collect();
+        let mut data = Vec::with_capacity(RECENT_BLOCKHASHES_ACCOUNT_SIZE);
+        data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (hash, lamports_per_signature) in entries {
+            data.extend_from_slice(hash.as_ref());
+            data.extend_from_slice(&lamports_per_signature.to_le_bytes());
+        }
+        data.resize(RECENT_BLOCKHASHES_ACCOUNT_SIZE, 0);
+
+        AccountSharedData::from(Account {
+            data,
+            owner: solana_sdk_ids::sysvar::id(),
+            ..Account::default()
+        })
+    }
+
+    /// Replaces the queue with the entries of a RecentBlockhashes sysvar account.
+    pub fn set_from_recent_blockhashes(&mut self, data: &[u8]) {
+        let Some((len, mut entries)) = data.split_first_chunk::<8>() else {
+            return;
+        };
+        let mut queue = VecDeque::new();
+        for _ in 0..u64::from_le_bytes(*len) {
+            let Some((hash, rest)) = entries.split_first_chunk::<32>() else {
+                break;
+            };
+            let Some((lamports_per_signature, rest)) = rest.split_first_chunk::<8>() else {
+                break;
+            };
+            queue.push_back((
+                Hash::new_from_array(*hash),
+                u64::from_le_bytes(*lamports_per_signature),
+            ));
+            entries = rest;
+        }
+        if !queue.is_empty() {
+            self.entries = queue;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::ReadableAccount;
+
+    use super::*;
+
+    #[test]
+    fn test_blockhash_queue() {
+        let mut queue = BlockhashQueue::default();
+        let genesis = queue.latest();
+        let next = queue.advance(1);
+        assert_ne!(genesis, next);
+        assert_eq!(queue.age(&genesis), Some(1));
+
+        queue.advance(MAX_PROCESSING_AGE as u64 - 1);
+        assert!(queue.is_valid(&genesis));
+        queue.advance(1);
+        assert!(!queue.is_valid(&genesis));
+        assert!(queue.is_expired(&genesis));
+        assert!(!queue.is_expired(&Hash::new_unique()));
+        queue.advance(MAX_ENTRIES as u64);
+        assert!(!queue.is_expired(&genesis));
+
+        let account = queue.recent_blockhashes_account();
+        assert_eq!(account.data().len(), 6008);
+        let mut copy = BlockhashQueue::default();
+        copy.set_from_recent_blockhashes(account.data());
+        assert_eq!(copy.latest(), queue.latest());
+        // The sysvar holds one entry fewer than the queue keeps valid.
+        assert_eq!(queue.age(&next), Some(MAX_PROCESSING_AGE));
+        assert_eq!(copy.age(&next), None);
+    }
+}
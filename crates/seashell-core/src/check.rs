@@ -0,0 +1,293 @@
+use std::ops::Range;
+
+use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_instruction::error::InstructionError;
+use solana_pubkey::Pubkey;
+
+use crate::layout::LayoutRegistry;
+use crate::seashell::{InstructionProcessingError, InstructionProcessingResult};
+
+/// One expectation about an instruction result, for [`crate::Seashell::process_and_validate`].
+///
+/// Account checks are built fluently: `Check::account(&pubkey).lamports(500).data_slice(64..72,
+/// &amount.to_le_bytes())`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    kind: CheckKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CheckKind {
+    Success,
+    Err(InstructionError),
+    ComputeUnits(u64),
+    ReturnData(Vec<u8>),
+    Account(AccountCheck),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AccountCheck {
+    pubkey: Pubkey,
+    lamports: Option<u64>,
+    owner: Option<Pubkey>,
+    executable: Option<bool>,
+    data: Option<Vec<u8>>,
+    space: Option<usize>,
+    data_slices: Vec<(Range<usize>, Vec<u8>)>,
+    closed: bool,
+}
+
+impl Check {
+    pub fn success() -> Self {
+        Check { kind: CheckKind::Success }
+    }
+
+    pub fn err(error: InstructionError) -> Self {
+        Check { kind: CheckKind::Err(error) }
+    }
+
+    pub fn compute_units(units: u64) -> Self {
+        Check { kind: CheckKind::ComputeUnits(units) }
+    }
+
+    pub fn return_data(data: &[u8]) -> Self {
+        Check { kind: CheckKind::ReturnData(data.to_vec()) }
+    }
+
+    /// Checks the post-execution state of `pubkey`; refine it with the account methods below.
+    pub fn account(pubkey: &Pubkey) -> Self {
+        Check {
+            kind: CheckKind::Account(AccountCheck {
+                pubkey: *pubkey,
+                lamports: None,
+                owner: None,
+                executable: None,
+                data: None,
+                space: None,
+                data_slices: Vec::new(),
+                closed: false,
+            }),
+        }
+    }
+
+    pub fn lamports(self, lamports: u64) -> Self {
+        self.with_account(|check| check.lamports = Some(lamports))
+    }
+
+    pub fn owner(self, owner: &Pubkey) -> Self {
+        self.with_account(|check| check.owner = Some(*owner))
+    }
+
+    pub fn executable(self, executable: bool) -> Self {
+        self.with_account(|check| check.executable = Some(executable))
+    }
+
+    pub fn data(self, data: &[u8]) -> Self {
+        self.with_account(|check| check.data = Some(data.to_vec()))
+    }
+
+    pub fn space(self, space: usize) -> Self {
+        self.with_account(|check| check.space = Some(space))
+    }
+
+    pub fn data_slice(self, range: Range<usize>, expected: &[u8]) -> Self {
+        self.with_account(|check| check.data_slices.push((range, expected.to_vec())))
+    }
+
+    /// The account has no lamports and no data left, as after a close.
+    pub fn closed(self) -> Self {
+        self.with_account(|check| check.closed = true)
+    }
+
+    #[track_caller]
+    fn with_account(mut self, f: impl FnOnce(&mut AccountCheck)) -> Self {
+        match &mut self.kind {
+            CheckKind::Account(check) => f(check),
+            kind => panic!("Account expectations only apply to Check::account, not {kind:?}"),
+        }
+        self
+    }
+
+    /// Describes every way `result` violates this check.
+    pub fn failures(&self, result: &InstructionProcessingResult) -> Vec<String> {
+        let mut failures = Vec::new();
+        match &self.kind {
+            CheckKind::Success => {
+                if let Some(error) = &result.error {
+                    failures.push(format!("Expected success, got {error:?}"));
+                }
+            }
+            CheckKind::Err(expected) => {
+                let expected = InstructionProcessingError::InstructionError(expected.clone());
+                if result.error.as_ref() != Some(&expected) {
+                    failures.push(format!("Expected error {expected:?}, got {:?}", result.error));
+                }
+            }
+            CheckKind::ComputeUnits(units) => {
+                if result.compute_units_consumed != *units {
+                    failures.push(format!(
+                        "Expected {units} compute units, consumed {}",
+                        result.compute_units_consumed
+                    ));
+                }
+            }
+            CheckKind::ReturnData(data) => {
+                if result.return_data != *data {
+                    failures.push(format!(
+                        "Expected return data {data:?}, got {:?}",
+                        result.return_data
+                    ));
+                }
+            }
+            CheckKind::Account(check) => check.collect_failures(result, &mut failures),
+        }
+        failures
+    }
+
+    /// For a failed account check, the expected and actual state of the account decoded side by
+    /// side. The expected state is the actual one with this check's expectations applied.
+    pub fn side_by_side(
+        &self,
+        result: &InstructionProcessingResult,
+        layouts: &LayoutRegistry,
+    ) -> Option<String> {
+        let CheckKind::Account(check) = &self.kind else {
+            return None;
+        };
+        let mut failures = Vec::new();
+        check.collect_failures(result, &mut failures);
+        if failures.is_empty() {
+            return None;
+        }
+        let actual = result
+            .post_account(&check.pubkey)
+            .map(|account| AccountSharedData::from(account.clone()));
+        let expected = check.expected_account(actual.clone().unwrap_or_default());
+        Some(layouts.side_by_side(
+            &check.pubkey,
+            ["expected", "actual"],
+            Some(&expected),
+            actual.as_ref(),
+        ))
+    }
+}
+
+impl AccountCheck {
+    fn expected_account(&self, mut account: AccountSharedData) -> AccountSharedData {
+        if let Some(lamports) = self.lamports {
+            account.set_lamports(lamports);
+        }
+        if let Some(owner) = self.owner {
+            account.set_owner(owner);
+        }
+        if let Some(executable) = self.executable {
+            account.set_executable(executable);
+        }
+        if let Some(data) = &self.data {
+            account.set_data_from_slice(data);
+        }
+        if let Some(space) = self.space {
+            account.resize(space, 0);
+        }
+        for (range, expected) in &self.data_slices {
+            if account.data().len() < range.end {
+                account.resize(range.end, 0);
+            }
+            if let Some(actual) = account.data_as_mut_slice().get_mut(range.clone()) {
+                if actual.len() == expected.len() {
+                    actual.copy_from_slice(expected);
+                }
+            }
+        }
+        if self.closed {
+            account.set_lamports(0);
+            account.set_data_from_slice(&[]);
+        }
+        account
+    }
+
+    fn collect_failures(&self, result: &InstructionProcessingResult, failures: &mut Vec<String>) {
+        let pubkey = self.pubkey;
+        let Some(account) = result.post_account(&pubkey) else {
+            failures.push(format!("Account {pubkey} is not in the post-execution state"));
+            return;
+        };
+
+        let mut mismatch = |field: &str, expected: String, actual: String| {
+            if expected != actual {
+                failures
+                    .push(format!("Account {pubkey}: expected {field} {expected}, got {actual}"));
+            }
+        };
+        if let Some(lamports) = self.lamports {
+            mismatch("lamports", lamports.to_string(), account.lamports().to_string());
+        }
+        if let Some(owner) = self.owner {
+            mismatch("owner", owner.to_string(), account.owner().to_string());
+        }
+        if let Some(executable) = self.executable {
+            mismatch("executable", executable.to_string(), account.executable().to_string());
+        }
+        if let Some(space) = self.space {
+            mismatch("data length", space.to_string(), account.data().len().to_string());
+        }
+        if let Some(data) = &self.data {
+            mismatch("data", format!("{data:?}"), format!("{:?}", account.data()));
+        }
+        for (range, expected) in &self.data_slices {
+            let actual = account
+                .data()
+                .get(range.clone())
+                .map(|actual| format!("{actual:?}"))
+                .unwrap_or_else(|| format!("out of bounds ({} bytes)", account.data().len()));
+            mismatch(&format!("data[{range:?}]"), format!("{expected:?}"), actual);
+        }
+        if self.closed && (account.lamports() != 0 || !account.data().is_empty()) {
+            failures.push(format!(
+                "Account {pubkey}: expected closed, has {} lamports and {} bytes of data",
+                account.lamports(),
+                account.data().len()
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::AccountSharedData;
+
+    use super::*;
+
+    #[test]
+    fn test_account_check_failures() {
+        let pubkey = Pubkey::new_unique();
+        let mut account = AccountSharedData::new(500, 8, &Pubkey::default());
+        account.set_data_from_slice(&7u64.to_le_bytes());
+        let result = InstructionProcessingResult {
+            post_execution_accounts: vec![(pubkey, account.into())],
+            compute_units_consumed: 150,
+            ..InstructionProcessingResult::from_error(InstructionProcessingError::ProgramError)
+        };
+
+        assert!(Check::compute_units(150).failures(&result).is_empty());
+        assert_eq!(Check::success().failures(&result).len(), 1);
+        assert!(Check::account(&pubkey)
+            .lamports(500)
+            .data_slice(0..8, &7u64.to_le_bytes())
+            .failures(&result)
+            .is_empty());
+
+        let failures = Check::account(&pubkey)
+            .lamports(400)
+            .data_slice(4..12, &[0; 8])
+            .closed()
+            .failures(&result);
+        assert_eq!(failures.len(), 3, "{failures:?}");
+        assert_eq!(
+            Check::account(&Pubkey::new_unique())
+                .failures(&result)
+                .len(),
+            1
+        );
+    }
+}
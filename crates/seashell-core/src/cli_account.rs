@@ -0,0 +1,165 @@
+//! Accounts in the JSON format of `solana account --output json`, which `solana-test-validator`
+//! loads with `--account <ADDRESS> <FILE>`. Exporting a scenario this way replays the state a
+//! seashell test ran against on a real validator, and importing takes in fixtures other tooling
+//! produced:
+//!
+//! ```ignore
+//! let args = scenario.export_to_dir("target/validator-accounts")?;
+//! // solana-test-validator $(cat target/validator-accounts/test-validator.args)
+//! scenario.import_account_dir("fixtures/accounts")?;
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use solana_account::{Account, AccountSharedData, ReadableAccount};
+use solana_account_decoder_client_types::{UiAccount, UiAccountData, UiAccountEncoding};
+use solana_pubkey::Pubkey;
+use solana_rpc_client_api::response::RpcKeyedAccount;
+
+use crate::error::SeashellError;
+use crate::scenario::Scenario;
+
+/// The file [`Scenario::export_to_dir`] writes the `solana-test-validator` arguments to.
+pub const VALIDATOR_ARGS_FILE: &str = "test-validator.args";
+
+/// `account` as `solana account --output json` prints it.
+pub fn to_keyed_account(pubkey: &Pubkey, account: &impl ReadableAccount) -> RpcKeyedAccount {
+    RpcKeyedAccount {
+        pubkey: pubkey.to_string(),
+        account: UiAccount {
+            lamports: account.lamports(),
+            data: UiAccountData::Binary(
+                base64::engine::general_purpose::STANDARD.encode(account.data()),
+                UiAccountEncoding::Base64,
+            ),
+            owner: account.owner().to_string(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+            space: Some(account.data().len() as u64),
+        },
+    }
+}
+
+/// The account of a `solana account --output json` document. Data may be in any binary
+/// encoding the RPC returns; parsed JSON data can't be turned back into bytes and is rejected.
+pub fn from_keyed_account(
+    keyed_account: &RpcKeyedAccount,
+) -> Result<(Pubkey, AccountSharedData), SeashellError> {
+    let pubkey: Pubkey = keyed_account.pubkey.parse().map_err(|e| {
+        SeashellError::Custom(format!("Invalid pubkey {}: {e}", keyed_account.pubkey))
+    })?;
+    let account = keyed_account
+        .account
+        .decode::<Account>()
+        .ok_or_else(|| SeashellError::Custom(format!("Failed to decode the data of {pubkey}")))?;
+    Ok((pubkey, account.into()))
+}
+
+impl Scenario {
+    /// Adds the account in `path`, a JSON file as written by `solana account --output json` or
+    /// [`Scenario::export_to_dir`], and returns its address.
+    pub fn import_account_file(&mut self, path: impl AsRef<Path>) -> Result<Pubkey, SeashellError> {
+        let path = path.as_ref();
+        let keyed_account: RpcKeyedAccount = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| {
+                SeashellError::Custom(format!("Invalid account file {}: {e}", path.display()))
+            })?;
+        let (pubkey, account) = from_keyed_account(&keyed_account)?;
+        self.insert(pubkey, account);
+        Ok(pubkey)
+    }
+
+    /// Adds every `.json` account file in `dir`, in file name order, and returns their
+    /// addresses. Fails on the first file that isn't an account.
+    pub fn import_account_dir(
+        &mut self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Vec<Pubkey>, SeashellError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| self.import_account_file(path))
+            .collect()
+    }
+
+    /// Writes every account of the scenario and its bases to `dir` as `<pubkey>.json`, along
+    /// with [`VALIDATOR_ARGS_FILE`] holding the matching `--account` arguments for
+    /// `solana-test-validator`, one per line. Returns the path of the arguments file.
+    pub fn export_to_dir(&self, dir: impl AsRef<Path>) -> Result<PathBuf, SeashellError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut args = String::new();
+        for pubkey in self.pubkeys() {
+            let Some(account) = self.get(&pubkey) else {
+                continue;
+            };
+            let path = dir.join(format!("{pubkey}.json"));
+            let json = serde_json::to_string_pretty(&to_keyed_account(&pubkey, &account))
+                .map_err(|e| SeashellError::Custom(format!("Failed to serialize {pubkey}: {e}")))?;
+            std::fs::write(&path, json)?;
+            args.push_str(&format!("--account {pubkey} {}\n", path.display()));
+        }
+        let args_path = dir.join(VALIDATOR_ARGS_FILE);
+        std::fs::write(&args_path, args)?;
+        Ok(args_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_to_dir() {
+        let mut scenario = Scenario::default();
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account =
+            Account { lamports: 1_000_000, data: vec![1, 2, 3], owner, ..Account::default() };
+        scenario.insert(pubkey, account.into());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let args_path = scenario.export_to_dir(dir.path()).unwrap();
+        let account_path = dir.path().join(format!("{pubkey}.json"));
+        assert_eq!(
+            std::fs::read_to_string(&args_path).unwrap(),
+            format!("--account {pubkey} {}\n", account_path.display())
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&account_path).unwrap()).unwrap();
+        assert_eq!(json["pubkey"], pubkey.to_string());
+        assert_eq!(json["account"]["lamports"], 1_000_000);
+        assert_eq!(json["account"]["data"], serde_json::json!(["AQID", "base64"]));
+        assert_eq!(json["account"]["owner"], owner.to_string());
+        assert_eq!(json["account"]["rentEpoch"], 0);
+        assert_eq!(json["account"]["space"], 3);
+
+        let mut imported = Scenario::default();
+        assert_eq!(imported.import_account_dir(dir.path()).unwrap(), vec![pubkey]);
+        assert_eq!(imported.get(&pubkey), scenario.get(&pubkey));
+
+        // As the CLI writes it for an empty, rent-exempt account.
+        let cli_path = dir.path().join("cli.json");
+        std::fs::write(
+            &cli_path,
+            format!(
+                r#"{{"pubkey":"{pubkey}","account":{{"lamports":5,"data":["","base64"],"owner":"{owner}","executable":false,"rentEpoch":18446744073709551615,"space":0}}}}"#
+            ),
+        )
+        .unwrap();
+        assert_eq!(imported.import_account_file(&cli_path).unwrap(), pubkey);
+        assert_eq!(imported.get(&pubkey).unwrap().lamports(), 5);
+    }
+}
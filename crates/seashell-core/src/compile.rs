@@ -1,5 +1,10 @@
+use std::collections::HashSet;
+
 use indexmap::IndexMap;
-use solana_instruction::Instruction;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_message::v0::{LoadedAddresses, LoadedMessage};
+use solana_message::VersionedMessage;
 use solana_pubkey::Pubkey;
 use solana_transaction_context::{IndexOfAccount, InstructionAccount};
 
@@ -41,6 +46,104 @@ pub fn compile_accounts_for_instruction(ixn: &Instruction) -> Vec<InstructionAcc
         .collect()
 }
 
+/// Expands a sanitized message back into instructions, with `loaded_addresses` appended to the
+/// static keys of a v0 message (writable ones first). Program ids and `reserved_account_keys`
+/// are demoted to readonly, as the runtime does.
+pub fn decompile_message(
+    message: &VersionedMessage,
+    loaded_addresses: &LoadedAddresses,
+    reserved_account_keys: &HashSet<Pubkey>,
+) -> Vec<Instruction> {
+    match message {
+        VersionedMessage::Legacy(message) => decompile_instructions(
+            &message.instructions,
+            &message.account_keys,
+            |index| message.is_signer(index),
+            |index| message.is_maybe_writable(index, Some(reserved_account_keys)),
+        ),
+        VersionedMessage::V0(message) => {
+            let loaded =
+                LoadedMessage::new_borrowed(message, loaded_addresses, reserved_account_keys);
+            let account_keys: Vec<Pubkey> = loaded.account_keys().iter().copied().collect();
+            decompile_instructions(
+                &message.instructions,
+                &account_keys,
+                |index| loaded.is_signer(index),
+                |index| loaded.is_writable(index),
+            )
+        }
+    }
+}
+
+/// The accounts `message` marks writable in its header or lookups that the runtime demotes to
+/// readonly.
+pub fn demoted_accounts(
+    message: &VersionedMessage,
+    loaded_addresses: &LoadedAddresses,
+    reserved_account_keys: &HashSet<Pubkey>,
+) -> Vec<Pubkey> {
+    let header = message.header();
+    let static_keys = message.static_account_keys();
+    let num_signed = usize::from(header.num_required_signatures);
+    let num_writable_signed = num_signed - usize::from(header.num_readonly_signed_accounts);
+    let num_writable_unsigned =
+        static_keys.len() - usize::from(header.num_readonly_unsigned_accounts);
+    let requested = (0..static_keys.len())
+        .filter(|index| {
+            *index < num_writable_signed || (num_signed..num_writable_unsigned).contains(index)
+        })
+        .chain(static_keys.len()..static_keys.len() + loaded_addresses.writable.len());
+
+    let is_writable: Vec<bool> = match message {
+        VersionedMessage::Legacy(message) => (0..static_keys.len())
+            .map(|index| message.is_maybe_writable(index, Some(reserved_account_keys)))
+            .collect(),
+        VersionedMessage::V0(message) => {
+            let loaded =
+                LoadedMessage::new_borrowed(message, loaded_addresses, reserved_account_keys);
+            (0..loaded.account_keys().len())
+                .map(|index| loaded.is_writable(index))
+                .collect()
+        }
+    };
+    requested
+        .filter(|index| !is_writable[*index])
+        .map(|index| {
+            static_keys
+                .get(index)
+                .copied()
+                .unwrap_or_else(|| loaded_addresses.writable[index - static_keys.len()])
+        })
+        .collect()
+}
+
+fn decompile_instructions(
+    instructions: &[CompiledInstruction],
+    account_keys: &[Pubkey],
+    is_signer: impl Fn(usize) -> bool,
+    is_writable: impl Fn(usize) -> bool,
+) -> Vec<Instruction> {
+    instructions
+        .iter()
+        .map(|instruction| Instruction {
+            program_id: account_keys[usize::from(instruction.program_id_index)],
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|index| {
+                    let index = usize::from(*index);
+                    AccountMeta {
+                        pubkey: account_keys[index],
+                        is_signer: is_signer(index),
+                        is_writable: is_writable(index),
+                    }
+                })
+                .collect(),
+            data: instruction.data.clone(),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use solana_instruction::{AccountMeta, Instruction};
@@ -221,4 +324,73 @@ mod tests {
         assert!(result[5].is_signer());
         assert!(result[5].is_writable());
     }
+
+    #[test]
+    fn test_decompile_v0_message() {
+        use solana_message::v0::{self, MessageAddressTableLookup};
+        use solana_message::MessageHeader;
+
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+
+        let message = VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer, program_id],
+            instructions: vec![CompiledInstruction::new_from_raw_parts(1, vec![7], vec![0, 2, 3])],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: Pubkey::new_unique(),
+                writable_indexes: vec![0],
+                readonly_indexes: vec![1],
+            }],
+            ..v0::Message::default()
+        });
+        let loaded_addresses =
+            LoadedAddresses { writable: vec![writable], readonly: vec![readonly] };
+
+        let instructions = decompile_message(&message, &loaded_addresses, &HashSet::new());
+        assert_eq!(
+            instructions,
+            vec![Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(writable, false),
+                    AccountMeta::new_readonly(readonly, false),
+                ],
+                data: vec![7],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decompile_demotes_reserved_accounts() {
+        use solana_message::Message;
+
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let clock = solana_sdk_ids::sysvar::clock::id();
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![AccountMeta::new(payer, true), AccountMeta::new(clock, false)],
+            data: vec![],
+        };
+        let message = VersionedMessage::Legacy(Message::new(&[instruction], Some(&payer)));
+        let loaded_addresses = LoadedAddresses::default();
+        let reserved = crate::reserved_keys::ReservedAccountKeys::default()
+            .active(&agave_feature_set::FeatureSet::all_enabled());
+
+        let instructions = decompile_message(&message, &loaded_addresses, &reserved);
+        assert_eq!(
+            instructions[0].accounts,
+            vec![AccountMeta::new(payer, true), AccountMeta::new_readonly(clock, false)]
+        );
+        assert_eq!(demoted_accounts(&message, &loaded_addresses, &reserved), vec![clock]);
+        assert!(demoted_accounts(&message, &loaded_addresses, &HashSet::new()).is_empty());
+    }
 }
@@ -0,0 +1,129 @@
+use agave_feature_set::FeatureSet;
+use solana_compute_budget::compute_budget::ComputeBudget;
+pub use solana_compute_budget::compute_budget_limits::ComputeBudgetLimits;
+use solana_compute_budget_instruction::instructions_processor::process_compute_budget_instructions;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_svm_transaction::instruction::SVMInstruction;
+
+use crate::error::SeashellError;
+
+/// The compute budget limits of a transaction made of `instructions`, derived by the runtime's own
+/// ComputeBudget instruction processor. Without a `SetComputeUnitLimit`, the limit is 200k units
+/// per non-builtin instruction and 3k per builtin one, capped at 1.4M. Fails like the runtime on
+/// malformed data, a repeated request or an invalid heap frame.
+pub fn compute_budget_limits(
+    instructions: &[Instruction],
+    feature_set: &FeatureSet,
+) -> Result<ComputeBudgetLimits, SeashellError> {
+    // The processor caches what it learns about a program by its index in the account keys, so
+    // every instruction of one program needs the same index.
+    let mut program_ids: Vec<&Pubkey> = Vec::new();
+    let instructions: Vec<(&Pubkey, SVMInstruction)> = instructions
+        .iter()
+        .map(|instruction| {
+            let program_id = &instruction.program_id;
+            let index = match program_ids.iter().position(|id| *id == program_id) {
+                Some(index) => index,
+                None => {
+                    program_ids.push(program_id);
+                    program_ids.len() - 1
+                }
+            };
+            let instruction = SVMInstruction {
+                program_id_index: index as u8,
+                accounts: &[],
+                data: &instruction.data,
+            };
+            (program_id, instruction)
+        })
+        .collect();
+
+    process_compute_budget_instructions(
+        instructions
+            .iter()
+            .map(|(program_id, instruction)| (*program_id, instruction.clone())),
+        feature_set,
+    )
+    .map_err(|err| SeashellError::Custom(format!("Invalid ComputeBudget instructions: {err}")))
+}
+
+/// `budget` with the transaction's compute unit limit and heap size applied.
+pub fn apply_limits(limits: &ComputeBudgetLimits, budget: ComputeBudget) -> ComputeBudget {
+    let mut budget = budget;
+    budget.compute_unit_limit = u64::from(limits.compute_unit_limit);
+    budget.heap_size = limits.updated_heap_bytes;
+    budget
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST_HEAP_FRAME: u8 = 1;
+    const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+    const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+    fn compute_budget_ix(data: Vec<u8>) -> Instruction {
+        Instruction { program_id: solana_sdk_ids::compute_budget::id(), accounts: vec![], data }
+    }
+
+    fn with_u32(discriminator: u8, value: u32) -> Instruction {
+        compute_budget_ix([&[discriminator][..], &value.to_le_bytes()].concat())
+    }
+
+    fn program_ix(program_id: Pubkey) -> Instruction {
+        Instruction { program_id, accounts: vec![], data: vec![] }
+    }
+
+    #[test]
+    fn test_compute_budget_limits() {
+        let feature_set = FeatureSet::all_enabled();
+        let price =
+            compute_budget_ix([&[SET_COMPUTE_UNIT_PRICE][..], &7u64.to_le_bytes()].concat());
+        let limits = compute_budget_limits(
+            &[
+                with_u32(SET_COMPUTE_UNIT_LIMIT, 2_000_000),
+                price,
+                with_u32(REQUEST_HEAP_FRAME, 64 * 1024),
+            ],
+            &feature_set,
+        )
+        .unwrap();
+        assert_eq!(limits.compute_unit_limit, 1_400_000);
+        assert_eq!(limits.compute_unit_price, 7);
+
+        let budget = apply_limits(&limits, ComputeBudget::new_with_defaults(false));
+        assert_eq!(budget.compute_unit_limit, 1_400_000);
+        assert_eq!(budget.heap_size, 64 * 1024);
+
+        let invalid = [
+            vec![with_u32(SET_COMPUTE_UNIT_LIMIT, 1), with_u32(SET_COMPUTE_UNIT_LIMIT, 2)],
+            vec![with_u32(REQUEST_HEAP_FRAME, 1000)],
+            vec![compute_budget_ix(vec![SET_COMPUTE_UNIT_LIMIT])],
+        ];
+        for instructions in invalid {
+            assert!(compute_budget_limits(&instructions, &feature_set).is_err());
+        }
+    }
+
+    #[test]
+    fn test_default_compute_unit_limit() {
+        let feature_set = FeatureSet::all_enabled();
+        let program = Pubkey::new_unique();
+        let limits =
+            compute_budget_limits(&[program_ix(program), program_ix(program)], &feature_set)
+                .unwrap();
+        assert_eq!(limits.compute_unit_limit, 400_000);
+
+        let instructions: Vec<Instruction> = std::iter::repeat_n(program_ix(program), 8).collect();
+        let limits = compute_budget_limits(&instructions, &feature_set).unwrap();
+        assert_eq!(limits.compute_unit_limit, 1_400_000);
+
+        // Builtin instructions reserve 3k units each.
+        let system_transfer = program_ix(solana_sdk_ids::system_program::id());
+        let limits =
+            compute_budget_limits(&[system_transfer, program_ix(program)], &feature_set).unwrap();
+        assert_eq!(limits.compute_unit_limit, 203_000);
+    }
+}
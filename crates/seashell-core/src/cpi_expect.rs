@@ -0,0 +1,243 @@
+//! Assertions over the CPIs an instruction made, to enforce integration contracts such as "we
+//! always call `transfer_checked`, never `transfer`":
+//!
+//! ```ignore
+//! let result = seashell.process_instruction(ixn);
+//! result.assert_cpis(&[
+//!     cpi_expect!(TOKEN_PROGRAM_ID, data_starts_with = [12], writable = [from, to]),
+//!     cpi_expect!(TOKEN_PROGRAM_ID, data_starts_with = [3], times = 0),
+//! ]);
+//! ```
+
+use solana_pubkey::Pubkey;
+
+use crate::seashell::InstructionProcessingResult;
+use crate::trace::TracedInstruction;
+
+/// Builds a [`CpiExpectation`] for `program_id`, refined by `key = value` pairs naming its
+/// builder methods: `cpi_expect!(TOKEN_PROGRAM_ID, data_starts_with = [3], times = 1)`.
+#[macro_export]
+macro_rules! cpi_expect {
+    ($program_id:expr $(, $method:ident = $value:expr)* $(,)?) => {
+        $crate::cpi_expect::CpiExpectation::new($program_id) $(.$method($value))*
+    };
+}
+
+/// CPIs to one program that an instruction is expected to make. Without [`Self::times`], at
+/// least one matching CPI is expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpiExpectation {
+    program_id: Pubkey,
+    data: Option<Vec<u8>>,
+    data_prefix: Option<Vec<u8>>,
+    writable: Vec<Pubkey>,
+    readonly: Vec<Pubkey>,
+    signers: Vec<Pubkey>,
+    times: Option<usize>,
+}
+
+impl CpiExpectation {
+    pub fn new(program_id: Pubkey) -> Self {
+        CpiExpectation {
+            program_id,
+            data: None,
+            data_prefix: None,
+            writable: Vec::new(),
+            readonly: Vec::new(),
+            signers: Vec::new(),
+            times: None,
+        }
+    }
+
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Typically the instruction discriminator, e.g. `[12]` for SPL Token's `TransferChecked`.
+    pub fn data_starts_with(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.data_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Accounts the CPI must pass as writable.
+    pub fn writable(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.writable.extend(pubkeys);
+        self
+    }
+
+    /// Accounts the CPI must pass, but not as writable.
+    pub fn readonly(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.readonly.extend(pubkeys);
+        self
+    }
+
+    /// Accounts the CPI must pass as signers.
+    pub fn signers(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.signers.extend(pubkeys);
+        self
+    }
+
+    /// Exactly `times` matching CPIs; `0` forbids them.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn matches(&self, cpi: &TracedInstruction) -> bool {
+        let meta = |pubkey: &Pubkey| cpi.accounts.iter().find(|meta| meta.pubkey == *pubkey);
+        cpi.program_id == self.program_id
+            && self.data.as_ref().is_none_or(|data| cpi.data == *data)
+            && self
+                .data_prefix
+                .as_ref()
+                .is_none_or(|prefix| cpi.data.starts_with(prefix))
+            && self
+                .writable
+                .iter()
+                .all(|pubkey| meta(pubkey).is_some_and(|meta| meta.is_writable))
+            && self
+                .readonly
+                .iter()
+                .all(|pubkey| meta(pubkey).is_some_and(|meta| !meta.is_writable))
+            && self
+                .signers
+                .iter()
+                .all(|pubkey| meta(pubkey).is_some_and(|meta| meta.is_signer))
+    }
+
+    /// Describes how `result` violates this expectation, if it does.
+    pub fn failure(&self, result: &InstructionProcessingResult) -> Option<String> {
+        let found = result
+            .inner_instructions()
+            .filter(|cpi| self.matches(cpi))
+            .count();
+        match self.times {
+            Some(times) if found != times => {
+                Some(format!("Expected {times} CPI(s) {self}, found {found}"))
+            }
+            None if found == 0 => Some(format!("Expected a CPI {self}, found none")),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CpiExpectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "to {}", self.program_id)?;
+        if let Some(data) = &self.data {
+            write!(f, " with data {}", hex::encode(data))?;
+        }
+        if let Some(prefix) = &self.data_prefix {
+            write!(f, " with data starting with {}", hex::encode(prefix))?;
+        }
+        for (label, pubkeys) in
+            [("writable", &self.writable), ("readonly", &self.readonly), ("signers", &self.signers)]
+        {
+            if !pubkeys.is_empty() {
+                let pubkeys: Vec<String> = pubkeys.iter().map(ToString::to_string).collect();
+                write!(f, ", {label} [{}]", pubkeys.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One line per CPI: its stack height, program, data and the flags of its accounts.
+fn format_cpis(result: &InstructionProcessingResult) -> String {
+    let mut lines = String::new();
+    for cpi in result.inner_instructions() {
+        let accounts: Vec<String> = cpi
+            .accounts
+            .iter()
+            .map(|meta| {
+                let flags = match (meta.is_signer, meta.is_writable) {
+                    (true, true) => " (signer, writable)",
+                    (true, false) => " (signer)",
+                    (false, true) => " (writable)",
+                    (false, false) => "",
+                };
+                format!("{}{flags}", meta.pubkey)
+            })
+            .collect();
+        lines.push_str(&format!(
+            "  [{}] {} data {}\n      {}\n",
+            cpi.stack_height,
+            cpi.program_id,
+            hex::encode(&cpi.data),
+            accounts.join("\n      ")
+        ));
+    }
+    if lines.is_empty() {
+        lines.push_str("  (none)\n");
+    }
+    lines
+}
+
+impl InstructionProcessingResult {
+    /// Panics listing every unmet expectation, along with the CPIs actually made.
+    #[track_caller]
+    pub fn assert_cpis(&self, expectations: &[CpiExpectation]) {
+        let failures: Vec<String> = expectations
+            .iter()
+            .filter_map(|expectation| expectation.failure(self))
+            .collect();
+        if !failures.is_empty() {
+            panic!(
+                "{} CPI expectation(s) failed:\n  {}\nCPIs made:\n{}",
+                failures.len(),
+                failures.join("\n  "),
+                format_cpis(self)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::seashell::InstructionProcessingError;
+
+    #[test]
+    fn test_cpi_expectations() {
+        let token_program = Pubkey::new_unique();
+        let (from, to, owner) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let cpi = |data: Vec<u8>, stack_height| TracedInstruction {
+            program_id: token_program,
+            accounts: vec![
+                AccountMeta::new(from, false),
+                AccountMeta::new(to, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+            data,
+            stack_height,
+        };
+        let result = InstructionProcessingResult {
+            error: None,
+            instruction_trace: vec![cpi(vec![3, 1], 1), cpi(vec![12, 1, 6], 2)],
+            ..InstructionProcessingResult::from_error(InstructionProcessingError::ProgramError)
+        };
+
+        result.assert_cpis(&[
+            cpi_expect!(token_program, data_starts_with = [12], writable = [from, to]),
+            cpi_expect!(token_program, data = [12, 1, 6], signers = [owner], times = 1),
+            // The top-level instruction is not a CPI.
+            cpi_expect!(token_program, data_starts_with = [3], times = 0),
+        ]);
+
+        let readonly_to = cpi_expect!(token_program, readonly = [to]);
+        assert_eq!(
+            readonly_to.failure(&result).unwrap(),
+            format!("Expected a CPI to {token_program}, readonly [{to}], found none")
+        );
+        let twice = cpi_expect!(token_program, times = 2);
+        assert!(twice.failure(&result).unwrap().ends_with("found 1"));
+        let panic =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| result.assert_cpis(&[twice])))
+                .unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.contains(&format!("[2] {token_program} data 0c0106")));
+    }
+}
@@ -0,0 +1,58 @@
+//! A bundled program that performs an arbitrary CPI described by its instruction data, so tests
+//! can produce any CPI shape without building their own helper program.
+//!
+//! The ELF in `src/spl/elfs` is built from `programs/cpi-tester` with
+//! `scripts/build-sbf.sh --program cpi-tester`, which copies it there.
+
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+pub const CPI_TESTER_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("CpiTester1111111111111111111111111111111111");
+
+const SIGNER: u8 = 0x1;
+const WRITABLE: u8 = 0x2;
+
+/// Wraps `target` so that the CPI tester invokes it, signing for PDAs of the tester with each
+/// entry of `signer_seeds` (bump included).
+///
+/// Accounts in `target` that are signers derived from `signer_seeds` are passed to the tester as
+/// non-signers; the tester signs for them when it invokes.
+pub fn invoke(target: &Instruction, signer_seeds: &[&[&[u8]]]) -> Instruction {
+    let pdas: Vec<Pubkey> = signer_seeds
+        .iter()
+        .map(|seeds| {
+            Pubkey::create_program_address(seeds, &CPI_TESTER_PROGRAM_ID)
+                .expect("Signer seeds must derive a valid program address")
+        })
+        .collect();
+
+    let mut data = Vec::with_capacity(2 + target.accounts.len() + target.data.len());
+    data.push(u8::try_from(target.accounts.len()).expect("Too many CPI accounts"));
+    data.extend(target.accounts.iter().map(|meta| {
+        (if meta.is_signer { SIGNER } else { 0 }) | (if meta.is_writable { WRITABLE } else { 0 })
+    }));
+    data.push(u8::try_from(signer_seeds.len()).expect("Too many signers"));
+    for seeds in signer_seeds {
+        data.push(u8::try_from(seeds.len()).expect("Too many seeds"));
+        for seed in *seeds {
+            data.push(u8::try_from(seed.len()).expect("Seed too long"));
+            data.extend_from_slice(seed);
+        }
+    }
+    data.extend_from_slice(&target.data);
+
+    let mut accounts = vec![AccountMeta::new_readonly(target.program_id, false)];
+    accounts.extend(target.accounts.iter().map(|meta| AccountMeta {
+        pubkey: meta.pubkey,
+        is_signer: meta.is_signer && !pdas.contains(&meta.pubkey),
+        is_writable: meta.is_writable,
+    }));
+
+    Instruction { program_id: CPI_TESTER_PROGRAM_ID, accounts, data }
+}
+
+pub(crate) fn load(seashell: &mut crate::Seashell) {
+    seashell
+        .load_program_from_bytes(CPI_TESTER_PROGRAM_ID, include_bytes!("spl/elfs/cpi_tester.so"));
+}
@@ -0,0 +1,164 @@
+//! Ships state between `Seashell`s, e.g. from a build step that runs an expensive setup once to
+//! the workers of a distributed backtest, which apply it instead of running the setup again.
+//!
+//! ```ignore
+//! // build step
+//! let handle = seashell.checkpoint();
+//! run_setup(&mut seashell);
+//! std::fs::write("setup.delta.json.gz", seashell.export_delta_since(&handle).to_bytes())?;
+//!
+//! // worker
+//! let delta = StateDelta::from_bytes(&std::fs::read("setup.delta.json.gz")?)?;
+//! seashell.apply_delta(&delta);
+//! ```
+//!
+//! Programs are not part of a delta: load them on the receiving side as usual. Their
+//! programdata accounts are carried like any other account.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use solana_account::{Account, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::scenario::AccountAsJsonAccount;
+use crate::seashell::{Seashell, StateHandle};
+use crate::sysvar::Sysvars;
+use crate::vote::EpochStakes;
+
+/// The changes made to a `Seashell` since a checkpoint.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateDelta {
+    /// Accounts created or written since the checkpoint, in their current state.
+    #[serde_as(as = "BTreeMap<serde_with::DisplayFromStr, AccountAsJsonAccount>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub accounts: BTreeMap<Pubkey, Account>,
+    /// Accounts that existed at the checkpoint and have been removed since.
+    #[serde_as(as = "BTreeSet<serde_with::DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub removed: BTreeSet<Pubkey>,
+    /// Sysvars that changed, e.g. the clock after a warp.
+    #[serde_as(as = "BTreeMap<serde_with::DisplayFromStr, AccountAsJsonAccount>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sysvars: BTreeMap<Pubkey, Account>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch_stakes: Option<EpochStakes>,
+}
+
+impl StateDelta {
+    pub fn is_empty(&self) -> bool {
+        *self == StateDelta::default()
+    }
+
+    /// Gzipped JSON, the format scenarios are stored in.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        serde_json::to_writer(&mut encoder, self).expect("Failed to serialize state delta");
+        encoder.finish().expect("Failed to compress state delta")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SeashellError> {
+        serde_json::from_reader(GzDecoder::new(bytes))
+            .map_err(|e| SeashellError::Custom(format!("Invalid state delta: {e}")))
+    }
+}
+
+impl Seashell {
+    /// Everything written since `handle` was taken, except programs and the scenario, which the
+    /// receiving side loads on its own.
+    pub fn export_delta_since(&self, handle: &StateHandle) -> StateDelta {
+        let checkpoint = &handle.accounts_db;
+        let (accounts, before) =
+            (self.accounts_db.written_accounts(), checkpoint.written_accounts());
+        let sysvars = &self.accounts_db.sysvars;
+        StateDelta {
+            accounts: accounts
+                .iter()
+                .filter(|(pubkey, account)| {
+                    !account.executable() && before.get(*pubkey) != Some(*account)
+                })
+                .map(|(pubkey, account)| (*pubkey, account.clone().into()))
+                .collect(),
+            removed: before
+                .iter()
+                .filter(|(pubkey, account)| {
+                    !account.executable() && !accounts.contains_key(*pubkey)
+                })
+                .map(|(pubkey, _)| *pubkey)
+                .collect(),
+            sysvars: Sysvars::ids()
+                .into_iter()
+                .filter(|id| checkpoint.sysvars.get(id) != sysvars.get(id))
+                .map(|id| (id, sysvars.get(&id).into()))
+                .collect(),
+            epoch_stakes: (handle.epoch_stakes != self.epoch_stakes)
+                .then(|| self.epoch_stakes.clone()),
+        }
+    }
+
+    /// Applies `delta`, exported from another `Seashell`, on top of the current state.
+    pub fn apply_delta(&mut self, delta: &StateDelta) {
+        for (pubkey, account) in delta.sysvars.iter().chain(&delta.accounts) {
+            self.set_account(*pubkey, account.clone());
+        }
+        self.accounts_db
+            .accounts
+            .write()
+            .retain(|pubkey, _| !delta.removed.contains(pubkey));
+        if let Some(epoch_stakes) = &delta.epoch_stakes {
+            self.epoch_stakes = epoch_stakes.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_apply_delta() {
+        let setup = Seashell::new();
+        let kept = Pubkey::new_unique();
+        let written = Pubkey::new_unique();
+        let removed = Pubkey::new_unique();
+        for pubkey in [kept, written, removed] {
+            setup.set_account(pubkey, Account { lamports: 1, ..Account::default() });
+        }
+        let handle = setup.checkpoint();
+
+        let created = Pubkey::new_unique();
+        setup.set_account(
+            created,
+            Account { lamports: 2, data: vec![1, 2, 3], ..Account::default() },
+        );
+        setup.set_account(written, Account { lamports: 3, ..Account::default() });
+        setup.accounts_db.accounts.write().remove(&removed);
+        setup.warp_to_slot(100);
+
+        let delta = setup.export_delta_since(&handle);
+        assert_eq!(
+            delta.accounts.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&created, &written])
+        );
+        assert_eq!(delta.removed, BTreeSet::from([removed]));
+        assert!(delta.epoch_stakes.is_none());
+        let delta = StateDelta::from_bytes(&delta.to_bytes()).unwrap();
+
+        let mut worker = Seashell::new();
+        for pubkey in [kept, written, removed] {
+            worker.set_account(pubkey, Account { lamports: 1, ..Account::default() });
+        }
+        worker.apply_delta(&delta);
+        assert_eq!(worker.account(&created).data, vec![1, 2, 3]);
+        assert_eq!(worker.account(&written).lamports, 3);
+        assert_eq!(worker.account(&kept).lamports, 1);
+        assert!(worker.accounts_db.account_maybe(&removed).is_none());
+        assert_eq!(worker.accounts_db.sysvars.clock(), setup.accounts_db.sysvars.clock());
+        assert!(worker.export_delta_since(&worker.checkpoint()).is_empty());
+    }
+}
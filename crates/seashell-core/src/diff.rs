@@ -0,0 +1,183 @@
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use solana_account::{AccountSharedData, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::layout::LayoutRegistry;
+
+/// How an instruction changed one account. Unchanged fields are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub pubkey: Pubkey,
+    /// `(pre, post)` lamports.
+    pub lamports: Option<(u64, u64)>,
+    pub owner: Option<(Pubkey, Pubkey)>,
+    pub executable: Option<(bool, bool)>,
+    /// `(pre, post)` data length, when the account was resized.
+    pub data_len: Option<(usize, usize)>,
+    /// Maximal runs of bytes that differ, in post-execution offsets. Bytes added by a resize
+    /// count as changed; bytes dropped by one only show up in `data_len`.
+    pub data_ranges: Vec<Range<usize>>,
+}
+
+impl AccountDiff {
+    /// Compares two states of `pubkey`, returning `None` if they are identical.
+    pub fn between(
+        pubkey: Pubkey,
+        pre: &AccountSharedData,
+        post: &AccountSharedData,
+    ) -> Option<Self> {
+        let changed = |pre, post| (pre != post).then_some((pre, post));
+        let diff = AccountDiff {
+            pubkey,
+            lamports: changed(pre.lamports(), post.lamports()),
+            owner: changed(*pre.owner(), *post.owner()),
+            executable: changed(pre.executable(), post.executable()),
+            data_len: changed(pre.data().len(), post.data().len()),
+            data_ranges: changed_ranges(pre.data(), post.data()),
+        };
+        (!diff.is_empty()).then_some(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lamports.is_none()
+            && self.owner.is_none()
+            && self.executable.is_none()
+            && self.data_len.is_none()
+            && self.data_ranges.is_empty()
+    }
+
+    /// Like the `Display` output, but names the layout fields the changed bytes fall in, using
+    /// the layout that matches `post`, the account's post-execution state. Bytes outside every
+    /// field are still shown as ranges.
+    pub fn format_with(&self, layouts: &LayoutRegistry, post: &impl ReadableAccount) -> String {
+        let Some(layout) = layouts
+            .find(post.owner(), post.data())
+            .filter(|layout| !layout.fields.is_empty())
+        else {
+            return self.to_string();
+        };
+
+        let mut out = String::new();
+        self.format_header(&mut out)
+            .expect("Writing to a String can't fail");
+        let mut fields: Vec<&str> = Vec::new();
+        let mut unnamed: Vec<Range<usize>> = Vec::new();
+        for offset in self.data_ranges.iter().flat_map(Range::clone) {
+            let field = layout
+                .fields
+                .iter()
+                .find(|field| (field.offset..field.offset + field.ty.size()).contains(&offset));
+            match field {
+                Some(field) if !fields.contains(&field.name.as_str()) => fields.push(&field.name),
+                Some(_) => {}
+                None => match unnamed.last_mut() {
+                    Some(range) if range.end == offset => range.end += 1,
+                    _ => unnamed.push(offset..offset + 1),
+                },
+            }
+        }
+        for field in fields {
+            let _ = writeln!(out, "  {}.{field} changed", layout.name);
+        }
+        for range in unnamed {
+            let _ = writeln!(out, "  data[{range:?}] changed");
+        }
+        out
+    }
+
+    fn format_header(&self, f: &mut impl Write) -> fmt::Result {
+        writeln!(f, "{}:", self.pubkey)?;
+        if let Some((pre, post)) = self.lamports {
+            writeln!(f, "  lamports: {pre} -> {post}")?;
+        }
+        if let Some((pre, post)) = self.owner {
+            writeln!(f, "  owner: {pre} -> {post}")?;
+        }
+        if let Some((pre, post)) = self.executable {
+            writeln!(f, "  executable: {pre} -> {post}")?;
+        }
+        if let Some((pre, post)) = self.data_len {
+            writeln!(f, "  data length: {pre} -> {post}")?;
+        }
+        Ok(())
+    }
+}
+
+fn changed_ranges(pre: &[u8], post: &[u8]) -> Vec<Range<usize>> {
+    // Accounts the instruction didn't write to still share their data with the pre-state.
+    if pre.as_ptr() == post.as_ptr() && pre.len() == post.len() {
+        return Vec::new();
+    }
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (offset, byte) in post.iter().enumerate() {
+        if pre.get(offset) == Some(byte) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end += 1,
+            _ => ranges.push(offset..offset + 1),
+        }
+    }
+    ranges
+}
+
+impl fmt::Display for AccountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.format_header(f)?;
+        for range in &self.data_ranges {
+            writeln!(f, "  data[{range:?}] changed")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::WritableAccount;
+
+    use super::*;
+    use crate::layout::{AccountLayout, FieldType};
+
+    #[test]
+    fn test_account_diff() {
+        let pubkey = Pubkey::new_unique();
+        let pre = AccountSharedData::new(100, 8, &Pubkey::default());
+        assert_eq!(AccountDiff::between(pubkey, &pre, &pre.clone()), None);
+
+        let mut post = pre.clone();
+        post.set_lamports(50);
+        post.set_data_from_slice(&[0, 1, 1, 0, 0, 0, 1, 0, 2, 2]);
+        let diff = AccountDiff::between(pubkey, &pre, &post).unwrap();
+        assert_eq!(diff.lamports, Some((100, 50)));
+        assert_eq!(diff.owner, None);
+        assert_eq!(diff.data_len, Some((8, 10)));
+        assert_eq!(diff.data_ranges, vec![1..3, 6..7, 8..10]);
+    }
+
+    #[test]
+    fn test_format_with_layout() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let pre = AccountSharedData::new(100, 12, &owner);
+        let mut post = pre.clone();
+        post.set_data_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3]);
+        let diff = AccountDiff::between(pubkey, &pre, &post).unwrap();
+
+        let mut layouts = LayoutRegistry::default();
+        assert_eq!(diff.format_with(&layouts, &post), diff.to_string());
+
+        layouts.register(
+            owner,
+            AccountLayout::new("Counter")
+                .field("count", FieldType::U64)
+                .field("bump", FieldType::U8),
+        );
+        assert_eq!(
+            diff.format_with(&layouts, &post),
+            format!("{pubkey}:\n  Counter.count changed\n  data[10..12] changed\n")
+        );
+    }
+}
@@ -3,6 +3,24 @@ pub enum SeashellError {
     #[error("{0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("Patch of {size} bytes at offset {offset} exceeds account data length {data_len}")]
+    PatchOutOfRange { offset: usize, size: usize, data_len: usize },
+
+    #[error("Program {program_name} not found; searched {searched:?}")]
+    ProgramNotFound { program_name: String, searched: Vec<std::path::PathBuf> },
+
+    #[error("Account not found for {0}")]
+    AccountNotFound(solana_pubkey::Pubkey),
+
+    #[error("Blockhash {0} not found")]
+    BlockhashNotFound(solana_hash::Hash),
+
+    #[error("Blockhash {0} has expired")]
+    BlockhashExpired(solana_hash::Hash),
+
+    #[error("Missing or invalid signatures for {0:?}")]
+    MissingSignatures(Vec<solana_pubkey::Pubkey>),
+
     #[error("{0}")]
     Custom(String),
 }
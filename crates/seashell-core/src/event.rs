@@ -0,0 +1,176 @@
+use base64::Engine;
+use solana_pubkey::Pubkey;
+
+use crate::idl::{Idl, IdlFields, IdlType};
+use crate::idl_edit::{decode_fields, decode_value};
+use crate::trace::TracedInstruction;
+
+/// Prefix of the instruction data Anchor programs pass to themselves to emit an event via CPI.
+pub const EVENT_IX_TAG_LE: [u8; 8] = 0x1d9acb512ea545e4u64.to_le_bytes();
+/// Seed of the PDA that signs Anchor event CPIs.
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+    /// `emit!`: a `Program data:` log line.
+    Log,
+    /// `emit_cpi!`: a self-CPI signed by the program's event authority.
+    Cpi,
+}
+
+/// An Anchor event emitted during execution, still serialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorEvent {
+    pub program_id: Pubkey,
+    pub discriminator: [u8; 8],
+    /// The borsh-serialized event, without the discriminator.
+    pub data: Vec<u8>,
+    pub source: EventSource,
+}
+
+impl AnchorEvent {
+    fn from_bytes(program_id: Pubkey, bytes: &[u8], source: EventSource) -> Option<Self> {
+        let (discriminator, data) = bytes.split_first_chunk::<8>()?;
+        Some(AnchorEvent { program_id, discriminator: *discriminator, data: data.to_vec(), source })
+    }
+
+    /// The event's name, as declared in the program's IDL.
+    pub fn name<'a>(&self, idl: &'a Idl) -> Option<&'a str> {
+        idl.event_by_discriminator(&self.discriminator)
+            .map(|event| event.name.as_str())
+    }
+
+    /// Decodes the event through the program's IDL, if the IDL declares it and the data matches
+    /// its fields.
+    pub fn decode(&self, idl: &Idl) -> Option<DecodedEvent> {
+        let event = idl.event_by_discriminator(&self.discriminator)?;
+        let mut data = self.data.as_slice();
+        let fields = match idl.defined_type(&event.name) {
+            Some(_) => decode_value(idl, &IdlType::Defined(event.name.clone()), &mut data),
+            None => decode_fields(idl, &IdlFields::Named(event.fields.clone()), &mut data, 0),
+        };
+        Some(DecodedEvent {
+            program_id: self.program_id,
+            name: event.name.clone(),
+            fields: fields.ok()?,
+            source: self.source,
+        })
+    }
+}
+
+/// An Anchor event decoded through its program's IDL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    pub program_id: Pubkey,
+    pub name: String,
+    /// The event's fields, in the JSON layout [`crate::idl_edit`] decodes accounts to.
+    pub fields: serde_json::Value,
+    pub source: EventSource,
+}
+
+pub fn event_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], program_id).0
+}
+
+/// Collects the events of both kinds in the order they were emitted, by walking the logs and
+/// matching every `invoke [n]` line to its entry in the instruction trace.
+pub(crate) fn anchor_events(trace: &[TracedInstruction], logs: &[String]) -> Vec<AnchorEvent> {
+    let mut events = Vec::new();
+    let mut invocations = 0;
+    let mut stack: Vec<Pubkey> = Vec::new();
+
+    for log in logs {
+        let Some(rest) = log.strip_prefix("Program ") else {
+            continue;
+        };
+
+        if let Some(fields) = rest.strip_prefix("data: ") {
+            let Some(program_id) = stack.last() else {
+                continue;
+            };
+            let bytes: Option<Vec<u8>> = fields
+                .split_whitespace()
+                .map(|field| base64::engine::general_purpose::STANDARD.decode(field).ok())
+                .collect::<Option<Vec<_>>>()
+                .map(|fields| fields.concat());
+            if let Some(event) = bytes
+                .and_then(|bytes| AnchorEvent::from_bytes(*program_id, &bytes, EventSource::Log))
+            {
+                events.push(event);
+            }
+            continue;
+        }
+
+        let Some((program_id, event)) = rest.split_once(' ') else {
+            continue;
+        };
+        let Ok(program_id) = program_id.parse::<Pubkey>() else {
+            continue;
+        };
+        if event.starts_with("invoke [") {
+            let is_self_cpi = stack.last() == Some(&program_id);
+            if let Some(instruction) = trace.get(invocations).filter(|_| is_self_cpi) {
+                if let Some(event) = event_from_cpi(instruction) {
+                    events.push(event);
+                }
+            }
+            invocations += 1;
+            stack.push(program_id);
+        } else if event == "success" || event.starts_with("failed") {
+            stack.pop();
+        }
+    }
+    events
+}
+
+fn event_from_cpi(instruction: &TracedInstruction) -> Option<AnchorEvent> {
+    let bytes = instruction.data.strip_prefix(&EVENT_IX_TAG_LE)?;
+    let authority = instruction.accounts.first()?;
+    if !authority.is_signer || authority.pubkey != event_authority(&instruction.program_id) {
+        return None;
+    }
+    AnchorEvent::from_bytes(instruction.program_id, bytes, EventSource::Cpi)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_anchor_events() {
+        let program_id = Pubkey::new_unique();
+        let payload = [[1u8; 8].as_slice(), &[42]].concat();
+        let traced = |accounts, data: Vec<u8>, stack_height| TracedInstruction {
+            program_id,
+            accounts,
+            data,
+            stack_height,
+        };
+        let trace = vec![
+            traced(vec![], vec![], 1),
+            traced(
+                vec![AccountMeta::new_readonly(event_authority(&program_id), true)],
+                [EVENT_IX_TAG_LE.as_slice(), &payload].concat(),
+                2,
+            ),
+        ];
+        let logs = vec![
+            format!("Program {program_id} invoke [1]"),
+            format!("Program data: {}", base64::engine::general_purpose::STANDARD.encode(payload)),
+            format!("Program {program_id} invoke [2]"),
+            format!("Program {program_id} success"),
+            format!("Program {program_id} success"),
+        ];
+
+        let events = anchor_events(&trace, &logs);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].source, EventSource::Log);
+        assert_eq!(events[1].source, EventSource::Cpi);
+        for event in events {
+            assert_eq!(event.discriminator, [1; 8]);
+            assert_eq!(event.data, vec![42]);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use agave_feature_set::FeatureSet;
+use solana_account::AccountSharedData;
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_hash::Hash;
+use solana_instruction::error::InstructionError;
+use solana_instruction::Instruction;
+use solana_program_runtime::invoke_context::{EnvironmentConfig, InvokeContext};
+use solana_program_runtime::loaded_programs::{ProgramCacheEntry, ProgramCacheForTxBatch};
+use solana_program_runtime::sysvar_cache::SysvarCache;
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_svm_log_collector::LogCollector;
+use solana_svm_timings::ExecuteTimings;
+use solana_transaction_context::TransactionAccount;
+
+use crate::agave::{self, SeashellInvokeContextCallback};
+use crate::fault::VmFault;
+use crate::trace::{ExecutionTimings, LoadedDataSize, TracedInstruction};
+use crate::vote::EpochStakes;
+
+/// Everything needed to run one instruction.
+#[derive(Clone)]
+pub(crate) struct ExecutionInput {
+    pub instruction: Instruction,
+    pub transaction_accounts: Vec<TransactionAccount>,
+    pub sysvar_cache: SysvarCache,
+    pub programs: ProgramCacheForTxBatch,
+    pub feature_set: FeatureSet,
+    pub epoch_stakes: EpochStakes,
+    pub compute_budget: ComputeBudget,
+    pub rent: Rent,
+    /// Record program logs, up to the runtime's byte limit.
+    pub record_logs: bool,
+    /// Map access violations onto the program's memory when the instruction fails. Needs logs.
+    pub capture_vm_fault: bool,
+    /// The latest blockhash and its fee rate, which durable nonces are advanced to.
+    pub blockhash: Hash,
+    pub lamports_per_signature: u64,
+    /// Top-level instructions run earlier in the same chain or transaction, which
+    /// `sol_get_processed_sibling_instruction` returns.
+    pub processed_siblings: Vec<Instruction>,
+    /// Data of every top-level instruction in the chain or transaction, which precompiles read
+    /// signatures, public keys and messages from. Empty for a lone instruction.
+    pub instruction_datas: Vec<Vec<u8>>,
+}
+
+/// The raw outcome of running an instruction, before anything is committed to the `AccountsDb`.
+pub(crate) struct ExecutionOutput {
+    pub result: Result<(), InstructionError>,
+    pub compute_units_consumed: u64,
+    pub return_data: Vec<u8>,
+    pub return_data_program_id: Option<Pubkey>,
+    /// State of every transaction account before execution, in transaction order.
+    pub pre_accounts: Vec<TransactionAccount>,
+    /// Post-execution state of every transaction account, in transaction order.
+    pub post_accounts: Vec<TransactionAccount>,
+    pub instruction_trace: Vec<TracedInstruction>,
+    /// Every program log up to the runtime's byte limit, empty unless `record_logs` was set.
+    pub logs: Vec<String>,
+    pub timings: ExecutionTimings,
+    /// Programs deployed, upgraded or closed through the loader during execution.
+    pub modified_programs: Vec<(Pubkey, Arc<ProgramCacheEntry>)>,
+    pub vm_fault: Option<VmFault>,
+    /// Filled in by the caller, which can resolve programdata accounts.
+    pub loaded_data_size: LoadedDataSize,
+}
+
+/// Runs the instruction. Logs are recorded here rather than into the caller's collector, since
+/// they're also needed to attribute compute units to CPI frames, and replayed into it afterwards.
+pub(crate) fn execute_instruction(input: ExecutionInput) -> ExecutionOutput {
+    let ExecutionInput {
+        instruction: ixn,
+        transaction_accounts,
+        sysvar_cache,
+        mut programs,
+        feature_set,
+        epoch_stakes,
+        compute_budget,
+        rent,
+        record_logs,
+        capture_vm_fault,
+        blockhash,
+        lamports_per_signature,
+        processed_siblings,
+        instruction_datas,
+    } = input;
+
+    // Account data is shared until a program writes to it, so keeping the pre-state is cheap.
+    let pre_accounts = transaction_accounts.clone();
+    let transaction_accounts = with_sibling_accounts(transaction_accounts, &processed_siblings);
+    let mut transaction_context =
+        agave::new_transaction_context(transaction_accounts, rent, &compute_budget);
+    agave::record_processed_instructions(&mut transaction_context, &processed_siblings);
+    agave::configure_instruction(&mut transaction_context, &ixn);
+
+    let epoch_stake_callback =
+        SeashellInvokeContextCallback { feature_set: &feature_set, epoch_stakes: &epoch_stakes };
+    let runtime_features = feature_set.runtime_features();
+    let log_collector = record_logs.then(LogCollector::new_ref);
+    let mut invoke_context = InvokeContext::new(
+        &mut transaction_context,
+        &mut programs,
+        EnvironmentConfig::new(
+            blockhash,
+            lamports_per_signature,
+            &epoch_stake_callback,
+            &runtime_features,
+            &sysvar_cache,
+        ),
+        log_collector.clone(),
+        compute_budget.to_budget(),
+        compute_budget.to_cost(),
+    );
+
+    let mut compute_units_consumed = 0;
+    let mut execute_timings = ExecuteTimings::default();
+
+    let start = Instant::now();
+    let result = if invoke_context.is_precompile(&ixn.program_id) {
+        let instruction_datas: Vec<&[u8]> = if instruction_datas.is_empty() {
+            vec![&ixn.data]
+        } else {
+            instruction_datas.iter().map(Vec::as_slice).collect()
+        };
+        invoke_context.process_precompile(&ixn.program_id, &ixn.data, instruction_datas.into_iter())
+    } else {
+        invoke_context.process_instruction(&mut compute_units_consumed, &mut execute_timings)
+    };
+    let timings = agave::execution_timings(&execute_timings, start.elapsed().as_micros() as u64);
+
+    let modified_programs = programs.drain_modified_entries().into_iter().collect();
+    let (return_data_program_id, return_data) = transaction_context.get_return_data();
+    let return_data_program_id = (!return_data.is_empty()).then_some(*return_data_program_id);
+    let return_data = return_data.to_owned();
+    let instruction_trace =
+        agave::instruction_trace(&transaction_context, processed_siblings.len());
+    // Duplicate keys share the account at their first index, so every entry reads from there.
+    let post_accounts = pre_accounts
+        .iter()
+        .map(|(pubkey, _)| {
+            let idx = transaction_context
+                .find_index_of_account(pubkey)
+                .expect("Every transaction account is in the transaction context");
+            let account = transaction_context
+                .accounts()
+                .try_borrow(idx)
+                .expect("Failed to borrow TransactionAccounts")
+                .clone();
+            (*pubkey, account)
+        })
+        .collect();
+    let logs = log_collector
+        .map(|log_collector| log_collector.borrow().get_recorded_content().to_owned())
+        .unwrap_or_default();
+    let vm_fault = if capture_vm_fault && result.is_err() {
+        VmFault::capture(&logs, &instruction_trace, &pre_accounts, compute_budget.heap_size)
+    } else {
+        None
+    };
+
+    ExecutionOutput {
+        result,
+        compute_units_consumed,
+        return_data,
+        return_data_program_id,
+        pre_accounts,
+        post_accounts,
+        instruction_trace,
+        logs,
+        timings,
+        modified_programs,
+        vm_fault,
+        loaded_data_size: LoadedDataSize::default(),
+    }
+}
+
+/// Appends placeholders for the accounts of `siblings` the instruction doesn't load. The runtime
+/// only reads their keys, and they are left out of the pre- and post-execution accounts, so they
+/// are never committed.
+fn with_sibling_accounts(
+    mut transaction_accounts: Vec<TransactionAccount>,
+    siblings: &[Instruction],
+) -> Vec<TransactionAccount> {
+    let keys = siblings.iter().flat_map(|sibling| {
+        std::iter::once(sibling.program_id).chain(sibling.accounts.iter().map(|meta| meta.pubkey))
+    });
+    for pubkey in keys {
+        if !transaction_accounts.iter().any(|(key, _)| *key == pubkey) {
+            transaction_accounts.push((pubkey, AccountSharedData::default()));
+        }
+    }
+    transaction_accounts
+}
+
+/// Compute units of the first run under a timeout, which measures how fast the VM goes.
+const FIRST_RUN_UNITS: u64 = 10_000;
+
+/// Runs the instruction, stopped once `timeout` has passed. Returns `None` if it was.
+///
+/// The VM can't read the clock between syscalls, so the deadline is enforced through the compute
+/// meter: the instruction first runs with a few units, then reruns from the same input with as
+/// many as the VM can get through before the deadline at the rate it went, until it finishes or
+/// runs out of units past the deadline. Execution only reads its input, so the reruns are
+/// unobservable, except to programs that branch on their remaining compute units. Syscalls made
+/// after the deadline fail straight away.
+pub(crate) fn execute_instruction_with_timeout(
+    input: ExecutionInput,
+    timeout: std::time::Duration,
+) -> Option<ExecutionOutput> {
+    let deadline = Instant::now() + timeout;
+    let limit = input.compute_budget.compute_unit_limit;
+    let mut units = FIRST_RUN_UNITS.min(limit);
+    loop {
+        let mut run = input.clone();
+        run.compute_budget.compute_unit_limit = units;
+        let start = Instant::now();
+        let (output, interrupted) = agave::with_deadline(deadline, || execute_instruction(run));
+        if interrupted {
+            return None;
+        }
+        let exhausted = output.result.is_err() && output.compute_units_consumed >= units;
+        if units == limit || !exhausted {
+            return Some(output);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        let units_per_second = units as f64 / (now - start).as_secs_f64();
+        let affordable = (units_per_second * (deadline - now).as_secs_f64()) as u64;
+        units = affordable.max(units.saturating_mul(2)).min(limit);
+    }
+}
@@ -0,0 +1,199 @@
+use solana_account::{Account, AccountSharedData, ReadableAccount};
+use solana_instruction::error::InstructionError;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+use crate::check::Check;
+use crate::seashell::{InstructionProcessingError, InstructionProcessingResult, Seashell};
+
+impl Seashell {
+    /// Runs `ixn` and panics unless it fails with exactly `expected`, printing the logs of the
+    /// execution on mismatch.
+    #[track_caller]
+    pub fn expect_err(
+        &self,
+        ixn: Instruction,
+        expected: InstructionError,
+    ) -> InstructionProcessingResult {
+        let pre_accounts = self.writable_accounts(&ixn);
+        let result = self.process_instruction(ixn);
+        let expected = InstructionProcessingError::InstructionError(expected);
+        if result.error.as_ref() != Some(&expected) {
+            panic!(
+                "Expected error {expected:?}, got {:?}\n{}{}",
+                result.error,
+                self.format_changes(&pre_accounts, &result),
+                format_logs(&result.logs)
+            );
+        }
+        result
+    }
+
+    /// Runs `ixn` and panics unless it fails with the Anchor error `error_name`, as declared in the
+    /// IDL loaded for the instruction's program with [`Seashell::load_idl`].
+    #[track_caller]
+    pub fn expect_anchor_err(
+        &self,
+        ixn: Instruction,
+        error_name: &str,
+    ) -> InstructionProcessingResult {
+        let program_id = ixn.program_id;
+        let idl = self
+            .idls
+            .get(&program_id)
+            .unwrap_or_else(|| panic!("No IDL loaded for program {program_id}"));
+        let code = idl
+            .error_by_name(error_name)
+            .unwrap_or_else(|| panic!("IDL of program {program_id} has no error {error_name}"))
+            .code;
+
+        let pre_accounts = self.writable_accounts(&ixn);
+        let result = self.process_instruction(ixn);
+        let actual = match &result.error {
+            Some(InstructionProcessingError::InstructionError(InstructionError::Custom(code))) => {
+                idl.error_by_code(*code)
+                    .map(|error| format!("{} ({code})", error.name))
+                    .unwrap_or_else(|| format!("Custom({code})"))
+            }
+            error => format!("{error:?}"),
+        };
+        if result.error
+            != Some(InstructionProcessingError::InstructionError(InstructionError::Custom(code)))
+        {
+            panic!(
+                "Expected error {error_name} ({code}), got {actual}\n{}{}",
+                self.format_changes(&pre_accounts, &result),
+                format_logs(&result.logs)
+            );
+        }
+        result
+    }
+
+    /// Runs `ixn` and panics listing every failed check, with the expected and actual state of
+    /// each mismatched account and the logs of the execution.
+    #[track_caller]
+    pub fn process_and_validate(
+        &self,
+        ixn: Instruction,
+        checks: &[Check],
+    ) -> InstructionProcessingResult {
+        let result = self.process_instruction(ixn);
+        let failures: Vec<String> = checks
+            .iter()
+            .flat_map(|check| check.failures(&result))
+            .collect();
+        if !failures.is_empty() {
+            let accounts: String = checks
+                .iter()
+                .filter_map(|check| check.side_by_side(&result, &self.layouts))
+                .collect();
+            panic!(
+                "{} check(s) failed:\n  {}\n{accounts}{}",
+                failures.len(),
+                failures.join("\n  "),
+                format_logs(&result.logs)
+            );
+        }
+        result
+    }
+
+    /// Panics unless `pubkey` holds `expected`, ignoring `rent_epoch`, showing both states decoded
+    /// side by side.
+    #[track_caller]
+    pub fn assert_account(&self, pubkey: &Pubkey, expected: &Account) {
+        let expected = AccountSharedData::from(expected.clone());
+        let actual = self.accounts_db.account_maybe(pubkey);
+        let matches = actual.as_ref().is_some_and(|actual| {
+            actual.lamports() == expected.lamports()
+                && actual.owner() == expected.owner()
+                && actual.executable() == expected.executable()
+                && actual.data() == expected.data()
+        });
+        if !matches {
+            panic!(
+                "Account {pubkey} doesn't match\n{}",
+                self.layouts.side_by_side(
+                    pubkey,
+                    ["expected", "actual"],
+                    Some(&expected),
+                    actual.as_ref()
+                )
+            );
+        }
+    }
+
+    /// Runs `ixns` as a chain and panics unless instruction `index` fails with `expected`.
+    #[track_caller]
+    pub fn expect_chain_err(
+        &self,
+        ixns: Vec<Instruction>,
+        index: usize,
+        expected: InstructionError,
+    ) {
+        let chain = self.process_instruction_chain(ixns);
+        let expected = (index, InstructionProcessingError::InstructionError(expected));
+        if chain.error.as_ref() != Some(&expected) {
+            let logs: Vec<String> = chain
+                .results
+                .iter()
+                .flat_map(|result| result.logs.iter().cloned())
+                .collect();
+            panic!(
+                "Expected instruction {} to fail with {:?}, got {:?}\n{}",
+                expected.0,
+                expected.1,
+                chain.error,
+                format_logs(&logs)
+            );
+        }
+    }
+}
+
+impl Seashell {
+    fn writable_accounts(&self, ixn: &Instruction) -> Vec<(Pubkey, Option<AccountSharedData>)> {
+        ixn.accounts
+            .iter()
+            .filter(|meta| meta.is_writable)
+            .map(|meta| (meta.pubkey, self.accounts_db.account_maybe(&meta.pubkey)))
+            .collect()
+    }
+
+    /// The accounts an instruction that should have failed changed, before and after.
+    fn format_changes(
+        &self,
+        pre_accounts: &[(Pubkey, Option<AccountSharedData>)],
+        result: &InstructionProcessingResult,
+    ) -> String {
+        result
+            .account_diffs
+            .iter()
+            .map(|diff| {
+                let pre = pre_accounts
+                    .iter()
+                    .find(|(pubkey, _)| *pubkey == diff.pubkey)
+                    .and_then(|(_, account)| account.as_ref());
+                self.layouts.side_by_side(
+                    &diff.pubkey,
+                    ["before", "after"],
+                    pre,
+                    result
+                        .post_account(&diff.pubkey)
+                        .map(|account| AccountSharedData::from(account.clone()))
+                        .as_ref(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn format_logs(logs: &[String]) -> String {
+    if logs.is_empty() {
+        return "No logs were recorded".to_string();
+    }
+    let mut out = String::from("Logs:");
+    for log in logs {
+        out.push_str("\n  ");
+        out.push_str(log);
+    }
+    out
+}
@@ -0,0 +1,377 @@
+//! Turns the loader's one-line access violation into a report program authors can act on.
+//!
+//! The VM's memory is gone by the time the instruction returns, but the input region is a pure
+//! function of the instruction and its accounts, so it can be rebuilt to show which account field
+//! the faulting address points into and the bytes around it. For the stack and heap only the
+//! offset into the region is known. The loader doesn't log the faulting instruction pointer, so
+//! the report can't point at a source line.
+
+use std::fmt;
+use std::ops::Range;
+
+use solana_account::{AccountSharedData, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::trace::TracedInstruction;
+
+/// Size of each virtual memory region of the SBF VM; region `n` starts at `n << 32`.
+const MM_REGION_SIZE: u64 = 1 << 32;
+/// Start of the serialized instruction input, which programs receive in `r1`.
+pub const MM_INPUT_START: u64 = 4 * MM_REGION_SIZE;
+/// Bytes an account may grow by within one instruction, reserved after its data.
+const MAX_PERMITTED_DATA_INCREASE: usize = 10 * 1024;
+const NON_DUP_MARKER: u8 = u8::MAX;
+/// Bytes of the reconstructed input shown before and after the faulting access.
+const DUMP_CONTEXT: usize = 32;
+
+/// The virtual memory region an address falls into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// Below the program region, usually a null or uninitialized pointer.
+    Unmapped,
+    Program,
+    Stack,
+    Heap,
+    Input,
+    /// Above the input region.
+    Beyond,
+}
+
+impl MemoryRegion {
+    pub fn of(address: u64) -> Self {
+        match address / MM_REGION_SIZE {
+            0 => MemoryRegion::Unmapped,
+            1 => MemoryRegion::Program,
+            2 => MemoryRegion::Stack,
+            3 => MemoryRegion::Heap,
+            4 => MemoryRegion::Input,
+            _ => MemoryRegion::Beyond,
+        }
+    }
+}
+
+/// Where in the serialized input an address points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLocation {
+    /// Index of the account among the instruction's accounts, and its key.
+    pub account: Option<(usize, Pubkey)>,
+    /// The serialized field, e.g. `lamports`, `data` or `realloc padding`.
+    pub field: &'static str,
+    /// Offset of the address into the field.
+    pub offset: u64,
+}
+
+/// Bytes of the rebuilt input region starting at `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryDump {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// An access violation reported by the loader, mapped back onto the program's memory layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmFault {
+    pub program_id: Pubkey,
+    pub address: u64,
+    pub size: u64,
+    pub region: MemoryRegion,
+    /// Offset of `address` into `region`.
+    pub offset: u64,
+    /// Length of the region as mapped for this invocation, when known.
+    pub region_len: Option<u64>,
+    /// Set for violations the loader attributes to a stack frame.
+    pub stack_frame: Option<i64>,
+    /// Set for input region addresses, unless the program is owned by the deprecated loader.
+    pub input_location: Option<InputLocation>,
+    pub memory: Option<MemoryDump>,
+}
+
+impl VmFault {
+    /// Finds the innermost access violation in `logs` and maps it onto the failing invocation's
+    /// memory. The input region is rebuilt from `accounts`, which for CPIs is the state before the
+    /// top-level instruction, so writes made earlier in the instruction are not reflected.
+    pub(crate) fn capture(
+        logs: &[String],
+        trace: &[TracedInstruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        heap_size: u32,
+    ) -> Option<Self> {
+        let (trace_index, violation) = find_violation(logs)?;
+        let region = MemoryRegion::of(violation.address);
+        let mut fault = VmFault {
+            program_id: violation.program_id,
+            address: violation.address,
+            size: violation.size,
+            region,
+            offset: violation.address % MM_REGION_SIZE,
+            region_len: None,
+            stack_frame: violation.stack_frame,
+            input_location: None,
+            memory: None,
+        };
+
+        match region {
+            MemoryRegion::Heap => fault.region_len = Some(u64::from(heap_size)),
+            MemoryRegion::Input => {
+                let instruction = trace_index
+                    .and_then(|index| trace.get(index))
+                    .filter(|instruction| !is_deprecated_loader(&instruction.program_id, accounts));
+                if let Some(instruction) = instruction {
+                    fault.map_input(&SerializedInput::new(instruction, accounts));
+                }
+            }
+            _ => {}
+        }
+        Some(fault)
+    }
+
+    fn map_input(&mut self, input: &SerializedInput) {
+        let offset = self.offset as usize;
+        self.region_len = Some(input.bytes.len() as u64);
+        self.input_location = input
+            .fields
+            .iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(range, location)| InputLocation {
+                offset: (offset - range.start) as u64,
+                ..location.clone()
+            });
+
+        let start = offset
+            .saturating_sub(DUMP_CONTEXT)
+            .min(input.bytes.len().saturating_sub(DUMP_CONTEXT));
+        let end = offset
+            .saturating_add(self.size as usize)
+            .saturating_add(DUMP_CONTEXT)
+            .min(input.bytes.len());
+        self.memory = Some(MemoryDump {
+            address: MM_INPUT_START + start as u64,
+            bytes: input.bytes[start..end].to_vec(),
+        });
+    }
+}
+
+/// Programs of the deprecated loader get the unaligned input layout, which isn't rebuilt.
+fn is_deprecated_loader(program_id: &Pubkey, accounts: &[(Pubkey, AccountSharedData)]) -> bool {
+    accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == program_id)
+        .is_some_and(|(_, program)| *program.owner() == solana_sdk_ids::bpf_loader_deprecated::id())
+}
+
+struct Violation {
+    program_id: Pubkey,
+    address: u64,
+    size: u64,
+    stack_frame: Option<i64>,
+}
+
+/// The first access violation in `logs`, with the index into the instruction trace of the
+/// invocation that hit it.
+fn find_violation(logs: &[String]) -> Option<(Option<usize>, Violation)> {
+    let mut invocations = 0;
+    let mut stack: Vec<usize> = Vec::new();
+
+    for log in logs {
+        let Some((program_id, event)) = log
+            .strip_prefix("Program ")
+            .and_then(|rest| rest.split_once(' '))
+        else {
+            continue;
+        };
+        let Ok(program_id) = program_id.parse::<Pubkey>() else {
+            continue;
+        };
+
+        if event.starts_with("invoke [") {
+            stack.push(invocations);
+            invocations += 1;
+        } else if event == "success" {
+            stack.pop();
+        } else if let Some(error) = event.strip_prefix("failed: ") {
+            if let Some(violation) = parse_violation(program_id, error) {
+                return Some((stack.last().copied(), violation));
+            }
+            stack.pop();
+        }
+    }
+    None
+}
+
+/// Parses the loader's `Access violation in <section> section at address <addr> of size <len>`
+/// and its stack frame variant.
+fn parse_violation(program_id: Pubkey, error: &str) -> Option<Violation> {
+    let rest = error.strip_prefix("Access violation in ")?;
+    let (location, rest) = rest.split_once(" at address ")?;
+    let (address, size) = rest.split_once(" of size ")?;
+    Some(Violation {
+        program_id,
+        address: u64::from_str_radix(address.strip_prefix("0x")?, 16).ok()?,
+        size: size.trim().parse().ok()?,
+        stack_frame: location
+            .strip_prefix("stack frame ")
+            .and_then(|frame| frame.parse().ok()),
+    })
+}
+
+/// The input region of an aligned-ABI program, with the range each field occupies.
+struct SerializedInput {
+    bytes: Vec<u8>,
+    fields: Vec<(Range<usize>, InputLocation)>,
+}
+
+impl SerializedInput {
+    fn new(instruction: &TracedInstruction, accounts: &[(Pubkey, AccountSharedData)]) -> Self {
+        let mut input = SerializedInput { bytes: Vec::new(), fields: Vec::new() };
+        input.push(None, "num_accounts", &(instruction.accounts.len() as u64).to_le_bytes());
+
+        for (index, meta) in instruction.accounts.iter().enumerate() {
+            let account = Some((index, meta.pubkey));
+            if let Some(first) = instruction.accounts[..index]
+                .iter()
+                .position(|other| other.pubkey == meta.pubkey)
+            {
+                input.push(account, "duplicate index", &[first as u8, 0, 0, 0, 0, 0, 0, 0]);
+                continue;
+            }
+
+            let state = accounts
+                .iter()
+                .find(|(pubkey, _)| *pubkey == meta.pubkey)
+                .map(|(_, account)| account.clone())
+                .unwrap_or_default();
+            let data = state.data();
+            input.push(
+                account,
+                "header",
+                &[
+                    NON_DUP_MARKER,
+                    meta.is_signer as u8,
+                    meta.is_writable as u8,
+                    state.executable() as u8,
+                    0,
+                    0,
+                    0,
+                    0,
+                ],
+            );
+            input.push(account, "key", meta.pubkey.as_ref());
+            input.push(account, "owner", state.owner().as_ref());
+            input.push(account, "lamports", &state.lamports().to_le_bytes());
+            input.push(account, "data_len", &(data.len() as u64).to_le_bytes());
+            input.push(account, "data", data);
+            let padding = MAX_PERMITTED_DATA_INCREASE + data.len().next_multiple_of(8) - data.len();
+            input.push(account, "realloc padding", &vec![0; padding]);
+            input.push(account, "rent_epoch", &state.rent_epoch().to_le_bytes());
+        }
+
+        input.push(None, "instruction data_len", &(instruction.data.len() as u64).to_le_bytes());
+        input.push(None, "instruction data", &instruction.data);
+        input.push(None, "program id", instruction.program_id.as_ref());
+        input
+    }
+
+    fn push(&mut self, account: Option<(usize, Pubkey)>, field: &'static str, bytes: &[u8]) {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.fields
+            .push((start..self.bytes.len(), InputLocation { account, field, offset: 0 }));
+    }
+}
+
+impl fmt::Display for InputLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((index, pubkey)) = self.account {
+            write!(f, "account #{index} ({pubkey}) ")?;
+        }
+        write!(f, "{} + {:#x}", self.field, self.offset)
+    }
+}
+
+impl fmt::Display for MemoryDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, chunk) in self.bytes.chunks(16).enumerate() {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+            writeln!(f, "  {:#x}: {}", self.address + line as u64 * 16, bytes.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for VmFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Program {} accessed {} bytes at {:#x} ({:?} region + {:#x})",
+            self.program_id, self.size, self.address, self.region, self.offset
+        )?;
+        if let Some(frame) = self.stack_frame {
+            writeln!(f, "  in stack frame {frame}")?;
+        }
+        if let Some(len) = self.region_len {
+            if self.offset + self.size > len {
+                writeln!(f, "  past the end of the {len}-byte region")?;
+            }
+        }
+        if let Some(location) = &self.input_location {
+            writeln!(f, "  at {location}")?;
+        }
+        if let Some(memory) = &self.memory {
+            write!(f, "{memory}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_capture_input_fault() {
+        let program_id = Pubkey::new_unique();
+        let pubkey = Pubkey::new_unique();
+        let account = AccountSharedData::new(1, 16, &program_id);
+        let trace = vec![TracedInstruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(pubkey, false),
+                AccountMeta::new_readonly(pubkey, false),
+            ],
+            data: vec![],
+            stack_height: 1,
+        }];
+        // The first account's data starts after the account count and 88 bytes of metadata.
+        let address = MM_INPUT_START + 8 + 88 + 4;
+        let logs = vec![
+            format!("Program {program_id} invoke [1]"),
+            format!(
+                "Program {program_id} failed: Access violation in input section at address \
+                 {address:#x} of size 8"
+            ),
+        ];
+
+        let fault = VmFault::capture(&logs, &trace, &[(pubkey, account)], 32 * 1024).unwrap();
+        assert_eq!(fault.region, MemoryRegion::Input);
+        assert_eq!(
+            fault.input_location,
+            Some(InputLocation { account: Some((0, pubkey)), field: "data", offset: 4 })
+        );
+        let memory = fault.memory.unwrap();
+        assert_eq!(memory.address, address - DUMP_CONTEXT as u64);
+        assert_eq!(memory.bytes.len(), 2 * DUMP_CONTEXT + 8);
+
+        let heap_logs = vec![format!(
+            "Program {program_id} failed: Access violation in heap section at address 0x300008000 \
+             of size 8"
+        )];
+        let fault = VmFault::capture(&heap_logs, &trace, &[], 32 * 1024).unwrap();
+        assert_eq!(fault.region, MemoryRegion::Heap);
+        assert_eq!(fault.offset, 0x8000);
+        assert!(fault
+            .to_string()
+            .contains("past the end of the 32768-byte region"));
+    }
+}
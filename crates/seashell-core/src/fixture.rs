@@ -0,0 +1,59 @@
+//! Keeps generated time-bearing accounts consistent with the simulated clock.
+//!
+//! Builders that stamp accounts with a slot, epoch or timestamp take the clock from
+//! [`crate::Seashell::fixture_clock`], which is the `Clock` sysvar shifted by
+//! `Config::fixture_clock_offset`. Fixtures then follow `warp` without each test threading the
+//! clock through by hand.
+
+use solana_clock::Clock;
+
+/// Shifts the clock fixtures are stamped with, relative to the simulated clock. Negative values
+/// date fixtures in the past, e.g. a vote account that last voted an hour before the current
+/// slot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockOffset {
+    pub slots: i64,
+    pub epochs: i64,
+    pub seconds: i64,
+}
+
+impl ClockOffset {
+    pub fn apply(&self, clock: &Clock) -> Clock {
+        Clock {
+            slot: clock.slot.saturating_add_signed(self.slots),
+            epoch_start_timestamp: clock.epoch_start_timestamp.saturating_add(self.seconds),
+            epoch: clock.epoch.saturating_add_signed(self.epochs),
+            leader_schedule_epoch: clock
+                .leader_schedule_epoch
+                .saturating_add_signed(self.epochs),
+            unix_timestamp: clock.unix_timestamp.saturating_add(self.seconds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_vote_interface::state::VoteStateV3;
+
+    use super::*;
+    use crate::seashell::{Config, Seashell};
+    use crate::vote::VoteAccountBuilder;
+
+    #[test]
+    fn test_fixture_clock_follows_warp() {
+        let seashell = Seashell::new_with_config(Config {
+            fixture_clock_offset: ClockOffset { slots: -10, seconds: -5, ..ClockOffset::default() },
+            ..Config::default()
+        });
+        seashell.warp(1000, 1_700_000_000);
+        let clock = seashell.fixture_clock();
+        assert_eq!(clock.slot, 990);
+        assert_eq!(clock.unix_timestamp, 1_699_999_995);
+
+        let vote_account = solana_pubkey::Pubkey::new_unique();
+        seashell.set_vote_account(vote_account, &VoteAccountBuilder::new(vote_account));
+        let vote_state = VoteStateV3::deserialize(&seashell.account(&vote_account).data).unwrap();
+        assert_eq!(vote_state.last_timestamp.slot, 990);
+        assert_eq!(vote_state.last_timestamp.timestamp, 1_699_999_995);
+    }
+}
@@ -0,0 +1,317 @@
+//! Estimates what a planned flow costs its fee payer, without running it: signature and priority
+//! fees, rent for the accounts it creates and the lamports it transfers out, e.g. tips. Tests and
+//! clients can check a payer is funded up front, then check the estimate once the flow ran:
+//!
+//! ```ignore
+//! let estimate = seashell.estimate_funding(&payer, &ixns)?;
+//! assert!(estimate.is_funded(), "short by {:?}", estimate.shortfall());
+//! let result = seashell.process_instruction_chain(ixns);
+//! assert_eq!(estimate.actual_spend(&result), Some(estimate.execution_cost()));
+//! ```
+//!
+//! Accounts are seen being created by System Program `CreateAccount` and
+//! `CreateAccountWithSeed`, and by the Associated Token Account program. Accounts other programs
+//! create through CPIs, like Anchor's `init`, are added with [`FundingEstimate::account_space`].
+
+use solana_account::ReadableAccount;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+
+use crate::compute_budget::compute_budget_limits;
+use crate::error::SeashellError;
+use crate::seashell::{InstructionChainResult, Seashell};
+use crate::spl::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_ACCOUNT_SIZE};
+
+/// Token-2022 associated token accounts carry the `ImmutableOwner` extension: the account type
+/// byte and an empty TLV entry.
+const TOKEN_2022_ATA_SIZE: usize = TOKEN_ACCOUNT_SIZE + 1 + 4;
+
+const CREATE_ACCOUNT: u32 = 0;
+const TRANSFER: u32 = 2;
+const CREATE_ACCOUNT_WITH_SEED: u32 = 3;
+
+/// An account the planned flow creates with the payer's lamports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedAccount {
+    /// The planned instruction creating it; `None` for accounts added by hand.
+    pub instruction_index: Option<usize>,
+    pub address: Option<Pubkey>,
+    pub space: usize,
+    /// Lamports the payer funds it with.
+    pub lamports: u64,
+}
+
+/// What a planned flow costs its fee payer. See [`Seashell::estimate_funding`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingEstimate {
+    pub payer: Pubkey,
+    /// The payer's balance when the estimate was made.
+    pub balance: u64,
+    pub signature_fee: u64,
+    pub priority_fee: u64,
+    pub created_accounts: Vec<PlannedAccount>,
+    /// Lamports transferred from the payer to other accounts, e.g. tips.
+    pub transfers: u64,
+    /// What the payer must keep if it keeps anything: the rent-exempt minimum of an account
+    /// without data.
+    pub payer_rent_exempt_minimum: u64,
+    rent: Rent,
+}
+
+impl FundingEstimate {
+    /// Adds an account of `space` bytes funded rent-exempt by the payer, for accounts the flow
+    /// creates through CPIs the estimate can't see.
+    pub fn account_space(mut self, space: usize) -> Self {
+        let lamports = self.rent.minimum_balance(space);
+        self.created_accounts.push(PlannedAccount {
+            instruction_index: None,
+            address: None,
+            space,
+            lamports,
+        });
+        self
+    }
+
+    pub fn fees(&self) -> u64 {
+        self.signature_fee.saturating_add(self.priority_fee)
+    }
+
+    pub fn rent(&self) -> u64 {
+        self.created_accounts
+            .iter()
+            .map(|account| account.lamports)
+            .fold(0, u64::saturating_add)
+    }
+
+    /// What running the flow costs the payer, fees aside.
+    pub fn execution_cost(&self) -> u64 {
+        self.rent().saturating_add(self.transfers)
+    }
+
+    pub fn required(&self) -> u64 {
+        self.fees().saturating_add(self.execution_cost())
+    }
+
+    /// Lamports the payer lacks: to cover [`Self::required`] and then either end up empty or
+    /// stay rent-exempt.
+    pub fn shortfall(&self) -> Option<u64> {
+        let required = self.required();
+        match self.balance.checked_sub(required) {
+            None => Some(required - self.balance),
+            Some(left) if left > 0 && left < self.payer_rent_exempt_minimum => {
+                Some(self.payer_rent_exempt_minimum - left)
+            }
+            Some(_) => None,
+        }
+    }
+
+    pub fn is_funded(&self) -> bool {
+        self.shortfall().is_none()
+    }
+
+    /// What running the flow actually cost the payer, `None` if it failed. Seashell charges no
+    /// fees, so this compares against [`Self::execution_cost`].
+    pub fn actual_spend(&self, result: &InstructionChainResult) -> Option<u64> {
+        if result.error.is_some() {
+            return None;
+        }
+        let post_balance = result
+            .post_account(&self.payer)
+            .map_or(self.balance, |account| account.lamports());
+        self.balance.checked_sub(post_balance)
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// The space and lamports of the account a System Program instruction creates, if it creates
+/// one.
+fn system_created_account(data: &[u8]) -> Option<(u64, u64)> {
+    let discriminator = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    let offset = match discriminator {
+        CREATE_ACCOUNT => 4,
+        CREATE_ACCOUNT_WITH_SEED => {
+            // Base pubkey, then the seed as a length-prefixed string.
+            let seed_len = read_u64(data, 36)? as usize;
+            44usize.checked_add(seed_len)?
+        }
+        _ => return None,
+    };
+    Some((read_u64(data, offset + 8)?, read_u64(data, offset)?))
+}
+
+impl Seashell {
+    /// Estimates what running `ixns` in one transaction costs `payer`, from the instructions
+    /// alone. Fails if the ComputeBudget instructions among them are invalid.
+    pub fn estimate_funding(
+        &self,
+        payer: &Pubkey,
+        ixns: &[Instruction],
+    ) -> Result<FundingEstimate, SeashellError> {
+        let rent = self.accounts_db.sysvars.rent();
+        let limits = compute_budget_limits(ixns, &self.feature_set)?;
+
+        let mut signers = vec![*payer];
+        let mut precompile_signatures = 0u64;
+        let mut created_accounts = Vec::new();
+        let mut transfers = 0u64;
+        for (index, ixn) in ixns.iter().enumerate() {
+            for meta in ixn.accounts.iter().filter(|meta| meta.is_signer) {
+                if !signers.contains(&meta.pubkey) {
+                    signers.push(meta.pubkey);
+                }
+            }
+            if agave_precompiles::is_precompile(&ixn.program_id, |_| true) {
+                precompile_signatures += u64::from(ixn.data.first().copied().unwrap_or(0));
+            }
+            let funded_by_payer = |position: usize| {
+                ixn.accounts.get(position).map(|meta| meta.pubkey) == Some(*payer)
+            };
+
+            if ixn.program_id == solana_sdk_ids::system_program::id() && funded_by_payer(0) {
+                if let Some((space, lamports)) = system_created_account(&ixn.data) {
+                    created_accounts.push(PlannedAccount {
+                        instruction_index: Some(index),
+                        address: ixn.accounts.get(1).map(|meta| meta.pubkey),
+                        space: space as usize,
+                        lamports,
+                    });
+                } else if ixn.data.get(..4) == Some(&TRANSFER.to_le_bytes()[..])
+                    && ixn.accounts.get(1).map(|meta| meta.pubkey) != Some(*payer)
+                {
+                    transfers = transfers.saturating_add(read_u64(&ixn.data, 4).unwrap_or(0));
+                }
+            } else if ixn.program_id == ASSOCIATED_TOKEN_PROGRAM_ID
+                && ixn.data.len() <= 1
+                && funded_by_payer(0)
+            {
+                // `Create` and `CreateIdempotent`, which only cost rent if the ATA is missing.
+                let Some(ata) = ixn.accounts.get(1).map(|meta| meta.pubkey) else {
+                    continue;
+                };
+                if self.accounts_db.account_maybe(&ata).is_some()
+                    || created_accounts
+                        .iter()
+                        .any(|account: &PlannedAccount| account.address == Some(ata))
+                {
+                    continue;
+                }
+                let token_program = ixn.accounts.get(5).map(|meta| meta.pubkey);
+                let space = if token_program == Some(TOKEN_2022_PROGRAM_ID) {
+                    TOKEN_2022_ATA_SIZE
+                } else {
+                    TOKEN_ACCOUNT_SIZE
+                };
+                created_accounts.push(PlannedAccount {
+                    instruction_index: Some(index),
+                    address: Some(ata),
+                    space,
+                    lamports: rent.minimum_balance(space),
+                });
+            }
+        }
+
+        let lamports_per_signature = self
+            .accounts_db
+            .sysvars
+            .blockhash_queue()
+            .lamports_per_signature();
+        let signature_fee = (signers.len() as u64 + precompile_signatures) * lamports_per_signature;
+        let priority_fee = (u128::from(limits.compute_unit_price)
+            * u128::from(limits.compute_unit_limit))
+        .div_ceil(1_000_000) as u64;
+
+        Ok(FundingEstimate {
+            payer: *payer,
+            balance: self
+                .accounts_db
+                .account_maybe(payer)
+                .map_or(0, |account| account.lamports()),
+            signature_fee,
+            priority_fee,
+            created_accounts,
+            transfers,
+            payer_rent_exempt_minimum: rent.minimum_balance(0),
+            rent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+    use solana_instruction::AccountMeta;
+    use solana_native_token::LAMPORTS_PER_SOL;
+
+    use super::*;
+    use crate::seashell::Config;
+
+    #[test]
+    fn test_estimate_funding() {
+        let seashell = Seashell::new_with_config(Config {
+            allow_uninitialized_accounts_local: true,
+            ..Config::default()
+        });
+        let (payer, new_account, tip) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        seashell.set_account(payer, Account { lamports: LAMPORTS_PER_SOL, ..Account::default() });
+        let rent = seashell.accounts_db.sysvars.rent();
+
+        let mut create = CREATE_ACCOUNT.to_le_bytes().to_vec();
+        create.extend_from_slice(&rent.minimum_balance(100).to_le_bytes());
+        create.extend_from_slice(&100u64.to_le_bytes());
+        create.extend_from_slice(Pubkey::new_unique().as_ref());
+        let mut transfer = TRANSFER.to_le_bytes().to_vec();
+        transfer.extend_from_slice(&10_000u64.to_le_bytes());
+        let system_program = solana_sdk_ids::system_program::id();
+        let ixns = vec![
+            Instruction {
+                program_id: system_program,
+                accounts: vec![AccountMeta::new(payer, true), AccountMeta::new(new_account, true)],
+                data: create,
+            },
+            Instruction {
+                program_id: system_program,
+                accounts: vec![AccountMeta::new(payer, true), AccountMeta::new(tip, false)],
+                data: transfer,
+            },
+        ];
+
+        let estimate = seashell.estimate_funding(&payer, &ixns).unwrap();
+        assert_eq!(estimate.signature_fee, 2 * 5000);
+        assert_eq!(estimate.priority_fee, 0);
+        assert_eq!(estimate.created_accounts.len(), 1);
+        assert_eq!(estimate.created_accounts[0].address, Some(new_account));
+        assert_eq!(estimate.rent(), rent.minimum_balance(100));
+        assert_eq!(estimate.transfers, 10_000);
+        assert!(estimate.is_funded());
+
+        let result = seashell.process_instruction_chain(ixns.clone());
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(estimate.actual_spend(&result), Some(estimate.execution_cost()));
+
+        // A CPI-created account and a priority fee of 1 lamport per 1000 units, on the 3k units
+        // reserved for each of the three builtin instructions.
+        let mut price = vec![3];
+        price.extend_from_slice(&1_000u64.to_le_bytes());
+        let priced = [
+            vec![Instruction {
+                program_id: solana_sdk_ids::compute_budget::id(),
+                accounts: Vec::new(),
+                data: price,
+            }],
+            ixns,
+        ]
+        .concat();
+        let estimate = seashell
+            .estimate_funding(&payer, &priced)
+            .unwrap()
+            .account_space(LAMPORTS_PER_SOL as usize);
+        assert_eq!(estimate.priority_fee, 9);
+        assert_eq!(estimate.created_accounts[1].instruction_index, None);
+        assert!(estimate.shortfall().unwrap() > 0);
+    }
+}
@@ -0,0 +1,334 @@
+//! Fuzz dictionaries and seed inputs derived from an Anchor IDL, to bias a fuzzer's instruction
+//! data toward inputs the program doesn't reject at deserialization: the instruction
+//! discriminators, the indices of the enum variants its arguments use, and the boundary values
+//! of its integer arguments.
+//!
+//! ```ignore
+//! let dictionary = seashell.fuzz_dictionary(&program_id).unwrap();
+//! dictionary.write_dict("fuzz/my_program.dict")?;
+//! dictionary.write_corpus("fuzz/corpus/process_instruction")?;
+//! ```
+//!
+//! then run e.g. `cargo fuzz run process_instruction -- -dict=fuzz/my_program.dict`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::idl::{Idl, IdlType, IdlTypeDefTy};
+use crate::seashell::Seashell;
+
+/// How deep defined types are followed, so self-referential types terminate.
+const MAX_TYPE_DEPTH: usize = 8;
+
+/// Tokens for the dictionary of a libFuzzer or AFL run, and seed inputs for its corpus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzDictionary {
+    /// Dictionary tokens by name, e.g. `ix_deposit` or `u64_max`.
+    pub entries: BTreeMap<String, Vec<u8>>,
+    /// Well-formed instruction data per instruction: every argument at its smallest value
+    /// (`<instruction>_min`) and at its largest (`<instruction>_max`).
+    pub seeds: BTreeMap<String, Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    Min,
+    Max,
+}
+
+impl FuzzDictionary {
+    pub fn from_idl(idl: &Idl) -> Self {
+        let mut dictionary = FuzzDictionary::default();
+        for instruction in &idl.instructions {
+            let discriminator = instruction.discriminator();
+            dictionary
+                .entries
+                .insert(token_name(&format!("ix_{}", instruction.name)), discriminator.clone());
+            for arg in &instruction.args {
+                dictionary.add_type_entries(idl, &arg.ty, 0);
+            }
+            for (suffix, boundary) in [("min", Boundary::Min), ("max", Boundary::Max)] {
+                let mut data = discriminator.clone();
+                for arg in &instruction.args {
+                    encode(idl, &arg.ty, boundary, 0, &mut data);
+                }
+                dictionary
+                    .seeds
+                    .insert(token_name(&format!("{}_{suffix}", instruction.name)), data);
+            }
+        }
+        dictionary
+    }
+
+    fn add_type_entries(&mut self, idl: &Idl, ty: &IdlType, depth: usize) {
+        if depth > MAX_TYPE_DEPTH {
+            return;
+        }
+        let mut integer = |name: &str, min: Vec<u8>, max: Vec<u8>, minus_one: Option<Vec<u8>>| {
+            let mut one = vec![0; min.len()];
+            one[0] = 1;
+            self.entries
+                .insert(format!("{name}_zero"), vec![0; min.len()]);
+            self.entries.insert(format!("{name}_one"), one);
+            self.entries.insert(format!("{name}_min"), min);
+            self.entries.insert(format!("{name}_max"), max);
+            if let Some(minus_one) = minus_one {
+                self.entries.insert(format!("{name}_minus_one"), minus_one);
+            }
+        };
+        match ty {
+            IdlType::U16 => integer("u16", vec![0; 2], u16::MAX.to_le_bytes().to_vec(), None),
+            IdlType::I16 => integer(
+                "i16",
+                i16::MIN.to_le_bytes().to_vec(),
+                i16::MAX.to_le_bytes().to_vec(),
+                Some(vec![0xff; 2]),
+            ),
+            IdlType::U32 => integer("u32", vec![0; 4], u32::MAX.to_le_bytes().to_vec(), None),
+            IdlType::I32 => integer(
+                "i32",
+                i32::MIN.to_le_bytes().to_vec(),
+                i32::MAX.to_le_bytes().to_vec(),
+                Some(vec![0xff; 4]),
+            ),
+            IdlType::U64 => integer("u64", vec![0; 8], u64::MAX.to_le_bytes().to_vec(), None),
+            IdlType::I64 => integer(
+                "i64",
+                i64::MIN.to_le_bytes().to_vec(),
+                i64::MAX.to_le_bytes().to_vec(),
+                Some(vec![0xff; 8]),
+            ),
+            IdlType::U128 => integer("u128", vec![0; 16], u128::MAX.to_le_bytes().to_vec(), None),
+            IdlType::I128 => integer(
+                "i128",
+                i128::MIN.to_le_bytes().to_vec(),
+                i128::MAX.to_le_bytes().to_vec(),
+                Some(vec![0xff; 16]),
+            ),
+            IdlType::Bytes | IdlType::String => self.add_length_entries(),
+            IdlType::Vec(inner) => {
+                self.add_length_entries();
+                self.add_type_entries(idl, inner, depth + 1);
+            }
+            IdlType::Option(inner) | IdlType::COption(inner) | IdlType::Array(inner, _) => {
+                self.add_type_entries(idl, inner, depth + 1)
+            }
+            IdlType::Defined(name) => match idl.type_by_name(name).map(|def| &def.ty) {
+                Some(IdlTypeDefTy::Struct { fields }) => {
+                    for field in fields.types() {
+                        self.add_type_entries(idl, field, depth + 1);
+                    }
+                }
+                Some(IdlTypeDefTy::Enum { variants }) => {
+                    for (index, variant) in variants.iter().enumerate() {
+                        self.entries.insert(
+                            token_name(&format!("{name}_{}", variant.name)),
+                            vec![index as u8],
+                        );
+                        for field in variant.fields.types() {
+                            self.add_type_entries(idl, field, depth + 1);
+                        }
+                    }
+                }
+                Some(IdlTypeDefTy::Type { alias }) => self.add_type_entries(idl, alias, depth + 1),
+                Some(IdlTypeDefTy::Other) | None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Borsh length prefixes: empty, a single element, and one no input could satisfy.
+    fn add_length_entries(&mut self) {
+        self.entries
+            .insert("len_zero".to_string(), 0u32.to_le_bytes().to_vec());
+        self.entries
+            .insert("len_one".to_string(), 1u32.to_le_bytes().to_vec());
+        self.entries
+            .insert("len_max".to_string(), u32::MAX.to_le_bytes().to_vec());
+    }
+
+    /// The dictionary in the `name="\x01\x02"` format libFuzzer's `-dict` and AFL's `-x` read.
+    pub fn to_dict(&self) -> String {
+        let mut dict = String::new();
+        for (name, bytes) in &self.entries {
+            let escaped: String = bytes.iter().map(|byte| format!("\\x{byte:02x}")).collect();
+            writeln!(dict, "{name}=\"{escaped}\"").unwrap();
+        }
+        dict
+    }
+
+    pub fn write_dict(&self, path: impl AsRef<Path>) -> Result<(), SeashellError> {
+        std::fs::write(path, self.to_dict())?;
+        Ok(())
+    }
+
+    /// Writes each seed input to its own file in `dir`, as fuzzers expect a corpus.
+    pub fn write_corpus(&self, dir: impl AsRef<Path>) -> Result<(), SeashellError> {
+        std::fs::create_dir_all(&dir)?;
+        for (name, data) in &self.seeds {
+            std::fs::write(dir.as_ref().join(name), data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Appends the Borsh encoding of `ty` at `boundary`: zero, empty, `None` and the first enum
+/// variant at the bottom; the largest value, one element, `Some` and the last variant at the top.
+fn encode(idl: &Idl, ty: &IdlType, boundary: Boundary, depth: usize, data: &mut Vec<u8>) {
+    if depth > MAX_TYPE_DEPTH {
+        return;
+    }
+    let max = boundary == Boundary::Max;
+    let mut integer = |len: usize, signed: bool| {
+        let start = data.len();
+        data.resize(start + len, if max { 0xff } else { 0 });
+        if signed {
+            // Little endian, so the sign bit is in the last byte.
+            data[start + len - 1] ^= 0x80;
+        }
+    };
+    match ty {
+        IdlType::Bool => data.push(max as u8),
+        IdlType::U8 => integer(1, false),
+        IdlType::I8 => integer(1, true),
+        IdlType::U16 => integer(2, false),
+        IdlType::I16 => integer(2, true),
+        IdlType::U32 => integer(4, false),
+        IdlType::I32 => integer(4, true),
+        IdlType::U64 => integer(8, false),
+        IdlType::I64 => integer(8, true),
+        IdlType::U128 => integer(16, false),
+        IdlType::I128 => integer(16, true),
+        IdlType::U256 => integer(32, false),
+        IdlType::I256 => integer(32, true),
+        IdlType::F32 => {
+            let value = if max { f32::MAX } else { 0.0 };
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        IdlType::F64 => {
+            let value = if max { f64::MAX } else { 0.0 };
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        IdlType::Pubkey => data.extend_from_slice(&[if max { 0xff } else { 0 }; 32]),
+        IdlType::Bytes | IdlType::String => {
+            data.extend_from_slice(&(max as u32).to_le_bytes());
+            if max {
+                data.push(b'a');
+            }
+        }
+        IdlType::Option(inner) => {
+            data.push(max as u8);
+            if max {
+                encode(idl, inner, boundary, depth + 1, data);
+            }
+        }
+        IdlType::COption(inner) => {
+            data.extend_from_slice(&(max as u32).to_le_bytes());
+            if max {
+                encode(idl, inner, boundary, depth + 1, data);
+            }
+        }
+        IdlType::Vec(inner) => {
+            data.extend_from_slice(&(max as u32).to_le_bytes());
+            if max {
+                encode(idl, inner, boundary, depth + 1, data);
+            }
+        }
+        IdlType::Array(inner, len) => {
+            for _ in 0..*len {
+                encode(idl, inner, boundary, depth + 1, data);
+            }
+        }
+        IdlType::Defined(name) => match idl.type_by_name(name).map(|def| &def.ty) {
+            Some(IdlTypeDefTy::Struct { fields }) => {
+                for field in fields.types() {
+                    encode(idl, field, boundary, depth + 1, data);
+                }
+            }
+            Some(IdlTypeDefTy::Enum { variants }) if !variants.is_empty() => {
+                let index = if max { variants.len() - 1 } else { 0 };
+                data.push(index as u8);
+                for field in variants[index].fields.types() {
+                    encode(idl, field, boundary, depth + 1, data);
+                }
+            }
+            Some(IdlTypeDefTy::Type { alias }) => encode(idl, alias, boundary, depth + 1, data),
+            _ => {}
+        },
+        IdlType::Other(_) => {}
+    }
+}
+
+/// `name` with everything but ASCII alphanumerics replaced, as dictionary token names allow.
+fn token_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+impl Seashell {
+    /// The fuzz dictionary of the IDL registered for `program_id` with `load_idl`.
+    pub fn fuzz_dictionary(&self, program_id: &Pubkey) -> Option<FuzzDictionary> {
+        self.idls.get(program_id).map(FuzzDictionary::from_idl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    #[test]
+    fn test_fuzz_dictionary_from_idl() {
+        let idl = Idl::from_json(
+            r#"{
+                "instructions": [
+                    {"name": "placeOrder", "args": [
+                        {"name": "side", "type": {"defined": "Side"}},
+                        {"name": "price", "type": "u64"},
+                        {"name": "offset", "type": "i32"},
+                        {"name": "memo", "type": {"option": "string"}}
+                    ]},
+                    {"name": "cancel", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8], "args": []}
+                ],
+                "types": [
+                    {"name": "Side", "type": {"kind": "enum", "variants": [{"name": "Bid"}, {"name": "Ask"}]}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let dictionary = FuzzDictionary::from_idl(&idl);
+
+        let place_order = idl
+            .instruction_by_name("placeOrder")
+            .unwrap()
+            .discriminator();
+        assert_eq!(place_order, Sha256::digest("global:place_order")[..8].to_vec());
+        assert_eq!(dictionary.entries["ix_placeOrder"], place_order);
+        assert_eq!(dictionary.entries["ix_cancel"], vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(dictionary.entries["Side_Ask"], vec![1]);
+        assert_eq!(dictionary.entries["u64_max"], vec![0xff; 8]);
+        assert_eq!(dictionary.entries["i32_min"], i32::MIN.to_le_bytes());
+        assert_eq!(dictionary.entries["len_zero"], vec![0; 4]);
+
+        let mut min = place_order.clone();
+        min.extend_from_slice(&[0; 1 + 8]);
+        min.extend_from_slice(&i32::MIN.to_le_bytes());
+        min.push(0);
+        assert_eq!(dictionary.seeds["placeOrder_min"], min);
+        let mut max = place_order;
+        max.push(1);
+        max.extend_from_slice(&u64::MAX.to_le_bytes());
+        max.extend_from_slice(&i32::MAX.to_le_bytes());
+        max.extend_from_slice(&[1, 1, 0, 0, 0, b'a']);
+        assert_eq!(dictionary.seeds["placeOrder_max"], max);
+        assert_eq!(dictionary.seeds["cancel_min"], vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        assert!(dictionary.to_dict().contains("Side_Bid=\"\\x00\"\n"));
+    }
+}
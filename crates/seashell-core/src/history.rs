@@ -0,0 +1,131 @@
+//! Runs one instruction against a series of scenario snapshots, to find out when mainnet state
+//! started breaking it.
+//!
+//! ```ignore
+//! let series = seashell.run_against_snapshots("scenarios/history", &ixn)?;
+//! println!("{series}");
+//! if let Some(outcome) = series.first_regression() {
+//!     panic!("Broke at {}: {:?}", outcome.label, outcome.error);
+//! }
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::SeashellError;
+use crate::seashell::InstructionProcessingError;
+
+/// The instruction's outcome against one snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotOutcome {
+    pub path: PathBuf,
+    /// The snapshot's file name without `.json.gz`, e.g. its timestamp.
+    pub label: String,
+    pub error: Option<InstructionProcessingError>,
+    pub compute_units_consumed: u64,
+    pub return_data: Vec<u8>,
+}
+
+impl SnapshotOutcome {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Outcomes in snapshot order, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotSeries {
+    pub outcomes: Vec<SnapshotOutcome>,
+}
+
+impl SnapshotSeries {
+    /// The first failure that follows a success: the snapshot where the instruction broke.
+    pub fn first_regression(&self) -> Option<&SnapshotOutcome> {
+        self.outcomes
+            .windows(2)
+            .find(|pair| pair[0].succeeded() && !pair[1].succeeded())
+            .map(|pair| &pair[1])
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SnapshotOutcome> {
+        self.outcomes.iter().filter(|outcome| !outcome.succeeded())
+    }
+}
+
+impl fmt::Display for SnapshotSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            match &outcome.error {
+                None => writeln!(
+                    f,
+                    "{}: ok, {} CU, return data {:?}",
+                    outcome.label, outcome.compute_units_consumed, outcome.return_data
+                )?,
+                Some(error) => writeln!(
+                    f,
+                    "{}: {error:?}, {} CU",
+                    outcome.label, outcome.compute_units_consumed
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `.json.gz` scenarios in `dir`, sorted by file name, so timestamped names come out in
+/// chronological order.
+pub fn snapshot_paths(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, SeashellError> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(".json.gz"))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+pub(crate) fn snapshot_label(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    name.strip_suffix(".json.gz").unwrap_or(&name).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::error::InstructionError;
+
+    use super::*;
+
+    #[test]
+    fn test_first_regression() {
+        let outcome = |label: &str, error| SnapshotOutcome {
+            path: PathBuf::from(format!("{label}.json.gz")),
+            label: label.to_string(),
+            error,
+            compute_units_consumed: 0,
+            return_data: Vec::new(),
+        };
+        let broken =
+            || Some(InstructionProcessingError::InstructionError(InstructionError::Custom(1)));
+        let series = SnapshotSeries {
+            outcomes: vec![
+                outcome("2024-01-01", broken()),
+                outcome("2024-02-01", None),
+                outcome("2024-03-01", broken()),
+                outcome("2024-04-01", broken()),
+            ],
+        };
+
+        assert_eq!(series.first_regression().unwrap().label, "2024-03-01");
+        assert_eq!(series.failures().count(), 3);
+        assert_eq!(snapshot_label(Path::new("history/2024-03-01.json.gz")), "2024-03-01");
+    }
+}
@@ -0,0 +1,507 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use solana_account::{Account, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::seashell::Seashell;
+
+/// Seed that, with the program's signer PDA as base, derives the canonical IDL account.
+const IDL_SEED: &str = "anchor:idl";
+
+/// The parts of an Anchor IDL seashell understands. Both the legacy and the 0.30+ formats are
+/// accepted; anything not modelled here is ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Idl {
+    #[serde(default)]
+    pub accounts: Vec<IdlAccountDef>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub errors: Vec<IdlErrorCode>,
+    #[serde(default)]
+    pub events: Vec<IdlEvent>,
+    #[serde(default)]
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IdlErrorCode {
+    pub code: u32,
+    pub name: String,
+    #[serde(default)]
+    pub msg: Option<String>,
+}
+
+/// An account type of the program. Legacy IDLs declare its type inline; 0.30+ IDLs declare it
+/// among `types` under the same name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlAccountDef {
+    pub name: String,
+    /// Only present in 0.30+ IDLs; legacy IDLs derive it from the name.
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    #[serde(default, rename = "type")]
+    pub ty: Option<IdlTypeDefTy>,
+}
+
+impl IdlAccountDef {
+    pub fn discriminator(&self) -> Vec<u8> {
+        match &self.discriminator {
+            Some(discriminator) => discriminator.clone(),
+            None => {
+                let hash = Sha256::digest(format!("account:{}", self.name));
+                hash[..8].to_vec()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlEvent {
+    pub name: String,
+    /// Only present in 0.30+ IDLs; legacy IDLs derive it from the name.
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    /// Only present in legacy IDLs; 0.30+ IDLs declare the event among `types`.
+    #[serde(default)]
+    pub fields: Vec<IdlField>,
+}
+
+impl IdlEvent {
+    pub fn discriminator(&self) -> [u8; 8] {
+        match self
+            .discriminator
+            .as_deref()
+            .and_then(|d| <[u8; 8]>::try_from(d).ok())
+        {
+            Some(discriminator) => discriminator,
+            None => {
+                let hash = Sha256::digest(format!("event:{}", self.name));
+                hash[..8].try_into().unwrap()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    /// Only present in 0.30+ IDLs; legacy IDLs derive it from the name.
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    pub accounts: Vec<IdlAccountItem>,
+    #[serde(default)]
+    pub args: Vec<IdlField>,
+}
+
+/// An account of an instruction, or a group of them nested as Anchor composite accounts are.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum IdlAccountItem {
+    Composite { name: String, accounts: Vec<IdlAccountItem> },
+    Account(IdlInstructionAccount),
+}
+
+/// An account an instruction expects, with the flags of either IDL format.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IdlInstructionAccount {
+    pub name: String,
+    #[serde(default, alias = "isMut")]
+    pub writable: bool,
+    #[serde(default, alias = "isSigner")]
+    pub signer: bool,
+    /// Optional accounts that are left out are passed as the program id.
+    #[serde(default, alias = "isOptional")]
+    pub optional: bool,
+}
+
+impl IdlInstruction {
+    /// The accounts in the order the instruction takes them, with composites flattened.
+    pub fn flat_accounts(&self) -> Vec<&IdlInstructionAccount> {
+        fn flatten<'a>(items: &'a [IdlAccountItem], accounts: &mut Vec<&'a IdlInstructionAccount>) {
+            for item in items {
+                match item {
+                    IdlAccountItem::Composite { accounts: nested, .. } => flatten(nested, accounts),
+                    IdlAccountItem::Account(account) => accounts.push(account),
+                }
+            }
+        }
+        let mut accounts = Vec::new();
+        flatten(&self.accounts, &mut accounts);
+        accounts
+    }
+
+    pub fn discriminator(&self) -> Vec<u8> {
+        match &self.discriminator {
+            Some(discriminator) => discriminator.clone(),
+            None => {
+                let hash = Sha256::digest(format!("global:{}", snake_case(&self.name)));
+                hash[..8].to_vec()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlType,
+}
+
+/// A Borsh type as IDLs spell it. Types seashell doesn't know, such as generics, are kept as
+/// [`IdlType::Other`] rather than failing the whole IDL.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "serde_json::Value")]
+pub enum IdlType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+    U128,
+    I128,
+    U256,
+    I256,
+    Bytes,
+    String,
+    Pubkey,
+    Option(Box<IdlType>),
+    /// A `COption`, with a 4-byte tag.
+    COption(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    /// A type from [`Idl::types`], or an account type a legacy IDL declares inline, by name.
+    Defined(String),
+    Other(serde_json::Value),
+}
+
+impl From<serde_json::Value> for IdlType {
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+
+        let primitive = match &value {
+            Value::String(name) => match name.as_str() {
+                "bool" => Some(IdlType::Bool),
+                "u8" => Some(IdlType::U8),
+                "i8" => Some(IdlType::I8),
+                "u16" => Some(IdlType::U16),
+                "i16" => Some(IdlType::I16),
+                "u32" => Some(IdlType::U32),
+                "i32" => Some(IdlType::I32),
+                "f32" => Some(IdlType::F32),
+                "u64" => Some(IdlType::U64),
+                "i64" => Some(IdlType::I64),
+                "f64" => Some(IdlType::F64),
+                "u128" => Some(IdlType::U128),
+                "i128" => Some(IdlType::I128),
+                "u256" => Some(IdlType::U256),
+                "i256" => Some(IdlType::I256),
+                "bytes" => Some(IdlType::Bytes),
+                "string" => Some(IdlType::String),
+                "pubkey" | "publicKey" => Some(IdlType::Pubkey),
+                _ => None,
+            },
+            Value::Object(object) if object.len() == 1 => {
+                let (kind, inner) = object.iter().next().unwrap();
+                let boxed = || Box::new(IdlType::from(inner.clone()));
+                match (kind.as_str(), inner) {
+                    ("option", _) => Some(IdlType::Option(boxed())),
+                    ("coption", _) => Some(IdlType::COption(boxed())),
+                    ("vec", _) => Some(IdlType::Vec(boxed())),
+                    ("array", Value::Array(array)) => match array.as_slice() {
+                        [ty, Value::Number(len)] => len.as_u64().map(|len| {
+                            IdlType::Array(Box::new(IdlType::from(ty.clone())), len as usize)
+                        }),
+                        _ => None,
+                    },
+                    // Legacy IDLs name the type directly, 0.30+ ones in an object.
+                    ("defined", Value::String(name)) => Some(IdlType::Defined(name.clone())),
+                    ("defined", Value::Object(defined)) => defined
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .map(|name| IdlType::Defined(name.to_string())),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        primitive.unwrap_or(IdlType::Other(value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefTy,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: IdlFields,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// The fields of a struct or enum variant: named, or a tuple of bare types.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+impl Default for IdlFields {
+    fn default() -> Self {
+        IdlFields::Named(Vec::new())
+    }
+}
+
+impl IdlFields {
+    pub fn types(&self) -> Vec<&IdlType> {
+        match self {
+            IdlFields::Named(fields) => fields.iter().map(|field| &field.ty).collect(),
+            IdlFields::Tuple(types) => types.iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: IdlFields,
+}
+
+/// `initializeMarket` to `initialize_market`, as Anchor derives legacy discriminators from.
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+impl Idl {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SeashellError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SeashellError> {
+        serde_json::from_str(json)
+            .map_err(|e| SeashellError::Custom(format!("Failed to parse IDL: {e}")))
+    }
+
+    pub fn error_by_name(&self, name: &str) -> Option<&IdlErrorCode> {
+        self.errors.iter().find(|error| error.name == name)
+    }
+
+    pub fn error_by_code(&self, code: u32) -> Option<&IdlErrorCode> {
+        self.errors.iter().find(|error| error.code == code)
+    }
+
+    pub fn instruction_by_name(&self, name: &str) -> Option<&IdlInstruction> {
+        self.instructions
+            .iter()
+            .find(|instruction| instruction.name == name)
+    }
+
+    /// The instruction whose discriminator `data` starts with.
+    pub fn instruction_for_data(&self, data: &[u8]) -> Option<&IdlInstruction> {
+        self.instructions
+            .iter()
+            .find(|instruction| data.starts_with(&instruction.discriminator()))
+    }
+
+    pub fn type_by_name(&self, name: &str) -> Option<&IdlTypeDef> {
+        self.types.iter().find(|ty| ty.name == name)
+    }
+
+    /// The definition of the type `name`, including account types legacy IDLs declare inline.
+    pub fn defined_type(&self, name: &str) -> Option<&IdlTypeDefTy> {
+        self.type_by_name(name).map(|def| &def.ty).or_else(|| {
+            self.accounts
+                .iter()
+                .find(|account| account.name == name)
+                .and_then(|account| account.ty.as_ref())
+        })
+    }
+
+    /// The account type whose discriminator `data` starts with.
+    pub fn account_for_data(&self, data: &[u8]) -> Option<&IdlAccountDef> {
+        self.accounts
+            .iter()
+            .find(|account| data.starts_with(&account.discriminator()))
+    }
+
+    pub fn event_by_discriminator(&self, discriminator: &[u8; 8]) -> Option<&IdlEvent> {
+        self.events
+            .iter()
+            .find(|event| event.discriminator() == *discriminator)
+    }
+}
+
+/// Address of the canonical IDL account of `program_id`, the one `anchor idl init` creates and
+/// explorers and client generators read.
+pub fn idl_address(program_id: &Pubkey) -> Pubkey {
+    let base = Pubkey::find_program_address(&[], program_id).0;
+    // `Pubkey::create_with_seed(&base, IDL_SEED, program_id)`
+    let hash = Sha256::new()
+        .chain_update(base)
+        .chain_update(IDL_SEED)
+        .chain_update(program_id)
+        .finalize();
+    Pubkey::new_from_array(hash.into())
+}
+
+/// An Anchor `IdlAccount`: the canonical IDL account of a program, or an IDL buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdlAccount {
+    pub authority: Pubkey,
+    pub json: String,
+}
+
+impl IdlAccount {
+    fn discriminator() -> [u8; 8] {
+        Sha256::digest("account:IdlAccount")[..8]
+            .try_into()
+            .unwrap()
+    }
+
+    /// The account data: discriminator, authority, then the length of the zlib-compressed JSON
+    /// and the JSON itself.
+    pub fn to_data(&self) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(self.json.as_bytes())
+            .expect("Failed to compress IDL");
+        let compressed = encoder.finish().expect("Failed to compress IDL");
+
+        let mut data = Self::discriminator().to_vec();
+        data.extend_from_slice(self.authority.as_ref());
+        data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        data.extend_from_slice(&compressed);
+        data
+    }
+
+    pub fn from_data(data: &[u8]) -> Result<Self, SeashellError> {
+        let invalid =
+            |reason: &str| SeashellError::Custom(format!("Invalid IDL account: {reason}"));
+        if data.len() < 44 || data[..8] != Self::discriminator() {
+            return Err(invalid("not an IdlAccount"));
+        }
+        let authority = Pubkey::try_from(&data[8..40]).unwrap();
+        let len = u32::from_le_bytes(data[40..44].try_into().unwrap()) as usize;
+        let compressed = data
+            .get(44..44 + len)
+            .ok_or_else(|| invalid("data shorter than its length"))?;
+        let mut json = String::new();
+        ZlibDecoder::new(compressed)
+            .read_to_string(&mut json)
+            .map_err(|e| invalid(&e.to_string()))?;
+        Ok(IdlAccount { authority, json })
+    }
+
+    pub fn idl(&self) -> Result<Idl, SeashellError> {
+        Idl::from_json(&self.json)
+    }
+}
+
+impl Seashell {
+    /// Writes the canonical IDL account of `program_id` holding `idl_json`, as `anchor idl init`
+    /// does, and returns its address.
+    pub fn set_idl_account(&self, program_id: Pubkey, authority: Pubkey, idl_json: &str) -> Pubkey {
+        let address = idl_address(&program_id);
+        self.set_idl_buffer(address, program_id, authority, idl_json);
+        address
+    }
+
+    /// Writes an IDL buffer at `buffer`, as `anchor idl write-buffer` does, e.g. as the source of
+    /// an `idl set-buffer` upgrade.
+    pub fn set_idl_buffer(
+        &self,
+        buffer: Pubkey,
+        program_id: Pubkey,
+        authority: Pubkey,
+        idl_json: &str,
+    ) {
+        let data = IdlAccount { authority, json: idl_json.to_string() }.to_data();
+        self.set_account(
+            buffer,
+            Account {
+                lamports: self.accounts_db.sysvars.rent().minimum_balance(data.len()),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+    }
+
+    /// Reads the canonical IDL account of `program_id`.
+    pub fn idl_account(&self, program_id: &Pubkey) -> Result<IdlAccount, SeashellError> {
+        let address = idl_address(program_id);
+        let account = self
+            .accounts_db
+            .account_maybe(&address)
+            .ok_or_else(|| SeashellError::Custom(format!("{program_id} has no IDL account")))?;
+        IdlAccount::from_data(account.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idl_account() {
+        let seashell = Seashell::new();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let json = r#"{"address":"Prog1111111111111111111111111111111111111111","errors":[{"code":6000,"name":"Stale"}]}"#;
+        let address = seashell.set_idl_account(program_id, authority, json);
+        assert_eq!(address, idl_address(&program_id));
+        let base = Pubkey::find_program_address(&[], &program_id).0;
+        assert_eq!(address, Pubkey::create_with_seed(&base, IDL_SEED, &program_id).unwrap());
+        assert_eq!(seashell.account(&address).owner, program_id);
+
+        let idl_account = seashell.idl_account(&program_id).unwrap();
+        assert_eq!(idl_account, IdlAccount { authority, json: json.to_string() });
+        assert_eq!(idl_account.idl().unwrap().error_by_code(6000).unwrap().name, "Stale");
+        assert!(seashell.idl_account(&Pubkey::new_unique()).is_err());
+        assert!(IdlAccount::from_data(&[0; 44]).is_err());
+    }
+}
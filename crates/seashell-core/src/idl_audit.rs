@@ -0,0 +1,185 @@
+//! Cross-checks the accounts an instruction wrote against the mutability its program's IDL
+//! declares, catching IDL drift that breaks clients: an account declared `mut` that the
+//! instruction never writes makes clients lock it for nothing, and one written without being
+//! declared `mut` fails for clients building the instruction from the IDL.
+//!
+//! Instructions to programs with an IDL registered through [`Seashell::load_idl`] are audited
+//! automatically; mismatches are logged as warnings and reported in
+//! `InstructionProcessingResult::mutability_mismatches`. Only successful top-level instructions
+//! are audited, since failed ones write nothing and CPIs don't report their own writes.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+use crate::diff::AccountDiff;
+use crate::idl::Idl;
+use crate::seashell::{InstructionProcessingResult, Seashell};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutabilityMismatchKind {
+    /// Declared mutable, but not written.
+    Unwritten,
+    /// Written, but not declared mutable.
+    Undeclared,
+}
+
+/// An account whose write disagrees with the IDL of the instruction's program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutabilityMismatch {
+    pub program_id: Pubkey,
+    pub instruction: String,
+    /// The name of the account in the IDL.
+    pub account: String,
+    pub pubkey: Pubkey,
+    pub kind: MutabilityMismatchKind,
+}
+
+impl fmt::Display for MutabilityMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let MutabilityMismatch { program_id, instruction, account, pubkey, .. } = self;
+        match self.kind {
+            MutabilityMismatchKind::Unwritten => write!(
+                f,
+                "{instruction} of {program_id} declares {account} ({pubkey}) mutable, but didn't \
+                 write it"
+            ),
+            MutabilityMismatchKind::Undeclared => write!(
+                f,
+                "{instruction} of {program_id} wrote {account} ({pubkey}), which its IDL doesn't \
+                 declare mutable"
+            ),
+        }
+    }
+}
+
+/// The mismatches between the accounts `ixn` declares mutable in `idl` and those it changed
+/// according to `diffs`. Instructions the IDL doesn't know and remaining accounts past the
+/// declared ones are skipped, as are optional accounts left out.
+pub fn audit_mutability(
+    idl: &Idl,
+    ixn: &Instruction,
+    diffs: &[AccountDiff],
+) -> Vec<MutabilityMismatch> {
+    let Some(instruction) = idl.instruction_for_data(&ixn.data) else {
+        return Vec::new();
+    };
+    let written: HashSet<Pubkey> = diffs.iter().map(|diff| diff.pubkey).collect();
+    let declared: Vec<_> = instruction
+        .flat_accounts()
+        .into_iter()
+        .zip(&ixn.accounts)
+        .filter(|(account, meta)| !(account.optional && meta.pubkey == ixn.program_id))
+        .collect();
+    let declared_mutable: HashSet<Pubkey> = declared
+        .iter()
+        .filter(|(account, _)| account.writable)
+        .map(|(_, meta)| meta.pubkey)
+        .collect();
+
+    let mut mismatches = Vec::new();
+    let mut seen = HashSet::new();
+    for (account, meta) in declared {
+        let kind = match (account.writable, written.contains(&meta.pubkey)) {
+            (true, false) => MutabilityMismatchKind::Unwritten,
+            (false, true) if !declared_mutable.contains(&meta.pubkey) => {
+                MutabilityMismatchKind::Undeclared
+            }
+            _ => continue,
+        };
+        if seen.insert(meta.pubkey) {
+            mismatches.push(MutabilityMismatch {
+                program_id: ixn.program_id,
+                instruction: instruction.name.clone(),
+                account: account.name.clone(),
+                pubkey: meta.pubkey,
+                kind,
+            });
+        }
+    }
+    mismatches
+}
+
+impl Seashell {
+    /// Audits `ixn` against the IDL registered for its program, if any, logging a warning per
+    /// mismatch.
+    pub(crate) fn audit_idl_mutability(
+        &self,
+        ixn: &Instruction,
+        result: &InstructionProcessingResult,
+    ) -> Vec<MutabilityMismatch> {
+        let Some(idl) = self.idls.get(&ixn.program_id) else {
+            return Vec::new();
+        };
+        if result.error.is_some() {
+            return Vec::new();
+        }
+        let mismatches = audit_mutability(idl, ixn, &result.account_diffs);
+        for mismatch in &mismatches {
+            log::warn!("{mismatch}");
+        }
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::AccountSharedData;
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_audit_mutability() {
+        let idl = Idl::from_json(
+            r#"{"instructions":[{"name":"deposit","discriminator":[1],"accounts":[
+                {"name":"vault","writable":true},
+                {"name":"config","writable":true},
+                {"name":"group","accounts":[{"name":"user","isMut":false,"isSigner":true}]},
+                {"name":"referrer","writable":true,"optional":true}
+            ],"args":[]}]}"#,
+        )
+        .unwrap();
+        let program_id = Pubkey::new_unique();
+        let (vault, config, user) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ixn = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(vault, false),
+                AccountMeta::new(config, false),
+                AccountMeta::new(user, true),
+                AccountMeta::new_readonly(program_id, false),
+            ],
+            data: vec![1],
+        };
+        let written = |pubkey| {
+            AccountDiff::between(
+                pubkey,
+                &AccountSharedData::new(1, 0, &program_id),
+                &AccountSharedData::new(2, 0, &program_id),
+            )
+            .unwrap()
+        };
+
+        let mismatches = audit_mutability(&idl, &ixn, &[written(vault), written(user)]);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|mismatch| (mismatch.account.as_str(), mismatch.kind))
+                .collect::<Vec<_>>(),
+            [
+                ("config", MutabilityMismatchKind::Unwritten),
+                ("user", MutabilityMismatchKind::Undeclared)
+            ]
+        );
+        assert!(mismatches[1]
+            .to_string()
+            .contains("doesn't declare mutable"));
+
+        let unknown = Instruction { data: vec![2], ..ixn };
+        assert!(audit_mutability(&idl, &unknown, &[]).is_empty());
+    }
+}
@@ -0,0 +1,433 @@
+//! Builds Anchor instructions from an IDL by name, with arguments Borsh-encoded from the types
+//! the IDL declares and the discriminator prepended, instead of packing bytes by hand:
+//!
+//! ```ignore
+//! let ixn = idl
+//!     .instruction("place_order")
+//!     .arg("side", "Bid")
+//!     .arg("price", 100u64)
+//!     .account("market", market)
+//!     .account("owner", owner)
+//!     .build()?;
+//! ```
+//!
+//! Arguments are anything `Serialize`, matched against their IDL type: numbers (or strings, for
+//! integers past `u64`), strings, pubkeys as base58 strings or bytes, `None` for options, arrays
+//! for vectors and tuples, objects for structs, and variant names or `{"Variant": fields}`
+//! objects for enums, as serde lays out Rust enums. Legacy camelCase names can be given in
+//! snake_case.
+
+use serde::Serialize;
+use serde_json::Value;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::idl::{snake_case, Idl, IdlFields, IdlInstruction, IdlType, IdlTypeDefTy};
+
+/// How deep defined types are followed, so self-referential types terminate.
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// An instruction being built with [`Idl::instruction`]. Errors are reported by [`Self::build`].
+pub struct IdlInstructionBuilder<'a> {
+    idl: &'a Idl,
+    name: String,
+    program_id: Option<Pubkey>,
+    args: Vec<(String, Result<Value, String>)>,
+    accounts: Vec<(String, Pubkey)>,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl Idl {
+    /// Starts building the instruction named `name`, for the program at the IDL's `address`.
+    pub fn instruction(&self, name: &str) -> IdlInstructionBuilder<'_> {
+        IdlInstructionBuilder {
+            idl: self,
+            name: name.to_string(),
+            program_id: self
+                .address
+                .as_deref()
+                .and_then(|address| address.parse().ok()),
+            args: Vec::new(),
+            accounts: Vec::new(),
+            remaining_accounts: Vec::new(),
+        }
+    }
+}
+
+/// Whether `name` is how an IDL names `given`, allowing snake_case for camelCase names.
+pub(crate) fn same_name(name: &str, given: &str) -> bool {
+    name == given || snake_case(name) == given
+}
+
+impl IdlInstructionBuilder<'_> {
+    /// Targets `program_id`, for IDLs without an address or deployed elsewhere.
+    pub fn program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = Some(program_id);
+        self
+    }
+
+    pub fn arg(mut self, name: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).map_err(|e| e.to_string());
+        self.args.push((name.to_string(), value));
+        self
+    }
+
+    /// Passes `pubkey` as the account named `name`, with the flags the IDL declares. Optional
+    /// accounts left out are passed as the program id.
+    pub fn account(mut self, name: &str, pubkey: Pubkey) -> Self {
+        self.accounts.push((name.to_string(), pubkey));
+        self
+    }
+
+    /// Appends `metas` after the declared accounts.
+    pub fn remaining_accounts(mut self, metas: impl IntoIterator<Item = AccountMeta>) -> Self {
+        self.remaining_accounts.extend(metas);
+        self
+    }
+
+    pub fn build(self) -> Result<Instruction, SeashellError> {
+        let error = |message: String| {
+            SeashellError::Custom(format!("Failed to build instruction {}: {message}", self.name))
+        };
+        let instruction: &IdlInstruction = self
+            .idl
+            .instructions
+            .iter()
+            .find(|instruction| same_name(&instruction.name, &self.name))
+            .ok_or_else(|| error("not in the IDL".to_string()))?;
+        let program_id = self
+            .program_id
+            .ok_or_else(|| error("the IDL has no address; set a program id".to_string()))?;
+
+        let mut data = instruction.discriminator();
+        for field in &instruction.args {
+            let (_, value) = self
+                .args
+                .iter()
+                .find(|(name, _)| same_name(&field.name, name))
+                .ok_or_else(|| error(format!("missing argument {}", field.name)))?;
+            let value = value
+                .as_ref()
+                .map_err(|e| error(format!("argument {}: {e}", field.name)))?;
+            encode_value(self.idl, &field.ty, value, &mut data)
+                .map_err(|e| error(format!("argument {}: {e}", field.name)))?;
+        }
+        if let Some((name, _)) = self.args.iter().find(|(name, _)| {
+            !instruction
+                .args
+                .iter()
+                .any(|field| same_name(&field.name, name))
+        }) {
+            return Err(error(format!("unknown argument {name}")));
+        }
+
+        let declared = instruction.flat_accounts();
+        let mut accounts = Vec::with_capacity(declared.len() + self.remaining_accounts.len());
+        for account in &declared {
+            let pubkey = match self
+                .accounts
+                .iter()
+                .find(|(name, _)| same_name(&account.name, name))
+            {
+                Some((_, pubkey)) => *pubkey,
+                None if account.optional => {
+                    accounts.push(AccountMeta::new_readonly(program_id, false));
+                    continue;
+                }
+                None => return Err(error(format!("missing account {}", account.name))),
+            };
+            accounts.push(if account.writable {
+                AccountMeta::new(pubkey, account.signer)
+            } else {
+                AccountMeta::new_readonly(pubkey, account.signer)
+            });
+        }
+        if let Some((name, _)) = self.accounts.iter().find(|(name, _)| {
+            !declared
+                .iter()
+                .any(|account| same_name(&account.name, name))
+        }) {
+            return Err(error(format!("unknown account {name}")));
+        }
+        accounts.extend(self.remaining_accounts);
+
+        Ok(Instruction { program_id, accounts, data })
+    }
+}
+
+/// Appends the Borsh encoding of `value` as `ty`, resolving defined types in `idl`.
+pub fn encode_value(
+    idl: &Idl,
+    ty: &IdlType,
+    value: &Value,
+    data: &mut Vec<u8>,
+) -> Result<(), String> {
+    encode(idl, ty, value, 0, data)
+}
+
+fn encode(
+    idl: &Idl,
+    ty: &IdlType,
+    value: &Value,
+    depth: usize,
+    data: &mut Vec<u8>,
+) -> Result<(), String> {
+    if depth > MAX_TYPE_DEPTH {
+        return Err("type nested too deeply".to_string());
+    }
+    let mismatch = || format!("expected {ty:?}, got {value}");
+    macro_rules! integer {
+        ($int:ty) => {
+            data.extend_from_slice(&integer::<$int>(value).ok_or_else(mismatch)?.to_le_bytes())
+        };
+    }
+    match ty {
+        IdlType::Bool => data.push(value.as_bool().ok_or_else(mismatch)? as u8),
+        IdlType::U8 => integer!(u8),
+        IdlType::I8 => integer!(i8),
+        IdlType::U16 => integer!(u16),
+        IdlType::I16 => integer!(i16),
+        IdlType::U32 => integer!(u32),
+        IdlType::I32 => integer!(i32),
+        IdlType::U64 => integer!(u64),
+        IdlType::I64 => integer!(i64),
+        IdlType::U128 => integer!(u128),
+        IdlType::I128 => integer!(i128),
+        IdlType::F32 => {
+            let float = value.as_f64().ok_or_else(mismatch)? as f32;
+            data.extend_from_slice(&float.to_le_bytes());
+        }
+        IdlType::F64 => data.extend_from_slice(&value.as_f64().ok_or_else(mismatch)?.to_le_bytes()),
+        IdlType::String => {
+            let string = value.as_str().ok_or_else(mismatch)?;
+            data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+            data.extend_from_slice(string.as_bytes());
+        }
+        IdlType::Bytes => {
+            let bytes = bytes(value).ok_or_else(mismatch)?;
+            data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+        IdlType::Pubkey => {
+            let pubkey = match value {
+                Value::String(string) => string.parse::<Pubkey>().ok(),
+                _ => bytes(value).and_then(|bytes| Pubkey::try_from(bytes.as_slice()).ok()),
+            };
+            data.extend_from_slice(pubkey.ok_or_else(mismatch)?.as_ref());
+        }
+        IdlType::Option(inner) => match value {
+            Value::Null => data.push(0),
+            _ => {
+                data.push(1);
+                encode(idl, inner, value, depth + 1, data)?;
+            }
+        },
+        IdlType::COption(inner) => match value {
+            Value::Null => data.extend_from_slice(&0u32.to_le_bytes()),
+            _ => {
+                data.extend_from_slice(&1u32.to_le_bytes());
+                encode(idl, inner, value, depth + 1, data)?;
+            }
+        },
+        IdlType::Vec(inner) => {
+            let elements = value.as_array().ok_or_else(mismatch)?;
+            data.extend_from_slice(&(elements.len() as u32).to_le_bytes());
+            for element in elements {
+                encode(idl, inner, element, depth + 1, data)?;
+            }
+        }
+        IdlType::Array(inner, len) => {
+            let elements = value
+                .as_array()
+                .filter(|elements| elements.len() == *len)
+                .ok_or_else(mismatch)?;
+            for element in elements {
+                encode(idl, inner, element, depth + 1, data)?;
+            }
+        }
+        IdlType::Defined(name) => {
+            let def = idl
+                .defined_type(name)
+                .ok_or_else(|| format!("type {name} is not in the IDL"))?;
+            match def {
+                IdlTypeDefTy::Struct { fields } => encode_fields(idl, fields, value, depth, data)?,
+                IdlTypeDefTy::Enum { variants } => {
+                    let (variant, fields_value) = match value {
+                        Value::String(variant) => (variant, &Value::Null),
+                        Value::Object(object) if object.len() == 1 => object.iter().next().unwrap(),
+                        _ => return Err(mismatch()),
+                    };
+                    let index = variants
+                        .iter()
+                        .position(|candidate| same_name(&candidate.name, variant))
+                        .ok_or_else(|| format!("{name} has no variant {variant}"))?;
+                    data.push(index as u8);
+                    encode_fields(idl, &variants[index].fields, fields_value, depth, data)?;
+                }
+                IdlTypeDefTy::Type { alias } => encode(idl, alias, value, depth + 1, data)?,
+                IdlTypeDefTy::Other => return Err(format!("type {name} can't be encoded")),
+            }
+        }
+        IdlType::U256 | IdlType::I256 | IdlType::Other(_) => {
+            return Err(format!("{ty:?} can't be encoded"))
+        }
+    }
+    Ok(())
+}
+
+/// Appends `fields` in order, from an object for named fields and an array for a tuple. A
+/// tuple of one field may also be given as the bare value, as serde lays out newtypes.
+fn encode_fields(
+    idl: &Idl,
+    fields: &IdlFields,
+    value: &Value,
+    depth: usize,
+    data: &mut Vec<u8>,
+) -> Result<(), String> {
+    match fields {
+        IdlFields::Named(fields) if fields.is_empty() => Ok(()),
+        IdlFields::Named(fields) => {
+            let object = value
+                .as_object()
+                .ok_or_else(|| format!("expected an object, got {value}"))?;
+            for field in fields {
+                let (_, field_value) = object
+                    .iter()
+                    .find(|(name, _)| same_name(&field.name, name))
+                    .ok_or_else(|| format!("missing field {}", field.name))?;
+                encode(idl, &field.ty, field_value, depth + 1, data)?;
+            }
+            Ok(())
+        }
+        IdlFields::Tuple(types) => {
+            let single = [value.clone()];
+            let elements = match value.as_array() {
+                Some(elements) if elements.len() == types.len() => elements.as_slice(),
+                _ if types.len() == 1 => &single,
+                _ => return Err(format!("expected {} fields, got {value}", types.len())),
+            };
+            for (ty, element) in types.iter().zip(elements) {
+                encode(idl, ty, element, depth + 1, data)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `value` as an integer: a JSON number, or a decimal string for values JSON numbers can't hold.
+fn integer<T>(value: &Value) -> Option<T>
+where
+    T: TryFrom<u64> + TryFrom<i64> + std::str::FromStr,
+{
+    match value {
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(unsigned), _) => T::try_from(unsigned).ok(),
+            (None, Some(signed)) => T::try_from(signed).ok(),
+            (None, None) => None,
+        },
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+/// `value` as bytes: an array of byte values.
+fn bytes(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|byte| integer::<u8>(byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    #[test]
+    fn test_idl_instruction_builder() {
+        let program_id = Pubkey::new_unique();
+        let idl = Idl::from_json(&format!(
+            r#"{{
+                "address": "{program_id}",
+                "instructions": [{{
+                    "name": "placeOrder",
+                    "accounts": [
+                        {{"name": "market", "isMut": true, "isSigner": false}},
+                        {{"name": "owner", "isMut": false, "isSigner": true}},
+                        {{"name": "referrer", "isMut": true, "isSigner": false, "isOptional": true}}
+                    ],
+                    "args": [
+                        {{"name": "side", "type": {{"defined": "Side"}}}},
+                        {{"name": "price", "type": "u64"}},
+                        {{"name": "size", "type": "u128"}},
+                        {{"name": "limits", "type": {{"defined": "Limits"}}}},
+                        {{"name": "memo", "type": {{"option": "string"}}}}
+                    ]
+                }}],
+                "types": [
+                    {{"name": "Side", "type": {{"kind": "enum", "variants": [
+                        {{"name": "Bid"}},
+                        {{"name": "Ask", "fields": ["u8"]}}
+                    ]}}}},
+                    {{"name": "Limits", "type": {{"kind": "struct", "fields": [
+                        {{"name": "minOut", "type": "i32"}},
+                        {{"name": "authority", "type": "pubkey"}}
+                    ]}}}}
+                ]
+            }}"#
+        ))
+        .unwrap();
+        let (market, owner, authority) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let ixn = idl
+            .instruction("place_order")
+            .arg("side", serde_json::json!({"Ask": 7}))
+            .arg("price", 100u64)
+            .arg("size", u128::MAX.to_string())
+            .arg("limits", serde_json::json!({"min_out": -1, "authority": authority.to_string()}))
+            .arg("memo", Option::<String>::None)
+            .account("market", market)
+            .account("owner", owner)
+            .build()
+            .unwrap();
+        assert_eq!(ixn.program_id, program_id);
+        assert_eq!(
+            ixn.accounts,
+            [
+                AccountMeta::new(market, false),
+                AccountMeta::new_readonly(owner, true),
+                AccountMeta::new_readonly(program_id, false)
+            ]
+        );
+        let mut data = Sha256::digest("global:place_order")[..8].to_vec();
+        data.extend_from_slice(&[1, 7]);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&u128::MAX.to_le_bytes());
+        data.extend_from_slice(&(-1i32).to_le_bytes());
+        data.extend_from_slice(authority.as_ref());
+        data.push(0);
+        assert_eq!(ixn.data, data);
+
+        let missing = idl
+            .instruction("placeOrder")
+            .arg("side", "Bid")
+            .arg("price", 1u64)
+            .build()
+            .unwrap_err();
+        assert!(missing.to_string().contains("missing argument size"));
+        let wrong_type = idl
+            .instruction("placeOrder")
+            .arg("side", "Bid")
+            .arg("price", -1)
+            .build()
+            .unwrap_err();
+        assert!(wrong_type
+            .to_string()
+            .contains("argument price: expected U64"));
+        assert!(idl.instruction("cancel").build().is_err());
+    }
+}
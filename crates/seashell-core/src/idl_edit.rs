@@ -0,0 +1,371 @@
+//! Edits Anchor account data by field name through the program's IDL, instead of patching bytes
+//! at offsets:
+//!
+//! ```ignore
+//! seashell.load_idl(program_id, "target/idl/market.json")?;
+//! seashell.set_idl_field(&market, "authority", my_key)?;
+//! seashell.edit_idl_account(&market, |market| {
+//!     market.set("fees.maker_bps", 0)?;
+//!     market.set("paused", false)
+//! })?;
+//! ```
+//!
+//! Accounts are Borsh-decoded into JSON as [`crate::idl_builder`] encodes arguments, so decoded
+//! fields can be written back as they are: integers past `u64` and pubkeys are strings, bytes
+//! are arrays, unit enum variants are their name and other variants `{"Variant": fields}`.
+//! Zero-copy accounts aren't Borsh-encoded and can't be edited this way.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use solana_account::ReadableAccount;
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::idl::{Idl, IdlFields, IdlType, IdlTypeDefTy};
+use crate::idl_builder::{encode_value, same_name};
+use crate::seashell::Seashell;
+
+/// How deep defined types are followed, so self-referential types terminate.
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// The data of an account, decoded through its program's IDL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedIdlAccount {
+    /// The name of the account type in the IDL.
+    pub name: String,
+    pub discriminator: Vec<u8>,
+    pub fields: Value,
+    /// Bytes past the encoded fields, like space reserved for growth, kept as they are.
+    pub trailing: Vec<u8>,
+}
+
+impl DecodedIdlAccount {
+    /// The value at `path`: field names separated by dots, and indices for vectors and arrays,
+    /// like `fees.maker_bps` or `slots.3`. Legacy camelCase names can be given in snake_case.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(&self.fields, |value, segment| match value {
+                Value::Object(object) => object
+                    .iter()
+                    .find(|(name, _)| same_name(name, segment))
+                    .map(|(_, value)| value),
+                Value::Array(elements) => elements.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    /// Replaces the value at `path`, which must already exist. The new value is checked against
+    /// the IDL when the account is encoded.
+    pub fn set(&mut self, path: &str, value: impl Serialize) -> Result<(), SeashellError> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| SeashellError::Custom(format!("Invalid value for {path}: {e}")))?;
+        let missing = || SeashellError::Custom(format!("{} has no field {path}", self.name));
+        let mut target = &mut self.fields;
+        for segment in path.split('.') {
+            target = match target {
+                Value::Object(object) => object
+                    .iter_mut()
+                    .find(|(name, _)| same_name(name, segment))
+                    .map(|(_, value)| value),
+                Value::Array(elements) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| elements.get_mut(index)),
+                _ => None,
+            }
+            .ok_or_else(missing)?;
+        }
+        *target = value;
+        Ok(())
+    }
+}
+
+impl Idl {
+    /// Decodes `data` as the account type whose discriminator it starts with.
+    pub fn decode_account(&self, data: &[u8]) -> Result<DecodedIdlAccount, SeashellError> {
+        let account = self.account_for_data(data).ok_or_else(|| {
+            SeashellError::Custom("No account type of the IDL matches the data".to_string())
+        })?;
+        let discriminator = account.discriminator();
+        let mut rest = &data[discriminator.len()..];
+        let fields = decode_value(self, &IdlType::Defined(account.name.clone()), &mut rest)
+            .map_err(|e| {
+                SeashellError::Custom(format!("Failed to decode account {}: {e}", account.name))
+            })?;
+        Ok(DecodedIdlAccount {
+            name: account.name.clone(),
+            discriminator,
+            fields,
+            trailing: rest.to_vec(),
+        })
+    }
+
+    /// Encodes `account` back into account data, the inverse of [`Idl::decode_account`].
+    pub fn encode_account(&self, account: &DecodedIdlAccount) -> Result<Vec<u8>, SeashellError> {
+        let mut data = account.discriminator.clone();
+        encode_value(self, &IdlType::Defined(account.name.clone()), &account.fields, &mut data)
+            .map_err(|e| {
+                SeashellError::Custom(format!("Failed to encode account {}: {e}", account.name))
+            })?;
+        data.extend_from_slice(&account.trailing);
+        Ok(data)
+    }
+}
+
+/// Decodes a Borsh value of type `ty` from the front of `data`, advancing past it.
+pub fn decode_value(idl: &Idl, ty: &IdlType, data: &mut &[u8]) -> Result<Value, String> {
+    decode(idl, ty, data, 0)
+}
+
+fn decode(idl: &Idl, ty: &IdlType, data: &mut &[u8], depth: usize) -> Result<Value, String> {
+    if depth > MAX_TYPE_DEPTH {
+        return Err("type nested too deeply".to_string());
+    }
+    macro_rules! le {
+        ($int:ty) => {
+            <$int>::from_le_bytes(take(data, size_of::<$int>())?.try_into().unwrap())
+        };
+    }
+    let value = match ty {
+        IdlType::Bool => Value::Bool(take(data, 1)?[0] != 0),
+        IdlType::U8 => le!(u8).into(),
+        IdlType::I8 => le!(i8).into(),
+        IdlType::U16 => le!(u16).into(),
+        IdlType::I16 => le!(i16).into(),
+        IdlType::U32 => le!(u32).into(),
+        IdlType::I32 => le!(i32).into(),
+        IdlType::U64 => le!(u64).into(),
+        IdlType::I64 => le!(i64).into(),
+        IdlType::U128 => le!(u128).to_string().into(),
+        IdlType::I128 => le!(i128).to_string().into(),
+        IdlType::F32 => le!(f32).into(),
+        IdlType::F64 => le!(f64).into(),
+        IdlType::String => {
+            let len = le!(u32) as usize;
+            String::from_utf8(take(data, len)?.to_vec())
+                .map_err(|_| "string is not UTF-8".to_string())?
+                .into()
+        }
+        IdlType::Bytes => {
+            let len = le!(u32) as usize;
+            take(data, len)?.to_vec().into()
+        }
+        IdlType::Pubkey => Pubkey::try_from(take(data, 32)?)
+            .unwrap()
+            .to_string()
+            .into(),
+        IdlType::Option(inner) => match le!(u8) {
+            0 => Value::Null,
+            _ => decode(idl, inner, data, depth + 1)?,
+        },
+        IdlType::COption(inner) => match le!(u32) {
+            0 => Value::Null,
+            _ => decode(idl, inner, data, depth + 1)?,
+        },
+        IdlType::Vec(inner) => {
+            let len = le!(u32) as usize;
+            // Every element takes at least a byte, so a corrupt length fails instead of
+            // allocating.
+            if len > data.len() {
+                return Err(format!("vector of {len} elements overruns the data"));
+            }
+            (0..len)
+                .map(|_| decode(idl, inner, data, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?
+                .into()
+        }
+        IdlType::Array(inner, len) => (0..*len)
+            .map(|_| decode(idl, inner, data, depth + 1))
+            .collect::<Result<Vec<_>, _>>()?
+            .into(),
+        IdlType::Defined(name) => {
+            let def = idl
+                .defined_type(name)
+                .ok_or_else(|| format!("type {name} is not in the IDL"))?;
+            match def {
+                IdlTypeDefTy::Struct { fields } => decode_fields(idl, fields, data, depth)?,
+                IdlTypeDefTy::Enum { variants } => {
+                    let index = le!(u8) as usize;
+                    let variant = variants
+                        .get(index)
+                        .ok_or_else(|| format!("{name} has no variant {index}"))?;
+                    match &variant.fields {
+                        IdlFields::Named(fields) if fields.is_empty() => {
+                            Value::String(variant.name.clone())
+                        }
+                        fields => {
+                            let fields = decode_fields(idl, fields, data, depth)?;
+                            Value::Object(Map::from_iter([(variant.name.clone(), fields)]))
+                        }
+                    }
+                }
+                IdlTypeDefTy::Type { alias } => decode(idl, alias, data, depth + 1)?,
+                IdlTypeDefTy::Other => return Err(format!("type {name} can't be decoded")),
+            }
+        }
+        IdlType::U256 | IdlType::I256 | IdlType::Other(_) => {
+            return Err(format!("{ty:?} can't be decoded"))
+        }
+    };
+    Ok(value)
+}
+
+/// Decodes `fields` in order, into an object for named fields and an array for a tuple. A tuple
+/// of one field decodes to the bare value, as serde lays out newtypes.
+pub(crate) fn decode_fields(
+    idl: &Idl,
+    fields: &IdlFields,
+    data: &mut &[u8],
+    depth: usize,
+) -> Result<Value, String> {
+    match fields {
+        IdlFields::Named(fields) => {
+            let mut object = Map::new();
+            for field in fields {
+                object.insert(field.name.clone(), decode(idl, &field.ty, data, depth + 1)?);
+            }
+            Ok(Value::Object(object))
+        }
+        IdlFields::Tuple(types) => {
+            let mut elements = types
+                .iter()
+                .map(|ty| decode(idl, ty, data, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            match elements.len() {
+                1 => Ok(elements.remove(0)),
+                _ => Ok(Value::Array(elements)),
+            }
+        }
+    }
+}
+
+/// Splits the first `len` bytes off `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err(format!("expected {len} more bytes, found {}", data.len()));
+    }
+    let (head, rest) = data.split_at(len);
+    *data = rest;
+    Ok(head)
+}
+
+impl Seashell {
+    /// Decodes an account through the IDL registered for its owner with [`Seashell::load_idl`].
+    pub fn decode_idl_account(&self, pubkey: &Pubkey) -> Result<DecodedIdlAccount, SeashellError> {
+        let account = self.accounts_db.try_account(pubkey)?;
+        let idl = self.idls.get(account.owner()).ok_or_else(|| {
+            SeashellError::Custom(format!("No IDL is registered for {}", account.owner()))
+        })?;
+        idl.decode_account(account.data())
+    }
+
+    /// Decodes an account through its owner's IDL, lets `edit` change it, and writes it back,
+    /// into the scenario if the account comes from it. Nothing is written if `edit` fails or the
+    /// edited fields don't match the IDL. The account is resized if its encoding changes size,
+    /// e.g. when a string grows.
+    pub fn edit_idl_account(
+        &mut self,
+        pubkey: &Pubkey,
+        edit: impl FnOnce(&mut DecodedIdlAccount) -> Result<(), SeashellError>,
+    ) -> Result<(), SeashellError> {
+        let mut account = self.accounts_db.try_account(pubkey)?;
+        let idl = self.idls.get(account.owner()).ok_or_else(|| {
+            SeashellError::Custom(format!("No IDL is registered for {}", account.owner()))
+        })?;
+        let mut decoded = idl.decode_account(account.data())?;
+        edit(&mut decoded)?;
+        account.set_data_from_slice(&idl.encode_account(&decoded)?);
+        self.accounts_db.replace_account(*pubkey, account);
+        Ok(())
+    }
+
+    /// Sets the field at `path` of an account, see [`DecodedIdlAccount::set`].
+    pub fn set_idl_field(
+        &mut self,
+        pubkey: &Pubkey,
+        path: &str,
+        value: impl Serialize,
+    ) -> Result<(), SeashellError> {
+        self.edit_idl_account(pubkey, |account| account.set(path, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+
+    use super::*;
+
+    #[test]
+    fn test_edit_idl_account() {
+        let program_id = Pubkey::new_unique();
+        let idl = Idl::from_json(
+            r#"{
+                "accounts": [{"name": "Market", "type": {"kind": "struct", "fields": [
+                    {"name": "authority", "type": "publicKey"},
+                    {"name": "name", "type": "string"},
+                    {"name": "fees", "type": {"defined": "Fees"}},
+                    {"name": "state", "type": {"defined": "State"}},
+                    {"name": "totalVolume", "type": "u128"}
+                ]}}],
+                "types": [
+                    {"name": "Fees", "type": {"kind": "struct", "fields": [
+                        {"name": "makerBps", "type": "i16"},
+                        {"name": "takerBps", "type": {"option": "u16"}}
+                    ]}},
+                    {"name": "State", "type": {"kind": "enum", "variants": [
+                        {"name": "Active"},
+                        {"name": "Paused", "fields": ["i64"]}
+                    ]}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (market, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = idl.accounts[0].discriminator();
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"ab");
+        data.extend_from_slice(&(-2i16).to_le_bytes());
+        data.push(0);
+        data.push(0);
+        data.extend_from_slice(&7u128.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+
+        let mut seashell = Seashell::new();
+        seashell.idls.insert(program_id, idl);
+        seashell.set_account(market, Account { owner: program_id, data, ..Account::default() });
+        let decoded = seashell.decode_idl_account(&market).unwrap();
+        assert_eq!(decoded.name, "Market");
+        assert_eq!(decoded.get("authority").unwrap(), &authority.to_string());
+        assert_eq!(decoded.get("fees.maker_bps").unwrap(), -2);
+        assert_eq!(decoded.get("state").unwrap(), "Active");
+        assert_eq!(decoded.get("total_volume").unwrap(), "7");
+        assert_eq!(decoded.trailing, [0; 4]);
+
+        let new_authority = Pubkey::new_unique();
+        seashell
+            .set_idl_field(&market, "authority", new_authority.to_string())
+            .unwrap();
+        seashell
+            .edit_idl_account(&market, |market| {
+                market.set("name", "abcd")?;
+                market.set("fees.taker_bps", 5)?;
+                market.set("state", serde_json::json!({"Paused": 9}))
+            })
+            .unwrap();
+        let decoded = seashell.decode_idl_account(&market).unwrap();
+        assert_eq!(decoded.get("authority").unwrap(), &new_authority.to_string());
+        assert_eq!(decoded.get("name").unwrap(), "abcd");
+        assert_eq!(decoded.get("fees.takerBps").unwrap(), 5);
+        assert_eq!(decoded.get("state.Paused").unwrap(), 9);
+        assert_eq!(decoded.trailing, [0; 4]);
+        assert_eq!(seashell.account(&market).data.len(), 8 + 32 + 8 + 5 + 9 + 16 + 4);
+
+        assert!(seashell
+            .set_idl_field(&market, "fees.maker_bps", "x")
+            .is_err());
+        assert!(seashell.set_idl_field(&market, "missing", 1).is_err());
+        assert_eq!(seashell.decode_idl_account(&market).unwrap(), decoded);
+    }
+}
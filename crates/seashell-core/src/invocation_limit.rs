@@ -0,0 +1,188 @@
+//! Names the CPI that ran into the runtime's limits on invocation nesting and on the number of
+//! instructions a transaction runs, which otherwise fail as a bare `CallDepth` or
+//! `MaxInstructionTraceLengthExceeded`, and lets stress tests raise both:
+//!
+//! ```ignore
+//! seashell.set_max_instruction_stack_depth(16);
+//! let result = seashell.process_instruction(recurse(20));
+//! let exceeded = result.invocation_limit.unwrap();
+//! assert_eq!(exceeded.limit, InvocationLimit::StackDepth);
+//! assert_eq!((exceeded.caller, exceeded.stack_height), (recursive_program, 16));
+//! ```
+//!
+//! Both limits are checked as an invocation is pushed, so the program blamed is the one whose
+//! CPI was refused, not the one that first failed to complete.
+
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_instruction::error::InstructionError;
+use solana_pubkey::Pubkey;
+
+use crate::seashell::{InstructionProcessingError, InstructionProcessingResult, Seashell};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvocationLimit {
+    /// How deep invocations nest, counting the top-level instruction.
+    StackDepth,
+    /// How many instructions, CPIs included, a transaction runs.
+    TraceLength,
+}
+
+/// The CPI that exceeded an [`InvocationLimit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvocationLimitError {
+    pub limit: InvocationLimit,
+    /// The value of the limit exceeded.
+    pub max: usize,
+    /// The program whose CPI was refused.
+    pub caller: Pubkey,
+    /// The stack height `caller` ran at.
+    pub stack_height: usize,
+    /// The program `caller` invoked. The runtime records an invocation in the trace before
+    /// checking its depth, but refuses one past the trace length before recording it, so this is
+    /// only known for [`InvocationLimit::StackDepth`].
+    pub callee: Option<Pubkey>,
+}
+
+impl std::fmt::Display for InvocationLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = match self.limit {
+            InvocationLimit::StackDepth => "stack depth",
+            InvocationLimit::TraceLength => "instruction trace length",
+        };
+        write!(
+            f,
+            "{} at stack height {} exceeded the {limit} of {}",
+            self.caller, self.stack_height, self.max
+        )?;
+        if let Some(callee) = &self.callee {
+            write!(f, " invoking {callee}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The innermost program that logged failing, with the stack height of its last invocation.
+fn failing_invocation(logs: &[String]) -> Option<(Pubkey, usize)> {
+    let (position, caller) = logs.iter().enumerate().find_map(|(position, log)| {
+        let (program_id, _) = log.strip_prefix("Program ")?.split_once(" failed: ")?;
+        Some((position, program_id.parse::<Pubkey>().ok()?))
+    })?;
+    let invoke = format!("Program {caller} invoke [");
+    let stack_height = logs[..position]
+        .iter()
+        .rev()
+        .find_map(|log| log.strip_prefix(&invoke)?.strip_suffix(']')?.parse().ok())?;
+    Some((caller, stack_height))
+}
+
+/// The CPI that made `result` fail on an invocation limit of `compute_budget`.
+pub fn invocation_limit(
+    result: &InstructionProcessingResult,
+    compute_budget: &ComputeBudget,
+) -> Option<InvocationLimitError> {
+    let Some(InstructionProcessingError::InstructionError(error)) = &result.error else {
+        return None;
+    };
+    match error {
+        InstructionError::CallDepth => {
+            let max = compute_budget.max_instruction_stack_depth;
+            // The refused invocation is in the trace, one deeper than the limit allows.
+            let refused = result
+                .instruction_trace
+                .iter()
+                .rposition(|traced| traced.stack_height > max)?;
+            let callee = &result.instruction_trace[refused];
+            let caller = result.instruction_trace[..refused]
+                .iter()
+                .rfind(|traced| traced.stack_height + 1 == callee.stack_height)?;
+            Some(InvocationLimitError {
+                limit: InvocationLimit::StackDepth,
+                max,
+                caller: caller.program_id,
+                stack_height: caller.stack_height,
+                callee: Some(callee.program_id),
+            })
+        }
+        InstructionError::MaxInstructionTraceLengthExceeded => {
+            let (caller, stack_height) = failing_invocation(&result.logs)?;
+            Some(InvocationLimitError {
+                limit: InvocationLimit::TraceLength,
+                max: compute_budget.max_instruction_trace_length,
+                caller,
+                stack_height,
+                callee: None,
+            })
+        }
+        _ => None,
+    }
+}
+
+impl Seashell {
+    /// Sets how deep invocations may nest, counting the top-level instruction, e.g. to stress
+    /// test deeply composed programs past mainnet's limit of 5.
+    pub fn set_max_instruction_stack_depth(&mut self, depth: usize) {
+        self.compute_budget.max_instruction_stack_depth = depth;
+    }
+
+    /// Sets how many instructions, CPIs included, a transaction may run; mainnet allows 64.
+    pub fn set_max_instruction_trace_length(&mut self, length: usize) {
+        self.compute_budget.max_instruction_trace_length = length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::TracedInstruction;
+
+    #[test]
+    fn test_invocation_limit() {
+        let (outer, inner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let traced = |program_id, stack_height| TracedInstruction {
+            program_id,
+            accounts: Vec::new(),
+            data: Vec::new(),
+            stack_height,
+        };
+        let failed = |error| {
+            InstructionProcessingResult::from_error(InstructionProcessingError::InstructionError(
+                error,
+            ))
+        };
+        let mut compute_budget = ComputeBudget::new_with_defaults(false);
+        compute_budget.max_instruction_stack_depth = 2;
+
+        let result = InstructionProcessingResult {
+            instruction_trace: vec![traced(outer, 1), traced(inner, 2), traced(outer, 3)],
+            ..failed(InstructionError::CallDepth)
+        };
+        let exceeded = invocation_limit(&result, &compute_budget).unwrap();
+        assert_eq!(exceeded.limit, InvocationLimit::StackDepth);
+        assert_eq!((exceeded.caller, exceeded.stack_height), (inner, 2));
+        assert_eq!((exceeded.max, exceeded.callee), (2, Some(outer)));
+
+        let result = InstructionProcessingResult {
+            logs: vec![
+                format!("Program {outer} invoke [1]"),
+                format!("Program {inner} invoke [2]"),
+                format!("Program {inner} success"),
+                format!("Program {inner} invoke [2]"),
+                format!("Program {inner} failed: Max instruction trace length exceeded"),
+                format!("Program {outer} failed: Max instruction trace length exceeded"),
+            ],
+            ..failed(InstructionError::MaxInstructionTraceLengthExceeded)
+        };
+        let exceeded = invocation_limit(&result, &compute_budget).unwrap();
+        assert_eq!(exceeded.limit, InvocationLimit::TraceLength);
+        assert_eq!((exceeded.caller, exceeded.stack_height, exceeded.callee), (inner, 2, None));
+        assert_eq!(exceeded.max, 64);
+
+        assert!(invocation_limit(&failed(InstructionError::Custom(1)), &compute_budget).is_none());
+
+        let mut seashell = Seashell::new();
+        seashell.set_max_instruction_stack_depth(16);
+        seashell.set_max_instruction_trace_length(256);
+        assert_eq!(seashell.compute_budget.max_instruction_stack_depth, 16);
+        assert_eq!(seashell.compute_budget.max_instruction_trace_length, 256);
+    }
+}
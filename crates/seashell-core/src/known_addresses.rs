@@ -0,0 +1,143 @@
+//! Flags instructions that sign with or write to well-known mainnet addresses.
+//!
+//! `process_instruction` doesn't verify signatures, so a test running against cloned mainnet state
+//! can sign as a production mint or program and pass, baking in an assumption the real cluster will
+//! never honor. With `Config::audit_known_addresses`, every such use is logged and reported in
+//! `InstructionProcessingResult::known_address_uses`. Readonly, unsigned references are fine and
+//! aren't reported.
+//!
+//! The built-in list covers widely used mints and programs; add a protocol's own upgrade
+//! authorities and treasury multisigs with [`KnownAddresses::add`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+const BUILTIN: &[(Pubkey, &str)] = &[
+    (Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), "USDC mint"),
+    (Pubkey::from_str_const("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB"), "USDT mint"),
+    (Pubkey::from_str_const("So11111111111111111111111111111111111111112"), "wrapped SOL mint"),
+    (Pubkey::from_str_const("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So"), "mSOL mint"),
+    (Pubkey::from_str_const("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn"), "JitoSOL mint"),
+    (Pubkey::from_str_const("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263"), "BONK mint"),
+    (Pubkey::from_str_const("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4"), "Jupiter v6 program"),
+    (Pubkey::from_str_const("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf"), "Squads v4 program"),
+    (
+        Pubkey::from_str_const("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"),
+        "Metaplex Token Metadata program",
+    ),
+];
+
+/// Mainnet addresses tests shouldn't sign with or write to, with a label for each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownAddresses {
+    addresses: HashMap<Pubkey, String>,
+}
+
+impl Default for KnownAddresses {
+    fn default() -> Self {
+        let mut known = KnownAddresses::empty();
+        for (pubkey, label) in BUILTIN {
+            known.add(*pubkey, *label);
+        }
+        known
+    }
+}
+
+impl KnownAddresses {
+    pub fn empty() -> Self {
+        KnownAddresses { addresses: HashMap::new() }
+    }
+
+    pub fn add(&mut self, pubkey: Pubkey, label: impl Into<String>) -> &mut Self {
+        self.addresses.insert(pubkey, label.into());
+        self
+    }
+
+    /// Stops flagging `pubkey`, e.g. a built-in mint a test legitimately controls.
+    pub fn remove(&mut self, pubkey: &Pubkey) -> &mut Self {
+        self.addresses.remove(pubkey);
+        self
+    }
+
+    pub fn label(&self, pubkey: &Pubkey) -> Option<&str> {
+        self.addresses.get(pubkey).map(String::as_str)
+    }
+
+    /// The known addresses `ixn` signs with or writes to.
+    pub fn audit(&self, ixn: &Instruction) -> Vec<KnownAddressUse> {
+        ixn.accounts
+            .iter()
+            .filter(|meta| meta.is_signer || meta.is_writable)
+            .filter_map(|meta| {
+                Some(KnownAddressUse {
+                    pubkey: meta.pubkey,
+                    label: self.label(&meta.pubkey)?.to_string(),
+                    program_id: ixn.program_id,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A known mainnet address an instruction signs with or writes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownAddressUse {
+    pub pubkey: Pubkey,
+    pub label: String,
+    pub program_id: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl fmt::Display for KnownAddressUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match (self.is_signer, self.is_writable) {
+            (true, true) => "signs with and writes to",
+            (true, false) => "signs with",
+            _ => "writes to",
+        };
+        write!(
+            f,
+            "Instruction to {} {access} {} ({}), which only works because seashell trusts \
+             is_signer and mainnet state",
+            self.program_id, self.label, self.pubkey
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_audit_known_addresses() {
+        let usdc = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let treasury = Pubkey::new_unique();
+        let mut known = KnownAddresses::default();
+        known.add(treasury, "treasury multisig");
+
+        let ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new_readonly(usdc, false),
+                AccountMeta::new_readonly(treasury, true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+            ],
+            data: vec![],
+        };
+        let uses = known.audit(&ixn);
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].label, "treasury multisig");
+        assert!(uses[0].is_signer);
+
+        known.remove(&treasury);
+        assert!(known.audit(&ixn).is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+//! Widely used mainnet programs, loadable by name without vendoring their binaries:
+//!
+//! ```ignore
+//! let metadata_program = seashell.load_known_program("metaplex_token_metadata")?;
+//! // Or fail unless mainnet still runs the binary the tests were written against:
+//! seashell.load_known_program_pinned("spl_memo", "a2f7...")?;
+//! ```
+//!
+//! Nothing here is loaded by `Seashell::new`. The first load of a program fetches its ELF from
+//! the scenario's RPC or `RPC_URL` and caches it under `target/seashell/programs/known`, along
+//! with its sha256; later loads read the cache and refetch only if the file no longer matches
+//! its hash. Pinned loads are cached under the pinned hash, so an upgrade on mainnet never
+//! changes what a pinned test runs.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::manifest::{read_cached, verify_sha256, write_cached};
+use crate::seashell::{try_find_workspace_root, Seashell};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownProgram {
+    pub name: &'static str,
+    pub program_id: Pubkey,
+    pub description: &'static str,
+}
+
+const fn known(name: &'static str, program_id: &str, description: &'static str) -> KnownProgram {
+    KnownProgram { name, program_id: Pubkey::from_str_const(program_id), description }
+}
+
+pub const KNOWN_PROGRAMS: &[KnownProgram] = &[
+    known("spl_memo", "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "SPL Memo v2"),
+    known("spl_memo_v1", "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo", "SPL Memo v1"),
+    known("spl_noop", "noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV", "SPL Noop, a CPI log target"),
+    known(
+        "spl_account_compression",
+        "cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK",
+        "SPL Account Compression",
+    ),
+    known("spl_name_service", "namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX", "SPL Name Service"),
+    known("spl_stake_pool", "SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy", "SPL Stake Pool"),
+    known("spl_governance", "GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw", "SPL Governance"),
+    known(
+        "metaplex_token_metadata",
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+        "Metaplex Token Metadata",
+    ),
+    known("squads_v4", "SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf", "Squads v4 multisig"),
+    known("jupiter_v6", "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4", "Jupiter v6 aggregator"),
+    known("orca_whirlpool", "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc", "Orca Whirlpools"),
+    known("raydium_amm_v4", "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8", "Raydium AMM v4"),
+    known("openbook_v2", "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb", "OpenBook v2"),
+    known("pyth_receiver", "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ", "Pyth Solana Receiver"),
+];
+
+pub fn known_program(name: &str) -> Option<&'static KnownProgram> {
+    KNOWN_PROGRAMS.iter().find(|program| program.name == name)
+}
+
+fn default_cache_dir() -> PathBuf {
+    try_find_workspace_root()
+        .unwrap_or_default()
+        .join("target/seashell/programs/known")
+}
+
+/// The ELF of `program` from `cache_dir`, or from `fetch` if it isn't cached or no longer matches
+/// its hash. Pinned to `sha256`, the ELF must hash to it; otherwise the hash of the first fetch
+/// is recorded next to the ELF.
+fn cached_elf(
+    cache_dir: &Path,
+    program: &KnownProgram,
+    sha256: Option<&str>,
+    fetch: impl FnOnce() -> Result<Vec<u8>, SeashellError>,
+) -> Result<Vec<u8>, SeashellError> {
+    let stem = match sha256 {
+        Some(sha256) => format!("{}-{}", program.name, sha256.to_lowercase()),
+        None => program.name.to_string(),
+    };
+    let cached = cache_dir.join(format!("{stem}.so"));
+    let recorded = cache_dir.join(format!("{stem}.sha256"));
+    let expected = match sha256 {
+        Some(sha256) => Some(sha256.to_string()),
+        None => std::fs::read_to_string(&recorded).ok(),
+    };
+    if let Some(bytes) = expected.and_then(|expected| read_cached(program.name, &cached, &expected))
+    {
+        return Ok(bytes);
+    }
+
+    log::info!("Fetching known program {} ({})", program.name, program.program_id);
+    let bytes = fetch()?;
+    if let Some(sha256) = sha256 {
+        verify_sha256(program.name, &bytes, sha256)?;
+    }
+    write_cached(&cached, &bytes)?;
+    write_cached(&recorded, hex::encode(Sha256::digest(&bytes)).as_bytes())?;
+    Ok(bytes)
+}
+
+impl Seashell {
+    /// Loads the [known program](self) `name`, fetching it on first use. Returns its program id.
+    pub fn load_known_program(&mut self, name: &str) -> Result<Pubkey, SeashellError> {
+        self.load_known_program_from(name, None)
+    }
+
+    /// Like [`Seashell::load_known_program`], failing unless the ELF hashes to `sha256`.
+    pub fn load_known_program_pinned(
+        &mut self,
+        name: &str,
+        sha256: &str,
+    ) -> Result<Pubkey, SeashellError> {
+        self.load_known_program_from(name, Some(sha256))
+    }
+
+    fn load_known_program_from(
+        &mut self,
+        name: &str,
+        sha256: Option<&str>,
+    ) -> Result<Pubkey, SeashellError> {
+        let program = known_program(name).ok_or_else(|| SeashellError::ProgramNotFound {
+            program_name: name.to_string(),
+            searched: Vec::new(),
+        })?;
+        let elf = cached_elf(&default_cache_dir(), program, sha256, || {
+            self.fetch_program_elf(&program.program_id)
+        })?;
+        self.load_program_from_bytes(program.program_id, &elf);
+        Ok(program.program_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_elf() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = known_program("spl_memo").unwrap();
+        assert_eq!(program.program_id.to_string(), "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+        assert!(known_program("missing").is_none());
+
+        // The first load fetches and records the hash; later ones are served from the cache.
+        let fetched = cached_elf(dir.path(), program, None, || Ok(b"elf".to_vec())).unwrap();
+        assert_eq!(fetched, b"elf");
+        let cached = cached_elf(dir.path(), program, None, || panic!("refetched")).unwrap();
+        assert_eq!(cached, b"elf");
+
+        // A cached ELF that no longer matches its hash is refetched.
+        std::fs::write(dir.path().join("spl_memo.so"), b"truncated").unwrap();
+        let refetched = cached_elf(dir.path(), program, None, || Ok(b"elf".to_vec())).unwrap();
+        assert_eq!(refetched, b"elf");
+
+        let hash = hex::encode(Sha256::digest(b"elf"));
+        let pinned = cached_elf(dir.path(), program, Some(&hash), || Ok(b"elf".to_vec())).unwrap();
+        assert_eq!(pinned, b"elf");
+        assert!(dir.path().join(format!("spl_memo-{hash}.so")).exists());
+        let stale_pin = "00".repeat(32);
+        let upgraded =
+            cached_elf(dir.path(), program, Some(&stale_pin), || Ok(b"upgraded elf".to_vec()));
+        assert!(upgraded.unwrap_err().to_string().contains("hash mismatch"));
+
+        let mut seashell = Seashell::new();
+        assert!(matches!(
+            seashell.load_known_program("missing"),
+            Err(SeashellError::ProgramNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use solana_account::{AccountSharedData, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+
+type Decoder = Arc<dyn Fn(&[u8]) -> Option<Vec<(String, String)>> + Send + Sync>;
+
+/// Primitive field types understood by [`AccountLayout`]. Integers are little-endian, matching
+/// borsh and bytemuck layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Bool,
+    Pubkey,
+    /// A `COption<Pubkey>` as used by SPL Token: a u32 tag followed by the key.
+    COptionPubkey,
+    /// A `COption<u64>` as used by SPL Token: a u32 tag followed by the value.
+    COptionU64,
+    Bytes(usize),
+}
+
+impl FieldType {
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::U8 | FieldType::I8 | FieldType::Bool => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 => 4,
+            FieldType::U64 | FieldType::I64 => 8,
+            FieldType::U128 | FieldType::I128 => 16,
+            FieldType::Pubkey => 32,
+            FieldType::COptionPubkey => 36,
+            FieldType::COptionU64 => 12,
+            FieldType::Bytes(len) => *len,
+        }
+    }
+
+    /// Renders the field from exactly `self.size()` bytes.
+    pub fn format(&self, bytes: &[u8]) -> String {
+        macro_rules! le {
+            ($ty:ty) => {
+                <$ty>::from_le_bytes(bytes.try_into().unwrap()).to_string()
+            };
+        }
+        match self {
+            FieldType::U8 => le!(u8),
+            FieldType::U16 => le!(u16),
+            FieldType::U32 => le!(u32),
+            FieldType::U64 => le!(u64),
+            FieldType::U128 => le!(u128),
+            FieldType::I8 => le!(i8),
+            FieldType::I16 => le!(i16),
+            FieldType::I32 => le!(i32),
+            FieldType::I64 => le!(i64),
+            FieldType::I128 => le!(i128),
+            FieldType::Bool => (bytes[0] != 0).to_string(),
+            FieldType::Pubkey => Pubkey::try_from(bytes).unwrap().to_string(),
+            FieldType::COptionPubkey | FieldType::COptionU64 => {
+                if bytes[..4] == [0; 4] {
+                    "None".to_string()
+                } else if *self == FieldType::COptionPubkey {
+                    format!("Some({})", FieldType::Pubkey.format(&bytes[4..]))
+                } else {
+                    format!("Some({})", FieldType::U64.format(&bytes[4..]))
+                }
+            }
+            FieldType::Bytes(_) => hex(bytes),
+        }
+    }
+
+    /// Encodes a JSON value into the field's bytes, the inverse of `format`. Integers are given
+    /// as numbers or strings, `COption`s as `null` or their value, and bytes as hex.
+    pub fn encode(&self, value: &serde_json::Value) -> Result<Vec<u8>, SeashellError> {
+        let invalid = || SeashellError::Custom(format!("Invalid {self:?} value {value}"));
+        macro_rules! le {
+            ($ty:ty) => {{
+                let parsed: $ty = match value {
+                    serde_json::Value::Number(number) => number.to_string().parse().ok(),
+                    serde_json::Value::String(string) => string.parse().ok(),
+                    _ => None,
+                }
+                .ok_or_else(invalid)?;
+                parsed.to_le_bytes().to_vec()
+            }};
+        }
+        let bytes = match self {
+            FieldType::U8 => le!(u8),
+            FieldType::U16 => le!(u16),
+            FieldType::U32 => le!(u32),
+            FieldType::U64 => le!(u64),
+            FieldType::U128 => le!(u128),
+            FieldType::I8 => le!(i8),
+            FieldType::I16 => le!(i16),
+            FieldType::I32 => le!(i32),
+            FieldType::I64 => le!(i64),
+            FieldType::I128 => le!(i128),
+            FieldType::Bool => vec![value.as_bool().ok_or_else(invalid)? as u8],
+            FieldType::Pubkey => value
+                .as_str()
+                .and_then(|string| string.parse::<Pubkey>().ok())
+                .ok_or_else(invalid)?
+                .to_bytes()
+                .to_vec(),
+            FieldType::COptionPubkey | FieldType::COptionU64 => {
+                let mut bytes = vec![0; self.size()];
+                if !value.is_null() {
+                    let inner = if *self == FieldType::COptionPubkey {
+                        FieldType::Pubkey
+                    } else {
+                        FieldType::U64
+                    };
+                    bytes[..4].copy_from_slice(&1u32.to_le_bytes());
+                    bytes[4..].copy_from_slice(&inner.encode(value)?);
+                }
+                bytes
+            }
+            FieldType::Bytes(len) => {
+                let bytes = value
+                    .as_str()
+                    .and_then(|string| hex::decode(string).ok())
+                    .ok_or_else(invalid)?;
+                if bytes.len() != *len {
+                    return Err(SeashellError::Custom(format!(
+                        "Expected {len} bytes, got {}",
+                        bytes.len()
+                    )));
+                }
+                bytes
+            }
+        };
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutField {
+    pub name: String,
+    pub offset: usize,
+    pub ty: FieldType,
+}
+
+/// Describes how to decode the data of accounts owned by a program.
+///
+/// Layouts are built field by field; `field` places fields back to back after the discriminator
+/// (borsh-style packing), while `field_at` pins a field to an explicit offset. Types that don't
+/// fit a flat layout can supply their own decoder with [`AccountLayout::with_decoder`].
+#[derive(Clone)]
+pub struct AccountLayout {
+    pub name: String,
+    pub discriminator: Vec<u8>,
+    pub data_len: Option<usize>,
+    pub fields: Vec<LayoutField>,
+    next_offset: usize,
+    decoder: Option<Decoder>,
+}
+
+impl std::fmt::Debug for AccountLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountLayout")
+            .field("name", &self.name)
+            .field("discriminator", &self.discriminator)
+            .field("data_len", &self.data_len)
+            .field("fields", &self.fields)
+            .field("custom_decoder", &self.decoder.is_some())
+            .finish()
+    }
+}
+
+impl AccountLayout {
+    pub fn new(name: impl Into<String>) -> Self {
+        AccountLayout {
+            name: name.into(),
+            discriminator: Vec::new(),
+            data_len: None,
+            fields: Vec::new(),
+            next_offset: 0,
+            decoder: None,
+        }
+    }
+
+    /// A layout decoded by an arbitrary function, e.g. `|data| T::try_from_slice(data).ok()...`
+    /// for borsh types. The decoder receives the data following the discriminator.
+    pub fn with_decoder(
+        name: impl Into<String>,
+        decoder: impl Fn(&[u8]) -> Option<Vec<(String, String)>> + Send + Sync + 'static,
+    ) -> Self {
+        AccountLayout { decoder: Some(Arc::new(decoder)), ..AccountLayout::new(name) }
+    }
+
+    /// Only match accounts whose data starts with `discriminator`; sequential fields follow it.
+    pub fn discriminator(mut self, discriminator: &[u8]) -> Self {
+        self.discriminator = discriminator.to_vec();
+        self.next_offset = self.next_offset.max(discriminator.len());
+        self
+    }
+
+    /// Only match accounts whose data is exactly `data_len` bytes long.
+    pub fn data_len(mut self, data_len: usize) -> Self {
+        self.data_len = Some(data_len);
+        self
+    }
+
+    /// Appends a field directly after the previous one.
+    pub fn field(self, name: impl Into<String>, ty: FieldType) -> Self {
+        let offset = self.next_offset;
+        self.field_at(name, offset, ty)
+    }
+
+    /// Adds a field at an explicit byte offset.
+    pub fn field_at(mut self, name: impl Into<String>, offset: usize, ty: FieldType) -> Self {
+        self.next_offset = offset + ty.size();
+        self.fields
+            .push(LayoutField { name: name.into(), offset, ty });
+        self
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&LayoutField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.starts_with(&self.discriminator) && self.data_len.is_none_or(|len| len == data.len())
+    }
+
+    /// Decodes every field that fits inside `data`, as `(name, rendered value)` pairs. `None` if
+    /// `data` is shorter than the discriminator or the custom decoder rejects it.
+    pub fn decode(&self, data: &[u8]) -> Option<Vec<(String, String)>> {
+        if let Some(decoder) = &self.decoder {
+            return decoder(data.get(self.discriminator.len()..)?);
+        }
+        let fields = self
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let bytes = data.get(field.offset..field.offset + field.ty.size())?;
+                Some((field.name.clone(), field.ty.format(bytes)))
+            })
+            .collect();
+        Some(fields)
+    }
+}
+
+/// An account decoded through a registered layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedAccount {
+    pub layout: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl DecodedAccount {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Layouts keyed by owning program. When several layouts of one owner match an account, the one
+/// with the longest discriminator wins, then the one registered first.
+#[derive(Debug, Default, Clone)]
+pub struct LayoutRegistry {
+    layouts: HashMap<Pubkey, Vec<AccountLayout>>,
+}
+
+impl LayoutRegistry {
+    pub fn register(&mut self, owner: Pubkey, layout: AccountLayout) {
+        self.layouts.entry(owner).or_default().push(layout);
+    }
+
+    pub fn find(&self, owner: &Pubkey, data: &[u8]) -> Option<&AccountLayout> {
+        self.layouts
+            .get(owner)?
+            .iter()
+            .filter(|layout| layout.matches(data))
+            .rev()
+            .max_by_key(|layout| layout.discriminator.len())
+    }
+
+    pub fn decode(&self, account: &impl ReadableAccount) -> Option<DecodedAccount> {
+        let layout = self.find(account.owner(), account.data())?;
+        let fields = layout.decode(account.data())?;
+        Some(DecodedAccount { layout: layout.name.clone(), fields })
+    }
+
+    /// Renders an account with its decoded fields, falling back to a hex dump of the data.
+    pub fn format_account(&self, pubkey: &Pubkey, account: &impl ReadableAccount) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Account {pubkey}");
+        let _ = writeln!(out, "  lamports: {}", account.lamports());
+        let _ = writeln!(out, "  owner: {}", account.owner());
+        let _ = writeln!(out, "  executable: {}", account.executable());
+        let _ = writeln!(out, "  data_len: {}", account.data().len());
+        match self.decode(account) {
+            Some(decoded) => {
+                let _ = writeln!(out, "  layout: {}", decoded.layout);
+                for (name, value) in decoded.fields {
+                    let _ = writeln!(out, "    {name}: {value}");
+                }
+            }
+            None => {
+                for (line, chunk) in account.data().chunks(32).enumerate() {
+                    let _ = writeln!(out, "    {:06x}: {}", line * 32, hex(chunk));
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders two states of an account next to each other, one row per account field and per
+    /// decoded field (or 16-byte chunk of undecoded data). Rows that differ are marked with `!`,
+    /// and a missing state shows as `-`.
+    pub fn side_by_side(
+        &self,
+        pubkey: &Pubkey,
+        headers: [&str; 2],
+        left: Option<&AccountSharedData>,
+        right: Option<&AccountSharedData>,
+    ) -> String {
+        let left = left.map(|account| self.rows(account)).unwrap_or_default();
+        let right = right.map(|account| self.rows(account)).unwrap_or_default();
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in left.iter().chain(&right) {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        let value = |rows: &[(String, String)], name: &str| -> String {
+            rows.iter()
+                .find(|(field, _)| field == name)
+                .map_or_else(|| "-".to_string(), |(_, value)| value.clone())
+        };
+
+        let name_width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let left_width = names
+            .iter()
+            .map(|name| value(&left, name).len())
+            .chain([headers[0].len()])
+            .max()
+            .unwrap_or(0);
+        let mut out = String::new();
+        let _ = writeln!(out, "Account {pubkey}");
+        let _ = writeln!(out, "  {:name_width$}  {:left_width$}  {}", "", headers[0], headers[1]);
+        for name in names {
+            let (left_value, right_value) = (value(&left, name), value(&right, name));
+            let marker = if left_value == right_value { ' ' } else { '!' };
+            let _ = writeln!(
+                out,
+                "{marker} {name:name_width$}  {left_value:left_width$}  {right_value}"
+            );
+        }
+        out
+    }
+
+    fn rows(&self, account: &AccountSharedData) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("lamports".to_string(), account.lamports().to_string()),
+            ("owner".to_string(), account.owner().to_string()),
+            ("executable".to_string(), account.executable().to_string()),
+            ("data_len".to_string(), account.data().len().to_string()),
+        ];
+        match self.decode(account) {
+            Some(decoded) => {
+                rows.push(("layout".to_string(), decoded.layout));
+                // Indented like `format_account`, which also keeps a decoded `owner` field apart
+                // from the account's owner.
+                rows.extend(
+                    decoded
+                        .fields
+                        .into_iter()
+                        .map(|(name, value)| (format!("  {name}"), value)),
+                );
+            }
+            None => {
+                for (line, chunk) in account.data().chunks(16).enumerate() {
+                    rows.push((format!("  {:06x}", line * 16), hex(chunk)));
+                }
+            }
+        }
+        rows
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::AccountSharedData;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_with_discriminator() {
+        let owner = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut registry = LayoutRegistry::default();
+        registry.register(
+            owner,
+            AccountLayout::new("Market")
+                .discriminator(&[7; 8])
+                .field("authority", FieldType::Pubkey)
+                .field("fee_bps", FieldType::U16)
+                .field("delta", FieldType::I64),
+        );
+
+        let mut data = vec![7; 8];
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.extend_from_slice(&(-4i64).to_le_bytes());
+        let mut account = AccountSharedData::new(1, 0, &owner);
+        account.set_data_from_slice(&data);
+
+        let decoded = registry.decode(&account).unwrap();
+        assert_eq!(decoded.layout, "Market");
+        assert_eq!(decoded.get("authority"), Some(authority.to_string().as_str()));
+        assert_eq!(decoded.get("fee_bps"), Some("30"));
+        assert_eq!(decoded.get("delta"), Some("-4"));
+
+        account.set_data_from_slice(&[0; 50]);
+        assert!(registry.decode(&account).is_none());
+    }
+
+    #[test]
+    fn test_decode_short_data() {
+        let layout = AccountLayout::with_decoder("Custom", |data| {
+            Some(vec![("len".to_string(), data.len().to_string())])
+        })
+        .discriminator(&[7; 8]);
+        assert_eq!(layout.decode(&[7; 10]), Some(vec![("len".to_string(), "2".to_string())]));
+        assert_eq!(layout.decode(&[7; 4]), None);
+    }
+}
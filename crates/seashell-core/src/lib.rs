@@ -1,12 +1,78 @@
 #![allow(clippy::expect_fun_call)]
+#[doc(hidden)]
 pub mod accounts_db;
+mod agave;
+pub mod anchor_error;
+pub mod block;
+pub mod blockhash;
+pub mod check;
+pub mod cli_account;
+#[doc(hidden)]
 pub mod compile;
+pub mod compute_budget;
+pub mod cpi_expect;
+pub mod cpi_tester;
+pub mod delta;
+pub mod diff;
 pub mod error;
+pub mod event;
+mod execute;
+mod expect;
+pub mod fault;
+pub mod fixture;
+pub mod funding;
+pub mod fuzz;
+pub mod history;
+pub mod idl;
+pub mod idl_audit;
+pub mod idl_builder;
+pub mod idl_edit;
+pub mod invocation_limit;
+pub mod known_addresses;
+pub mod known_programs;
+pub mod layout;
+pub mod litesvm;
+pub mod log_filter;
+pub mod lookup_table;
+pub mod manifest;
+pub mod matrix;
+pub mod metrics;
+pub mod nonblocking;
+pub mod patch;
+pub mod plugin;
+pub mod population;
+#[cfg(feature = "precompiles")]
+pub mod precompile_keys;
+#[doc(hidden)]
 pub mod precompiles;
+pub mod prelude;
+mod program_registry;
+pub mod program_test;
+#[cfg(feature = "proto")]
+pub mod proto;
+pub mod reload;
+pub mod reserved_keys;
+pub mod rpc;
 pub mod scenario;
+pub mod script;
 pub mod seashell;
+pub mod shrink;
+pub mod signing;
+pub mod simulation;
+pub mod snapshot;
 pub mod spl;
+pub mod stake;
+#[cfg(feature = "subscriptions")]
+pub mod subscription;
+#[doc(hidden)]
 pub mod sysvar;
+#[cfg(test)]
+mod test_utils;
+pub mod trace;
+pub mod vesting;
+pub mod vote;
+pub mod wallet;
+pub mod watch;
 
 pub use seashell::*;
 
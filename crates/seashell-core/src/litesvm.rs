@@ -0,0 +1,297 @@
+//! A facade with the common surface of LiteSVM, so LiteSVM test suites move to Seashell by
+//! changing imports:
+//!
+//! ```ignore
+//! use seashell::litesvm::LiteSVM;
+//!
+//! let mut svm = LiteSVM::new();
+//! svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+//! let tx = Transaction::new(&[&payer], message, svm.latest_blockhash());
+//! let meta = svm.send_transaction(tx).unwrap();
+//! ```
+//!
+//! Transactions are committed as in LiteSVM, and signatures and blockhashes are checked unless
+//! turned off with [`LiteSVM::with_sigverify`] and [`LiteSVM::with_blockhash_check`]. Unlike
+//! LiteSVM, no fees are charged and a transaction can be sent twice. The `Seashell` underneath
+//! stays reachable through [`LiteSVM::seashell`] for everything the facade doesn't cover.
+
+use std::path::Path;
+
+use solana_account::{Account, ReadableAccount};
+use solana_hash::Hash;
+use solana_instruction::error::InstructionError;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_error::TransactionError;
+
+use crate::error::SeashellError;
+use crate::seashell::{Config, InstructionChainResult, InstructionProcessingResult, Seashell};
+use crate::simulation::{instruction_error, rejection_error};
+
+/// What a transaction that ran did, as LiteSVM's `TransactionMetadata`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransactionMetadata {
+    pub signature: Signature,
+    pub logs: Vec<String>,
+    pub compute_units_consumed: u64,
+    /// Return data of the last instruction that set any.
+    pub return_data: Vec<u8>,
+}
+
+impl TransactionMetadata {
+    fn new(signature: Signature, results: &[InstructionProcessingResult]) -> Self {
+        TransactionMetadata {
+            signature,
+            logs: results
+                .iter()
+                .flat_map(|result| result.logs.iter().cloned())
+                .collect(),
+            compute_units_consumed: results
+                .iter()
+                .map(|result| result.compute_units_consumed)
+                .sum(),
+            return_data: results
+                .iter()
+                .rev()
+                .find(|result| !result.return_data.is_empty())
+                .map(|result| result.return_data.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedTransactionMetadata {
+    pub err: TransactionError,
+    /// What the instructions did up to and including the failing one; empty if the transaction
+    /// was rejected before running.
+    pub meta: TransactionMetadata,
+}
+
+pub type TransactionResult = Result<TransactionMetadata, FailedTransactionMetadata>;
+
+/// A successful simulation, with the state of every account the transaction touched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedTransactionInfo {
+    pub meta: TransactionMetadata,
+    pub post_accounts: Vec<(Pubkey, Account)>,
+}
+
+pub struct LiteSVM {
+    seashell: Seashell,
+    sigverify: bool,
+}
+
+impl Default for LiteSVM {
+    fn default() -> Self {
+        LiteSVM::new()
+    }
+}
+
+impl LiteSVM {
+    /// A `Seashell` that commits every transaction and treats missing accounts as empty, as
+    /// LiteSVM does.
+    pub fn new() -> Self {
+        LiteSVM::from_seashell(Seashell::new_with_config(Config {
+            memoize: true,
+            allow_uninitialized_accounts_local: true,
+            ..Config::default()
+        }))
+    }
+
+    /// Wraps `seashell` as is, keeping its configuration.
+    pub fn from_seashell(seashell: Seashell) -> Self {
+        LiteSVM { seashell, sigverify: true }
+    }
+
+    pub fn seashell(&self) -> &Seashell {
+        &self.seashell
+    }
+
+    pub fn seashell_mut(&mut self) -> &mut Seashell {
+        &mut self.seashell
+    }
+
+    pub fn into_seashell(self) -> Seashell {
+        self.seashell
+    }
+
+    pub fn with_sigverify(mut self, sigverify: bool) -> Self {
+        self.sigverify = sigverify;
+        self
+    }
+
+    pub fn with_blockhash_check(mut self, check: bool) -> Self {
+        self.seashell.config.check_recent_blockhash = check;
+        self
+    }
+
+    pub fn send_transaction(&mut self, tx: impl Into<VersionedTransaction>) -> TransactionResult {
+        let tx = tx.into();
+        let result = self.run(&self.seashell, &tx)?;
+        Ok(TransactionMetadata::new(signature_of(&tx), &result.results))
+    }
+
+    /// Runs `tx` on a fork, leaving the state untouched.
+    pub fn simulate_transaction(
+        &self,
+        tx: impl Into<VersionedTransaction>,
+    ) -> Result<SimulatedTransactionInfo, FailedTransactionMetadata> {
+        let tx = tx.into();
+        let mut fork = self.seashell.fork();
+        fork.config.memoize = false;
+        let result = self.run(&fork, &tx)?;
+        Ok(SimulatedTransactionInfo {
+            meta: TransactionMetadata::new(signature_of(&tx), &result.results),
+            post_accounts: result.post_execution_accounts,
+        })
+    }
+
+    fn run(
+        &self,
+        seashell: &Seashell,
+        tx: &VersionedTransaction,
+    ) -> Result<InstructionChainResult, FailedTransactionMetadata> {
+        let processed = if self.sigverify {
+            seashell.process_transaction_signed(tx)
+        } else {
+            seashell.process_transaction(tx)
+        };
+        let result = processed.map_err(|error| FailedTransactionMetadata {
+            err: rejection_error(&error),
+            meta: TransactionMetadata { signature: signature_of(tx), ..Default::default() },
+        })?;
+        match &result.error {
+            Some((index, error)) => Err(FailedTransactionMetadata {
+                err: instruction_error(*index, error),
+                meta: TransactionMetadata::new(signature_of(tx), &result.results),
+            }),
+            None => Ok(result),
+        }
+    }
+
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.seashell
+            .accounts_db
+            .account_maybe(pubkey)
+            .map(Into::into)
+    }
+
+    pub fn get_balance(&self, pubkey: &Pubkey) -> Option<u64> {
+        self.seashell
+            .accounts_db
+            .account_maybe(pubkey)
+            .map(|account| account.lamports())
+    }
+
+    pub fn set_account(&mut self, pubkey: Pubkey, account: Account) -> Result<(), SeashellError> {
+        self.seashell.set_account(pubkey, account);
+        Ok(())
+    }
+
+    /// Credits `lamports` to `pubkey` directly; unlike LiteSVM, no transaction is sent.
+    pub fn airdrop(&mut self, pubkey: &Pubkey, lamports: u64) -> TransactionResult {
+        self.seashell.airdrop(*pubkey, lamports);
+        Ok(TransactionMetadata::default())
+    }
+
+    pub fn add_program(&mut self, program_id: Pubkey, program_bytes: &[u8]) {
+        self.seashell
+            .load_program_from_bytes(program_id, program_bytes);
+    }
+
+    pub fn add_program_from_file(
+        &mut self,
+        program_id: Pubkey,
+        path: impl AsRef<Path>,
+    ) -> Result<(), SeashellError> {
+        let bytes = std::fs::read(path)?;
+        self.add_program(program_id, &bytes);
+        Ok(())
+    }
+
+    pub fn latest_blockhash(&self) -> Hash {
+        self.seashell.latest_blockhash()
+    }
+
+    pub fn expire_blockhash(&mut self) {
+        self.seashell.expire_blockhash();
+    }
+
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        self.seashell.warp_to_slot(slot);
+    }
+}
+
+fn signature_of(tx: &VersionedTransaction) -> Signature {
+    tx.signatures.first().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_keypair::Keypair;
+    use solana_message::Message;
+    use solana_signer::Signer;
+    use solana_transaction::Transaction;
+
+    use super::*;
+    use crate::test_utils::system_transfer;
+
+    #[test]
+    fn test_litesvm_facade() {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        svm.airdrop(&payer.pubkey(), 1_000).unwrap();
+
+        let transfer = |lamports| system_transfer(payer.pubkey(), to, lamports);
+        let tx = |lamports, blockhash| {
+            Transaction::new(
+                &[&payer],
+                Message::new(&[transfer(lamports)], Some(&payer.pubkey())),
+                blockhash,
+            )
+        };
+
+        let simulated = svm
+            .simulate_transaction(tx(400, svm.latest_blockhash()))
+            .unwrap();
+        let (_, simulated_to) = simulated
+            .post_accounts
+            .iter()
+            .find(|(pubkey, _)| *pubkey == to)
+            .unwrap();
+        assert_eq!(simulated_to.lamports, 400);
+        assert_eq!(svm.get_balance(&to), None);
+
+        let meta = svm
+            .send_transaction(tx(400, svm.latest_blockhash()))
+            .unwrap();
+        assert_eq!(meta.signature, tx(400, svm.latest_blockhash()).signatures[0]);
+        assert_eq!(svm.get_balance(&payer.pubkey()), Some(600));
+        assert_eq!(svm.get_account(&to).unwrap().lamports, 400);
+
+        let failed = svm
+            .send_transaction(tx(4000, svm.latest_blockhash()))
+            .unwrap_err();
+        // SystemError::ResultWithNegativeLamports
+        assert_eq!(failed.err, TransactionError::InstructionError(0, InstructionError::Custom(1)));
+        assert_eq!(
+            svm.send_transaction(tx(1, Hash::new_unique()))
+                .unwrap_err()
+                .err,
+            TransactionError::BlockhashNotFound
+        );
+
+        let mut unsigned = tx(1, svm.latest_blockhash());
+        unsigned.signatures[0] = Signature::default();
+        assert_eq!(
+            svm.send_transaction(unsigned.clone()).unwrap_err().err,
+            TransactionError::SignatureFailure
+        );
+        let mut svm = svm.with_sigverify(false);
+        svm.send_transaction(unsigned).unwrap();
+        assert_eq!(svm.get_balance(&to), Some(401));
+    }
+}
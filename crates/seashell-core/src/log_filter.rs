@@ -0,0 +1,131 @@
+//! Per-program log verbosity, to keep CPI-heavy traces readable by muting the logs of
+//! dependencies while keeping those of the program under test:
+//!
+//! ```ignore
+//! seashell.config.log_filter =
+//!     LogFilter::new(LogLevel::Invocations).with(my_program, LogLevel::All);
+//! ```
+//!
+//! Each line is attributed to the program executing when it was logged, so the logs of a program
+//! invoked by a muted one are still kept. The filter applies to
+//! `InstructionProcessingResult::logs` and the log collector. Compute unit frames are computed
+//! from the unfiltered logs, but Anchor events logged by muted programs are dropped with them.
+
+use std::collections::HashMap;
+
+use solana_pubkey::Pubkey;
+
+/// How much of a program's logs to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    /// Nothing, not even the runtime's `invoke` and `success` lines.
+    Off,
+    /// Only the runtime's `invoke`, `consumed`, `success` and `failed` lines.
+    Invocations,
+    /// Everything, including `Program log:` and `Program data:` lines.
+    #[default]
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LogFilter {
+    default: LogLevel,
+    levels: HashMap<Pubkey, LogLevel>,
+}
+
+impl LogFilter {
+    /// A filter applying `default` to every program without a level of its own.
+    pub fn new(default: LogLevel) -> Self {
+        LogFilter { default, levels: HashMap::new() }
+    }
+
+    pub fn with(mut self, program_id: Pubkey, level: LogLevel) -> Self {
+        self.set(program_id, level);
+        self
+    }
+
+    pub fn set(&mut self, program_id: Pubkey, level: LogLevel) {
+        self.levels.insert(program_id, level);
+    }
+
+    pub fn level_of(&self, program_id: &Pubkey) -> LogLevel {
+        self.levels.get(program_id).copied().unwrap_or(self.default)
+    }
+
+    /// Whether the filter keeps every line.
+    pub fn is_noop(&self) -> bool {
+        self.default == LogLevel::All && self.levels.values().all(|level| *level == LogLevel::All)
+    }
+
+    /// The lines of `logs` kept at the level of the program that logged them. Lines logged
+    /// outside any invocation, such as `Log truncated`, are always kept.
+    pub fn filter(&self, logs: &[String]) -> Vec<String> {
+        if self.is_noop() {
+            return logs.to_vec();
+        }
+        let mut stack: Vec<Pubkey> = Vec::new();
+        let mut filtered = Vec::new();
+        for log in logs {
+            let keep = match runtime_log(log) {
+                Some((program_id, event)) => {
+                    if event.starts_with("invoke [") {
+                        stack.push(program_id);
+                    } else if event == "success" || event.starts_with("failed") {
+                        stack.pop();
+                    }
+                    self.level_of(&program_id) >= LogLevel::Invocations
+                }
+                None => stack
+                    .last()
+                    .is_none_or(|program_id| self.level_of(program_id) == LogLevel::All),
+            };
+            if keep {
+                filtered.push(log.clone());
+            }
+        }
+        filtered
+    }
+}
+
+/// The program and event of a line the runtime logs about an invocation, like
+/// `Program <id> invoke [1]`, as opposed to output of the program itself.
+fn runtime_log(log: &str) -> Option<(Pubkey, &str)> {
+    let (program_id, event) = log.strip_prefix("Program ")?.split_once(' ')?;
+    Some((program_id.parse().ok()?, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        let (mine, dependency) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let logs: Vec<String> = [
+            format!("Program {mine} invoke [1]"),
+            "Program log: mine".to_string(),
+            format!("Program {dependency} invoke [2]"),
+            "Program log: dependency".to_string(),
+            format!("Program {dependency} consumed 100 of 1000 compute units"),
+            format!("Program {dependency} success"),
+            "Program data: bWluZQ==".to_string(),
+            format!("Program {mine} consumed 300 of 1000 compute units"),
+            format!("Program {mine} success"),
+        ]
+        .into();
+
+        assert_eq!(LogFilter::default().filter(&logs), logs);
+        let quiet_dependency = LogFilter::default().with(dependency, LogLevel::Invocations);
+        let filtered = quiet_dependency.filter(&logs);
+        assert_eq!(filtered.len(), logs.len() - 1);
+        assert!(!filtered.contains(&"Program log: dependency".to_string()));
+
+        let only_mine = LogFilter::new(LogLevel::Off).with(mine, LogLevel::All);
+        let filtered = only_mine.filter(&logs);
+        assert_eq!(filtered.len(), 5);
+        assert!(filtered
+            .iter()
+            .all(|log| !log.contains(&dependency.to_string())));
+        assert!(filtered.contains(&"Program data: bWluZQ==".to_string()));
+    }
+}
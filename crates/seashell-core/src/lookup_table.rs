@@ -0,0 +1,224 @@
+use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_clock::Slot;
+use solana_message::v0::{LoadedAddresses, MessageAddressTableLookup};
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_slot_hashes::SlotHashes;
+
+use crate::error::SeashellError;
+
+/// Size of the serialized `ProgramState::LookupTable` header preceding the addresses.
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+const LOOKUP_TABLE_DISCRIMINATOR: u32 = 1;
+const DEACTIVATION_SLOT_OFFSET: usize = 4;
+const LAST_EXTENDED_SLOT_OFFSET: usize = 12;
+const LAST_EXTENDED_SLOT_START_INDEX_OFFSET: usize = 20;
+const AUTHORITY_OFFSET: usize = 21;
+
+/// An address lookup table as stored on chain by the address lookup table program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressLookupTable {
+    /// `Slot::MAX` while the table is active.
+    pub deactivation_slot: Slot,
+    pub last_extended_slot: Slot,
+    /// Number of addresses the table held before it was last extended.
+    pub last_extended_slot_start_index: u8,
+    /// `None` once the table is frozen.
+    pub authority: Option<Pubkey>,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl AddressLookupTable {
+    /// An active table holding `addresses`, all usable from genesis on.
+    pub fn new(authority: Option<Pubkey>, addresses: Vec<Pubkey>) -> Self {
+        AddressLookupTable {
+            deactivation_slot: Slot::MAX,
+            last_extended_slot: 0,
+            last_extended_slot_start_index: u8::try_from(addresses.len()).unwrap_or(u8::MAX),
+            authority,
+            addresses,
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, SeashellError> {
+        let invalid = || SeashellError::Custom("Invalid address lookup table data".to_string());
+        if data.len() < LOOKUP_TABLE_META_SIZE {
+            return Err(invalid());
+        }
+        let read_u64 =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        let discriminator = u32::from_le_bytes(data[..4].try_into().unwrap());
+        if discriminator != LOOKUP_TABLE_DISCRIMINATOR {
+            return Err(SeashellError::Custom("Address lookup table is uninitialized".to_string()));
+        }
+        let authority = match data[AUTHORITY_OFFSET] {
+            0 => None,
+            1 => {
+                Some(Pubkey::try_from(&data[AUTHORITY_OFFSET + 1..AUTHORITY_OFFSET + 33]).unwrap())
+            }
+            _ => return Err(invalid()),
+        };
+
+        let addresses = &data[LOOKUP_TABLE_META_SIZE..];
+        if !addresses.len().is_multiple_of(32) {
+            return Err(invalid());
+        }
+
+        Ok(AddressLookupTable {
+            deactivation_slot: read_u64(DEACTIVATION_SLOT_OFFSET),
+            last_extended_slot: read_u64(LAST_EXTENDED_SLOT_OFFSET),
+            last_extended_slot_start_index: data[LAST_EXTENDED_SLOT_START_INDEX_OFFSET],
+            authority,
+            addresses: addresses
+                .chunks_exact(32)
+                .map(|chunk| Pubkey::try_from(chunk).unwrap())
+                .collect(),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![0; LOOKUP_TABLE_META_SIZE + self.addresses.len() * 32];
+        data[..4].copy_from_slice(&LOOKUP_TABLE_DISCRIMINATOR.to_le_bytes());
+        data[DEACTIVATION_SLOT_OFFSET..DEACTIVATION_SLOT_OFFSET + 8]
+            .copy_from_slice(&self.deactivation_slot.to_le_bytes());
+        data[LAST_EXTENDED_SLOT_OFFSET..LAST_EXTENDED_SLOT_OFFSET + 8]
+            .copy_from_slice(&self.last_extended_slot.to_le_bytes());
+        data[LAST_EXTENDED_SLOT_START_INDEX_OFFSET] = self.last_extended_slot_start_index;
+        if let Some(authority) = self.authority {
+            data[AUTHORITY_OFFSET] = 1;
+            data[AUTHORITY_OFFSET + 1..AUTHORITY_OFFSET + 33].copy_from_slice(authority.as_ref());
+        }
+        for (address, chunk) in self
+            .addresses
+            .iter()
+            .zip(data[LOOKUP_TABLE_META_SIZE..].chunks_exact_mut(32))
+        {
+            chunk.copy_from_slice(address.as_ref());
+        }
+        data
+    }
+
+    /// A rent-exempt account owned by the address lookup table program.
+    pub fn to_account(&self, rent: &Rent) -> AccountSharedData {
+        let data = self.serialize();
+        let mut account = AccountSharedData::new(
+            rent.minimum_balance(data.len()),
+            data.len(),
+            &solana_sdk_ids::address_lookup_table::id(),
+        );
+        account.data_as_mut_slice().copy_from_slice(&data);
+        account
+    }
+
+    /// Whether transactions at `current_slot` may still load addresses from this table. A
+    /// deactivated table stays usable until its deactivation slot leaves the slot hashes.
+    pub fn is_active(&self, current_slot: Slot, slot_hashes: &SlotHashes) -> bool {
+        self.deactivation_slot == Slot::MAX
+            || self.deactivation_slot == current_slot
+            || slot_hashes.get(&self.deactivation_slot).is_some()
+    }
+
+    /// Addresses appended in `current_slot` can't be used until the next slot.
+    pub fn active_addresses(&self, current_slot: Slot) -> &[Pubkey] {
+        let len = if current_slot > self.last_extended_slot {
+            self.addresses.len()
+        } else {
+            usize::from(self.last_extended_slot_start_index).min(self.addresses.len())
+        };
+        &self.addresses[..len]
+    }
+
+    /// Resolves the indexes of `lookup` against this table, as the runtime does when loading a v0
+    /// message.
+    pub fn lookup(
+        &self,
+        lookup: &MessageAddressTableLookup,
+        current_slot: Slot,
+        slot_hashes: &SlotHashes,
+    ) -> Result<LoadedAddresses, SeashellError> {
+        if !self.is_active(current_slot, slot_hashes) {
+            return Err(SeashellError::Custom(format!(
+                "Address lookup table {} is deactivated",
+                lookup.account_key
+            )));
+        }
+
+        let addresses = self.active_addresses(current_slot);
+        let resolve = |indexes: &[u8]| {
+            indexes
+                .iter()
+                .map(|index| {
+                    addresses.get(usize::from(*index)).copied().ok_or_else(|| {
+                        SeashellError::Custom(format!(
+                            "Invalid index {index} into address lookup table {}",
+                            lookup.account_key
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(LoadedAddresses {
+            writable: resolve(&lookup.writable_indexes)?,
+            readonly: resolve(&lookup.readonly_indexes)?,
+        })
+    }
+
+    /// Deserializes `account` after checking that it is owned by the lookup table program.
+    pub fn from_account(account: &AccountSharedData) -> Result<Self, SeashellError> {
+        if *account.owner() != solana_sdk_ids::address_lookup_table::id() {
+            return Err(SeashellError::Custom(format!(
+                "Account owned by {} is not an address lookup table",
+                account.owner()
+            )));
+        }
+        Self::deserialize(account.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_hash::Hash;
+
+    use super::*;
+
+    #[test]
+    fn test_lookup_indexes() {
+        let addresses: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let table = AddressLookupTable::new(Some(Pubkey::new_unique()), addresses.clone());
+        let roundtrip = AddressLookupTable::deserialize(&table.serialize()).unwrap();
+        assert_eq!(roundtrip, table);
+
+        let slot_hashes = SlotHashes::new(&[(1, Hash::default())]);
+        let lookup = MessageAddressTableLookup {
+            account_key: Pubkey::new_unique(),
+            writable_indexes: vec![3, 0],
+            readonly_indexes: vec![1],
+        };
+        let loaded = table.lookup(&lookup, 10, &slot_hashes).unwrap();
+        assert_eq!(loaded.writable, vec![addresses[3], addresses[0]]);
+        assert_eq!(loaded.readonly, vec![addresses[1]]);
+
+        let out_of_range =
+            MessageAddressTableLookup { readonly_indexes: vec![4], ..lookup.clone() };
+        assert!(table.lookup(&out_of_range, 10, &slot_hashes).is_err());
+
+        // Addresses appended in the current slot are not usable yet.
+        let extended = AddressLookupTable {
+            last_extended_slot: 10,
+            last_extended_slot_start_index: 2,
+            ..table.clone()
+        };
+        assert!(extended.lookup(&lookup, 10, &slot_hashes).is_err());
+        assert!(extended.lookup(&lookup, 11, &slot_hashes).is_ok());
+
+        // Deactivated tables remain usable while the deactivation slot is in the slot hashes.
+        let deactivated = AddressLookupTable { deactivation_slot: 1, ..table };
+        assert!(deactivated.lookup(&lookup, 10, &slot_hashes).is_ok());
+        assert!(deactivated
+            .lookup(&lookup, 10, &SlotHashes::new(&[]))
+            .is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_with::serde_as;
+use sha2::{Digest, Sha256};
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+
+/// One program pinned by a manifest. Exactly one of `path` and `url` must be set.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramEntry {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub program_id: Pubkey,
+    /// A local ELF, relative to the manifest's directory.
+    pub path: Option<PathBuf>,
+    /// A remote ELF, downloaded once into the cache directory.
+    pub url: Option<String>,
+    /// Hex-encoded sha256 of the ELF. Required for `url` entries, checked for `path` entries.
+    pub sha256: Option<String>,
+}
+
+/// Pins the third-party program binaries a test suite runs against.
+///
+/// ```toml
+/// cache_dir = "target/seashell/programs" # optional, relative to the manifest
+///
+/// [programs.token_swap]
+/// program_id = "SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8"
+/// url = "https://github.com/org/repo/releases/download/v1.0.0/token_swap.so"
+/// sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+///
+/// [programs.my_program]
+/// program_id = "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS"
+/// path = "target/deploy/my_program.so"
+/// ```
+///
+/// Remote binaries are cached under their hash, so bumping a pin never serves a stale file and
+/// unchanged pins never hit the network again.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramManifest {
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub programs: BTreeMap<String, ProgramEntry>,
+    /// Directory relative paths are resolved against.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl ProgramManifest {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SeashellError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self::from_toml_str(&contents, base_dir)
+    }
+
+    pub fn from_toml_str(
+        contents: &str,
+        base_dir: impl Into<PathBuf>,
+    ) -> Result<Self, SeashellError> {
+        let mut manifest: ProgramManifest = toml::from_str(contents)
+            .map_err(|e| SeashellError::Custom(format!("Failed to parse program manifest: {e}")))?;
+        manifest.base_dir = base_dir.into();
+        for (name, entry) in &manifest.programs {
+            match (&entry.path, &entry.url) {
+                (Some(_), None) => {}
+                (None, Some(_)) if entry.sha256.is_some() => {}
+                (None, Some(_)) => {
+                    return Err(SeashellError::Custom(format!(
+                        "Program {name} has a url but no sha256 pin"
+                    )))
+                }
+                _ => {
+                    return Err(SeashellError::Custom(format!(
+                        "Program {name} must set exactly one of path and url"
+                    )))
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ProgramEntry> {
+        self.programs.get(name)
+    }
+
+    /// Where downloaded binaries are stored: `cache_dir` if set, otherwise
+    /// `target/seashell/programs` under the workspace root.
+    pub fn cache_dir(&self) -> PathBuf {
+        match &self.cache_dir {
+            Some(cache_dir) => self.base_dir.join(cache_dir),
+            None => crate::seashell::try_find_workspace_root()
+                .unwrap_or_else(|| self.base_dir.clone())
+                .join("target/seashell/programs"),
+        }
+    }
+
+    /// Returns the ELF of program `name`, downloading it into the cache if needed, after checking
+    /// its hash against the pin.
+    pub fn fetch(&self, name: &str) -> Result<Vec<u8>, SeashellError> {
+        let entry = self
+            .get(name)
+            .ok_or_else(|| SeashellError::ProgramNotFound {
+                program_name: name.to_string(),
+                searched: Vec::new(),
+            })?;
+
+        if let Some(path) = &entry.path {
+            let bytes = std::fs::read(self.base_dir.join(path))?;
+            if let Some(expected) = &entry.sha256 {
+                verify_sha256(name, &bytes, expected)?;
+            }
+            return Ok(bytes);
+        }
+
+        let url = entry.url.as_ref().expect("validated on load");
+        let expected = entry.sha256.as_ref().expect("validated on load");
+        let cached = self
+            .cache_dir()
+            .join(format!("{}.so", expected.to_lowercase()));
+        if let Some(bytes) = read_cached(name, &cached, expected) {
+            return Ok(bytes);
+        }
+
+        log::info!("Downloading program {name} from {url}");
+        let bytes = download(url)?;
+        verify_sha256(name, &bytes, expected)?;
+        write_cached(&cached, &bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// The file at `cached`, if it exists and hashes to `expected`.
+pub(crate) fn read_cached(name: &str, cached: &Path, expected: &str) -> Option<Vec<u8>> {
+    let bytes = std::fs::read(cached).ok()?;
+    verify_sha256(name, &bytes, expected.trim()).ok()?;
+    Some(bytes)
+}
+
+/// Writes `bytes` to `cached`, creating its directory.
+pub(crate) fn write_cached(cached: &Path, bytes: &[u8]) -> Result<(), SeashellError> {
+    if let Some(dir) = cached.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file first so concurrent test binaries never see a partial file.
+    let mut temp = cached.as_os_str().to_owned();
+    temp.push(format!(".{}", std::process::id()));
+    std::fs::write(&temp, bytes)?;
+    std::fs::rename(&temp, cached)?;
+    Ok(())
+}
+
+#[cfg(feature = "download")]
+fn download(url: &str) -> Result<Vec<u8>, SeashellError> {
+    let response = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| SeashellError::Custom(format!("Failed to download {url}: {e}")))?;
+    let bytes = response
+        .bytes()
+        .map_err(|e| SeashellError::Custom(format!("Failed to download {url}: {e}")))?;
+    Ok(bytes.to_vec())
+}
+
+#[cfg(not(feature = "download"))]
+fn download(url: &str) -> Result<Vec<u8>, SeashellError> {
+    Err(SeashellError::Custom(format!("Downloading {url} needs the `download` feature")))
+}
+
+pub(crate) fn verify_sha256(name: &str, bytes: &[u8], expected: &str) -> Result<(), SeashellError> {
+    let actual = hex::encode(Sha256::digest(bytes));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(SeashellError::Custom(format!(
+            "Program {name} hash mismatch: expected {expected}, got {actual}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_entry_hash_is_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("prog.so"), b"elf").unwrap();
+        let program_id = Pubkey::new_unique();
+        let hash = hex::encode(Sha256::digest(b"elf"));
+
+        let manifest = ProgramManifest::from_toml_str(
+            &format!(
+                r#"
+                [programs.prog]
+                program_id = "{program_id}"
+                path = "prog.so"
+                sha256 = "{hash}"
+
+                [programs.bad]
+                program_id = "{program_id}"
+                path = "prog.so"
+                sha256 = "00"
+                "#
+            ),
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(manifest.get("prog").unwrap().program_id, program_id);
+        assert_eq!(manifest.fetch("prog").unwrap(), b"elf");
+        assert!(manifest.fetch("bad").is_err());
+        assert!(matches!(manifest.fetch("missing"), Err(SeashellError::ProgramNotFound { .. })));
+    }
+
+    #[test]
+    fn test_url_entry_requires_pin() {
+        let program_id = Pubkey::new_unique();
+        let err = ProgramManifest::from_toml_str(
+            &format!(
+                r#"
+                [programs.prog]
+                program_id = "{program_id}"
+                url = "https://example.com/prog.so"
+                "#
+            ),
+            ".",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("sha256"));
+    }
+}
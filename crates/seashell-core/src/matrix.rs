@@ -0,0 +1,253 @@
+//! Runs one test body under several runtime configurations.
+//!
+//! ```ignore
+//! let presets = [
+//!     Preset::all_enabled(),
+//!     Preset::all_enabled().without_feature("pending feature", pending_feature::id()),
+//! ];
+//! seashell::matrix::run(&presets, |seashell| {
+//!     seashell.process_instruction(ixn.clone()).compute_units_consumed
+//! })
+//! .assert_consistent();
+//! ```
+//!
+//! Programs that must work both before and after account data direct mapping activates run
+//! under [`Preset::direct_mapping_pair`]; bugs in how they handle account data, such as writing
+//! past the end of it, often only show with direct mapping on.
+
+use std::fmt::Debug;
+use std::panic::AssertUnwindSafe;
+
+use agave_feature_set::FeatureSet;
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_pubkey::Pubkey;
+
+use crate::seashell::{Config, Seashell};
+
+/// A named feature set and compute budget to run a test body under.
+#[derive(Debug, Clone)]
+pub struct Preset {
+    pub name: String,
+    pub feature_set: FeatureSet,
+    pub compute_budget: ComputeBudget,
+}
+
+impl Preset {
+    pub fn new(name: impl Into<String>, feature_set: FeatureSet) -> Self {
+        Preset {
+            name: name.into(),
+            feature_set,
+            compute_budget: ComputeBudget::new_with_defaults(false),
+        }
+    }
+
+    /// Every known feature active, which is what `Seashell::new` uses.
+    pub fn all_enabled() -> Self {
+        Preset::new("all_enabled", FeatureSet::all_enabled())
+    }
+
+    /// No feature active.
+    pub fn none_enabled() -> Self {
+        Preset::new("none_enabled", FeatureSet::default())
+    }
+
+    /// Activates `feature_id` and appends `+label` to the name.
+    pub fn with_feature(mut self, label: &str, feature_id: Pubkey) -> Self {
+        self.feature_set.activate(&feature_id, 0);
+        self.name = format!("{}+{label}", self.name);
+        self
+    }
+
+    /// Deactivates `feature_id` and appends `-label` to the name, e.g. to run against the cluster
+    /// as it is before a pending activation.
+    pub fn without_feature(mut self, label: &str, feature_id: Pubkey) -> Self {
+        self.feature_set.deactivate(&feature_id);
+        self.name = format!("{}-{label}", self.name);
+        self
+    }
+
+    /// Activates account data direct mapping, along with the stricter ABI it builds on, and
+    /// appends `+direct_mapping` to the name.
+    pub fn with_direct_mapping(mut self) -> Self {
+        self.feature_set
+            .activate(&agave_feature_set::stricter_abi_and_runtime_constraints::id(), 0);
+        self.with_feature("direct_mapping", agave_feature_set::account_data_direct_mapping::id())
+    }
+
+    /// Deactivates account data direct mapping and appends `-direct_mapping` to the name. The
+    /// stricter ABI is left as it is.
+    pub fn without_direct_mapping(self) -> Self {
+        self.without_feature("direct_mapping", agave_feature_set::account_data_direct_mapping::id())
+    }
+
+    /// This preset with direct mapping, then without it.
+    pub fn direct_mapping_pair(self) -> [Preset; 2] {
+        [self.clone().with_direct_mapping(), self.without_direct_mapping()]
+    }
+
+    pub fn compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget = compute_budget;
+        self
+    }
+
+    /// A fresh `Seashell` running under this preset.
+    pub fn seashell(&self, config: Config) -> Seashell {
+        let mut seashell =
+            Seashell::new_with_runtime(self.feature_set.clone(), self.compute_budget);
+        seashell.config = config;
+        seashell
+    }
+}
+
+impl Seashell {
+    /// Whether programs run with account data direct mapping. Set it up front with
+    /// [`Preset::with_direct_mapping`] or [`Preset::without_direct_mapping`]: programs are
+    /// verified for the runtime they were loaded under.
+    pub fn is_direct_mapping_active(&self) -> bool {
+        self.feature_set
+            .is_active(&agave_feature_set::account_data_direct_mapping::id())
+    }
+}
+
+/// The outcome of a test body under one preset: its return value, or the panic message.
+#[derive(Debug)]
+pub struct PresetOutcome<T> {
+    pub preset: String,
+    pub result: Result<T, String>,
+}
+
+#[derive(Debug)]
+pub struct MatrixReport<T> {
+    pub outcomes: Vec<PresetOutcome<T>>,
+}
+
+impl<T> MatrixReport<T> {
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.result {
+                Ok(_) => None,
+                Err(message) => Some((outcome.preset.as_str(), message.as_str())),
+            })
+    }
+
+    /// Panics listing every preset under which the test body panicked.
+    #[track_caller]
+    pub fn assert_all_passed(&self) {
+        let failures: Vec<String> = self
+            .failures()
+            .map(|(preset, message)| format!("  {preset}: {message}"))
+            .collect();
+        if !failures.is_empty() {
+            panic!(
+                "Test failed under {} of {} presets:\n{}",
+                failures.len(),
+                self.outcomes.len(),
+                failures.join("\n")
+            );
+        }
+    }
+}
+
+impl<T: PartialEq + Debug> MatrixReport<T> {
+    /// Panics unless the test body passed under every preset and returned the same value, so a
+    /// feature that changes behavior shows up as a diff between presets.
+    #[track_caller]
+    pub fn assert_consistent(&self) {
+        self.assert_all_passed();
+        let Some((first, rest)) = self.outcomes.split_first() else {
+            return;
+        };
+        let expected = first.result.as_ref().unwrap();
+        let diverging: Vec<String> = rest
+            .iter()
+            .filter(|outcome| outcome.result.as_ref().unwrap() != expected)
+            .map(|outcome| format!("  {}: {:?}", outcome.preset, outcome.result.as_ref().unwrap()))
+            .collect();
+        if !diverging.is_empty() {
+            panic!(
+                "Presets disagree with {} ({expected:?}):\n{}",
+                first.preset,
+                diverging.join("\n")
+            );
+        }
+    }
+}
+
+/// Runs `test` once per preset, each time on a fresh `Seashell` with the default `Config`.
+/// Panics in `test` are caught and reported per preset instead of aborting the matrix.
+pub fn run<T>(presets: &[Preset], test: impl Fn(&mut Seashell) -> T) -> MatrixReport<T> {
+    run_with_config(presets, Config::default, test)
+}
+
+/// Like [`run`], building each preset's `Config` with `config`.
+pub fn run_with_config<T>(
+    presets: &[Preset],
+    config: impl Fn() -> Config,
+    test: impl Fn(&mut Seashell) -> T,
+) -> MatrixReport<T> {
+    let outcomes = presets
+        .iter()
+        .map(|preset| {
+            let mut seashell = preset.seashell(config());
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| test(&mut seashell)))
+                .map_err(|payload| {
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "non-string panic payload".to_string())
+                });
+            PresetOutcome { preset: preset.name.clone(), result }
+        })
+        .collect();
+    MatrixReport { outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+
+    use super::*;
+    use crate::test_utils::system_transfer;
+
+    #[test]
+    fn test_matrix_run() {
+        let mut low_budget = ComputeBudget::new_with_defaults(false);
+        low_budget.compute_unit_limit = 100;
+        let presets = [
+            Preset::all_enabled(),
+            Preset::new("low_budget", FeatureSet::all_enabled()).compute_budget(low_budget),
+        ];
+
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let report = run(&presets, |seashell| {
+            seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+            seashell.set_account(to, Account::default());
+            let result = seashell.process_instruction(system_transfer(from, to, 600));
+            assert!(result.error.is_none(), "Transfer failed: {:?}", result.error);
+            result.account(&to).lamports
+        });
+
+        // The system program needs 150 units, so only the default budget passes.
+        assert_eq!(report.outcomes[0].result, Ok(600));
+        let failures: Vec<_> = report.failures().map(|(preset, _)| preset).collect();
+        assert_eq!(failures, vec!["low_budget"]);
+        assert!(std::panic::catch_unwind(|| report.assert_all_passed()).is_err());
+    }
+
+    #[test]
+    fn test_direct_mapping_pair() {
+        let presets = Preset::none_enabled().direct_mapping_pair();
+        assert_eq!(presets[0].name, "none_enabled+direct_mapping");
+        assert_eq!(presets[1].name, "none_enabled-direct_mapping");
+        assert!(presets[0]
+            .feature_set
+            .is_active(&agave_feature_set::stricter_abi_and_runtime_constraints::id()));
+
+        let report = run(&presets, |seashell| seashell.is_direct_mapping_active());
+        assert_eq!(report.outcomes[0].result, Ok(true));
+        assert_eq!(report.outcomes[1].result, Ok(false));
+    }
+}
@@ -0,0 +1,257 @@
+//! Execution counters and latency histograms for services embedding Seashell, like simulation
+//! APIs and backtest farms.
+//!
+//! Metrics go to a [`MetricsSink`] installed with [`crate::Seashell::set_metrics_sink`]; without
+//! one, nothing is recorded. [`PrometheusMetrics`] keeps them in memory for scraping and
+//! [`StatsdMetrics`] forwards them over UDP:
+//!
+//! ```ignore
+//! let metrics = Arc::new(PrometheusMetrics::new());
+//! metrics.serve("0.0.0.0:9464")?;
+//! seashell.set_metrics_sink(metrics.clone());
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{Read, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// Top-level instructions run, including each instruction of a chain or transaction.
+pub const INSTRUCTIONS: &str = "seashell_instructions_total";
+/// Instructions that failed, whether in the program or before it ran.
+pub const INSTRUCTION_ERRORS: &str = "seashell_instruction_errors_total";
+/// Wall-clock time of the runtime call of each instruction.
+pub const INSTRUCTION_SECONDS: &str = "seashell_instruction_duration_seconds";
+pub const COMPUTE_UNITS: &str = "seashell_compute_units";
+/// Accounts missing locally and in the scenario that were fetched from RPC.
+pub const RPC_FETCHES: &str = "seashell_rpc_fetches_total";
+pub const RPC_FETCH_FAILURES: &str = "seashell_rpc_fetch_failures_total";
+pub const RPC_FETCH_SECONDS: &str = "seashell_rpc_fetch_duration_seconds";
+
+/// Receives every metric Seashell records. Implementations must be cheap, since they are called
+/// for every instruction.
+pub trait MetricsSink: Send + Sync {
+    fn increment(&self, name: &'static str, value: u64);
+
+    /// Records one observation of a histogram.
+    fn observe(&self, name: &'static str, value: f64);
+}
+
+/// The sink metrics are recorded to, shared between a `Seashell` and its forks.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    sink: Option<Arc<dyn MetricsSink>>,
+}
+
+impl Metrics {
+    pub fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Metrics { sink: Some(sink) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn increment(&self, name: &'static str, value: u64) {
+        if let Some(sink) = &self.sink {
+            sink.increment(name, value);
+        }
+    }
+
+    pub fn observe(&self, name: &'static str, value: f64) {
+        if let Some(sink) = &self.sink {
+            sink.observe(name, value);
+        }
+    }
+}
+
+/// Upper bounds of the latency buckets, in seconds.
+const SECONDS_BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+const COMPUTE_UNIT_BUCKETS: &[f64] =
+    &[1_000.0, 5_000.0, 10_000.0, 50_000.0, 100_000.0, 200_000.0, 400_000.0, 1_400_000.0];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Histogram {
+    bounds: Vec<f64>,
+    /// Observations per bucket, not cumulative; the last entry counts those above every bound.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Histogram { bounds: bounds.to_vec(), counts: vec![0; bounds.len() + 1], sum: 0.0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+}
+
+/// Metrics kept in memory and rendered in the Prometheus text format.
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+    histograms: Mutex<BTreeMap<&'static str, Histogram>>,
+    buckets: BTreeMap<&'static str, Vec<f64>>,
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> Self {
+        PrometheusMetrics::default()
+    }
+
+    /// Uses `bounds` as the bucket upper bounds of histogram `name` instead of the defaults.
+    pub fn with_buckets(mut self, name: &'static str, bounds: &[f64]) -> Self {
+        self.buckets.insert(name, bounds.to_vec());
+        self
+    }
+
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters.lock().get(name).copied().unwrap_or(0)
+    }
+
+    /// Renders every metric recorded so far in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in self.counters.lock().iter() {
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        for (name, histogram) in self.histograms.lock().iter() {
+            let _ = writeln!(out, "# TYPE {name} histogram");
+            let mut cumulative = 0;
+            for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+                cumulative += count;
+                let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+            }
+            cumulative += histogram.counts[histogram.bounds.len()];
+            let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+            let _ = writeln!(out, "{name}_sum {}", histogram.sum);
+            let _ = writeln!(out, "{name}_count {cumulative}");
+        }
+        out
+    }
+
+    /// Answers every HTTP request on `addr` with `render` from a background thread, for
+    /// Prometheus to scrape. Returns the bound address, useful when binding port 0.
+    pub fn serve(self: &Arc<Self>, addr: impl ToSocketAddrs) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let metrics = self.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(err) = metrics.respond(stream) {
+                    log::debug!("Failed to serve metrics: {err}");
+                }
+            }
+        });
+        Ok(local_addr)
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        // Any path gets the metrics, so only the start of the request is read.
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request)?;
+        let body = self.render();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+             {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+}
+
+impl MetricsSink for PrometheusMetrics {
+    fn increment(&self, name: &'static str, value: u64) {
+        *self.counters.lock().entry(name).or_default() += value;
+    }
+
+    fn observe(&self, name: &'static str, value: f64) {
+        self.histograms
+            .lock()
+            .entry(name)
+            .or_insert_with(|| {
+                let bounds = self.buckets.get(name).map(Vec::as_slice);
+                Histogram::new(bounds.unwrap_or(match name {
+                    COMPUTE_UNITS => COMPUTE_UNIT_BUCKETS,
+                    _ => SECONDS_BUCKETS,
+                }))
+            })
+            .observe(value);
+    }
+}
+
+/// Sends each metric as a statsd packet, counters as `name:value|c` and histogram observations
+/// as `name:value|h`. Send errors are ignored, as usual for statsd.
+pub struct StatsdMetrics {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdMetrics {
+    /// Sends to the statsd agent at `addr`, prepending `prefix` (e.g. `"backtest."`) to names.
+    pub fn new(addr: impl ToSocketAddrs, prefix: impl Into<String>) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        Ok(StatsdMetrics { socket, prefix: prefix.into() })
+    }
+
+    fn send(&self, name: &str, value: impl std::fmt::Display, kind: &str) {
+        let packet = format!("{}{name}:{value}|{kind}", self.prefix);
+        let _ = self.socket.send(packet.as_bytes());
+    }
+}
+
+impl MetricsSink for StatsdMetrics {
+    fn increment(&self, name: &'static str, value: u64) {
+        self.send(name, value, "c");
+    }
+
+    fn observe(&self, name: &'static str, value: f64) {
+        self.send(name, value, "h");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seashell::Seashell;
+    use crate::test_utils::{system_transfer, transfer_accounts};
+
+    #[test]
+    fn test_metrics_sinks() {
+        let mut seashell = Seashell::new();
+        let metrics = Arc::new(PrometheusMetrics::new());
+        seashell.set_metrics_sink(metrics.clone());
+
+        let (from, to) = transfer_accounts(&seashell);
+        let transfer = |lamports| system_transfer(from, to, lamports);
+        seashell.process_instruction(transfer(400));
+        seashell.process_instruction(transfer(4000));
+
+        assert_eq!(metrics.counter(INSTRUCTIONS), 2);
+        assert_eq!(metrics.counter(INSTRUCTION_ERRORS), 1);
+        let rendered = metrics.render();
+        assert!(rendered.contains("# TYPE seashell_instruction_duration_seconds histogram"));
+        assert!(rendered.contains("seashell_instruction_duration_seconds_count 2"));
+        assert!(rendered.contains("seashell_compute_units_bucket{le=\"+Inf\"} 2"));
+
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let statsd = StatsdMetrics::new(agent.local_addr().unwrap(), "svc.").unwrap();
+        statsd.increment(INSTRUCTIONS, 3);
+        let mut packet = [0; 128];
+        let len = agent.recv(&mut packet).unwrap();
+        assert_eq!(&packet[..len], b"svc.seashell_instructions_total:3|c");
+    }
+}
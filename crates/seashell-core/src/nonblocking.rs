@@ -0,0 +1,200 @@
+//! Fetching scenario accounts without blocking an async runtime, for embedding seashell in
+//! tokio-based services.
+//!
+//! The blocking `RpcClient` behind [`Scenario::try_fetch_from_rpc`] drives its own runtime and
+//! must not be called from async code. Here the accounts an instruction is missing are fetched
+//! with the nonblocking client first, so executing it never goes to the RPC:
+//!
+//! ```ignore
+//! let result = seashell.process_instruction_async(ixn).await?;
+//! ```
+//!
+//! That future borrows the `Seashell`, so it can't be handed to `tokio::spawn`. To run the fetch
+//! on another task, split it: [`PendingFetch`] holds no reference to the `Seashell`.
+//!
+//! ```ignore
+//! if let Some(fetch) = seashell.pending_fetch_for(&ixn) {
+//!     let fetched = tokio::spawn(fetch.run()).await?;
+//!     seashell.store_fetch(fetched);
+//! }
+//! let result = seashell.process_instruction(ixn);
+//! ```
+//!
+//! [`Scenario::try_fetch_from_rpc`]: crate::scenario::Scenario::try_fetch_from_rpc
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use solana_account::{Account, AccountSharedData};
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+
+use crate::error::SeashellError;
+use crate::metrics;
+use crate::scenario::{account_info_config, PREFETCH_BATCH_SIZE};
+use crate::seashell::{InstructionProcessingResult, Seashell};
+
+/// A batch of RPC fetches for a scenario, created by `Scenario::pending_fetch`.
+pub struct PendingFetch {
+    pub(crate) rpc_client: RpcClient,
+    /// The scenario's slot when the fetch was created.
+    pub(crate) slot: Option<u64>,
+    pub(crate) pubkeys: Vec<Pubkey>,
+}
+
+/// What a [`PendingFetch`] got back, to store with `Scenario::store_fetch`.
+#[derive(Debug, Clone, Default)]
+pub struct FetchedAccounts {
+    /// The slot the fetches were pinned to, taken from the RPC if the scenario had none.
+    pub(crate) slot: Option<u64>,
+    /// The context slot of each `getMultipleAccounts` response, with its accounts.
+    pub(crate) batches: Vec<(u64, Vec<(Pubkey, Option<Account>)>)>,
+    pub(crate) requested: usize,
+    pub(crate) elapsed: Duration,
+}
+
+impl PendingFetch {
+    pub fn pubkeys(&self) -> &[Pubkey] {
+        &self.pubkeys
+    }
+
+    /// Fetches the accounts with one `getMultipleAccounts` request per 100 accounts. Failed
+    /// requests are logged and their accounts left out.
+    pub async fn run(self) -> FetchedAccounts {
+        let start = Instant::now();
+        let commitment = self.rpc_client.commitment();
+        let slot = match self.slot {
+            Some(slot) => Some(slot),
+            None => match self.rpc_client.get_slot_with_commitment(commitment).await {
+                Ok(slot) => Some(slot),
+                Err(err) => {
+                    log::warn!("Failed to get the slot to pin the scenario to: {err}");
+                    None
+                }
+            },
+        };
+        let config = account_info_config(commitment, slot);
+
+        let mut batches = Vec::new();
+        for batch in self.pubkeys.chunks(PREFETCH_BATCH_SIZE) {
+            match self
+                .rpc_client
+                .get_multiple_accounts_with_config(batch, config.clone())
+                .await
+            {
+                Ok(response) => batches.push((
+                    response.context.slot,
+                    batch.iter().copied().zip(response.value).collect(),
+                )),
+                Err(err) => log::warn!("Failed to fetch {} accounts: {err}", batch.len()),
+            }
+        }
+        FetchedAccounts { slot, batches, requested: self.pubkeys.len(), elapsed: start.elapsed() }
+    }
+}
+
+impl Seashell {
+    /// A fetch of the accounts `ixn` uses that are neither in the `AccountsDb` nor in the
+    /// scenario. `None` when nothing is missing or the scenario has no RPC client.
+    pub fn pending_fetch_for(&self, ixn: &Instruction) -> Option<PendingFetch> {
+        let mut missing = Vec::new();
+        let pubkeys =
+            std::iter::once(&ixn.program_id).chain(ixn.accounts.iter().map(|meta| &meta.pubkey));
+        for pubkey in pubkeys {
+            if *pubkey != solana_sdk_ids::sysvar::instructions::id()
+                && !missing.contains(pubkey)
+                && self.accounts_db.account_maybe(pubkey).is_none()
+            {
+                missing.push(*pubkey);
+            }
+        }
+        if missing.is_empty() {
+            return None;
+        }
+        self.accounts_db.scenario.pending_fetch(missing)
+    }
+
+    /// Stores the accounts of a finished fetch in the scenario, recording it in `metrics`, and
+    /// returns them.
+    pub fn store_fetch(&self, fetched: FetchedAccounts) -> HashMap<Pubkey, AccountSharedData> {
+        let requested = fetched.requested;
+        let elapsed = fetched.elapsed;
+        let accounts = self.accounts_db.scenario.store_fetch(fetched);
+        self.accounts_db
+            .metrics
+            .observe(metrics::RPC_FETCH_SECONDS, elapsed.as_secs_f64());
+        self.accounts_db
+            .metrics
+            .increment(metrics::RPC_FETCHES, requested as u64);
+        let failures = requested.saturating_sub(accounts.len());
+        if failures > 0 {
+            self.accounts_db
+                .metrics
+                .increment(metrics::RPC_FETCH_FAILURES, failures as u64);
+        }
+        accounts
+    }
+
+    /// `process_instruction` that fetches the accounts `ixn` is missing without blocking the
+    /// async runtime. Fails without running `ixn` if the RPC doesn't have one of them, rather
+    /// than falling back to a blocking fetch.
+    pub async fn process_instruction_async(
+        &self,
+        ixn: Instruction,
+    ) -> Result<InstructionProcessingResult, SeashellError> {
+        if let Some(fetch) = self.pending_fetch_for(&ixn) {
+            let pubkeys = fetch.pubkeys().to_vec();
+            let fetched = self.store_fetch(fetch.run().await);
+            let missing: Vec<String> = pubkeys
+                .iter()
+                .filter(|pubkey| !fetched.contains_key(*pubkey))
+                .map(ToString::to_string)
+                .collect();
+            if !missing.is_empty() {
+                return Err(SeashellError::Custom(format!(
+                    "Failed to fetch accounts {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+        Ok(self.process_instruction(ixn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+    use crate::seashell::Config;
+    use crate::test_utils::system_transfer;
+
+    #[tokio::test]
+    async fn test_process_instruction_async() {
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        let transfer = system_transfer(from, to, 400);
+
+        // Nothing is fetched without an RPC client.
+        assert!(seashell.pending_fetch_for(&transfer).is_none());
+
+        // The RPC is never reached: the fetch is stored as if it had returned `to`.
+        seashell.accounts_db.scenario = Scenario::rpc_only("http://127.0.0.1:1".to_string(), false);
+        let fetch = seashell.pending_fetch_for(&transfer).unwrap();
+        assert_eq!(fetch.pubkeys(), &[to]);
+        seashell.store_fetch(FetchedAccounts {
+            slot: Some(42),
+            batches: vec![(42, vec![(to, Some(Account::default()))])],
+            requested: 1,
+            elapsed: Duration::ZERO,
+        });
+        assert_eq!(seashell.accounts_db.scenario.slot(), Some(42));
+        assert!(seashell.pending_fetch_for(&transfer).is_none());
+
+        let result = seashell.process_instruction_async(transfer).await.unwrap();
+        assert!(result.error.is_none());
+        assert_eq!(seashell.account(&from).lamports, 600);
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use solana_account::ReadableAccount;
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::layout::LayoutRegistry;
+
+/// A typed write into account data at a fixed byte offset.
+///
+/// All integers are written little-endian; 128-bit values are encoded as strings in JSON.
+/// Every patch is bounds-checked against the account data before anything is written, so a bad
+/// offset fails loudly instead of corrupting a neighbouring field.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldPatch {
+    U8 {
+        offset: usize,
+        value: u8,
+    },
+    U16 {
+        offset: usize,
+        value: u16,
+    },
+    U32 {
+        offset: usize,
+        value: u32,
+    },
+    U64 {
+        offset: usize,
+        value: u64,
+    },
+    U128 {
+        offset: usize,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        value: u128,
+    },
+    I8 {
+        offset: usize,
+        value: i8,
+    },
+    I16 {
+        offset: usize,
+        value: i16,
+    },
+    I32 {
+        offset: usize,
+        value: i32,
+    },
+    I64 {
+        offset: usize,
+        value: i64,
+    },
+    I128 {
+        offset: usize,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        value: i128,
+    },
+    F64 {
+        offset: usize,
+        value: f64,
+    },
+    Bool {
+        offset: usize,
+        value: bool,
+    },
+    Pubkey {
+        offset: usize,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        value: Pubkey,
+    },
+    /// A fixed-point amount, e.g. `1.5` with 6 decimals is written as the u64 `1_500_000`.
+    Decimal {
+        offset: usize,
+        value: f64,
+        decimals: u8,
+    },
+    Bytes {
+        offset: usize,
+        #[serde_as(as = "serde_with::hex::Hex")]
+        value: Vec<u8>,
+    },
+}
+
+impl FieldPatch {
+    pub fn offset(&self) -> usize {
+        match self {
+            FieldPatch::U8 { offset, .. }
+            | FieldPatch::U16 { offset, .. }
+            | FieldPatch::U32 { offset, .. }
+            | FieldPatch::U64 { offset, .. }
+            | FieldPatch::U128 { offset, .. }
+            | FieldPatch::I8 { offset, .. }
+            | FieldPatch::I16 { offset, .. }
+            | FieldPatch::I32 { offset, .. }
+            | FieldPatch::I64 { offset, .. }
+            | FieldPatch::I128 { offset, .. }
+            | FieldPatch::F64 { offset, .. }
+            | FieldPatch::Bool { offset, .. }
+            | FieldPatch::Pubkey { offset, .. }
+            | FieldPatch::Decimal { offset, .. }
+            | FieldPatch::Bytes { offset, .. } => *offset,
+        }
+    }
+
+    /// The little-endian bytes this patch writes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SeashellError> {
+        let bytes = match self {
+            FieldPatch::U8 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::U16 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::U32 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::U64 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::U128 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::I8 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::I16 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::I32 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::I64 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::I128 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::F64 { value, .. } => value.to_le_bytes().to_vec(),
+            FieldPatch::Bool { value, .. } => vec![*value as u8],
+            FieldPatch::Pubkey { value, .. } => value.to_bytes().to_vec(),
+            FieldPatch::Decimal { value, decimals, .. } => {
+                let scaled = value * 10f64.powi(*decimals as i32);
+                if !scaled.is_finite() || scaled < 0.0 || scaled >= u64::MAX as f64 {
+                    return Err(SeashellError::Custom(format!(
+                        "Decimal {value} with {decimals} decimals does not fit in a u64"
+                    )));
+                }
+                (scaled.round() as u64).to_le_bytes().to_vec()
+            }
+            FieldPatch::Bytes { value, .. } => value.clone(),
+        };
+        Ok(bytes)
+    }
+
+    pub fn apply(&self, data: &mut [u8]) -> Result<(), SeashellError> {
+        let bytes = self.to_bytes()?;
+        let offset = self.offset();
+        let end = offset
+            .checked_add(bytes.len())
+            .filter(|end| *end <= data.len())
+            .ok_or(SeashellError::PatchOutOfRange {
+                offset,
+                size: bytes.len(),
+                data_len: data.len(),
+            })?;
+        data[offset..end].copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// A write into account data, either a [`FieldPatch`] at an explicit offset or a value for a named
+/// field of the account's registered layout:
+///
+/// ```json
+/// [
+///   { "type": "bytes", "offset": 1024, "value": "01" },
+///   { "field": "amount", "value": 1000000 }
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DataPatch {
+    Offset(FieldPatch),
+    Field { field: String, value: serde_json::Value },
+}
+
+impl From<FieldPatch> for DataPatch {
+    fn from(patch: FieldPatch) -> Self {
+        DataPatch::Offset(patch)
+    }
+}
+
+impl DataPatch {
+    /// The offset patch to apply to `account`, looking named fields up in the layout `layouts`
+    /// finds for it.
+    pub fn resolve(
+        &self,
+        account: &impl ReadableAccount,
+        layouts: &LayoutRegistry,
+    ) -> Result<FieldPatch, SeashellError> {
+        let (field, value) = match self {
+            DataPatch::Offset(patch) => return Ok(patch.clone()),
+            DataPatch::Field { field, value } => (field, value),
+        };
+        let layout = layouts
+            .find(account.owner(), account.data())
+            .ok_or_else(|| {
+                SeashellError::Custom(format!(
+                    "No registered layout of program {} matches the account",
+                    account.owner()
+                ))
+            })?;
+        let field = layout.get_field(field).ok_or_else(|| {
+            SeashellError::Custom(format!("Layout {} has no field {field}", layout.name))
+        })?;
+        Ok(FieldPatch::Bytes { offset: field.offset, value: field.ty.encode(value)? })
+    }
+}
+
+/// Applies every patch in order, leaving `data` untouched if any of them is invalid.
+pub fn apply_patches(data: &mut [u8], patches: &[FieldPatch]) -> Result<(), SeashellError> {
+    let mut patched = data.to_vec();
+    for patch in patches {
+        patch.apply(&mut patched)?;
+    }
+    data.copy_from_slice(&patched);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_patches() {
+        let mut data = vec![0u8; 48];
+        let key = Pubkey::new_unique();
+        apply_patches(
+            &mut data,
+            &[
+                FieldPatch::I64 { offset: 0, value: -2 },
+                FieldPatch::Pubkey { offset: 8, value: key },
+                FieldPatch::Decimal { offset: 40, value: 1.5, decimals: 6 },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(i64::from_le_bytes(data[0..8].try_into().unwrap()), -2);
+        assert_eq!(&data[8..40], key.as_ref());
+        assert_eq!(u64::from_le_bytes(data[40..48].try_into().unwrap()), 1_500_000);
+    }
+
+    #[test]
+    fn test_out_of_range_patch_is_atomic() {
+        let mut data = vec![0u8; 8];
+        let err = apply_patches(
+            &mut data,
+            &[FieldPatch::U8 { offset: 0, value: 1 }, FieldPatch::U64 { offset: 1, value: 1 }],
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, SeashellError::PatchOutOfRange { offset: 1, size: 8, data_len: 8 }));
+        assert_eq!(data, vec![0u8; 8]);
+    }
+
+    #[test]
+    fn test_decimal_overflow() {
+        let mut data = vec![0u8; 8];
+        // u64::MAX rounds up to 2^64 as an f64, which no longer fits.
+        let patch = FieldPatch::Decimal { offset: 0, value: u64::MAX as f64, decimals: 0 };
+        assert!(patch.apply(&mut data).is_err());
+        let patch = FieldPatch::Decimal { offset: 0, value: 1.8e19, decimals: 0 };
+        patch.apply(&mut data).unwrap();
+        assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), 18_000_000_000_000_000_000);
+    }
+
+    #[test]
+    fn test_patch_json_format() {
+        let patch: FieldPatch =
+            serde_json::from_str(r#"{"type":"i128","offset":4,"value":"-5"}"#).unwrap();
+        assert_eq!(patch, FieldPatch::I128 { offset: 4, value: -5 });
+    }
+}
@@ -0,0 +1,185 @@
+//! Hooks for extensions living outside seashell, like profilers, exporters and security checkers.
+//!
+//! A [`SeashellPlugin`] implements the hooks it needs and is registered with
+//! [`Seashell::register_plugin`]. Hooks take `&self`, so plugins keep their state behind a lock
+//! and are shared with whoever reads it afterwards:
+//!
+//! ```ignore
+//! let profiler = Arc::new(Profiler::default());
+//! seashell.register_plugin(profiler.clone());
+//! seashell.process_instruction(ixn);
+//! drop(seashell);
+//! profiler.write_report("target/profile.json")?;
+//! ```
+//!
+//! Forks run the plugins of the `Seashell` they were made from, but only the original ends their
+//! session.
+
+use std::sync::Arc;
+
+use solana_account::AccountSharedData;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+use crate::seashell::{InstructionProcessingResult, Seashell};
+
+/// An extension called back as a `Seashell` runs. Every hook does nothing by default.
+pub trait SeashellPlugin: Send + Sync {
+    /// Called before each top-level instruction runs, including each instruction of a chain or
+    /// transaction.
+    fn on_instruction_start(&self, _ixn: &Instruction) {}
+
+    /// Called once `ixn` ran, with its result after log filtering and audits.
+    fn on_instruction_end(&self, _ixn: &Instruction, _result: &InstructionProcessingResult) {}
+
+    /// Called for every account a successful instruction changed, with its post-execution state,
+    /// whether or not the write is committed to the `AccountsDb`.
+    fn on_account_write(&self, _pubkey: &Pubkey, _account: &AccountSharedData) {}
+
+    /// Called once when the `Seashell` the plugin was registered with is dropped.
+    fn on_session_end(&self) {}
+}
+
+/// The plugins of a `Seashell`, each with whether this `Seashell` ends its session.
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<(Arc<dyn SeashellPlugin>, bool)>,
+}
+
+impl Plugins {
+    pub fn register(&mut self, plugin: Arc<dyn SeashellPlugin>) {
+        self.plugins.push((plugin, true));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.plugins.len()
+    }
+
+    /// The same plugins, with their sessions left to this registry.
+    pub fn fork(&self) -> Self {
+        Plugins {
+            plugins: self
+                .plugins
+                .iter()
+                .map(|(plugin, _)| (plugin.clone(), false))
+                .collect(),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Arc<dyn SeashellPlugin>> {
+        self.plugins.iter().map(|(plugin, _)| plugin)
+    }
+
+    pub fn instruction_start(&self, ixn: &Instruction) {
+        for plugin in self.iter() {
+            plugin.on_instruction_start(ixn);
+        }
+    }
+
+    pub fn instruction_end(&self, ixn: &Instruction, result: &InstructionProcessingResult) {
+        for plugin in self.iter() {
+            for diff in &result.account_diffs {
+                if let Some(account) = result.post_account(&diff.pubkey) {
+                    plugin.on_account_write(&diff.pubkey, &account.clone().into());
+                }
+            }
+            plugin.on_instruction_end(ixn, result);
+        }
+    }
+}
+
+impl Drop for Plugins {
+    fn drop(&mut self) {
+        for (plugin, owns_session) in &self.plugins {
+            if *owns_session {
+                plugin.on_session_end();
+            }
+        }
+    }
+}
+
+impl Seashell {
+    /// Calls `plugin` back on every instruction this `Seashell` and its later forks run, and
+    /// ends its session when this `Seashell` is dropped.
+    pub fn register_plugin(&mut self, plugin: Arc<dyn SeashellPlugin>) {
+        self.plugins.register(plugin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+    use solana_account::{Account, ReadableAccount};
+
+    use super::*;
+    use crate::test_utils::system_transfer;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl SeashellPlugin for Recorder {
+        fn on_instruction_start(&self, ixn: &Instruction) {
+            self.events.lock().push(format!("start {}", ixn.data[0]));
+        }
+
+        fn on_instruction_end(&self, _ixn: &Instruction, result: &InstructionProcessingResult) {
+            self.events
+                .lock()
+                .push(format!("end {}", result.error.is_none()));
+        }
+
+        fn on_account_write(&self, _pubkey: &Pubkey, account: &AccountSharedData) {
+            self.events
+                .lock()
+                .push(format!("write {}", account.lamports()));
+        }
+
+        fn on_session_end(&self) {
+            self.events.lock().push("session end".to_string());
+        }
+    }
+
+    #[test]
+    fn test_plugin_hooks() {
+        let mut seashell = Seashell::new();
+        seashell.config.allow_uninitialized_accounts_local = true;
+        let recorder = Arc::new(Recorder::default());
+        seashell.register_plugin(recorder.clone());
+
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        let transfer = |lamports| system_transfer(from, to, lamports);
+        seashell.process_instruction(transfer(400));
+        seashell.process_instruction(transfer(4000));
+
+        // Forks share the plugins, but don't end their session.
+        let fork = seashell.fork();
+        fork.process_instruction(transfer(400));
+        drop(fork);
+        drop(seashell);
+
+        assert_eq!(
+            *recorder.events.lock(),
+            [
+                "start 2",
+                "write 600",
+                "write 400",
+                "end true",
+                "start 2",
+                "end false",
+                "start 2",
+                "write 600",
+                "write 400",
+                "end true",
+                "session end"
+            ]
+        );
+    }
+}
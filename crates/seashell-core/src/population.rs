@@ -0,0 +1,259 @@
+//! Large synthetic token account populations for load-testing instructions that iterate or
+//! paginate over many accounts, like airdrop claims and reward distributions.
+//!
+//! Populations are generated from a seed, so the same [`TokenPopulation`] always yields the same
+//! addresses and balances, and are written into the `AccountsDb` in one batch:
+//!
+//! ```ignore
+//! let population = seashell.generate_token_population(&TokenPopulation {
+//!     mints: 4,
+//!     accounts: 100_000,
+//!     amounts: Distribution::PowerLaw { min: 1, max: 1_000_000_000, exponent: 1.2 },
+//!     ..TokenPopulation::default()
+//! });
+//! ```
+
+use solana_account::{Account, AccountSharedData};
+use solana_pubkey::Pubkey;
+
+use crate::seashell::Seashell;
+use crate::spl::{MINT_ACCOUNT_SIZE, TOKEN_ACCOUNT_SIZE, TOKEN_PROGRAM_ID};
+
+/// How token amounts are spread over the accounts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Constant(u64),
+    /// Every amount in `min..=max` is equally likely.
+    Uniform {
+        min: u64,
+        max: u64,
+    },
+    /// A bounded Pareto distribution: most accounts hold close to `min` and a few hold up to
+    /// `max`, like real token holdings. Smaller exponents give heavier tails.
+    PowerLaw {
+        min: u64,
+        max: u64,
+        exponent: f64,
+    },
+}
+
+impl Distribution {
+    fn sample(&self, rng: &mut SplitMix64) -> u64 {
+        match *self {
+            Distribution::Constant(amount) => amount,
+            Distribution::Uniform { min, max } => {
+                let span = max.saturating_sub(min).saturating_add(1);
+                min + rng.next_u64() % span
+            }
+            Distribution::PowerLaw { min, max, exponent } => {
+                let (min, max) = (min.max(1), max.max(min.max(1)));
+                let low = (min as f64).powf(-exponent);
+                let high = (max as f64).powf(-exponent);
+                let amount = (low - rng.next_f64() * (low - high)).powf(-1.0 / exponent);
+                (amount as u64).clamp(min, max)
+            }
+        }
+    }
+}
+
+/// What to generate with [`Seashell::generate_token_population`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPopulation {
+    pub token_program: Pubkey,
+    pub mints: usize,
+    pub accounts: usize,
+    pub decimals: u8,
+    pub amounts: Distribution,
+    /// Relative share of the accounts each mint gets, e.g. `[1, 1, 2]`; empty spreads them
+    /// evenly.
+    pub mint_weights: Vec<u64>,
+    /// Number of distinct wallets owning the accounts, drawn uniformly; `0` gives every account
+    /// its own owner.
+    pub owners: usize,
+    pub seed: u64,
+}
+
+impl Default for TokenPopulation {
+    fn default() -> Self {
+        TokenPopulation {
+            token_program: TOKEN_PROGRAM_ID,
+            mints: 1,
+            accounts: 1_000,
+            decimals: 6,
+            amounts: Distribution::Constant(1_000_000),
+            mint_weights: Vec::new(),
+            owners: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// The addresses of a generated population.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Population {
+    /// Mint and freeze authority of every mint.
+    pub authority: Pubkey,
+    pub mints: Vec<Pubkey>,
+    /// `(token account, mint, owner)` in generation order.
+    pub token_accounts: Vec<(Pubkey, Pubkey, Pubkey)>,
+}
+
+impl Population {
+    /// The token accounts of `mint`.
+    pub fn accounts_of(&self, mint: &Pubkey) -> impl Iterator<Item = &Pubkey> {
+        self.token_accounts
+            .iter()
+            .filter(move |(_, account_mint, _)| account_mint == mint)
+            .map(|(account, ..)| account)
+    }
+}
+
+impl Seashell {
+    /// Writes the mints and token accounts described by `population` into the `AccountsDb`.
+    /// Each mint's supply is the sum of its accounts' balances.
+    pub fn generate_token_population(&self, population: &TokenPopulation) -> Population {
+        let mut rng = SplitMix64(population.seed);
+        let authority = rng.pubkey();
+        let mints: Vec<Pubkey> = (0..population.mints).map(|_| rng.pubkey()).collect();
+        let owners: Vec<Pubkey> = (0..population.owners).map(|_| rng.pubkey()).collect();
+        let total_weight: u64 = population.mint_weights.iter().sum();
+        let rent = self.accounts_db.sysvars.rent();
+
+        let mut supplies = vec![0u64; mints.len()];
+        let mut accounts = Vec::with_capacity(population.accounts + mints.len());
+        let mut token_accounts = Vec::with_capacity(population.accounts);
+        for index in 0..population.accounts {
+            if mints.is_empty() {
+                break;
+            }
+            let mint_index = if total_weight == 0 {
+                index % mints.len()
+            } else {
+                let mut pick = rng.next_u64() % total_weight;
+                population
+                    .mint_weights
+                    .iter()
+                    .position(|weight| {
+                        let hit = pick < *weight;
+                        pick = pick.saturating_sub(*weight);
+                        hit
+                    })
+                    .unwrap_or(0)
+                    .min(mints.len() - 1)
+            };
+            let owner = match owners.len() {
+                0 => rng.pubkey(),
+                count => owners[(rng.next_u64() % count as u64) as usize],
+            };
+            let amount = population.amounts.sample(&mut rng);
+            supplies[mint_index] = supplies[mint_index].saturating_add(amount);
+
+            let mut data = vec![0; TOKEN_ACCOUNT_SIZE];
+            data[0..32].copy_from_slice(mints[mint_index].as_ref());
+            data[32..64].copy_from_slice(owner.as_ref());
+            data[64..72].copy_from_slice(&amount.to_le_bytes());
+            data[108] = 1; // AccountState::Initialized
+            let pubkey = rng.pubkey();
+            accounts.push((pubkey, token_account(&population.token_program, &rent, data)));
+            token_accounts.push((pubkey, mints[mint_index], owner));
+        }
+
+        for (mint, supply) in mints.iter().zip(supplies) {
+            let mut data = vec![0; MINT_ACCOUNT_SIZE];
+            data[0..4].copy_from_slice(&1u32.to_le_bytes());
+            data[4..36].copy_from_slice(authority.as_ref());
+            data[36..44].copy_from_slice(&supply.to_le_bytes());
+            data[44] = population.decimals;
+            data[45] = 1; // is_initialized
+            data[46..50].copy_from_slice(&1u32.to_le_bytes());
+            data[50..82].copy_from_slice(authority.as_ref());
+            accounts.push((*mint, token_account(&population.token_program, &rent, data)));
+        }
+
+        self.accounts_db.accounts.write().extend(accounts);
+        Population { authority, mints, token_accounts }
+    }
+}
+
+fn token_account(
+    token_program: &Pubkey,
+    rent: &solana_rent::Rent,
+    data: Vec<u8>,
+) -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: *token_program,
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+/// A small, fast generator; populations only need to be reproducible, not unpredictable.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn pubkey(&mut self) -> Pubkey {
+        let mut bytes = [0; 32];
+        for chunk in bytes.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes());
+        }
+        Pubkey::new_from_array(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token_population() {
+        let seashell = Seashell::new();
+        let spec = TokenPopulation {
+            mints: 3,
+            accounts: 4_000,
+            amounts: Distribution::PowerLaw { min: 10, max: 1_000_000, exponent: 1.1 },
+            mint_weights: vec![1, 1, 2],
+            owners: 50,
+            seed: 7,
+            ..TokenPopulation::default()
+        };
+        let population = seashell.generate_token_population(&spec);
+        assert_eq!(population.token_accounts.len(), 4_000);
+        assert_eq!(population, Seashell::new().generate_token_population(&spec));
+
+        // The heaviest mint gets about half of the accounts.
+        let heavy = population.accounts_of(&population.mints[2]).count();
+        assert!((1_700..2_300).contains(&heavy), "{heavy}");
+
+        let mut supply = 0u64;
+        for pubkey in population.accounts_of(&population.mints[0]) {
+            let decoded = seashell
+                .layouts
+                .decode(&seashell.accounts_db.account_must(pubkey))
+                .unwrap();
+            let amount: u64 = decoded.get("amount").unwrap().parse().unwrap();
+            assert!((10..=1_000_000).contains(&amount));
+            supply += amount;
+        }
+        let mint = seashell
+            .layouts
+            .decode(&seashell.accounts_db.account_must(&population.mints[0]))
+            .unwrap();
+        assert_eq!(mint.get("supply"), Some(supply.to_string().as_str()));
+        assert_eq!(mint.get("decimals"), Some("6"));
+    }
+}
@@ -0,0 +1,203 @@
+//! Deterministic keys for the ed25519, secp256k1 and secp256r1 precompiles, so tests of
+//! signature-gated instructions can build valid verification instructions without pulling the
+//! crypto libraries behind them into their own dev-dependencies:
+//!
+//! ```ignore
+//! let oracle = Ed25519TestKey::from_seed(7);
+//! let verify = oracle.verify_instruction(&price_message);
+//! let result = seashell.process_instruction_chain(vec![verify, update_price(oracle.pubkey())]);
+//! ```
+//!
+//! The same seed always gives the same key, so fixtures that store public keys or addresses
+//! stay valid across runs. Each curve derives its key from the seed separately.
+
+use ed25519_dalek::Signer;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use sha2::{Digest, Sha256};
+use solana_instruction::Instruction;
+
+/// 32 bytes derived from `seed` for `curve`; another `round` gives other bytes to retry with.
+fn seed_bytes(curve: &str, seed: u64, round: u8) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(curve);
+    hasher.update(seed.to_le_bytes());
+    hasher.update([round]);
+    hasher.finalize().into()
+}
+
+/// An ed25519 key for the ed25519 precompile.
+pub struct Ed25519TestKey {
+    keypair: ed25519_dalek::Keypair,
+}
+
+impl Ed25519TestKey {
+    pub fn from_seed(seed: u64) -> Self {
+        Self::from_secret(seed_bytes("ed25519", seed, 0))
+    }
+
+    pub fn from_secret(secret: [u8; 32]) -> Self {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&secret).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        Ed25519TestKey { keypair: ed25519_dalek::Keypair { secret, public } }
+    }
+
+    pub fn pubkey(&self) -> [u8; 32] {
+        self.keypair.public.to_bytes()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.keypair.sign(message).to_bytes()
+    }
+
+    /// An ed25519 precompile instruction verifying this key's signature of `message`.
+    pub fn verify_instruction(&self, message: &[u8]) -> Instruction {
+        solana_ed25519_program::new_ed25519_instruction_with_signature(
+            message,
+            &self.sign(message),
+            &self.pubkey(),
+        )
+    }
+}
+
+/// A secp256k1 key for the secp256k1 precompile, which checks Ethereum-style signatures.
+pub struct Secp256k1TestKey {
+    secret: libsecp256k1::SecretKey,
+}
+
+impl Secp256k1TestKey {
+    pub fn from_seed(seed: u64) -> Self {
+        // A hash is out of the curve's range with negligible probability; rehash if it is.
+        let secret = (0..=u8::MAX)
+            .find_map(|round| {
+                libsecp256k1::SecretKey::parse(&seed_bytes("secp256k1", seed, round)).ok()
+            })
+            .unwrap();
+        Secp256k1TestKey { secret }
+    }
+
+    /// The public key, uncompressed and without its `0x04` prefix.
+    pub fn pubkey(&self) -> [u8; 64] {
+        libsecp256k1::PublicKey::from_secret_key(&self.secret).serialize()[1..]
+            .try_into()
+            .unwrap()
+    }
+
+    /// The Ethereum address the precompile checks signatures against.
+    pub fn eth_address(&self) -> [u8; 20] {
+        solana_secp256k1_program::eth_address_from_pubkey(&self.pubkey())
+    }
+
+    /// The signature of the Keccak-256 hash of `message`, with its recovery id.
+    pub fn sign(&self, message: &[u8]) -> ([u8; 64], u8) {
+        solana_secp256k1_program::sign_message(&self.secret.serialize(), message).unwrap()
+    }
+
+    /// A secp256k1 precompile instruction verifying this key's signature of `message`.
+    pub fn verify_instruction(&self, message: &[u8]) -> Instruction {
+        let (signature, recovery_id) = self.sign(message);
+        solana_secp256k1_program::new_secp256k1_instruction_with_signature(
+            message,
+            &signature,
+            recovery_id,
+            &self.eth_address(),
+        )
+    }
+}
+
+/// A P-256 key for the secp256r1 precompile, as passkeys and WebAuthn use. Signatures are
+/// randomized, but always verify.
+pub struct Secp256r1TestKey {
+    key: EcKey<Private>,
+}
+
+impl Secp256r1TestKey {
+    pub fn from_seed(seed: u64) -> Self {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let mut ctx = BigNumContext::new().unwrap();
+        let mut order = BigNum::new().unwrap();
+        group.order(&mut order, &mut ctx).unwrap();
+        // Maps the seed into [1, order - 1].
+        let mut range = BigNum::new().unwrap();
+        range
+            .checked_sub(&order, &BigNum::from_u32(1).unwrap())
+            .unwrap();
+        let mut private = BigNum::new().unwrap();
+        private
+            .nnmod(
+                &BigNum::from_slice(&seed_bytes("secp256r1", seed, 0)).unwrap(),
+                &range,
+                &mut ctx,
+            )
+            .unwrap();
+        private.add_word(1).unwrap();
+        let mut public = EcPoint::new(&group).unwrap();
+        public.mul_generator(&group, &private, &ctx).unwrap();
+        let key = EcKey::from_private_components(&group, &private, &public).unwrap();
+        Secp256r1TestKey { key }
+    }
+
+    /// The public key, compressed.
+    pub fn pubkey(&self) -> [u8; 33] {
+        let mut ctx = BigNumContext::new().unwrap();
+        self.key
+            .public_key()
+            .to_bytes(self.key.group(), PointConversionForm::COMPRESSED, &mut ctx)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    /// The low-S signature of the SHA-256 hash of `message`, as the precompile requires.
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        solana_secp256r1_program::sign_message(message, &self.key.private_key_to_der().unwrap())
+            .unwrap()
+    }
+
+    /// A secp256r1 precompile instruction verifying this key's signature of `message`.
+    pub fn verify_instruction(&self, message: &[u8]) -> Instruction {
+        solana_secp256r1_program::new_secp256r1_instruction_with_signature(
+            message,
+            &self.sign(message),
+            &self.pubkey(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seashell::Seashell;
+
+    #[test]
+    fn test_precompile_keys() {
+        let seashell = Seashell::new();
+        let message = b"price: 42";
+
+        assert_eq!(Ed25519TestKey::from_seed(1).pubkey(), Ed25519TestKey::from_seed(1).pubkey());
+        assert_ne!(Ed25519TestKey::from_seed(1).pubkey(), Ed25519TestKey::from_seed(2).pubkey());
+        assert_eq!(
+            Secp256k1TestKey::from_seed(1).eth_address(),
+            Secp256k1TestKey::from_seed(1).eth_address()
+        );
+        assert_eq!(
+            Secp256r1TestKey::from_seed(1).pubkey(),
+            Secp256r1TestKey::from_seed(1).pubkey()
+        );
+
+        for ixn in [
+            Ed25519TestKey::from_seed(1).verify_instruction(message),
+            Secp256k1TestKey::from_seed(1).verify_instruction(message),
+            Secp256r1TestKey::from_seed(1).verify_instruction(message),
+        ] {
+            let result = seashell.process_instruction(ixn);
+            assert!(result.error.is_none(), "{:?}", result.error);
+        }
+
+        let mut forged = Ed25519TestKey::from_seed(1).verify_instruction(message);
+        *forged.data.last_mut().unwrap() ^= 1;
+        assert!(seashell.process_instruction(forged).error.is_some());
+    }
+}
@@ -0,0 +1,22 @@
+//! The types most tests need, as `use seashell::prelude::*;`.
+//!
+//! Everything re-exported here follows semver: it only changes in breaking ways on a major version
+//! bump. The rest of the public API is reachable through its module; modules hidden from the docs
+//! (`accounts_db`, `compile`, `precompiles`, `sysvar`) and the `Seashell::accounts_db` field are
+//! internals that may change in any release.
+
+pub use crate::diff::AccountDiff;
+pub use crate::error::SeashellError;
+pub use crate::manifest::ProgramManifest;
+pub use crate::patch::{DataPatch, FieldPatch};
+pub use crate::scenario::PinningSuggestion;
+pub use crate::seashell::{
+    Config, InstructionChainResult, InstructionProcessingError, InstructionProcessingResult,
+    Seashell,
+};
+pub use crate::spl::extensions::{ExtensionAccountBuilder, ExtensionMintBuilder};
+pub use crate::spl::{
+    associated_token_address, TokenBalance, ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+};
+pub use crate::trace::{ComputeUnitFrame, TracedInstruction};
@@ -0,0 +1,45 @@
+//! Builtins, precompiles and SPL programs verified once per process.
+//!
+//! Verifying the bundled ELFs dominates the cost of constructing a `Seashell`, and every
+//! `Seashell::new` verifies the same programs under the same runtime. The first call loads them
+//! into a template; later calls clone its program cache, which only copies `Arc`s.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use solana_account::AccountSharedData;
+use solana_program_runtime::loaded_programs::ProgramCacheForTxBatch;
+use solana_pubkey::Pubkey;
+
+use crate::accounts_db::AccountsDb;
+use crate::seashell::Seashell;
+
+static DEFAULT_PROGRAMS: OnceLock<DefaultPrograms> = OnceLock::new();
+
+/// The programs and program accounts `Seashell::new` starts with. Read-only once built: every
+/// instance gets its own copy of the cache, so loading or upgrading a program never leaks into
+/// other instances.
+pub(crate) struct DefaultPrograms {
+    programs: ProgramCacheForTxBatch,
+    accounts: HashMap<Pubkey, AccountSharedData>,
+}
+
+impl DefaultPrograms {
+    pub(crate) fn get() -> &'static Self {
+        DEFAULT_PROGRAMS.get_or_init(|| {
+            let mut seashell = Seashell::default();
+            seashell.load_default_programs();
+            let AccountsDb { programs, accounts, .. } = seashell.accounts_db;
+            DefaultPrograms { programs: programs.into_inner(), accounts: accounts.into_inner() }
+        })
+    }
+
+    pub(crate) fn install(&self, accounts_db: &mut AccountsDb) {
+        *accounts_db.programs.get_mut() = self.programs.clone();
+        accounts_db.accounts.get_mut().extend(
+            self.accounts
+                .iter()
+                .map(|(pubkey, account)| (*pubkey, account.clone())),
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! A shim with the shape of `solana-program-test`, so its tests port by changing imports:
+//!
+//! ```ignore
+//! use seashell::program_test::ProgramTest;
+//!
+//! let (mut banks_client, payer, blockhash) =
+//!     ProgramTest::new("my_program", my_program::id(), None).start().await;
+//! let tx =
+//!     Transaction::new_signed_with_payer(&[ixn], Some(&payer.pubkey()), &[&payer], blockhash);
+//! banks_client.process_transaction(tx).await.unwrap();
+//! ```
+//!
+//! Programs are loaded as [`Seashell::load_program_from_environment`] finds them, and native
+//! processors are not supported, so the third argument of [`ProgramTest::new`] is always `None`.
+//! The [`BanksClient`] runs transactions in place on a [`LiteSVM`] facade; its methods are async
+//! only to match, and complete without yielding.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use solana_account::{Account, AccountSharedData};
+use solana_hash::Hash;
+use solana_keypair::Keypair;
+use solana_native_token::LAMPORTS_PER_SOL;
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_error::TransactionError;
+
+use crate::litesvm::LiteSVM;
+use crate::seashell::Seashell;
+
+/// Lamports the payer of a started `ProgramTest` is funded with.
+pub const PAYER_LAMPORTS: u64 = 1_000_000 * LAMPORTS_PER_SOL;
+
+/// Stands in for the native processor `solana-program-test` can run a program with, which
+/// seashell doesn't support: only `None` can be passed.
+pub type BuiltinFunction = Infallible;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BanksClientError {
+    #[error("transaction error: {0}")]
+    TransactionError(TransactionError),
+}
+
+impl BanksClientError {
+    /// The error of the failed transaction, as `solana-banks-client` offers it.
+    pub fn unwrap(&self) -> TransactionError {
+        match self {
+            BanksClientError::TransactionError(err) => err.clone(),
+        }
+    }
+}
+
+/// A `BanksClient` lookalike. Clones share the same state.
+#[derive(Clone)]
+pub struct BanksClient {
+    svm: Arc<Mutex<LiteSVM>>,
+}
+
+impl BanksClient {
+    pub async fn process_transaction(
+        &mut self,
+        transaction: impl Into<VersionedTransaction>,
+    ) -> Result<(), BanksClientError> {
+        self.svm
+            .lock()
+            .send_transaction(transaction)
+            .map(|_| ())
+            .map_err(|failed| BanksClientError::TransactionError(failed.err))
+    }
+
+    /// Processes `transactions` in order, stopping at the first that fails.
+    pub async fn process_transactions(
+        &mut self,
+        transactions: Vec<impl Into<VersionedTransaction>>,
+    ) -> Result<(), BanksClientError> {
+        for transaction in transactions {
+            self.process_transaction(transaction).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_account(
+        &mut self,
+        address: Pubkey,
+    ) -> Result<Option<Account>, BanksClientError> {
+        Ok(self.svm.lock().get_account(&address))
+    }
+
+    pub async fn get_balance(&mut self, address: Pubkey) -> Result<u64, BanksClientError> {
+        Ok(self.svm.lock().get_balance(&address).unwrap_or(0))
+    }
+
+    pub async fn get_latest_blockhash(&mut self) -> Result<Hash, BanksClientError> {
+        Ok(self.svm.lock().latest_blockhash())
+    }
+
+    pub async fn get_rent(&mut self) -> Result<Rent, BanksClientError> {
+        Ok(self.svm.lock().seashell().accounts_db.sysvars.rent())
+    }
+
+    pub async fn get_root_slot(&mut self) -> Result<u64, BanksClientError> {
+        Ok(self.svm.lock().seashell().accounts_db.sysvars.clock().slot)
+    }
+}
+
+/// What [`ProgramTest::start_with_context`] returns, as `ProgramTestContext`.
+pub struct ProgramTestContext {
+    pub banks_client: BanksClient,
+    pub last_blockhash: Hash,
+    pub payer: Keypair,
+}
+
+impl ProgramTestContext {
+    /// Advances the clock to `slot`, see [`Seashell::warp_to_slot`].
+    pub fn warp_to_slot(&mut self, slot: u64) -> Result<(), BanksClientError> {
+        self.banks_client.svm.lock().warp_to_slot(slot);
+        Ok(())
+    }
+
+    pub fn set_account(&mut self, address: &Pubkey, account: &AccountSharedData) {
+        self.banks_client
+            .svm
+            .lock()
+            .seashell()
+            .set_account_from_account_shared_data(*address, account.clone());
+    }
+
+    /// Registers a new blockhash and makes it `last_blockhash`.
+    pub async fn get_new_latest_blockhash(&mut self) -> std::io::Result<Hash> {
+        let mut svm = self.banks_client.svm.lock();
+        svm.expire_blockhash();
+        self.last_blockhash = svm.latest_blockhash();
+        Ok(self.last_blockhash)
+    }
+}
+
+/// A `ProgramTest` lookalike building a [`LiteSVM`] facade over a fresh `Seashell`.
+#[derive(Default)]
+pub struct ProgramTest {
+    programs: Vec<(String, Pubkey)>,
+    accounts: Vec<(Pubkey, Account)>,
+    compute_max_units: Option<u64>,
+}
+
+impl ProgramTest {
+    pub fn new(
+        program_name: &str,
+        program_id: Pubkey,
+        builtin_function: Option<BuiltinFunction>,
+    ) -> Self {
+        let mut program_test = ProgramTest::default();
+        program_test.add_program(program_name, program_id, builtin_function);
+        program_test
+    }
+
+    /// Loads `<program_name>.so` when started, see [`Seashell::load_program_from_environment`].
+    pub fn add_program(
+        &mut self,
+        program_name: &str,
+        program_id: Pubkey,
+        _builtin_function: Option<BuiltinFunction>,
+    ) {
+        self.programs.push((program_name.to_string(), program_id));
+    }
+
+    pub fn add_account(&mut self, address: Pubkey, account: Account) {
+        self.accounts.push((address, account));
+    }
+
+    pub fn set_compute_max_units(&mut self, compute_max_units: u64) {
+        self.compute_max_units = Some(compute_max_units);
+    }
+
+    /// Builds the `Seashell`, funding a new payer with [`PAYER_LAMPORTS`]. Panics if a program
+    /// can't be found, like `solana-program-test` does.
+    pub async fn start(self) -> (BanksClient, Keypair, Hash) {
+        let ProgramTestContext { banks_client, last_blockhash, payer } =
+            self.start_with_context().await;
+        (banks_client, payer, last_blockhash)
+    }
+
+    pub async fn start_with_context(self) -> ProgramTestContext {
+        let mut svm = LiteSVM::new();
+        let seashell: &mut Seashell = svm.seashell_mut();
+        if let Some(compute_max_units) = self.compute_max_units {
+            seashell.compute_budget.compute_unit_limit = compute_max_units;
+        }
+        for (program_name, program_id) in &self.programs {
+            if let Err(err) = seashell.load_program_from_environment(program_name, *program_id) {
+                panic!("Failed to load {program_name}: {err}");
+            }
+        }
+        for (address, account) in self.accounts {
+            seashell.set_account(address, account);
+        }
+        let payer = Keypair::new();
+        seashell.airdrop(payer.pubkey(), PAYER_LAMPORTS);
+        let last_blockhash = svm.latest_blockhash();
+        ProgramTestContext {
+            banks_client: BanksClient { svm: Arc::new(Mutex::new(svm)) },
+            last_blockhash,
+            payer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::error::InstructionError;
+    use solana_instruction::{AccountMeta, Instruction};
+    use solana_transaction::Transaction;
+
+    use super::*;
+    use crate::test_utils::system_transfer;
+
+    #[tokio::test]
+    async fn test_program_test_shim() {
+        let mut program_test = ProgramTest::default();
+        let to = Pubkey::new_unique();
+        program_test.add_account(to, Account { lamports: 5, ..Account::default() });
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+
+        let transfer = |lamports| system_transfer(payer.pubkey(), to, lamports);
+        let tx = |lamports, blockhash| {
+            Transaction::new_signed_with_payer(
+                &[transfer(lamports)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                blockhash,
+            )
+        };
+
+        let banks_client = &mut context.banks_client;
+        banks_client
+            .process_transaction(tx(400, context.last_blockhash))
+            .await
+            .unwrap();
+        assert_eq!(banks_client.get_balance(to).await.unwrap(), 405);
+
+        let err = banks_client
+            .process_transaction(tx(PAYER_LAMPORTS, context.last_blockhash))
+            .await
+            .unwrap_err();
+        // SystemError::ResultWithNegativeLamports
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(1))
+        );
+
+        let blockhash = context.get_new_latest_blockhash().await.unwrap();
+        assert_ne!(blockhash, Hash::default());
+        context.warp_to_slot(100).unwrap();
+        assert_eq!(context.banks_client.get_root_slot().await.unwrap(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_program_test_invokes_program() {
+        let spl_elfs_out_dir = crate::seashell::try_find_workspace_root()
+            .unwrap()
+            .join("crates/seashell-core/src/spl/elfs");
+        unsafe { std::env::set_var("SBF_OUT_DIR", spl_elfs_out_dir.to_str().unwrap()) }
+
+        // SPL Token under a fresh id, initializing a mint it owns.
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("tokenkeg", program_id, None);
+        let (mint, mint_authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        program_test.add_account(
+            mint,
+            Account {
+                lamports: Rent::default().minimum_balance(crate::spl::MINT_ACCOUNT_SIZE),
+                data: vec![0; crate::spl::MINT_ACCOUNT_SIZE],
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let (mut banks_client, payer, blockhash) = program_test.start().await;
+
+        let mut data = vec![20, 6];
+        data.extend_from_slice(mint_authority.as_ref());
+        data.push(0);
+        let initialize_mint =
+            Instruction { program_id, accounts: vec![AccountMeta::new(mint, false)], data };
+        let tx = Transaction::new_signed_with_payer(
+            &[initialize_mint],
+            Some(&payer.pubkey()),
+            &[&payer],
+            blockhash,
+        );
+        banks_client.process_transaction(tx).await.unwrap();
+
+        let mint_account = banks_client.get_account(mint).await.unwrap().unwrap();
+        assert_eq!(&mint_account.data[4..36], mint_authority.as_ref());
+        // decimals, is_initialized
+        assert_eq!(mint_account.data[44..46], [6, 1]);
+    }
+}
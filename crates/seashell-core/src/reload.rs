@@ -0,0 +1,296 @@
+//! A rebuild-and-rerun loop for program development: every time the program's source or the
+//! scenario changes, the program is rebuilt with `cargo build-sbf`, reloaded into a warm
+//! `Seashell` that keeps its accounts, and the chosen instruction runs again against a fork of
+//! it. Each run prints its outcome and how it differs from the previous one.
+//!
+//! ```ignore
+//! let mut seashell = Seashell::new();
+//! seashell.load_scenario("phoenix_market");
+//! let reload = ReloadLoop::new("phoenix", PHOENIX_ID, "programs/phoenix/Cargo.toml");
+//! seashell.run_reload_loop(&reload, |seashell| seashell.process_instruction(place_order()))?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use solana_pubkey::Pubkey;
+
+use crate::diff::AccountDiff;
+use crate::error::SeashellError;
+use crate::layout::LayoutRegistry;
+use crate::seashell::{InstructionProcessingError, InstructionProcessingResult, Seashell};
+
+/// What [`Seashell::run_reload_loop`] builds and watches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReloadLoop {
+    pub program_name: String,
+    pub program_id: Pubkey,
+    /// `Cargo.toml` of the program. Its directory's `src` is watched.
+    pub manifest_path: PathBuf,
+    /// Extra arguments to `cargo build-sbf`, e.g. `["--features", "bpf-entrypoint"]`.
+    pub build_args: Vec<String>,
+    /// Files and directories watched besides the program's sources and the loaded scenario.
+    pub watch_paths: Vec<PathBuf>,
+    pub poll_interval: Duration,
+    /// Stop after this many runs; `None` runs until the process is stopped.
+    pub max_runs: Option<usize>,
+}
+
+impl ReloadLoop {
+    pub fn new(
+        program_name: impl Into<String>,
+        program_id: Pubkey,
+        manifest_path: impl Into<PathBuf>,
+    ) -> Self {
+        ReloadLoop {
+            program_name: program_name.into(),
+            program_id,
+            manifest_path: manifest_path.into(),
+            build_args: Vec::new(),
+            watch_paths: Vec::new(),
+            poll_interval: Duration::from_millis(500),
+            max_runs: None,
+        }
+    }
+
+    fn program_dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Where the built `.so` is written.
+    pub fn out_dir(&self) -> PathBuf {
+        self.program_dir().join("target/deploy")
+    }
+
+    /// Runs `cargo build-sbf` on the program.
+    pub fn build(&self) -> Result<(), SeashellError> {
+        let cargo = std::env::var("CARGO").unwrap_or("cargo".to_owned());
+        let status = Command::new(cargo)
+            .arg("build-sbf")
+            .arg("--manifest-path")
+            .arg(&self.manifest_path)
+            .arg("--sbf-out-dir")
+            .arg(self.out_dir())
+            .args(&self.build_args)
+            .status()?;
+        if !status.success() {
+            return Err(SeashellError::Custom(format!(
+                "cargo build-sbf of {} failed with {status}",
+                self.program_name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Modification times of every file under a set of paths, to tell which ones changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileStamps(BTreeMap<PathBuf, SystemTime>);
+
+impl FileStamps {
+    /// Stamps every file in `paths`, descending into directories except `target`.
+    pub fn take(paths: &[PathBuf]) -> Self {
+        let mut stamps = BTreeMap::new();
+        let mut pending = paths.to_vec();
+        while let Some(path) = pending.pop() {
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if path.file_name().is_some_and(|name| name == "target") {
+                    continue;
+                }
+                if let Ok(entries) = std::fs::read_dir(&path) {
+                    pending.extend(entries.flatten().map(|entry| entry.path()));
+                }
+            } else if let Ok(modified) = metadata.modified() {
+                stamps.insert(path, modified);
+            }
+        }
+        FileStamps(stamps)
+    }
+
+    /// Files added, removed or modified since `earlier`.
+    pub fn changed_since(&self, earlier: &FileStamps) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .0
+            .iter()
+            .filter(|(path, modified)| earlier.0.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            earlier
+                .0
+                .keys()
+                .filter(|path| !self.0.contains_key(*path))
+                .cloned(),
+        );
+        changed
+    }
+}
+
+/// What one run of the loop produced, kept to compare with the next run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub error: Option<InstructionProcessingError>,
+    pub compute_units_consumed: u64,
+    pub account_diffs: Vec<AccountDiff>,
+    /// `account_diffs` with the changed data named through the layouts, see
+    /// [`AccountDiff::format_with`].
+    pub account_changes: Vec<String>,
+    pub logs: Vec<String>,
+}
+
+impl RunSummary {
+    pub fn new(result: &InstructionProcessingResult, layouts: &LayoutRegistry) -> Self {
+        let account_changes = result
+            .account_diffs
+            .iter()
+            .map(|diff| match result.post_account(&diff.pubkey) {
+                Some(post) => diff.format_with(layouts, post),
+                None => diff.to_string(),
+            })
+            .collect();
+        RunSummary {
+            error: result.error.clone(),
+            compute_units_consumed: result.compute_units_consumed,
+            account_diffs: result.account_diffs.clone(),
+            account_changes,
+            logs: result.logs.clone(),
+        }
+    }
+
+    /// The outcome of this run and its account changes, noting what differs from `previous`.
+    pub fn report(&self, previous: Option<&RunSummary>) -> String {
+        let mut out = String::new();
+        match &self.error {
+            None => out.push_str("ok"),
+            Some(error) => {
+                let _ = write!(out, "failed: {error:?}");
+            }
+        }
+        let _ = write!(out, ", {} CU", self.compute_units_consumed);
+        if let Some(previous) = previous {
+            let delta = self.compute_units_consumed as i64 - previous.compute_units_consumed as i64;
+            if delta != 0 {
+                let _ = write!(out, " ({delta:+})");
+            }
+            if previous.error != self.error {
+                match &previous.error {
+                    None => out.push_str(", previously ok"),
+                    Some(error) => {
+                        let _ = write!(out, ", previously failed: {error:?}");
+                    }
+                }
+            }
+        }
+        out.push('\n');
+        for change in &self.account_changes {
+            let _ = writeln!(out, "  {change}");
+        }
+        if previous.is_some_and(|previous| previous.account_diffs != self.account_diffs) {
+            out.push_str("  account changes differ from the previous run\n");
+        }
+        if previous.is_some_and(|previous| previous.logs != self.logs) {
+            out.push_str("  logs:\n");
+            for log in &self.logs {
+                let _ = writeln!(out, "    {log}");
+            }
+        }
+        out
+    }
+}
+
+impl Seashell {
+    /// Builds and loads the program of `reload`, then runs `run` against a fork of this
+    /// `Seashell` every time a watched file changes, printing each run's [`RunSummary::report`].
+    /// A changed scenario file is reloaded first. Build failures are printed and wait for the
+    /// next change; other errors end the loop.
+    pub fn run_reload_loop(
+        &mut self,
+        reload: &ReloadLoop,
+        mut run: impl FnMut(&Seashell) -> InstructionProcessingResult,
+    ) -> Result<(), SeashellError> {
+        let scenario_path = self.accounts_db.scenario.path().map(Path::to_path_buf);
+        let mut watched = vec![reload.program_dir().join("src"), reload.manifest_path.clone()];
+        watched.extend(reload.watch_paths.iter().cloned());
+        watched.extend(scenario_path.clone());
+
+        let mut stamps = FileStamps::take(&watched);
+        let mut previous: Option<RunSummary> = None;
+        let mut runs = 0;
+        loop {
+            match reload.build() {
+                Ok(()) => {
+                    self.load_program_from_directories(
+                        &reload.program_name,
+                        reload.program_id,
+                        &[reload.out_dir()],
+                    )?;
+                    let summary = RunSummary::new(&run(&self.fork()), &self.layouts);
+                    runs += 1;
+                    println!("[run {runs}] {}", summary.report(previous.as_ref()));
+                    previous = Some(summary);
+                    if reload.max_runs.is_some_and(|max_runs| runs >= max_runs) {
+                        return Ok(());
+                    }
+                }
+                Err(e) => println!("{e}"),
+            }
+
+            let changed = loop {
+                std::thread::sleep(reload.poll_interval);
+                let current = FileStamps::take(&watched);
+                let changed = current.changed_since(&stamps);
+                stamps = current;
+                if !changed.is_empty() {
+                    break changed;
+                }
+            };
+            if scenario_path
+                .as_ref()
+                .is_some_and(|scenario_path| changed.contains(scenario_path))
+            {
+                self.accounts_db.scenario.reload()?;
+                self.apply_scenario_metadata()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{system_transfer, transfer_accounts};
+
+    #[test]
+    fn test_stamps_and_run_reports() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("target")).unwrap();
+        std::fs::write(src.join("lib.rs"), "").unwrap();
+        let stamps = FileStamps::take(&[temp_dir.path().to_path_buf()]);
+        std::fs::write(src.join("nested/state.rs"), "").unwrap();
+        std::fs::write(temp_dir.path().join("target/program.so"), "").unwrap();
+        assert_eq!(
+            FileStamps::take(&[temp_dir.path().to_path_buf()]).changed_since(&stamps),
+            vec![src.join("nested/state.rs")]
+        );
+
+        let seashell = Seashell::new();
+        let (from, to) = transfer_accounts(&seashell);
+        let transfer = |lamports| system_transfer(from, to, lamports);
+        let ok = RunSummary::new(&seashell.process_instruction(transfer(400)), &seashell.layouts);
+        let failed =
+            RunSummary::new(&seashell.process_instruction(transfer(4000)), &seashell.layouts);
+        assert!(ok.report(None).starts_with("ok, "));
+        let report = failed.report(Some(&ok));
+        assert!(report.starts_with("failed: InstructionError(Custom(1))"), "{report}");
+        assert!(report.contains("previously ok"), "{report}");
+        assert!(report.contains("account changes differ"), "{report}");
+    }
+}
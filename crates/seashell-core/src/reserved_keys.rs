@@ -0,0 +1,133 @@
+//! Accounts a transaction can never write to. The runtime demotes them to readonly when a
+//! message marks them writable, so a program that expects to write one fails on-chain even
+//! though the instruction asked for write access.
+
+use std::collections::{HashMap, HashSet};
+
+use agave_feature_set::FeatureSet;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::{
+    address_lookup_table, bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable,
+    compute_budget, config, ed25519_program, feature, loader_v4, native_loader, secp256k1_program,
+    secp256r1_program, stake, system_program, sysvar, vote, zk_elgamal_proof_program,
+    zk_token_proof_program,
+};
+
+/// The reserved account keys, each either always reserved or reserved once a feature activates.
+///
+/// Defaults to the runtime's own set. Add the keys an upcoming activation reserves with
+/// [`ReservedAccountKeys::add_pending`] to test against the cluster both before and after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReservedAccountKeys {
+    /// Reserved keys, with the feature gating each one, if any.
+    keys: HashMap<Pubkey, Option<Pubkey>>,
+}
+
+impl Default for ReservedAccountKeys {
+    /// Builtin programs, precompiles, loaders and sysvars, as reserved by the runtime.
+    fn default() -> Self {
+        let mut reserved = ReservedAccountKeys::empty();
+        for key in [
+            address_lookup_table::id(),
+            bpf_loader::id(),
+            bpf_loader_deprecated::id(),
+            bpf_loader_upgradeable::id(),
+            compute_budget::id(),
+            config::id(),
+            ed25519_program::id(),
+            feature::id(),
+            loader_v4::id(),
+            native_loader::id(),
+            secp256k1_program::id(),
+            stake::id(),
+            system_program::id(),
+            vote::id(),
+            zk_elgamal_proof_program::id(),
+            zk_token_proof_program::id(),
+            sysvar::id(),
+            sysvar::clock::id(),
+            sysvar::epoch_rewards::id(),
+            sysvar::epoch_schedule::id(),
+            sysvar::fees::id(),
+            sysvar::instructions::id(),
+            sysvar::last_restart_slot::id(),
+            sysvar::recent_blockhashes::id(),
+            sysvar::rent::id(),
+            sysvar::rewards::id(),
+            sysvar::slot_hashes::id(),
+            sysvar::slot_history::id(),
+            sysvar::stake_history::id(),
+        ] {
+            reserved.add(key);
+        }
+        reserved.add_pending(
+            secp256r1_program::id(),
+            agave_feature_set::enable_secp256r1_precompile::id(),
+        );
+        reserved
+    }
+}
+
+impl ReservedAccountKeys {
+    /// No reserved keys: only program ids get demoted.
+    pub fn empty() -> Self {
+        ReservedAccountKeys { keys: HashMap::new() }
+    }
+
+    /// Reserves `key` regardless of the feature set.
+    pub fn add(&mut self, key: Pubkey) -> &mut Self {
+        self.keys.insert(key, None);
+        self
+    }
+
+    /// Reserves `key` once `feature_id` is active.
+    pub fn add_pending(&mut self, key: Pubkey, feature_id: Pubkey) -> &mut Self {
+        self.keys.insert(key, Some(feature_id));
+        self
+    }
+
+    pub fn remove(&mut self, key: &Pubkey) -> &mut Self {
+        self.keys.remove(key);
+        self
+    }
+
+    pub fn is_reserved(&self, key: &Pubkey, feature_set: &FeatureSet) -> bool {
+        match self.keys.get(key) {
+            Some(Some(feature_id)) => feature_set.is_active(feature_id),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// The keys reserved under `feature_set`.
+    pub fn active(&self, feature_set: &FeatureSet) -> HashSet<Pubkey> {
+        self.keys
+            .keys()
+            .filter(|key| self.is_reserved(key, feature_set))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_reserved_keys() {
+        let key = Pubkey::new_unique();
+        let feature_id = Pubkey::new_unique();
+        let mut reserved = ReservedAccountKeys::default();
+        reserved.add_pending(key, feature_id);
+
+        let mut feature_set = FeatureSet::default();
+        assert!(!reserved.is_reserved(&key, &feature_set));
+        assert!(reserved.is_reserved(&sysvar::clock::id(), &feature_set));
+
+        feature_set.activate(&feature_id, 0);
+        assert!(reserved.active(&feature_set).contains(&key));
+
+        reserved.remove(&key);
+        assert!(!reserved.is_reserved(&key, &feature_set));
+    }
+}
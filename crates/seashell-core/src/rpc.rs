@@ -0,0 +1,169 @@
+//! Retries, timeouts and fallback endpoints for the RPC requests scenarios make, so a rate limit
+//! or a flaky node doesn't fail a test that would otherwise pass.
+//!
+//! Each request is retried against the primary URL with exponential backoff, then against every
+//! fallback URL in order; only when all of them fail does the fetch fail.
+//!
+//! ```ignore
+//! let seashell = Seashell::new_with_config(Config {
+//!     rpc: RpcOptions {
+//!         fallback_urls: vec!["https://api.mainnet-beta.solana.com".to_string()],
+//!         ..RpcOptions::default()
+//!     },
+//!     ..Config::default()
+//! });
+//! ```
+
+use std::time::Duration;
+
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::client_error::Result as ClientResult;
+
+/// How scenario RPC requests are retried, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcOptions {
+    /// Retries per endpoint after the first attempt.
+    pub retries: u32,
+    /// Wait before the first retry, doubled after each one up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Limit for a single request.
+    pub timeout: Duration,
+    /// Endpoints tried in order once the primary URL is out of retries.
+    pub fallback_urls: Vec<String>,
+    /// Websocket endpoint for account subscriptions, derived from the primary URL if unset.
+    pub websocket_url: Option<String>,
+}
+
+impl Default for RpcOptions {
+    fn default() -> Self {
+        RpcOptions {
+            retries: 3,
+            backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(4),
+            timeout: Duration::from_secs(30),
+            fallback_urls: Vec::new(),
+            websocket_url: None,
+        }
+    }
+}
+
+impl RpcOptions {
+    /// The wait before retry number `retry`, counting from zero.
+    pub fn backoff_for(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// Clients for a primary RPC URL and its fallbacks.
+pub struct RpcEndpoints {
+    clients: Vec<RpcClient>,
+    options: RpcOptions,
+}
+
+impl RpcEndpoints {
+    pub fn new(url: String, options: RpcOptions) -> Self {
+        let clients = std::iter::once(url)
+            .chain(options.fallback_urls.iter().cloned())
+            .map(|url| RpcClient::new_with_timeout(url, options.timeout))
+            .collect();
+        RpcEndpoints { clients, options }
+    }
+
+    pub fn primary(&self) -> &RpcClient {
+        &self.clients[0]
+    }
+
+    pub fn options(&self) -> &RpcOptions {
+        &self.options
+    }
+
+    /// Runs `request` against each endpoint in turn, retrying each as configured, until one
+    /// succeeds. Returns the last error if none does.
+    pub fn call<T>(
+        &self,
+        what: &str,
+        request: impl Fn(&RpcClient) -> ClientResult<T>,
+    ) -> ClientResult<T> {
+        let mut last_error = None;
+        for rpc_client in &self.clients {
+            for attempt in 0..=self.options.retries {
+                if attempt > 0 {
+                    std::thread::sleep(self.options.backoff_for(attempt - 1));
+                }
+                match request(rpc_client) {
+                    Ok(value) => return Ok(value),
+                    Err(err) => {
+                        log::warn!(
+                            "{what} failed on {} (attempt {}/{}): {err}",
+                            rpc_client.url(),
+                            attempt + 1,
+                            self.options.retries + 1
+                        );
+                        last_error = Some(err);
+                    }
+                }
+            }
+        }
+        Err(last_error.expect("RpcEndpoints always has a primary client"))
+    }
+}
+
+impl Clone for RpcEndpoints {
+    /// New clients for the same endpoints, so requests made through the clone don't queue
+    /// behind those of the original.
+    fn clone(&self) -> Self {
+        RpcEndpoints::new(self.primary().url(), self.options.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use solana_rpc_client_api::client_error::{Error as ClientError, ErrorKind as ClientErrorKind};
+
+    use super::*;
+
+    #[test]
+    fn test_retries_and_fallbacks() {
+        let options = RpcOptions {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(3),
+            fallback_urls: vec!["http://127.0.0.1:2".to_string()],
+            ..RpcOptions::default()
+        };
+        assert_eq!(options.backoff_for(0), Duration::from_millis(1));
+        assert_eq!(options.backoff_for(1), Duration::from_millis(2));
+        assert_eq!(options.backoff_for(10), Duration::from_millis(3));
+
+        let endpoints = RpcEndpoints::new("http://127.0.0.1:1".to_string(), options);
+        let attempts = Cell::new(Vec::new());
+        let request = |fail_until: usize| {
+            attempts.set(Vec::new());
+            endpoints.call("test request", |rpc_client| {
+                let mut seen = attempts.take();
+                seen.push(rpc_client.url());
+                let done = seen.len() > fail_until;
+                attempts.set(seen);
+                if done {
+                    Ok(())
+                } else {
+                    Err(ClientError::from(ClientErrorKind::Custom("429 Too Many Requests".into())))
+                }
+            })
+        };
+
+        // A transient failure is retried on the primary.
+        assert!(request(1).is_ok());
+        assert_eq!(attempts.take(), vec!["http://127.0.0.1:1"; 2]);
+        // Then the fallback is tried, and the last error returned once it is out of retries too.
+        assert!(request(4).is_ok());
+        assert_eq!(attempts.take()[3..], ["http://127.0.0.1:2", "http://127.0.0.1:2"]);
+        assert!(request(6).is_err());
+        assert_eq!(attempts.take().len(), 6);
+    }
+}
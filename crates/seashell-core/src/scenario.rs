@@ -1,5 +1,5 @@
 use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -10,9 +10,46 @@ use parking_lot::RwLock;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use solana_account::{Account, AccountSharedData};
+use solana_account::{Account, AccountSharedData, ReadableAccount, WritableAccount};
+use solana_commitment_config::CommitmentConfig;
+use solana_instruction::{AccountMeta, Instruction};
 use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
 use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_rpc_client_api::filter::RpcFilterType;
+
+use crate::error::SeashellError;
+use crate::layout::LayoutRegistry;
+use crate::nonblocking::{FetchedAccounts, PendingFetch};
+use crate::patch::{apply_patches, DataPatch};
+use crate::rpc::{RpcEndpoints, RpcOptions};
+use crate::script::ScriptStep;
+#[cfg(feature = "subscriptions")]
+use crate::subscription::{websocket_url, AccountSubscriptions};
+use crate::vote::EpochStakes;
+
+/// Embeds a scenario's `.json.gz` into the binary at compile time, evaluating to its bytes.
+///
+/// By default the file is read from the `scenarios` directory of the invoking crate; pass a
+/// directory relative to the crate manifest as the second argument to look elsewhere, e.g.
+/// `embed_scenario!("my_test", "../../scenarios")`. Load the bytes with
+/// [`crate::Seashell::load_scenario_from_bytes`].
+#[macro_export]
+macro_rules! embed_scenario {
+    ($name:literal) => {
+        $crate::embed_scenario!($name, "scenarios")
+    };
+    ($name:literal, $dir:literal) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $name, ".json.gz"))
+            as &'static [u8]
+    };
+}
+
+/// Accounts fetched per `getMultipleAccounts` request, the RPC maximum.
+pub(crate) const PREFETCH_BATCH_SIZE: usize = 100;
+/// Batches fetched in parallel while prefetching.
+const PREFETCH_CONCURRENCY: usize = 8;
 
 /// Scenario manages account overrides with automatic persistence.
 /// It stores accounts as AccountSharedData internally but serializes as Account.
@@ -23,17 +60,265 @@ pub struct Scenario {
     pub(crate) allow_uninitialized_accounts: bool,
     dirty: Cell<bool>,
     data: Arc<RwLock<HashMap<Pubkey, AccountSharedData>>>,
+    metadata: RwLock<ScenarioMetadata>,
+    /// Accounts fetched from RPC by this process, as opposed to loaded from the scenario.
+    live: RwLock<BTreeSet<Pubkey>>,
     path: Option<PathBuf>,
-    rpc_client: Option<RpcClient>,
+    rpc: Option<RpcEndpoints>,
+    /// Read-only scenarios this one is layered over, lowest first; see [`Scenario::add_base`].
+    bases: Vec<Arc<ScenarioLayer>>,
+    /// Websocket subscriptions applied by [`Scenario::refresh`]; not shared with forks.
+    #[cfg(feature = "subscriptions")]
+    subscriptions: Option<AccountSubscriptions>,
+    /// Writes over the accounts above made during this run, see [`Scenario::write`].
+    writes: RwLock<HashMap<Pubkey, AccountSharedData>>,
+}
+
+/// The accounts and metadata of a base scenario.
+struct ScenarioLayer {
+    data: HashMap<Pubkey, AccountSharedData>,
+    metadata: ScenarioMetadata,
+}
+
+/// Information about a scenario stored alongside its accounts.
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ScenarioMetadata {
+    /// Every account the scenario is known to need. Accounts fetched from RPC are recorded here,
+    /// so a scenario can be prefetched in full when its account data is missing or refreshed.
+    #[serde_as(as = "BTreeSet<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    pub accounts: BTreeSet<Pubkey>,
+    /// Slot the scenario was snapshotted at, set by its first RPC fetch. Later fetches ask for
+    /// state no older than it, and ones answered from a newer slot are logged, since accounts
+    /// from different slots can be mutually inconsistent (e.g. a DEX market and its vaults).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Stake table synced with `Seashell::sync_epoch_stake_from_rpc`, restored on load.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch_stakes: Option<EpochStakes>,
+    /// Instructions run once after the scenario is loaded, e.g. initializing a protocol's config
+    /// account, so a fresh scenario reaches a usable state without test-side setup.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bootstrap: Vec<BootstrapInstruction>,
+    /// Accounts written by each `Seashell::ensure` setup, keyed by the hash of its inputs.
+    #[serde_as(as = "BTreeMap<_, BTreeSet<serde_with::DisplayFromStr>>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub setups: BTreeMap<String, BTreeSet<Pubkey>>,
+    /// Steps run by `Seashell::run_scenario_script`, see [`crate::script`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub script: Vec<ScriptStep>,
+    /// Edits to account data applied every time the scenario is loaded, so a flag deep inside a
+    /// large account can be flipped without touching its hex. See [`DataPatch`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<AccountPatch>,
+    /// Locally constructed accounts captured by `Seashell::start_recording`, in the state the
+    /// test first used them. They are written to the `AccountsDb` on load instead of overriding
+    /// it like the scenario's accounts, so the test's own writes still take precedence.
+    #[serde_as(as = "BTreeMap<serde_with::DisplayFromStr, AccountAsJsonAccount>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub recorded: BTreeMap<Pubkey, Account>,
+}
+
+impl ScenarioMetadata {
+    fn is_empty(&self) -> bool {
+        *self == ScenarioMetadata::default()
+    }
+}
+
+/// An instruction stored in a scenario's bootstrap list.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BootstrapInstruction {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub program_id: Pubkey,
+    pub accounts: Vec<BootstrapAccountMeta>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    pub data: Vec<u8>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BootstrapAccountMeta {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub pubkey: Pubkey,
+    #[serde(default)]
+    pub is_signer: bool,
+    #[serde(default)]
+    pub is_writable: bool,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AccountPatch {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub pubkey: Pubkey,
+    pub patches: Vec<DataPatch>,
+}
+
+impl From<&Instruction> for BootstrapInstruction {
+    fn from(ixn: &Instruction) -> Self {
+        BootstrapInstruction {
+            program_id: ixn.program_id,
+            accounts: ixn
+                .accounts
+                .iter()
+                .map(|meta| BootstrapAccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: ixn.data.clone(),
+        }
+    }
+}
+
+impl From<&BootstrapInstruction> for Instruction {
+    fn from(ixn: &BootstrapInstruction) -> Self {
+        Instruction {
+            program_id: ixn.program_id,
+            accounts: ixn
+                .accounts
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: ixn.data.clone(),
+        }
+    }
+}
+
+/// Reported after every batch while prefetching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefetchProgress {
+    /// Accounts requested so far, including ones the RPC didn't have.
+    pub fetched: usize,
+    pub total: usize,
+}
+
+/// Live RPC accounts a failed instruction depended on that don't match the pinned scenario.
+///
+/// Produced by [`crate::Seashell::load_live_scenario`] runs; the `Display` output says which
+/// accounts drifted and how to freeze them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinningSuggestion {
+    /// The scenario file the live accounts were compared against.
+    pub scenario: Option<PathBuf>,
+    pub accounts: Vec<AccountDrift>,
+    /// Whether the instruction succeeded when retried against the pinned accounts, i.e. the
+    /// failure is explained by live state alone.
+    pub retry_succeeded: bool,
+}
+
+/// One live account and its pinned counterpart, if the scenario has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDrift {
+    pub pubkey: Pubkey,
+    pub pinned: Option<AccountSharedData>,
+    pub live: AccountSharedData,
+}
+
+impl std::fmt::Display for AccountDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(pinned) = &self.pinned else {
+            return write!(f, "{}: not pinned", self.pubkey);
+        };
+
+        let mut changes = Vec::new();
+        if pinned.lamports() != self.live.lamports() {
+            changes.push(format!("lamports {} -> {}", pinned.lamports(), self.live.lamports()));
+        }
+        if pinned.owner() != self.live.owner() {
+            changes.push(format!("owner {} -> {}", pinned.owner(), self.live.owner()));
+        }
+        if pinned.data().len() != self.live.data().len() {
+            changes.push(format!(
+                "data length {} -> {}",
+                pinned.data().len(),
+                self.live.data().len()
+            ));
+        } else {
+            let differing = pinned
+                .data()
+                .iter()
+                .zip(self.live.data())
+                .filter(|(a, b)| a != b)
+                .count();
+            if differing > 0 {
+                changes.push(format!("{differing} data bytes differ"));
+            }
+        }
+        if changes.is_empty() {
+            changes.push("metadata differs".to_string());
+        }
+        write!(f, "{}: {}", self.pubkey, changes.join(", "))
+    }
+}
+
+impl std::fmt::Display for PinningSuggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scenario = match &self.scenario {
+            Some(path) => path.display().to_string(),
+            None => "the scenario".to_string(),
+        };
+        writeln!(
+            f,
+            "{} account(s) the failed instruction fetched from RPC differ from {scenario}:",
+            self.accounts.len()
+        )?;
+        for drift in &self.accounts {
+            writeln!(f, "  {drift}")?;
+        }
+        if self.retry_succeeded {
+            writeln!(
+                f,
+                "The instruction succeeds against the pinned accounts, so the failure comes from \
+                 live state."
+            )?;
+        }
+        write!(
+            f,
+            "Set Config::auto_pin to freeze live accounts of passing runs into {scenario}, or \
+             load it with Seashell::load_scenario to run against pinned state only."
+        )
+    }
 }
 
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
-struct SerializableScenario(
+struct SerializableAccounts(
     #[serde_as(as = "HashMap<serde_with::DisplayFromStr, AccountAsJsonAccount>")]
     HashMap<Pubkey, Account>,
 );
 
+/// The on-disk format. Scenarios without metadata are written as a bare account map, which is
+/// also how every scenario was stored before metadata existed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum SerializableScenario {
+    WithMetadata { metadata: ScenarioMetadata, accounts: SerializableAccounts },
+    Accounts(SerializableAccounts),
+}
+
+impl SerializableScenario {
+    fn into_parts(self) -> (HashMap<Pubkey, AccountSharedData>, ScenarioMetadata) {
+        let (accounts, metadata) = match self {
+            SerializableScenario::WithMetadata { metadata, accounts } => (accounts, metadata),
+            SerializableScenario::Accounts(accounts) => (accounts, ScenarioMetadata::default()),
+        };
+        let accounts = accounts
+            .0
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect();
+        (accounts, metadata)
+    }
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 struct JsonAccount {
@@ -74,7 +359,7 @@ impl From<Account> for JsonAccount {
 }
 
 serde_with::serde_conv!(
-    AccountAsJsonAccount,
+    pub(crate) AccountAsJsonAccount,
     Account,
     |account: &Account| { JsonAccount::from(account.clone()) },
     |account: JsonAccount| -> Result<_, std::convert::Infallible> { Ok(account.into()) }
@@ -83,15 +368,10 @@ serde_with::serde_conv!(
 impl Scenario {
     /// Load a scenario from a file, or create an empty one if the file doesn't exist.
     pub fn from_file(path: PathBuf, allow_uninitialized_accounts: bool) -> Self {
-        let data = if path.exists() {
-            let serializable: SerializableScenario = read_json_gz(&path);
-            serializable
-                .0
-                .into_iter()
-                .map(|(pubkey, account)| (pubkey, account.into()))
-                .collect()
+        let (data, metadata) = if path.exists() {
+            read_json_gz::<SerializableScenario>(&path).into_parts()
         } else {
-            HashMap::new()
+            (HashMap::new(), ScenarioMetadata::default())
         };
 
         Scenario {
@@ -99,8 +379,35 @@ impl Scenario {
             allow_uninitialized_accounts,
             dirty: Cell::new(false),
             data: Arc::new(RwLock::new(data)),
+            metadata: RwLock::new(metadata),
+            live: RwLock::default(),
             path: Some(path),
-            rpc_client: None,
+            rpc: None,
+            bases: Vec::new(),
+            #[cfg(feature = "subscriptions")]
+            subscriptions: None,
+            writes: RwLock::default(),
+        }
+    }
+
+    /// Load a scenario from gzipped JSON bytes, e.g. ones embedded with [`crate::embed_scenario`].
+    /// The scenario has no backing file, so it is never persisted.
+    pub fn from_bytes(bytes: &[u8], allow_uninitialized_accounts: bool) -> Self {
+        let (data, metadata) = decode_json_gz::<SerializableScenario>(bytes).into_parts();
+
+        Scenario {
+            should_persist: Cell::new(false),
+            allow_uninitialized_accounts,
+            dirty: Cell::new(false),
+            data: Arc::new(RwLock::new(data)),
+            metadata: RwLock::new(metadata),
+            live: RwLock::default(),
+            path: None,
+            rpc: None,
+            bases: Vec::new(),
+            #[cfg(feature = "subscriptions")]
+            subscriptions: None,
+            writes: RwLock::default(),
         }
     }
 
@@ -111,7 +418,7 @@ impl Scenario {
         allow_uninitialized_accounts: bool,
     ) -> Self {
         let mut scenario = Self::from_file(path, allow_uninitialized_accounts);
-        scenario.rpc_client = Some(RpcClient::new(rpc_url));
+        scenario.rpc = Some(RpcEndpoints::new(rpc_url, RpcOptions::default()));
         scenario
     }
 
@@ -121,11 +428,77 @@ impl Scenario {
             allow_uninitialized_accounts,
             dirty: Cell::new(false),
             data: Arc::new(RwLock::new(HashMap::new())),
+            metadata: RwLock::default(),
+            live: RwLock::default(),
             path: None,
-            rpc_client: Some(RpcClient::new(rpc_url)),
+            rpc: Some(RpcEndpoints::new(rpc_url, RpcOptions::default())),
+            bases: Vec::new(),
+            #[cfg(feature = "subscriptions")]
+            subscriptions: None,
+            writes: RwLock::default(),
         }
     }
 
+    /// An in-memory copy with the same accounts and RPC endpoint. Forks are never persisted, so
+    /// only the original writes the scenario file.
+    pub fn fork(&self) -> Self {
+        Scenario {
+            should_persist: Cell::new(false),
+            allow_uninitialized_accounts: self.allow_uninitialized_accounts,
+            dirty: Cell::new(false),
+            data: Arc::new(RwLock::new(self.data.read().clone())),
+            metadata: RwLock::new(self.metadata()),
+            live: RwLock::new(self.live_accounts()),
+            path: None,
+            rpc: self.rpc.clone(),
+            bases: self.bases.clone(),
+            #[cfg(feature = "subscriptions")]
+            subscriptions: None,
+            writes: RwLock::new(self.writes()),
+        }
+    }
+
+    /// Layers `base` under this scenario, above the bases added before it. This scenario's
+    /// accounts override those of its bases, and the metadata of a base (epoch stakes, patches,
+    /// bootstrap instructions) applies before this scenario's. Bases are read-only: fetches,
+    /// writes and pins all go to this scenario, so only it is persisted.
+    pub fn add_base(&mut self, mut base: Scenario) {
+        base.should_persist.set(false);
+        self.bases.append(&mut base.bases);
+        self.bases.push(Arc::new(ScenarioLayer {
+            data: std::mem::take(&mut *base.data.write()),
+            metadata: base.metadata(),
+        }));
+    }
+
+    /// The metadata of every base, lowest first, followed by this scenario's.
+    pub fn layered_metadata(&self) -> Vec<ScenarioMetadata> {
+        self.bases
+            .iter()
+            .map(|base| base.metadata.clone())
+            .chain([self.metadata()])
+            .collect()
+    }
+
+    /// Re-reads the scenario file, discarding unsaved changes, e.g. after it was edited by hand.
+    /// Fails without changing anything if the file can't be parsed.
+    pub(crate) fn reload(&self) -> Result<(), SeashellError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let bytes = std::fs::read(path)?;
+        let scenario: SerializableScenario = serde_json::from_reader(BufReader::new(
+            GzDecoder::new(bytes.as_slice()),
+        ))
+        .map_err(|e| SeashellError::Custom(format!("Failed to parse {}: {e}", path.display())))?;
+        let (data, metadata) = scenario.into_parts();
+        *self.data.write() = data;
+        *self.metadata.write() = metadata;
+        self.writes.write().clear();
+        self.dirty.set(false);
+        Ok(())
+    }
+
     /// Fetch an account from RPC and store it in the scenario.
     /// Panics if RPC is not configured or if the RPC request fails.
     pub fn must_fetch_from_rpc(&self, pubkey: &Pubkey) -> AccountSharedData {
@@ -134,43 +507,514 @@ impl Scenario {
 
     pub fn try_fetch_from_rpc(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
         log::debug!("Attempting to fetch account: {pubkey}");
-        let rpc_client = self.rpc_client.as_ref().expect(
+        let rpc = self.rpc.as_ref().expect(
             "Account not found in scenario or accounts. RPC URL must be configured to fetch \
              missing accounts.",
         );
 
-        match rpc_client.get_account(pubkey) {
-            Ok(account) => {
-                let account_shared: AccountSharedData = account.into();
-                self.dirty.set(true);
-                self.data.write().insert(*pubkey, account_shared.clone());
-                Some(account_shared)
+        let config = self.fetch_config(rpc);
+        let response = rpc.call("getAccountInfo", |rpc_client| {
+            rpc_client.get_account_with_config(pubkey, config.clone())
+        });
+        match response.map(|response| (response.context.slot, response.value)) {
+            Ok((slot, Some(account))) => {
+                self.observe_slot(slot, 1);
+                let account: AccountSharedData = account.into();
+                self.store_fetched(*pubkey, account.clone());
+                Some(account)
             }
-            // For AccountNotFound, return None if uninitialized accounts are allowed
-            Err(err)
-                if err.to_string().contains("AccountNotFound")
-                    && self.allow_uninitialized_accounts =>
-            {
+            // For a missing account, return None unless uninitialized accounts are allowed
+            Ok((_, None)) if self.allow_uninitialized_accounts => {
                 log::debug!(
                     "Account not found on RPC: {pubkey}. Returning default uninitialized account."
                 );
                 Some(AccountSharedData::default())
             }
-            Err(_) => None,
+            Ok(_) | Err(_) => None,
+        }
+    }
+
+    /// Like [`Scenario::try_fetch_from_rpc`] for many accounts at once, with one
+    /// `getMultipleAccounts` request per 100 accounts. Accounts the RPC doesn't have are left
+    /// out, unless uninitialized accounts are allowed. Returns nothing without an RPC client.
+    pub fn try_fetch_many_from_rpc(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> HashMap<Pubkey, AccountSharedData> {
+        let mut fetched = HashMap::with_capacity(pubkeys.len());
+        let Some(rpc) = &self.rpc else {
+            return fetched;
+        };
+        let config = self.fetch_config(rpc);
+        for batch in pubkeys.chunks(PREFETCH_BATCH_SIZE) {
+            let response = match rpc.call("getMultipleAccounts", |rpc_client| {
+                rpc_client.get_multiple_accounts_with_config(batch, config.clone())
+            }) {
+                Ok(response) => response,
+                Err(err) => {
+                    log::warn!("Failed to fetch {} accounts: {err}", batch.len());
+                    continue;
+                }
+            };
+            self.store_batch(response.context.slot, batch.iter().zip(response.value), &mut fetched);
+        }
+        fetched
+    }
+
+    /// The requests of [`Scenario::try_fetch_many_from_rpc`], taken out of the scenario to run
+    /// on an async runtime. Returns `None` without an RPC client.
+    pub fn pending_fetch(&self, pubkeys: Vec<Pubkey>) -> Option<PendingFetch> {
+        let rpc = self.rpc.as_ref()?;
+        Some(PendingFetch {
+            rpc_client: NonblockingRpcClient::new_with_timeout_and_commitment(
+                rpc.primary().url(),
+                rpc.options().timeout,
+                rpc.primary().commitment(),
+            ),
+            slot: self.slot(),
+            pubkeys,
+        })
+    }
+
+    /// Stores the accounts of a finished [`PendingFetch`] like
+    /// [`Scenario::try_fetch_many_from_rpc`] does, and returns them.
+    pub fn store_fetch(&self, fetch: FetchedAccounts) -> HashMap<Pubkey, AccountSharedData> {
+        if self.slot().is_none() && fetch.slot.is_some() {
+            self.dirty.set(true);
+            self.metadata.write().slot = fetch.slot;
+        }
+        let mut fetched = HashMap::with_capacity(fetch.requested);
+        for (slot, batch) in fetch.batches {
+            self.store_batch(
+                slot,
+                batch
+                    .iter()
+                    .map(|(pubkey, account)| (pubkey, account.clone())),
+                &mut fetched,
+            );
+        }
+        fetched
+    }
+
+    /// Fetches every account owned by `program_id` that matches all of `filters` with one
+    /// `getProgramAccounts` request, e.g. every market or position of a protocol, and stores
+    /// them like any other fetched account. Returns their pubkeys.
+    pub fn clone_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<Pubkey>, SeashellError> {
+        let rpc = self.rpc.as_ref().ok_or_else(|| {
+            SeashellError::Custom(
+                "RPC URL must be configured to clone program accounts".to_string(),
+            )
+        })?;
+        let config = RpcProgramAccountsConfig {
+            filters: (!filters.is_empty()).then_some(filters),
+            account_config: self.fetch_config(rpc),
+            with_context: None,
+            sort_results: None,
+        };
+        let accounts = rpc
+            .call("getProgramAccounts", |rpc_client| {
+                rpc_client.get_program_accounts_with_config(program_id, config.clone())
+            })
+            .map_err(|e| {
+                SeashellError::Custom(format!("Failed to fetch accounts of {program_id}: {e}"))
+            })?;
+        log::debug!("Cloned {} accounts of {program_id}", accounts.len());
+        Ok(accounts
+            .into_iter()
+            .map(|(pubkey, account)| {
+                self.store_fetched(pubkey, account.into());
+                pubkey
+            })
+            .collect())
+    }
+
+    fn store_batch<'a>(
+        &self,
+        slot: u64,
+        batch: impl ExactSizeIterator<Item = (&'a Pubkey, Option<Account>)>,
+        fetched: &mut HashMap<Pubkey, AccountSharedData>,
+    ) {
+        self.observe_slot(slot, batch.len());
+        for (pubkey, account) in batch {
+            match account {
+                Some(account) => {
+                    let account: AccountSharedData = account.into();
+                    self.store_fetched(*pubkey, account.clone());
+                    fetched.insert(*pubkey, account);
+                }
+                None if self.allow_uninitialized_accounts => {
+                    fetched.insert(*pubkey, AccountSharedData::default());
+                }
+                None => log::debug!("Account not found on RPC: {pubkey}"),
+            }
+        }
+    }
+
+    fn store_fetched(&self, pubkey: Pubkey, account: AccountSharedData) {
+        self.dirty.set(true);
+        self.data.write().insert(pubkey, account);
+        self.metadata.write().accounts.insert(pubkey);
+        self.live.write().insert(pubkey);
+    }
+
+    /// The slot the scenario's RPC fetches are pinned to, see [`ScenarioMetadata::slot`].
+    pub fn slot(&self) -> Option<u64> {
+        self.metadata.read().slot
+    }
+
+    /// How every fetch asks for accounts: no older than the scenario's slot, which is taken from
+    /// the RPC on the first fetch.
+    fn fetch_config(&self, rpc: &RpcEndpoints) -> RpcAccountInfoConfig {
+        let commitment = rpc.primary().commitment();
+        if self.slot().is_none() {
+            match rpc.call("getSlot", |rpc_client| rpc_client.get_slot_with_commitment(commitment))
+            {
+                Ok(slot) => {
+                    self.dirty.set(true);
+                    self.metadata.write().slot = Some(slot);
+                }
+                Err(err) => log::warn!("Failed to get the slot to pin the scenario to: {err}"),
+            }
+        }
+        account_info_config(commitment, self.slot())
+    }
+
+    /// Warns when `count` accounts were fetched at a slot after the scenario's.
+    fn observe_slot(&self, slot: u64, count: usize) {
+        if let Some(pinned) = self.slot().filter(|pinned| slot > *pinned) {
+            log::warn!(
+                "Fetched {count} account(s) at slot {slot}, {} slots after the scenario's slot \
+                 {pinned}; they may be inconsistent with the accounts fetched before",
+                slot - pinned
+            );
         }
     }
 
     pub fn get(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
-        self.data.read().get(pubkey).cloned()
+        if let Some(account) = self.writes.read().get(pubkey) {
+            return Some(account.clone());
+        }
+        if let Some(account) = self.data.read().get(pubkey) {
+            return Some(account.clone());
+        }
+        self.bases
+            .iter()
+            .rev()
+            .find_map(|base| base.data.get(pubkey).cloned())
+    }
+
+    fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.data.read().contains_key(pubkey)
+            || self.bases.iter().any(|base| base.data.contains_key(pubkey))
     }
 
     pub fn insert(&mut self, pubkey: Pubkey, account: AccountSharedData) {
         self.dirty.set(true);
+        self.writes.write().remove(&pubkey);
         self.data.write().insert(pubkey, account);
     }
 
+    /// Overwrites an account the scenario supplies for the rest of this run, e.g. with the
+    /// result of an instruction. Unlike [`Scenario::insert`], the write is never persisted, so
+    /// the scenario keeps the state it was recorded in.
+    pub fn write(&self, pubkey: Pubkey, account: AccountSharedData) {
+        self.writes.write().insert(pubkey, account);
+    }
+
+    /// The accounts overwritten with [`Scenario::write`].
+    pub fn writes(&self) -> HashMap<Pubkey, AccountSharedData> {
+        self.writes.read().clone()
+    }
+
+    pub(crate) fn set_writes(&self, writes: HashMap<Pubkey, AccountSharedData>) {
+        *self.writes.write() = writes;
+    }
+
     pub fn rpc_enabled(&self) -> bool {
-        self.rpc_client.is_some()
+        self.rpc.is_some()
+    }
+
+    /// Rebuilds the RPC clients with `options`, keeping the primary URL. Does nothing without
+    /// an RPC client.
+    pub fn set_rpc_options(&mut self, options: RpcOptions) {
+        if let Some(rpc) = &self.rpc {
+            self.rpc = Some(RpcEndpoints::new(rpc.primary().url(), options));
+        }
+    }
+
+    pub fn metadata(&self) -> ScenarioMetadata {
+        self.metadata.read().clone()
+    }
+
+    pub fn set_epoch_stakes(&self, epoch_stakes: EpochStakes) {
+        self.dirty.set(true);
+        self.metadata.write().epoch_stakes = Some(epoch_stakes);
+    }
+
+    /// The instructions to run after loading, in order, starting with those of the bases.
+    pub fn bootstrap_instructions(&self) -> Vec<Instruction> {
+        self.layered_metadata()
+            .iter()
+            .flat_map(|metadata| metadata.bootstrap.iter().map(Instruction::from))
+            .collect()
+    }
+
+    /// The epoch stakes of the topmost layer that has them.
+    pub fn epoch_stakes(&self) -> Option<EpochStakes> {
+        self.layered_metadata()
+            .into_iter()
+            .rev()
+            .find_map(|metadata| metadata.epoch_stakes)
+    }
+
+    /// Appends `ixn` to the bootstrap list saved with the scenario.
+    pub fn add_bootstrap_instruction(&self, ixn: &Instruction) {
+        self.dirty.set(true);
+        self.metadata.write().bootstrap.push(ixn.into());
+    }
+
+    /// Saves `patches` of `pubkey` with the scenario, to be applied on every load.
+    pub fn add_patch(&self, pubkey: Pubkey, patches: Vec<DataPatch>) {
+        self.dirty.set(true);
+        self.metadata
+            .write()
+            .patches
+            .push(AccountPatch { pubkey, patches });
+    }
+
+    /// Writes `patches` into the scenario's copy of `pubkey` and saves the result, fetching the
+    /// account first if it's missing and RPC is configured. Named fields are looked up in
+    /// `layouts`. Nothing is written if any patch is invalid.
+    pub fn patch_account(
+        &self,
+        pubkey: &Pubkey,
+        patches: &[DataPatch],
+        layouts: &LayoutRegistry,
+    ) -> Result<(), SeashellError> {
+        self.write_patches(pubkey, patches, layouts)?;
+        self.dirty.set(true);
+        Ok(())
+    }
+
+    /// Applies the patches saved in the metadata of every layer, lowest first.
+    pub(crate) fn apply_saved_patches(
+        &self,
+        layouts: &LayoutRegistry,
+    ) -> Result<(), SeashellError> {
+        for metadata in self.layered_metadata() {
+            for patch in metadata.patches {
+                self.write_patches(&patch.pubkey, &patch.patches, layouts)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_patches(
+        &self,
+        pubkey: &Pubkey,
+        patches: &[DataPatch],
+        layouts: &LayoutRegistry,
+    ) -> Result<(), SeashellError> {
+        let mut account = match self.get(pubkey) {
+            Some(account) => account,
+            None if self.rpc_enabled() => self.try_fetch_from_rpc(pubkey).ok_or_else(|| {
+                SeashellError::Custom(format!("Failed to fetch account {pubkey} to patch"))
+            })?,
+            None => {
+                return Err(SeashellError::Custom(format!(
+                    "Account {pubkey} to patch is not in the scenario"
+                )))
+            }
+        };
+        let patches = patches
+            .iter()
+            .map(|patch| patch.resolve(&account, layouts))
+            .collect::<Result<Vec<_>, _>>()?;
+        apply_patches(account.data_as_mut_slice(), &patches)?;
+        self.data.write().insert(*pubkey, account);
+        Ok(())
+    }
+
+    /// The accounts the setup keyed by `key` wrote, if it ran against this scenario before.
+    pub fn setup_accounts(&self, key: &str) -> Option<BTreeSet<Pubkey>> {
+        self.layered_metadata()
+            .into_iter()
+            .rev()
+            .find_map(|mut metadata| metadata.setups.remove(key))
+    }
+
+    pub fn record_setup(&self, key: String, accounts: BTreeSet<Pubkey>) {
+        self.dirty.set(true);
+        self.metadata.write().setups.insert(key, accounts);
+    }
+
+    /// Saves a locally constructed account with the scenario, see [`ScenarioMetadata::recorded`].
+    pub fn record_account(&self, pubkey: Pubkey, account: Account) {
+        let mut metadata = self.metadata.write();
+        if metadata.recorded.get(&pubkey) != Some(&account) {
+            self.dirty.set(true);
+            metadata.recorded.insert(pubkey, account);
+        }
+    }
+
+    pub fn rpc_client(&self) -> Option<&RpcClient> {
+        self.rpc.as_ref().map(RpcEndpoints::primary)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Every account the scenario or its bases hold.
+    pub fn pubkeys(&self) -> BTreeSet<Pubkey> {
+        let mut pubkeys: BTreeSet<Pubkey> = self.data.read().keys().copied().collect();
+        for base in &self.bases {
+            pubkeys.extend(base.data.keys().copied());
+        }
+        pubkeys
+    }
+
+    /// Subscribes to updates of `pubkeys` over the websocket of the RPC endpoint. Fails without
+    /// an RPC client.
+    #[cfg(feature = "subscriptions")]
+    pub fn subscribe(
+        &mut self,
+        pubkeys: impl IntoIterator<Item = Pubkey>,
+    ) -> Result<(), SeashellError> {
+        let rpc = self.rpc.as_ref().ok_or_else(|| {
+            SeashellError::Custom("Subscribing to accounts needs an RPC client".to_string())
+        })?;
+        let subscriptions = self.subscriptions.get_or_insert_with(|| {
+            let url = rpc
+                .options()
+                .websocket_url
+                .clone()
+                .unwrap_or_else(|| websocket_url(&rpc.primary().url()));
+            AccountSubscriptions::new(url, rpc.primary().commitment())
+        });
+        for pubkey in pubkeys {
+            subscriptions.subscribe(pubkey)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces subscribed accounts with their latest update, returning those that changed.
+    /// Refreshed accounts are only persisted if the scenario is written for another reason.
+    #[cfg(feature = "subscriptions")]
+    pub fn refresh(&self) -> Vec<Pubkey> {
+        let Some(subscriptions) = &self.subscriptions else {
+            return Vec::new();
+        };
+        let updates = subscriptions.drain();
+        let mut data = self.data.write();
+        let mut live = self.live.write();
+        let mut refreshed: Vec<Pubkey> = updates.keys().copied().collect();
+        refreshed.sort();
+        for (pubkey, account) in updates {
+            data.insert(pubkey, account);
+            live.insert(pubkey);
+        }
+        refreshed
+    }
+
+    /// Accounts fetched from RPC since the scenario was loaded.
+    pub fn live_accounts(&self) -> BTreeSet<Pubkey> {
+        self.live.read().clone()
+    }
+
+    /// Stores `account` as if it had been fetched, so it is persisted with the scenario.
+    pub fn pin(&self, pubkey: Pubkey, account: AccountSharedData) {
+        self.dirty.set(true);
+        self.writes.write().remove(&pubkey);
+        self.data.write().insert(pubkey, account);
+        self.metadata.write().accounts.insert(pubkey);
+    }
+
+    /// Records accounts the scenario needs, to be fetched by the next [`Scenario::prefetch`].
+    pub fn add_known_accounts(&self, pubkeys: impl IntoIterator<Item = Pubkey>) {
+        self.dirty.set(true);
+        self.metadata.write().accounts.extend(pubkeys);
+    }
+
+    /// Fetches every known account that isn't in the scenario yet, several `getMultipleAccounts`
+    /// batches at a time, so the first run doesn't pay for one round trip per account while
+    /// executing. `progress` is called on this thread after each batch. Accounts the RPC doesn't
+    /// have are skipped and left to the lazy path. Does nothing without an RPC client.
+    pub fn prefetch(&self, mut progress: impl FnMut(PrefetchProgress)) {
+        let Some(rpc) = &self.rpc else {
+            return;
+        };
+        let missing: Vec<Pubkey> = self
+            .metadata
+            .read()
+            .accounts
+            .iter()
+            .filter(|pubkey| !self.contains(pubkey))
+            .copied()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let total = missing.len();
+        let batches: Vec<&[Pubkey]> = missing.chunks(PREFETCH_BATCH_SIZE).collect();
+        let config = self.fetch_config(rpc);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            for workers_batches in batches.chunks(batches.len().div_ceil(PREFETCH_CONCURRENCY)) {
+                let sender = sender.clone();
+                // One set of clients per worker so requests don't queue behind each other.
+                let rpc = rpc.clone();
+                let config = config.clone();
+                scope.spawn(move || {
+                    for batch in workers_batches {
+                        let accounts = rpc.call("getMultipleAccounts", |rpc_client| {
+                            rpc_client.get_multiple_accounts_with_config(batch, config.clone())
+                        });
+                        if sender.send((*batch, accounts)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut fetched = 0;
+            for (batch, accounts) in receiver {
+                fetched += batch.len();
+                match accounts {
+                    Ok(response) => {
+                        self.observe_slot(response.context.slot, batch.len());
+                        let mut data = self.data.write();
+                        for (pubkey, account) in batch.iter().zip(response.value) {
+                            if let Some(account) = account {
+                                data.insert(*pubkey, account.into());
+                                self.live.write().insert(*pubkey);
+                                self.dirty.set(true);
+                            }
+                        }
+                    }
+                    Err(err) => log::warn!("Failed to prefetch {} accounts: {err}", batch.len()),
+                }
+                progress(PrefetchProgress { fetched, total });
+            }
+        });
+    }
+}
+
+/// How scenario fetches ask for accounts: at `commitment`, no older than `slot`.
+pub(crate) fn account_info_config(
+    commitment: CommitmentConfig,
+    slot: Option<u64>,
+) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        commitment: Some(commitment),
+        min_context_slot: slot,
+        ..RpcAccountInfoConfig::default()
     }
 }
 
@@ -186,7 +1030,13 @@ impl Drop for Scenario {
                     .map(|(pubkey, account_shared)| (*pubkey, account_shared.clone().into()))
                     .collect();
 
-                let serializable = SerializableScenario(accounts);
+                let accounts = SerializableAccounts(accounts);
+                let metadata = self.metadata.read().clone();
+                let serializable = if metadata.is_empty() {
+                    SerializableScenario::Accounts(accounts)
+                } else {
+                    SerializableScenario::WithMetadata { metadata, accounts }
+                };
 
                 // Ensure the parent directory exists
                 if let Some(parent) = path.parent() {
@@ -235,6 +1085,15 @@ where
     serde_json::from_reader(bytes).unwrap()
 }
 
+pub fn decode_json_gz<T>(bytes: &[u8]) -> T
+where
+    T: DeserializeOwned,
+{
+    let decoded = BufReader::new(GzDecoder::new(bytes));
+
+    serde_json::from_reader(decoded).expect("Failed to decode gzipped JSON")
+}
+
 fn open_read(path: &Path) -> std::fs::File {
     std::fs::OpenOptions::new()
         .read(true)
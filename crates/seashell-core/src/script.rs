@@ -0,0 +1,323 @@
+//! Declarative test flows stored in a scenario file, so variations of a flow (e.g. placing and
+//! cancelling orders in a loop, with manual market edits between rounds) don't need Rust.
+//!
+//! The steps live in the scenario's `metadata.script`:
+//!
+//! ```json
+//! [
+//!   { "type": "patch", "pubkey": "...", "patches": [{ "type": "u64", "offset": 72, "value": 0 }] },
+//!   { "type": "repeat", "times": 3, "steps": [
+//!     { "type": "instruction", "program_id": "...",
+//!       "accounts": [{ "pubkey": "...", "is_signer": true, "is_writable": true }],
+//!       "data": { "base58": "..." },
+//!       "expect": { "success": true, "accounts": [{ "pubkey": "...", "lamports": 0 }] } }
+//!   ] }
+//! ]
+//! ```
+//!
+//! Steps run in order against the current state, so scripts need `Config::memoize`. A failed
+//! expectation is reported and the script carries on; malformed steps abort it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use solana_instruction::error::InstructionError;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use crate::check::Check;
+use crate::error::SeashellError;
+use crate::patch::FieldPatch;
+use crate::scenario::BootstrapAccountMeta;
+use crate::seashell::{InstructionProcessingResult, Seashell};
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScriptStep {
+    Instruction {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        program_id: Pubkey,
+        #[serde(default)]
+        accounts: Vec<BootstrapAccountMeta>,
+        #[serde(default)]
+        data: Option<ScriptData>,
+        #[serde(default)]
+        expect: Expectation,
+    },
+    /// Writes into an account's data between instructions, see [`Seashell::modify_account`].
+    Patch {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        pubkey: Pubkey,
+        patches: Vec<FieldPatch>,
+    },
+    Repeat {
+        times: usize,
+        steps: Vec<ScriptStep>,
+    },
+}
+
+/// Instruction data, written as `{ "hex": "..." }` or `{ "base58": "..." }`.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptData {
+    Hex(#[serde_as(as = "serde_with::hex::Hex")] Vec<u8>),
+    Base58(String),
+}
+
+impl ScriptData {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SeashellError> {
+        match self {
+            ScriptData::Hex(bytes) => Ok(bytes.clone()),
+            ScriptData::Base58(encoded) => bs58::decode(encoded)
+                .into_vec()
+                .map_err(|e| SeashellError::Custom(format!("Invalid base58 data {encoded}: {e}"))),
+        }
+    }
+}
+
+/// What an instruction step must produce. Unset fields aren't checked.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expectation {
+    /// `true` requires success, `false` any failure.
+    #[serde(default)]
+    pub success: Option<bool>,
+    /// The `InstructionError::Custom` code the instruction must fail with.
+    #[serde(default)]
+    pub custom_error: Option<u32>,
+    #[serde(default)]
+    pub accounts: Vec<AccountExpectation>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountExpectation {
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub pubkey: Pubkey,
+    #[serde(default)]
+    pub lamports: Option<u64>,
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[serde(default)]
+    pub owner: Option<Pubkey>,
+    #[serde(default)]
+    pub space: Option<usize>,
+    /// Bytes expected at an offset of the account data.
+    #[serde(default)]
+    pub data: Vec<DataExpectation>,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataExpectation {
+    pub offset: usize,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    pub bytes: Vec<u8>,
+}
+
+impl Expectation {
+    fn failures(&self, result: &InstructionProcessingResult) -> Vec<String> {
+        let mut checks = Vec::new();
+        if self.success == Some(true) {
+            checks.push(Check::success());
+        }
+        if let Some(code) = self.custom_error {
+            checks.push(Check::err(InstructionError::Custom(code)));
+        }
+        for expected in &self.accounts {
+            let mut check = Check::account(&expected.pubkey);
+            if let Some(lamports) = expected.lamports {
+                check = check.lamports(lamports);
+            }
+            if let Some(owner) = &expected.owner {
+                check = check.owner(owner);
+            }
+            if let Some(space) = expected.space {
+                check = check.space(space);
+            }
+            for slice in &expected.data {
+                check =
+                    check.data_slice(slice.offset..slice.offset + slice.bytes.len(), &slice.bytes);
+            }
+            if expected.closed {
+                check = check.closed();
+            }
+            checks.push(check);
+        }
+
+        let mut failures: Vec<String> = checks
+            .iter()
+            .flat_map(|check| check.failures(result))
+            .collect();
+        if self.success == Some(false) && result.error.is_none() {
+            failures.push("Expected failure, got success".to_string());
+        }
+        failures
+    }
+}
+
+/// The outcome of one executed instruction or patch step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepOutcome {
+    /// Position of the step, with one index per level of nesting and repeats counted, e.g.
+    /// `1.2.0` for the first step of the third round of the repeat at step 1.
+    pub step: String,
+    pub description: String,
+    pub failures: Vec<String>,
+    /// Program logs of an instruction step that failed its expectation.
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptReport {
+    pub steps: Vec<StepOutcome>,
+}
+
+impl ScriptReport {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.failures.is_empty())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &StepOutcome> {
+        self.steps.iter().filter(|step| !step.failures.is_empty())
+    }
+}
+
+impl fmt::Display for ScriptReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let status = if step.failures.is_empty() { "ok" } else { "FAILED" };
+            writeln!(f, "step {}: {} {status}", step.step, step.description)?;
+            for failure in &step.failures {
+                writeln!(f, "    {failure}")?;
+            }
+            for log in &step.logs {
+                writeln!(f, "    | {log}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Seashell {
+    /// Runs the script stored in the loaded scenario.
+    pub fn run_scenario_script(&mut self) -> Result<ScriptReport, SeashellError> {
+        let script = self.accounts_db.scenario.metadata().script;
+        self.run_script(&script)
+    }
+
+    /// Runs `steps` in order. Expectation failures are collected in the report; a step that
+    /// can't run at all, like a patch out of range, fails the whole script.
+    pub fn run_script(&mut self, steps: &[ScriptStep]) -> Result<ScriptReport, SeashellError> {
+        let mut report = ScriptReport::default();
+        self.run_steps(steps, "", &mut report)?;
+        Ok(report)
+    }
+
+    fn run_steps(
+        &mut self,
+        steps: &[ScriptStep],
+        prefix: &str,
+        report: &mut ScriptReport,
+    ) -> Result<(), SeashellError> {
+        for (index, step) in steps.iter().enumerate() {
+            let position = format!("{prefix}{index}");
+            match step {
+                ScriptStep::Instruction { program_id, accounts, data, expect } => {
+                    let ixn = Instruction {
+                        program_id: *program_id,
+                        accounts: accounts
+                            .iter()
+                            .map(|meta| AccountMeta {
+                                pubkey: meta.pubkey,
+                                is_signer: meta.is_signer,
+                                is_writable: meta.is_writable,
+                            })
+                            .collect(),
+                        data: match data {
+                            Some(data) => data.to_bytes()?,
+                            None => Vec::new(),
+                        },
+                    };
+                    let result = self.process_instruction(ixn);
+                    let failures = expect.failures(&result);
+                    report.steps.push(StepOutcome {
+                        step: position,
+                        description: format!("instruction to {program_id}"),
+                        logs: if failures.is_empty() { Vec::new() } else { result.logs },
+                        failures,
+                    });
+                }
+                ScriptStep::Patch { pubkey, patches } => {
+                    self.modify_account(pubkey, patches)?;
+                    report.steps.push(StepOutcome {
+                        step: position,
+                        description: format!("patch {pubkey}"),
+                        failures: Vec::new(),
+                        logs: Vec::new(),
+                    });
+                }
+                ScriptStep::Repeat { times, steps } => {
+                    for round in 0..*times {
+                        self.run_steps(steps, &format!("{position}.{round}."), report)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+
+    use super::*;
+    use crate::seashell::Config;
+
+    #[test]
+    fn test_run_script() {
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let market = Pubkey::new_unique();
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        seashell.set_account(to, Account::default());
+        seashell.set_account(market, Account { data: vec![0; 8], ..Account::default() });
+
+        let mut transfer = 2u32.to_le_bytes().to_vec();
+        transfer.extend_from_slice(&300u64.to_le_bytes());
+        let script: Vec<ScriptStep> = serde_json::from_value(serde_json::json!([
+            { "type": "patch", "pubkey": market.to_string(),
+              "patches": [{ "type": "u64", "offset": 0, "value": 42 }] },
+            { "type": "repeat", "times": 4, "steps": [
+                { "type": "instruction",
+                  "program_id": solana_sdk_ids::system_program::id().to_string(),
+                  "accounts": [
+                      { "pubkey": from.to_string(), "is_signer": true, "is_writable": true },
+                      { "pubkey": to.to_string(), "is_writable": true }
+                  ],
+                  "data": { "base58": bs58::encode(&transfer).into_string() },
+                  "expect": { "success": true } }
+            ] },
+            { "type": "instruction",
+              "program_id": solana_sdk_ids::system_program::id().to_string(),
+              "data": { "hex": "00" },
+              "expect": { "accounts": [{ "pubkey": to.to_string(), "lamports": 900 }] } }
+        ]))
+        .unwrap();
+
+        let report = seashell.run_script(&script).unwrap();
+        assert_eq!(report.steps.len(), 6);
+        // The fourth transfer runs out of lamports, and the last step checks an account the
+        // instruction doesn't touch.
+        let failed: Vec<&str> = report.failures().map(|step| step.step.as_str()).collect();
+        assert_eq!(failed, vec!["1.3.0", "2"]);
+        assert!(!report.failures().next().unwrap().logs.is_empty());
+        assert_eq!(seashell.account(&to).lamports, 900);
+        assert_eq!(seashell.account(&market).data, 42u64.to_le_bytes());
+    }
+}
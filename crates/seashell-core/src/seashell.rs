@@ -1,30 +1,113 @@
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use agave_feature_set::FeatureSet;
+use indexmap::IndexMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use solana_account::{Account, AccountSharedData, ReadableAccount, WritableAccount};
+use solana_clock::Clock;
 use solana_compute_budget::compute_budget::ComputeBudget;
 use solana_hash::Hash;
 use solana_instruction::error::InstructionError;
 use solana_instruction::Instruction;
-use solana_precompile_error::PrecompileError;
-use solana_program_runtime::invoke_context::{EnvironmentConfig, InvokeContext};
+use solana_keypair::Keypair;
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
+use solana_message::v0::LoadedAddresses;
+use solana_message::VersionedMessage;
+use solana_program_runtime::loaded_programs::ProgramCacheEntry;
 use solana_pubkey::Pubkey;
-use solana_svm_callback::InvokeContextCallback;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_signer::Signer;
 use solana_svm_log_collector::LogCollector;
-use solana_svm_timings::ExecuteTimings;
-use solana_transaction_context::{IndexOfAccount, TransactionContext};
-
-use crate::accounts_db::AccountsDb;
-use crate::compile::{compile_accounts_for_instruction, INSTRUCTION_PROGRAM_ID_INDEX};
+use solana_transaction::versioned::VersionedTransaction;
+
+use crate::accounts_db::{parse_programdata, AccountsDb, AccountsDbCheckpoint};
+use crate::anchor_error::AnchorError;
+use crate::block::{BlockBuilder, BlockLimits, TransactionCost};
+use crate::compile::{decompile_message, demoted_accounts};
+use crate::compute_budget::{apply_limits, compute_budget_limits};
+use crate::diff::AccountDiff;
 use crate::error::SeashellError;
-use crate::scenario::Scenario;
-
+use crate::event::{anchor_events, AnchorEvent, DecodedEvent};
+use crate::execute::{
+    execute_instruction, execute_instruction_with_timeout, ExecutionInput, ExecutionOutput,
+};
+use crate::fault::VmFault;
+use crate::fixture::ClockOffset;
+use crate::history::{snapshot_label, snapshot_paths, SnapshotOutcome, SnapshotSeries};
+use crate::idl::Idl;
+use crate::idl_audit::MutabilityMismatch;
+use crate::invocation_limit::{invocation_limit, InvocationLimitError};
+use crate::known_addresses::{KnownAddressUse, KnownAddresses};
+use crate::layout::LayoutRegistry;
+use crate::log_filter::LogFilter;
+use crate::lookup_table::AddressLookupTable;
+use crate::manifest::ProgramManifest;
+use crate::metrics::{self, Metrics, MetricsSink};
+use crate::patch::FieldPatch;
+use crate::plugin::Plugins;
+use crate::program_registry::DefaultPrograms;
+use crate::reserved_keys::ReservedAccountKeys;
+use crate::rpc::RpcOptions;
+use crate::scenario::{AccountDrift, PinningSuggestion, PrefetchProgress, Scenario};
+use crate::signing::{missing_signers, unverified_signers};
+use crate::spl::{self, TokenBalance};
+use crate::trace::{
+    compute_unit_frames, ComputeUnitFrame, ExecutionTimings, LoadedDataSize, TracedInstruction,
+};
+use crate::vote::{EpochStakes, VoteAccountBuilder};
+use crate::wallet::{KeypairRegistry, MockWallet};
+use crate::watch::Watchlist;
+
+#[derive(Clone)]
 pub struct Config {
     pub memoize: bool,
     pub allow_uninitialized_accounts_local: bool,
     pub allow_uninitialized_accounts_fetched: bool,
+    /// Wall-clock limit for a single `process_instruction` call. Programs running past it are
+    /// stopped, even between syscalls, and reported as `InstructionProcessingError::Timeout`.
+    pub instruction_timeout: Option<Duration>,
+    /// Extra directories `load_program_from_environment` searches before `SBF_OUT_DIR` and the
+    /// workspace `target/deploy`.
+    pub program_search_paths: Vec<PathBuf>,
+    /// With `load_live_scenario`, writes the live accounts of every successful instruction into
+    /// the pinned scenario, so a passing run freezes the state it passed against.
+    pub auto_pin: bool,
+    /// On access violations, rebuild the failing program's input region to report which account
+    /// field the faulting address points into, with the bytes around it. See `crate::fault`.
+    pub capture_vm_faults: bool,
+    /// Shifts the clock time-bearing fixtures are stamped with, see [`Seashell::fixture_clock`].
+    pub fixture_clock_offset: ClockOffset,
+    /// Slots the clock advances after every `process_instruction` call, and after every chain
+    /// or transaction, as with [`Seashell::warp_to_slot`]. `0` keeps the clock still.
+    pub slots_per_instruction: u64,
+    /// Report instructions that sign with or write to `Seashell::known_addresses`, meant for
+    /// runs against cloned mainnet state. See `crate::known_addresses`.
+    pub audit_known_addresses: bool,
+    /// Retries, timeout and fallback URLs for the RPC requests of scenarios loaded with
+    /// `RPC_URL`. See `crate::rpc`.
+    pub rpc: RpcOptions,
+    /// Report an account that is neither local, in the scenario nor on the RPC as
+    /// `InstructionProcessingError::AccountNotFound` instead of panicking, for CI runs without
+    /// RPC access.
+    pub strict_accounts: bool,
+    /// Reject transactions whose recent blockhash has expired or was never registered, as
+    /// `SeashellError::BlockhashExpired` and `SeashellError::BlockhashNotFound`. Durable nonce
+    /// transactions are never checked.
+    pub check_recent_blockhash: bool,
+    /// Per-program verbosity of `InstructionProcessingResult::logs` and the log collector. See
+    /// `crate::log_filter`.
+    pub log_filter: LogFilter,
+    /// Record program logs into `InstructionProcessingResult::logs`, up to the runtime's 10 KB
+    /// limit. Compute unit frames, Anchor error names and invocation limit errors are read from
+    /// them, so without logs they're left empty. Logs are still recorded while a log collector
+    /// is enabled or with `capture_vm_faults`.
+    pub record_logs: bool,
 }
 
 // Allow deriving Default manually to be explicit about configuration defaults
@@ -35,16 +118,50 @@ impl Default for Config {
             memoize: false,
             allow_uninitialized_accounts_local: false,
             allow_uninitialized_accounts_fetched: false,
+            instruction_timeout: None,
+            program_search_paths: Vec::new(),
+            auto_pin: false,
+            capture_vm_faults: false,
+            fixture_clock_offset: ClockOffset::default(),
+            slots_per_instruction: 0,
+            audit_known_addresses: false,
+            rpc: RpcOptions::default(),
+            strict_accounts: false,
+            check_recent_blockhash: true,
+            log_filter: LogFilter::default(),
+            record_logs: true,
         }
     }
 }
 
 pub struct Seashell {
     pub config: Config,
+    #[doc(hidden)]
     pub accounts_db: AccountsDb,
     pub compute_budget: ComputeBudget,
     pub feature_set: FeatureSet,
+    /// Accounts demoted to readonly when a transaction marks them writable.
+    pub reserved_account_keys: ReservedAccountKeys,
     pub log_collector: Option<Rc<RefCell<LogCollector>>>,
+    pub layouts: LayoutRegistry,
+    /// Anchor IDLs keyed by program id, used to resolve error names.
+    pub idls: HashMap<Pubkey, Idl>,
+    /// Stake reported to programs by the epoch stake syscalls.
+    pub epoch_stakes: EpochStakes,
+    /// Scenario live accounts are compared against, set by `load_live_scenario`.
+    pub pinned_scenario: Option<Scenario>,
+    /// Lamport thresholds checked whenever an instruction's writes are committed.
+    pub watchlist: Watchlist,
+    /// Packs every processed transaction into simulated blocks, set by `enable_block_builder`.
+    pub block_builder: Option<RefCell<BlockBuilder>>,
+    /// Mainnet addresses checked with `Config::audit_known_addresses`.
+    pub known_addresses: KnownAddresses,
+    /// Keypairs created with `new_wallet`, signing for `MockWallet`s.
+    pub keypairs: KeypairRegistry,
+    /// Accounts seen since `start_recording`, each recorded into the scenario at first sight.
+    pub recording: Option<RefCell<HashSet<Pubkey>>>,
+    /// Extensions called back on every instruction, see `crate::plugin`.
+    pub plugins: Plugins,
 }
 
 unsafe impl Send for Seashell {}
@@ -57,37 +174,51 @@ impl Default for Seashell {
             accounts_db: AccountsDb::default(),
             compute_budget: ComputeBudget::new_with_defaults(false),
             feature_set: FeatureSet::all_enabled(),
+            reserved_account_keys: ReservedAccountKeys::default(),
             log_collector: None,
+            layouts: LayoutRegistry::default(),
+            idls: HashMap::new(),
+            epoch_stakes: EpochStakes::default(),
+            pinned_scenario: None,
+            watchlist: Watchlist::default(),
+            block_builder: None,
+            known_addresses: KnownAddresses::default(),
+            keypairs: KeypairRegistry::default(),
+            recording: None,
+            plugins: Plugins::default(),
         }
     }
 }
-struct SeashellInvokeContextCallback<'a> {
-    feature_set: &'a FeatureSet,
-}
 
-impl InvokeContextCallback for SeashellInvokeContextCallback<'_> {
-    fn is_precompile(&self, program_id: &Pubkey) -> bool {
-        agave_precompiles::is_precompile(program_id, |feature| self.feature_set.is_active(feature))
+impl Seashell {
+    /// A `Seashell` with every feature active and the default compute budget. The builtins,
+    /// precompiles and SPL programs are verified once per process and shared between instances.
+    pub fn new() -> Self {
+        let mut seashell = Seashell::default();
+        DefaultPrograms::get().install(&mut seashell.accounts_db);
+        seashell.setup();
+        seashell
     }
 
-    fn process_precompile(
-        &self,
-        program_id: &Pubkey,
-        data: &[u8],
-        instruction_datas: Vec<&[u8]>,
-    ) -> Result<(), PrecompileError> {
-        if let Some(precompile) = agave_precompiles::get_precompile(program_id, |feature_id| {
-            self.feature_set.is_active(feature_id)
-        }) {
-            precompile.verify(data, &instruction_datas, self.feature_set)
-        } else {
-            Err(PrecompileError::InvalidPublicKey)
-        }
+    /// Like `new`, with builtins and programs loaded under `feature_set` and `compute_budget`.
+    /// The programs are verified for this instance alone.
+    pub fn new_with_runtime(feature_set: FeatureSet, compute_budget: ComputeBudget) -> Self {
+        let mut seashell = Seashell { feature_set, compute_budget, ..Seashell::default() };
+        seashell.load_default_programs();
+        seashell.setup();
+        seashell
     }
-}
 
-impl Seashell {
-    pub fn new() -> Self {
+    pub(crate) fn load_default_programs(&mut self) {
+        self.accounts_db.load_builtins(&self.feature_set);
+        self.accounts_db
+            .configure_program_runtime_environment(&self.feature_set, &self.compute_budget);
+
+        self.load_spl();
+        self.load_precompiles();
+    }
+
+    fn setup(&mut self) {
         #[rustfmt::skip]
         solana_logger::setup_with_default(
             "solana_rbpf::vm=debug,\
@@ -95,14 +226,37 @@ impl Seashell {
              solana_runtime::system_instruction_processor=trace",
         );
 
-        let mut seashell = Seashell::default();
-
-        seashell.accounts_db.load_builtins(&seashell.feature_set);
-
-        seashell.load_spl();
-        seashell.load_precompiles();
+        crate::spl::register_layouts(&mut self.layouts);
+    }
 
-        seashell
+    /// A copy of this `Seashell` to run tests against without affecting it. Loaded programs,
+    /// accounts and scenario accounts are shared copy-on-write, so an expensive base state (cloned
+    /// mainnet accounts, verified programs) can be built once and forked per test.
+    ///
+    /// The fork's scenario is never written back to disk and it has no pinned scenario. If a log
+    /// collector is enabled, the fork gets its own, empty one.
+    pub fn fork(&self) -> Seashell {
+        Seashell {
+            config: self.config.clone(),
+            accounts_db: self.accounts_db.fork(),
+            compute_budget: self.compute_budget,
+            feature_set: self.feature_set.clone(),
+            reserved_account_keys: self.reserved_account_keys.clone(),
+            log_collector: self
+                .log_collector
+                .as_ref()
+                .map(|_| Rc::new(RefCell::new(LogCollector::default()))),
+            layouts: self.layouts.clone(),
+            idls: self.idls.clone(),
+            epoch_stakes: self.epoch_stakes.clone(),
+            pinned_scenario: None,
+            watchlist: self.watchlist.clone(),
+            block_builder: self.block_builder.clone(),
+            known_addresses: self.known_addresses.clone(),
+            keypairs: self.keypairs.clone(),
+            recording: None,
+            plugins: self.plugins.fork(),
+        }
     }
 
     /// Replaces the Tokenkeg binary with the P-Token binary.
@@ -120,6 +274,33 @@ impl Seashell {
         self.log_collector = Some(Rc::new(RefCell::new(LogCollector::default())))
     }
 
+    /// Packs every transaction `process_transaction` runs from now on into simulated blocks
+    /// under `limits`. See `crate::block`.
+    pub fn enable_block_builder(&mut self, limits: BlockLimits) {
+        self.block_builder = Some(RefCell::new(BlockBuilder::new(limits)));
+    }
+
+    /// Captures every account the following instructions touch into the loaded scenario, so a
+    /// test that depends on live RPC replays offline on the next run. Accounts fetched from RPC
+    /// are persisted as usual; locally constructed ones are saved in the state the first
+    /// instruction using them saw, and written back to the `AccountsDb` when the scenario is
+    /// loaded again. Programs are not recorded. Loading a scenario with `SEASHELL_RECORD` set
+    /// starts recording too.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(RefCell::new(HashSet::new()));
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// The blocks the transactions processed so far were packed into.
+    pub fn block_report(&self) -> Option<BlockBuilder> {
+        self.block_builder
+            .as_ref()
+            .map(|builder| builder.borrow().clone())
+    }
+
     pub fn logs(&self) -> Option<Vec<String>> {
         self.log_collector
             .as_ref()
@@ -130,6 +311,11 @@ impl Seashell {
         crate::spl::load(self);
     }
 
+    /// Loads the bundled CPI tester; build instructions for it with [`crate::cpi_tester::invoke`].
+    pub fn load_cpi_tester(&mut self) {
+        crate::cpi_tester::load(self);
+    }
+
     pub fn load_precompiles(&mut self) {
         crate::precompiles::load(self);
     }
@@ -144,45 +330,106 @@ impl Seashell {
         );
     }
 
-    /// Attempts to locate a program `.so` in the workspace root `target/deploy` directory or the `SBF_OUT_DIR` named `<program_name>.so`.
+    /// Deploys a program under `bpf_loader_upgradeable`, with its programdata account at the
+    /// canonical address. The deployment slot is the current slot, so warp forward before
+    /// upgrading it through the loader.
+    pub fn load_upgradeable_program_from_bytes(
+        &mut self,
+        program_id: Pubkey,
+        bytes: &[u8],
+        upgrade_authority: Option<Pubkey>,
+    ) {
+        self.accounts_db.load_upgradeable_program_from_bytes(
+            program_id,
+            bytes,
+            upgrade_authority,
+            &self.feature_set,
+            &self.compute_budget,
+        );
+    }
+
+    /// Creates a loader buffer holding `bytes`, e.g. as the source of an `Upgrade` instruction.
+    pub fn set_program_buffer(&self, buffer: Pubkey, authority: Option<Pubkey>, bytes: &[u8]) {
+        self.accounts_db
+            .set_program_buffer(buffer, authority, bytes);
+    }
+
+    /// Attempts to locate a program named `<program_name>.so` and load it.
+    ///
+    /// Searches `Config::program_search_paths`, then every directory listed in `SBF_OUT_DIR`
+    /// (separated like `PATH`), then the workspace root `target/deploy` directory. Returns the path
+    /// that was loaded, or `SeashellError::ProgramNotFound` listing every searched path.
     pub fn load_program_from_environment(
         &mut self,
         program_name: &str,
         program_id: Pubkey,
-    ) -> Result<(), SeashellError> {
-        let program_so_directory = if let Ok(out_dir) = std::env::var("SBF_OUT_DIR") {
-            // First try to read from the SBF_OUT_DIR environment variable
-            PathBuf::from(out_dir)
-        } else {
-            // If not present, attempt to locate the workspace root
-            let workspace_root = try_find_workspace_root()
-                .ok_or(SeashellError::Custom("Could not locate workspace root".to_string()))?;
-            workspace_root.join("target/deploy")
+    ) -> Result<PathBuf, SeashellError> {
+        let mut directories = self.config.program_search_paths.clone();
+        if let Some(out_dirs) = std::env::var_os("SBF_OUT_DIR") {
+            directories.extend(std::env::split_paths(&out_dirs));
+        }
+        if let Some(workspace_root) = try_find_workspace_root() {
+            directories.push(workspace_root.join("target/deploy"));
+        }
+
+        self.load_program_from_directories(program_name, program_id, &directories)
+    }
+
+    /// Loads `<program_name>.so` from the first of `directories` that contains it.
+    pub fn load_program_from_directories(
+        &mut self,
+        program_name: &str,
+        program_id: Pubkey,
+        directories: &[PathBuf],
+    ) -> Result<PathBuf, SeashellError> {
+        let searched: Vec<PathBuf> = directories
+            .iter()
+            .map(|directory| directory.join(format!("{program_name}.so")))
+            .collect();
+
+        let Some(path) = searched.iter().find(|path| path.is_file()) else {
+            return Err(SeashellError::ProgramNotFound {
+                program_name: program_name.to_string(),
+                searched,
+            });
         };
 
-        let entries = std::fs::read_dir(program_so_directory)?;
+        let program_bytes = std::fs::read(path)?;
+        self.load_program_from_bytes(program_id, &program_bytes);
 
-        for entry_maybe in entries {
-            let entry = entry_maybe?;
-            let path = entry.path();
+        Ok(path.clone())
+    }
 
-            if path.extension().is_some_and(|ext| ext == "so")
-                && path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .is_some_and(|stem| stem == program_name)
-            {
-                let program_bytes = std::fs::read(path)?;
-                self.accounts_db.load_program_from_bytes_with_loader(
-                    program_id,
-                    &program_bytes,
-                    solana_sdk_ids::bpf_loader::id(),
-                    &self.feature_set,
-                    &self.compute_budget,
-                );
-            }
+    /// Loads the program pinned as `program_name` in `manifest`, downloading it if necessary.
+    pub fn load_program_from_manifest(
+        &mut self,
+        manifest: &ProgramManifest,
+        program_name: &str,
+    ) -> Result<Pubkey, SeashellError> {
+        let program_bytes = manifest.fetch(program_name)?;
+        let program_id = manifest.programs[program_name].program_id;
+        self.load_program_from_bytes(program_id, &program_bytes);
+        Ok(program_id)
+    }
+
+    /// Loads every program pinned in `manifest`.
+    pub fn load_programs_from_manifest(
+        &mut self,
+        manifest: &ProgramManifest,
+    ) -> Result<(), SeashellError> {
+        for program_name in manifest.programs.keys() {
+            self.load_program_from_manifest(manifest, program_name)?;
         }
+        Ok(())
+    }
 
+    /// Loads the Anchor IDL of `program_id` from a JSON file.
+    pub fn load_idl(
+        &mut self,
+        program_id: Pubkey,
+        path: impl AsRef<Path>,
+    ) -> Result<(), SeashellError> {
+        self.idls.insert(program_id, Idl::from_file(path)?);
         Ok(())
     }
 
@@ -193,280 +440,1440 @@ impl Seashell {
     /// When the scenario is dropped, it will be written back to the file.
     ///
     /// If the RPC URL environment variable is set, missing accounts will be fetched from the RPC.
+    /// Accounts listed in the scenario metadata are prefetched up front.
     pub fn load_scenario(&mut self, scenario_name: &str) {
-        let workspace_root = try_find_workspace_root().expect("Failed to locate workspace root");
-        let scenario_path = workspace_root.join(format!("scenarios/{scenario_name}.json.gz"));
+        self.load_scenario_with_progress(scenario_name, |progress| {
+            log::info!("Prefetched {}/{} scenario accounts", progress.fetched, progress.total)
+        });
+    }
+
+    /// Like [`Seashell::load_scenario`], reporting prefetch progress to `progress`.
+    pub fn load_scenario_with_progress(
+        &mut self,
+        scenario_name: &str,
+        progress: impl FnMut(PrefetchProgress),
+    ) {
+        self.accounts_db.scenario = self.open_scenario(scenario_name);
+        if std::env::var_os("SEASHELL_RECORD").is_some() {
+            self.start_recording();
+        }
+        self.accounts_db.scenario.prefetch(progress);
+        self.apply_scenario_metadata()
+            .unwrap_or_else(|e| panic!("Failed to bootstrap scenario {scenario_name}: {e}"));
+    }
+
+    /// Loads several scenarios as layers, each overriding the accounts of those before it, e.g.
+    /// `load_scenarios(&["mainnet_phoenix_base", "my_seat_overrides"])`. The last scenario is
+    /// loaded like [`Seashell::load_scenario`] and receives every fetched, pinned or patched
+    /// account; the earlier ones are read-only bases whose files are never rewritten. Metadata
+    /// applies bottom up: the patches and bootstrap instructions of every layer run in order, and
+    /// the topmost epoch stakes win.
+    pub fn load_scenarios(&mut self, scenario_names: &[&str]) {
+        let Some((top, bases)) = scenario_names.split_last() else {
+            return;
+        };
+        let mut scenario = self.open_scenario(top);
+        for name in bases {
+            scenario.add_base(Scenario::from_file(
+                scenario_path(name),
+                self.config.allow_uninitialized_accounts_fetched,
+            ));
+        }
+        self.accounts_db.scenario = scenario;
+        self.accounts_db.scenario.prefetch(|progress| {
+            log::info!("Prefetched {}/{} scenario accounts", progress.fetched, progress.total)
+        });
+        self.apply_scenario_metadata()
+            .unwrap_or_else(|e| panic!("Failed to bootstrap scenarios {scenario_names:?}: {e}"));
+    }
 
-        self.accounts_db.scenario = if let Ok(ref rpc_url) = std::env::var("RPC_URL") {
-            Scenario::from_file_with_rpc(
+    /// Opens the scenario file of `scenario_name`, with RPC fallback when `RPC_URL` is set.
+    fn open_scenario(&self, scenario_name: &str) -> Scenario {
+        let scenario_path = scenario_path(scenario_name);
+        if let Ok(rpc_url) = std::env::var("RPC_URL") {
+            let mut scenario = Scenario::from_file_with_rpc(
                 scenario_path,
-                rpc_url.clone(),
+                rpc_url,
                 self.config.allow_uninitialized_accounts_fetched,
-            )
+            );
+            scenario.set_rpc_options(self.config.rpc.clone());
+            scenario
         } else {
             Scenario::from_file(scenario_path, self.config.allow_uninitialized_accounts_fetched)
-        };
+        }
     }
 
-    pub fn load_temporary_scenario(&mut self) {
-        let rpc_url = std::env::var("RPC_URL")
-            .expect("RPC_URL environment variable must be set for temporary scenarios");
+    /// Loads a scenario from gzipped JSON bytes, typically embedded with
+    /// [`crate::embed_scenario`]. No filesystem or RPC access happens, and nothing is persisted.
+    pub fn load_scenario_from_bytes(&mut self, bytes: &[u8]) {
         self.accounts_db.scenario =
-            Scenario::rpc_only(rpc_url, self.config.allow_uninitialized_accounts_fetched);
+            Scenario::from_bytes(bytes, self.config.allow_uninitialized_accounts_fetched);
+        self.apply_scenario_metadata()
+            .unwrap_or_else(|e| panic!("Failed to bootstrap scenario: {e}"));
     }
 
-    pub fn process_instruction(&self, ixn: Instruction) -> InstructionProcessingResult {
-        let transaction_accounts = self
-            .accounts_db
-            .accounts_for_instruction(self.config.allow_uninitialized_accounts_local, &ixn);
+    /// Applies what a freshly loaded scenario carries besides accounts: its epoch stakes, its
+    /// saved patches, then its bootstrap instructions.
+    pub(crate) fn apply_scenario_metadata(&mut self) -> Result<(), SeashellError> {
+        if let Some(epoch_stakes) = self.accounts_db.scenario.epoch_stakes() {
+            self.epoch_stakes = epoch_stakes;
+        }
+        for metadata in self.accounts_db.scenario.layered_metadata() {
+            for (pubkey, account) in metadata.recorded {
+                self.set_account(pubkey, account);
+            }
+        }
+        self.accounts_db
+            .scenario
+            .apply_saved_patches(&self.layouts)?;
+        let bootstrap = self.accounts_db.scenario.bootstrap_instructions();
+        for (index, ixn) in bootstrap.into_iter().enumerate() {
+            if let Some(error) = self.process_instruction(ixn).error {
+                return Err(SeashellError::Custom(format!(
+                    "Bootstrap instruction {index} failed: {error:?}"
+                )));
+            }
+        }
+        Ok(())
+    }
 
-        let sysvar_cache = self
-            .accounts_db
-            .sysvars_for_instruction(&transaction_accounts);
-        let mut transaction_context = TransactionContext::new(
-            transaction_accounts.clone(),
-            self.accounts_db.sysvars.rent(),
-            self.compute_budget.max_instruction_stack_depth,
-            self.compute_budget.max_instruction_trace_length,
-        );
-
-        let instruction_accounts = compile_accounts_for_instruction(&ixn);
-
-        let mut dedup_map = vec![u8::MAX; solana_transaction_context::MAX_ACCOUNTS_PER_TRANSACTION];
-        for (idx, account) in instruction_accounts.iter().enumerate() {
-            let index_in_instruction = dedup_map
-                .get_mut(account.index_in_transaction as usize)
-                .unwrap();
-            if *index_in_instruction == u8::MAX {
-                *index_in_instruction = idx as u8;
+    /// Runs `setup` unless it already ran against the loaded scenario with the same `inputs` and
+    /// every account it wrote is still there. Returns whether `setup` ran.
+    ///
+    /// `inputs` stands in for the closure, which can't be hashed: it should cover everything the
+    /// setup depends on, such as seeds, amounts and authorities. After a run, the accounts
+    /// `setup` wrote are pinned into the scenario, so later loads reuse them instead of
+    /// initializing twice. Executable accounts aren't pinned; load programs outside `ensure`.
+    pub fn ensure<T: Serialize>(
+        &mut self,
+        inputs: &T,
+        setup: impl FnOnce(&mut Seashell),
+    ) -> Result<bool, SeashellError> {
+        let inputs = serde_json::to_vec(inputs)
+            .map_err(|e| SeashellError::Custom(format!("Failed to serialize setup inputs: {e}")))?;
+        let key = hex::encode(Sha256::digest(&inputs));
+        let scenario = &self.accounts_db.scenario;
+        if let Some(accounts) = scenario.setup_accounts(&key) {
+            if accounts.iter().all(|pubkey| scenario.get(pubkey).is_some()) {
+                return Ok(false);
             }
         }
 
-        transaction_context
-            .configure_next_instruction(
-                INSTRUCTION_PROGRAM_ID_INDEX as IndexOfAccount,
-                instruction_accounts,
-                dedup_map,
-                &ixn.data,
-            )
-            .expect("Failed to configure instruction");
-
-        let epoch_stake_callback = SeashellInvokeContextCallback { feature_set: &self.feature_set };
-        let runtime_features = self.feature_set.runtime_features();
-        let mut programs = self.accounts_db.programs.clone();
-        let mut invoke_context = InvokeContext::new(
-            &mut transaction_context,
-            &mut programs,
-            EnvironmentConfig::new(
-                Hash::default(),
-                /* blockhash_lamports_per_signature */ 5000, // The default value
-                &epoch_stake_callback,
-                &runtime_features,
-                &sysvar_cache,
-            ),
-            self.log_collector.clone(),
-            self.compute_budget.to_budget(),
-            self.compute_budget.to_cost(),
-        );
-
-        let mut compute_units_consumed = 0;
-
-        let result = if invoke_context.is_precompile(&ixn.program_id) {
-            invoke_context.process_precompile(
-                &ixn.program_id,
-                &ixn.data,
-                std::iter::once(ixn.data.as_slice()),
-            )
-        } else {
-            invoke_context
-                .process_instruction(&mut compute_units_consumed, &mut ExecuteTimings::default())
-        };
+        let before = self.accounts_db.written_accounts();
+        setup(self);
+        let mut written = BTreeSet::new();
+        for (pubkey, account) in &self.accounts_db.written_accounts() {
+            if account.executable() || before.get(pubkey) == Some(account) {
+                continue;
+            }
+            self.accounts_db.scenario.pin(*pubkey, account.clone());
+            written.insert(*pubkey);
+        }
+        self.accounts_db.scenario.record_setup(key, written);
+        Ok(true)
+    }
 
-        let return_data = transaction_context.get_return_data().1.to_owned();
-        match result {
-            Ok(_) => {
-                let post_execution_accounts: Vec<(Pubkey, Account)> = transaction_accounts
-                    .iter()
-                    .map(|(pubkey, account_shared_data)| {
-                        transaction_context
-                            .find_index_of_account(pubkey)
-                            .map(|idx| {
-                                let accounts = transaction_context.accounts();
-                                let account = accounts
-                                    .try_borrow(idx)
-                                    .expect("Failed to borrow TransactionAccounts")
-                                    .clone();
-                                if self.config.memoize {
-                                    self.set_account_from_account_shared_data(
-                                        *pubkey,
-                                        account.clone(),
-                                    );
-                                }
-
-                                (*pubkey, account.into())
-                            })
-                            .unwrap_or((*pubkey, account_shared_data.to_owned().into()))
-                    })
-                    .collect();
+    /// Runs `ixn` and, if it succeeds, saves it to the scenario's bootstrap list, so every later
+    /// load of the scenario runs it too.
+    pub fn add_bootstrap_instruction(&self, ixn: Instruction) -> InstructionProcessingResult {
+        let result = self.process_instruction(ixn.clone());
+        if result.error.is_none() {
+            self.accounts_db.scenario.add_bootstrap_instruction(&ixn);
+        }
+        result
+    }
 
-                InstructionProcessingResult {
-                    compute_units_consumed,
-                    return_data,
-                    error: None,
-                    post_execution_accounts,
-                }
+    /// Replaces the epoch stake table with the activated stake of every vote account reported by
+    /// `getVoteAccounts`, using the scenario's RPC or `RPC_URL`. The table is saved with the
+    /// scenario, so later runs without RPC see the same stakes.
+    pub fn sync_epoch_stake_from_rpc(&mut self) -> Result<(), SeashellError> {
+        let epoch_stakes = self.with_rpc_client(EpochStakes::from_rpc)?;
+        self.accounts_db
+            .scenario
+            .set_epoch_stakes(epoch_stakes.clone());
+        self.epoch_stakes = epoch_stakes;
+        Ok(())
+    }
+
+    /// Clones a deployed program from the cluster: fetches the program account (and for
+    /// upgradeable programs its programdata account), extracts the ELF and loads it. Accounts
+    /// already in the scenario are reused, so a recorded scenario replays without RPC.
+    pub fn load_program_from_rpc(&mut self, program_id: Pubkey) -> Result<(), SeashellError> {
+        match self.fetch_program_accounts(&program_id)? {
+            (program_account, Some((programdata_address, programdata_account))) => {
+                self.accounts_db.load_upgradeable_program_from_accounts(
+                    program_id,
+                    program_account,
+                    programdata_address,
+                    programdata_account,
+                    &self.feature_set,
+                    &self.compute_budget,
+                )?;
             }
-            Err(e) => {
-                InstructionProcessingResult {
-                    compute_units_consumed,
-                    return_data,
-                    error: Some(InstructionProcessingError::InstructionError(e)),
-                    post_execution_accounts: Vec::default(),
-                }
+            (program_account, None) => {
+                self.accounts_db.load_program_from_bytes_with_loader(
+                    program_id,
+                    program_account.data(),
+                    *program_account.owner(),
+                    &self.feature_set,
+                    &self.compute_budget,
+                );
+                self.accounts_db.set_account(program_id, program_account);
             }
         }
+        Ok(())
     }
 
-    pub fn airdrop(&mut self, pubkey: Pubkey, amount: u64) {
-        let mut account = self
-            .accounts_db
-            .account_maybe(&pubkey)
-            .unwrap_or_else(|| AccountSharedData::new(0, 0, &solana_sdk_ids::system_program::id()));
-        account.set_lamports(account.lamports() + amount);
-        self.set_account_from_account_shared_data(pubkey, account);
+    /// The ELF deployed at `program_id`, from the scenario or RPC.
+    pub(crate) fn fetch_program_elf(&self, program_id: &Pubkey) -> Result<Vec<u8>, SeashellError> {
+        match self.fetch_program_accounts(program_id)? {
+            (_, Some((programdata_address, programdata_account))) => {
+                let (_, elf) = parse_programdata(programdata_account.data()).ok_or_else(|| {
+                    SeashellError::Custom(format!(
+                        "Account {programdata_address} is not a programdata account"
+                    ))
+                })?;
+                Ok(elf.to_vec())
+            }
+            (program_account, None) => Ok(program_account.data().to_vec()),
+        }
     }
 
-    pub fn account(&self, pubkey: &Pubkey) -> Account {
-        self.accounts_db.account_must(pubkey).into()
+    /// The program account at `program_id` from the scenario or RPC, with its programdata address
+    /// and account if the program is upgradeable. Fails unless a BPF loader owns it.
+    fn fetch_program_accounts(
+        &self,
+        program_id: &Pubkey,
+    ) -> Result<(AccountSharedData, Option<(Pubkey, AccountSharedData)>), SeashellError> {
+        let program_account = self.fetch_from_scenario_or_rpc(program_id)?;
+        let loader = *program_account.owner();
+
+        if loader == solana_sdk_ids::bpf_loader_upgradeable::id() {
+            let Ok(UpgradeableLoaderState::Program { programdata_address }) =
+                bincode::deserialize(program_account.data())
+            else {
+                return Err(SeashellError::Custom(format!(
+                    "Account {program_id} is not an upgradeable program account"
+                )));
+            };
+            let programdata_account = self.fetch_from_scenario_or_rpc(&programdata_address)?;
+            Ok((program_account, Some((programdata_address, programdata_account))))
+        } else if loader == solana_sdk_ids::bpf_loader::id()
+            || loader == solana_sdk_ids::bpf_loader_deprecated::id()
+        {
+            Ok((program_account, None))
+        } else {
+            Err(SeashellError::Custom(format!(
+                "Account {program_id} is owned by {loader}, which is not a supported loader"
+            )))
+        }
     }
 
-    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
-        self.accounts_db.set_account(pubkey, account.into());
+    pub(crate) fn fetch_from_scenario_or_rpc(
+        &self,
+        pubkey: &Pubkey,
+    ) -> Result<AccountSharedData, SeashellError> {
+        if let Some(account) = self.accounts_db.scenario.get(pubkey) {
+            return Ok(account);
+        }
+        if self.accounts_db.scenario.rpc_enabled() {
+            return self
+                .accounts_db
+                .fetch_from_rpc(pubkey)
+                .ok_or_else(|| SeashellError::Custom(format!("Failed to fetch account {pubkey}")));
+        }
+        self.with_rpc_client(|rpc_client| {
+            rpc_client
+                .get_account(pubkey)
+                .map(AccountSharedData::from)
+                .map_err(|e| {
+                    SeashellError::Custom(format!("Failed to fetch account {pubkey}: {e}"))
+                })
+        })
     }
 
-    pub fn set_account_from_account_shared_data(
+    /// Runs `f` with the scenario's RPC client, or one for `RPC_URL` if the scenario has none.
+    fn with_rpc_client<T>(
         &self,
-        pubkey: Pubkey,
-        account: AccountSharedData,
-    ) {
-        self.accounts_db.set_account(pubkey, account);
+        f: impl FnOnce(&RpcClient) -> Result<T, SeashellError>,
+    ) -> Result<T, SeashellError> {
+        match self.accounts_db.scenario.rpc_client() {
+            Some(rpc_client) => f(rpc_client),
+            None => {
+                let rpc_url = std::env::var("RPC_URL").map_err(|_| {
+                    SeashellError::Custom("RPC_URL must be set to use the RPC".to_string())
+                })?;
+                f(&RpcClient::new(rpc_url))
+            }
+        }
     }
 
-    pub fn clear_non_program_accounts(&self) {
-        self.accounts_db.clear_non_program_accounts();
+    /// Runs against live RPC state like [`Seashell::load_temporary_scenario`], using the named
+    /// scenario as the pinned reference. When an instruction fails after fetching accounts that
+    /// differ from their pinned versions, it is retried against the pinned versions and the
+    /// result carries a [`PinningSuggestion`]. With `Config::auto_pin`, passing instructions
+    /// write their live accounts into the scenario instead.
+    pub fn load_live_scenario(&mut self, scenario_name: &str) {
+        self.load_temporary_scenario();
+        self.pinned_scenario = Some(Scenario::from_file(
+            scenario_path(scenario_name),
+            self.config.allow_uninitialized_accounts_fetched,
+        ));
     }
 
-    pub fn warp(&self, slot: u64, timestamp: u64) {
-        self.accounts_db.warp(slot, timestamp as i64);
+    pub fn load_temporary_scenario(&mut self) {
+        let rpc_url = std::env::var("RPC_URL")
+            .expect("RPC_URL environment variable must be set for temporary scenarios");
+        let mut scenario =
+            Scenario::rpc_only(rpc_url, self.config.allow_uninitialized_accounts_fetched);
+        scenario.set_rpc_options(self.config.rpc.clone());
+        self.accounts_db.scenario = scenario;
     }
-}
 
-pub struct InstructionProcessingResult {
-    pub compute_units_consumed: u64,
-    pub return_data: Vec<u8>,
-    pub error: Option<InstructionProcessingError>,
-    pub post_execution_accounts: Vec<(Pubkey, Account)>,
-}
+    /// Runs `ixn` against every `.json.gz` scenario snapshot in `dir`, oldest first by file
+    /// name, each on a fork of this `Seashell`. Programs and accounts set locally apply on top of
+    /// every snapshot; the snapshots themselves are never written to.
+    pub fn run_against_snapshots(
+        &self,
+        dir: impl AsRef<Path>,
+        ixn: &Instruction,
+    ) -> Result<SnapshotSeries, SeashellError> {
+        let mut series = SnapshotSeries::default();
+        for path in snapshot_paths(dir)? {
+            let mut fork = self.fork();
+            fork.accounts_db.scenario = Scenario::from_bytes(
+                &std::fs::read(&path)?,
+                self.config.allow_uninitialized_accounts_fetched,
+            );
+            fork.apply_scenario_metadata()?;
+
+            let result = fork.process_instruction(ixn.clone());
+            series.outcomes.push(SnapshotOutcome {
+                label: snapshot_label(&path),
+                path,
+                error: result.error,
+                compute_units_consumed: result.compute_units_consumed,
+                return_data: result.return_data,
+            });
+        }
+        Ok(series)
+    }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum InstructionProcessingError {
-    InstructionError(InstructionError),
-    ProgramError,
-}
+    pub fn process_instruction(&self, ixn: Instruction) -> InstructionProcessingResult {
+        let result = self.process_single_instruction(ixn);
+        self.advance_clock();
+        result
+    }
 
-pub fn try_find_workspace_root() -> Option<PathBuf> {
-    let cargo = std::env::var("CARGO").unwrap_or("cargo".to_owned());
-    let output = std::process::Command::new(cargo)
-        .arg("locate-project")
-        .arg("--workspace")
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
+    /// Like `process_instruction`, but fails without running `ixn` unless `signers` holds a
+    /// keypair for every account it marks as a signer.
+    pub fn process_instruction_signed(
+        &self,
+        ixn: Instruction,
+        signers: &[&Keypair],
+    ) -> Result<InstructionProcessingResult, SeashellError> {
+        let signers: Vec<Pubkey> = signers.iter().map(|keypair| keypair.pubkey()).collect();
+        let missing = missing_signers(std::slice::from_ref(&ixn), &signers);
+        if !missing.is_empty() {
+            return Err(SeashellError::MissingSignatures(missing));
+        }
+        Ok(self.process_instruction(ixn))
     }
 
-    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
-    let root = parsed["root"]
-        .as_str()
-        .unwrap()
-        .strip_suffix("Cargo.toml")?;
+    /// Like `process_instruction`, but an account that could not be found with
+    /// `Config::strict_accounts` is returned as `SeashellError::AccountNotFound`.
+    pub fn try_process_instruction(
+        &self,
+        ixn: Instruction,
+    ) -> Result<InstructionProcessingResult, SeashellError> {
+        let result = self.process_instruction(ixn);
+        match result.error {
+            Some(InstructionProcessingError::AccountNotFound(pubkey)) => {
+                Err(SeashellError::AccountNotFound(pubkey))
+            }
+            _ => Ok(result),
+        }
+    }
 
-    Some(PathBuf::from(root))
-}
+    fn process_single_instruction(&self, ixn: Instruction) -> InstructionProcessingResult {
+        let pinned_ixn = self.pinned_scenario.as_ref().map(|_| ixn.clone());
+        let audited_ixn = self.idls.contains_key(&ixn.program_id).then(|| ixn.clone());
+        let plugged_ixn = (!self.plugins.is_empty()).then(|| ixn.clone());
+        let known_address_uses = self.audit_known_addresses(&ixn);
+        self.record_accounts(&ixn, &WorkingSet::default());
+        self.plugins.instruction_start(&ixn);
+        let output = match self.execute(ixn, &WorkingSet::default(), self.compute_budget) {
+            Ok(output) => output,
+            Err(error) => {
+                let result = InstructionProcessingResult {
+                    known_address_uses,
+                    ..InstructionProcessingResult::from_error(error)
+                };
+                self.record_metrics(&result);
+                if let Some(ixn) = &plugged_ixn {
+                    self.plugins.instruction_end(ixn, &result);
+                }
+                return result;
+            }
+        };
 
-#[cfg(test)]
-mod tests {
-    use solana_instruction::AccountMeta;
+        let pinning_suggestion = match pinned_ixn {
+            Some(ixn) if output.result.is_ok() => {
+                if self.config.auto_pin {
+                    self.pin_live_accounts(&ixn);
+                }
+                None
+            }
+            Some(ixn) => self.suggest_pinning(ixn),
+            None => None,
+        };
 
-    use super::*;
+        if output.result.is_ok() && self.config.memoize {
+            self.commit_accounts(output.post_accounts.iter().cloned());
+            self.accounts_db
+                .commit_programs(output.modified_programs.iter().cloned());
+        }
 
-    fn create_mint_account(seashell: &mut Seashell, pubkey: Pubkey, amount: u64) {
-        const MINT_ACCOUNT_SIZE: usize = 82;
-        const MINT_ACCOUNT_RENT_EXEMPTION: u64 = 1461600;
-        let mut account = AccountSharedData::new(
-            MINT_ACCOUNT_RENT_EXEMPTION,
-            MINT_ACCOUNT_SIZE,
-            &solana_sdk_ids::system_program::id(),
-        );
-        account.set_owner(crate::spl::TOKEN_PROGRAM_ID);
-        let mut data = vec![0; MINT_ACCOUNT_SIZE];
-        data[36..44].copy_from_slice(&amount.to_le_bytes());
-        account.set_data_from_slice(&data);
-        account.set_lamports(1000);
-        seashell.accounts_db.set_account(pubkey, account.clone());
-    }
-
-    fn create_token_account(
-        seashell: &mut Seashell,
-        pubkey: Pubkey,
-        mint: Pubkey,
-        owner: Pubkey,
-        amount: u64,
-    ) {
-        const TOKEN_ACCOUNT_SIZE: usize = 165;
-        const TOKEN_ACCOUNT_RENT_EXEMPTION: u64 = 2039000;
-        let mut account = AccountSharedData::new(
-            TOKEN_ACCOUNT_RENT_EXEMPTION,
-            TOKEN_ACCOUNT_SIZE,
-            &solana_sdk_ids::system_program::id(),
-        );
-        account.set_owner(crate::spl::TOKEN_PROGRAM_ID);
-        let mut data = vec![0; TOKEN_ACCOUNT_SIZE];
-        data[0..32].copy_from_slice(&mint.to_bytes());
-        data[32..64].copy_from_slice(&owner.to_bytes());
-        data[64..72].copy_from_slice(&amount.to_le_bytes());
-        data[108] = 1; // `AccountState::Initialized` state
-        account.set_data_from_slice(&data);
-        account.set_lamports(1000);
-        account.set_owner(crate::spl::TOKEN_PROGRAM_ID);
-        seashell.accounts_db.set_account(pubkey, account.clone());
+        let mut result = InstructionProcessingResult::from_output(output, |mint| {
+            self.mint_decimals(mint, &WorkingSet::default())
+        });
+        result.pinning_suggestion = pinning_suggestion;
+        result.known_address_uses = known_address_uses;
+        if let Some(ixn) = &audited_ixn {
+            result.mutability_mismatches = self.audit_idl_mutability(ixn, &result);
+        }
+        self.decode_with_idls(&mut result);
+        result.invocation_limit = invocation_limit(&result, &self.compute_budget);
+        result.logs = self.config.log_filter.filter(&result.logs);
+        self.record_metrics(&result);
+        if let Some(ixn) = &plugged_ixn {
+            self.plugins.instruction_end(ixn, &result);
+        }
+        result
     }
 
-    #[test]
-    fn test_native_transfer() {
-        crate::set_log();
-        let mut seashell = Seashell::new();
-
-        let from = solana_pubkey::Pubkey::new_unique();
-        let to = solana_pubkey::Pubkey::new_unique();
-        seashell.airdrop(from, 1000);
-        seashell.accounts_db.set_account_mock(to);
-        println!("Airdropped 1000 lamports to {from}");
+    fn record_metrics(&self, result: &InstructionProcessingResult) {
+        let sink = &self.accounts_db.metrics;
+        if !sink.is_enabled() {
+            return;
+        }
+        sink.increment(metrics::INSTRUCTIONS, 1);
+        if result.error.is_some() {
+            sink.increment(metrics::INSTRUCTION_ERRORS, 1);
+        }
+        sink.observe(metrics::INSTRUCTION_SECONDS, result.timings.total_us as f64 / 1e6);
+        sink.observe(metrics::COMPUTE_UNITS, result.compute_units_consumed as f64);
+    }
 
-        let mut data = Vec::with_capacity(12);
-        data.extend_from_slice(&2u32.to_le_bytes());
-        data.extend_from_slice(&500u64.to_le_bytes());
+    fn audit_known_addresses(&self, ixn: &Instruction) -> Vec<KnownAddressUse> {
+        if !self.config.audit_known_addresses {
+            return Vec::new();
+        }
+        let uses = self.known_addresses.audit(ixn);
+        for known_address_use in &uses {
+            log::warn!("{known_address_use}");
+        }
+        uses
+    }
 
-        let ixn = Instruction {
-            program_id: solana_sdk_ids::system_program::id(),
-            accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
-            data,
+    /// Records the local accounts of `ixn` the recording hasn't seen yet into the scenario.
+    /// Accounts the scenario holds are already persisted, and missing ones are persisted when
+    /// they are fetched. Accounts an earlier instruction of the chain wrote are skipped, since
+    /// replaying the chain recreates them.
+    fn record_accounts(&self, ixn: &Instruction, working_set: &WorkingSet) {
+        let Some(recording) = &self.recording else {
+            return;
         };
+        let mut seen = recording.borrow_mut();
+        let scenario = &self.accounts_db.scenario;
+        for pubkey in
+            std::iter::once(ixn.program_id).chain(ixn.accounts.iter().map(|meta| meta.pubkey))
+        {
+            if !seen.insert(pubkey)
+                || working_set.accounts.contains_key(&pubkey)
+                || self.accounts_db.sysvars.is_sysvar(&pubkey)
+                || scenario.get(&pubkey).is_some()
+            {
+                continue;
+            }
+            let account = self.accounts_db.accounts.read().get(&pubkey).cloned();
+            if let Some(account) = account.filter(|account| !account.executable()) {
+                scenario.record_account(pubkey, account.into());
+            }
+        }
+    }
+
+    /// The instruction's accounts that were fetched live, with the state it saw them in.
+    fn live_accounts_for(&self, ixn: &Instruction) -> IndexMap<Pubkey, AccountSharedData> {
+        let live = self.accounts_db.scenario.live_accounts();
+        std::iter::once(ixn.program_id)
+            .chain(ixn.accounts.iter().map(|meta| meta.pubkey))
+            .filter(|pubkey| live.contains(pubkey))
+            .filter_map(|pubkey| Some((pubkey, self.accounts_db.account_maybe(&pubkey)?)))
+            .collect()
+    }
+
+    fn pin_live_accounts(&self, ixn: &Instruction) {
+        let Some(pinned) = &self.pinned_scenario else {
+            return;
+        };
+        for (pubkey, account) in self.live_accounts_for(ixn) {
+            pinned.pin(pubkey, account);
+        }
+    }
+
+    /// Compares the live accounts of a failed instruction with the pinned scenario and retries it
+    /// against the pinned versions. Returns `None` if no live account drifted.
+    fn suggest_pinning(&self, ixn: Instruction) -> Option<PinningSuggestion> {
+        let pinned = self.pinned_scenario.as_ref()?;
+        let accounts: Vec<AccountDrift> = self
+            .live_accounts_for(&ixn)
+            .into_iter()
+            .filter_map(|(pubkey, live)| {
+                let pinned = pinned.get(&pubkey);
+                (pinned.as_ref() != Some(&live)).then_some(AccountDrift { pubkey, pinned, live })
+            })
+            .collect();
+        if accounts.is_empty() {
+            return None;
+        }
+
+        let overlay = WorkingSet {
+            accounts: accounts
+                .iter()
+                .filter_map(|drift| Some((drift.pubkey, drift.pinned.clone()?)))
+                .collect(),
+            ..WorkingSet::default()
+        };
+        let retry_succeeded = !overlay.accounts.is_empty()
+            && self
+                .execute(ixn, &overlay, self.compute_budget)
+                .is_ok_and(|output| output.result.is_ok());
+
+        let suggestion = PinningSuggestion {
+            scenario: pinned.path().map(Path::to_path_buf),
+            accounts,
+            retry_succeeded,
+        };
+        log::warn!("{suggestion}");
+        Some(suggestion)
+    }
+
+    /// Runs `ixns` in order against a shared working set of accounts, so each instruction sees
+    /// the writes of the ones before it. The chain stops at the first failing instruction and none
+    /// of its writes are kept; if every instruction succeeds the final state is committed when
+    /// `Config::memoize` is set, just like `process_instruction`.
+    ///
+    /// The instructions that already ran are visible to later ones as processed siblings, and in
+    /// the instructions sysvar, as in a transaction.
+    pub fn process_instruction_chain(&self, ixns: Vec<Instruction>) -> InstructionChainResult {
+        let result = self.process_chain(ixns, self.compute_budget, None);
+        self.advance_clock();
+        result
+    }
+
+    /// Moves the clock forward by `Config::slots_per_instruction`.
+    fn advance_clock(&self) {
+        if self.config.slots_per_instruction > 0 {
+            let slot = self.accounts_db.sysvars.clock().slot;
+            self.warp_to_slot(slot + self.config.slots_per_instruction);
+        }
+    }
+
+    /// Runs a chain where every instruction gets `compute_budget`, except that with
+    /// `shared_unit_limit` the instructions draw from one pool of compute units, like the
+    /// instructions of a transaction.
+    fn process_chain(
+        &self,
+        ixns: Vec<Instruction>,
+        compute_budget: ComputeBudget,
+        shared_unit_limit: Option<u64>,
+    ) -> InstructionChainResult {
+        let mut working_set = WorkingSet {
+            instruction_datas: ixns.iter().map(|ixn| ixn.data.clone()).collect(),
+            ..WorkingSet::default()
+        };
+        let mut results = Vec::with_capacity(ixns.len());
+        let mut remaining_units = shared_unit_limit;
+
+        for (index, ixn) in ixns.into_iter().enumerate() {
+            let mut compute_budget = compute_budget;
+            if let Some(remaining_units) = remaining_units {
+                compute_budget.compute_unit_limit = remaining_units;
+            }
+            let known_address_uses = self.audit_known_addresses(&ixn);
+            self.record_accounts(&ixn, &working_set);
+            let processed = ixn.clone();
+            self.plugins.instruction_start(&processed);
+            let audited_ixn = self.idls.contains_key(&ixn.program_id).then(|| ixn.clone());
+            let mut result = match self.execute(ixn, &working_set, compute_budget) {
+                Ok(output) => {
+                    remaining_units = remaining_units
+                        .map(|units| units.saturating_sub(output.compute_units_consumed));
+                    if output.result.is_ok() {
+                        working_set
+                            .programs
+                            .extend(output.modified_programs.iter().cloned());
+                        working_set.accounts.extend(
+                            output
+                                .post_accounts
+                                .iter()
+                                .filter(|(pubkey, _)| {
+                                    *pubkey != solana_sdk_ids::sysvar::instructions::id()
+                                })
+                                .cloned(),
+                        );
+                    }
+                    InstructionProcessingResult::from_output(output, |mint| {
+                        self.mint_decimals(mint, &working_set)
+                    })
+                }
+                Err(error) => InstructionProcessingResult::from_error(error),
+            };
+            result.known_address_uses = known_address_uses;
+            if let Some(ixn) = &audited_ixn {
+                result.mutability_mismatches = self.audit_idl_mutability(ixn, &result);
+            }
+            self.decode_with_idls(&mut result);
+            result.invocation_limit = invocation_limit(&result, &compute_budget);
+            result.logs = self.config.log_filter.filter(&result.logs);
+            self.record_metrics(&result);
+            self.plugins.instruction_end(&processed, &result);
+
+            if let Some(error) = result.error.clone() {
+                results.push(result);
+                return InstructionChainResult {
+                    loaded_data_size: chain_loaded_data_size(&results),
+                    results,
+                    error: Some((index, error)),
+                    post_execution_accounts: Vec::default(),
+                };
+            }
+            working_set.processed.push(processed);
+            results.push(result);
+        }
+
+        if self.config.memoize {
+            self.commit_accounts(
+                working_set
+                    .accounts
+                    .iter()
+                    .map(|(pubkey, account)| (*pubkey, account.clone())),
+            );
+            self.accounts_db
+                .commit_programs(working_set.programs.iter().cloned());
+        }
+
+        InstructionChainResult {
+            loaded_data_size: chain_loaded_data_size(&results),
+            results,
+            error: None,
+            post_execution_accounts: working_set
+                .accounts
+                .into_iter()
+                .map(|(pubkey, account)| (pubkey, account.into()))
+                .collect(),
+        }
+    }
+
+    /// Runs the instructions of `transaction` as a chain, see `process_instruction_chain`.
+    /// Addresses a v0 message loads from lookup tables are resolved first, with lookup tables
+    /// read from the `AccountsDb`, the scenario or RPC like any other account. The recent blockhash
+    /// is checked unless disabled with `Config::check_recent_blockhash`. Signatures are not
+    /// verified; see `process_transaction_signed`.
+    ///
+    /// ComputeBudget instructions are applied to the configured budget: a requested heap frame
+    /// applies to every instruction, and the instructions share one compute unit limit, the
+    /// requested one or else the runtime's default of 200k units per non-builtin instruction.
+    pub fn process_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<InstructionChainResult, SeashellError> {
+        transaction
+            .message
+            .sanitize()
+            .map_err(|e| SeashellError::Custom(format!("Invalid transaction message: {e}")))?;
+        if self.config.check_recent_blockhash {
+            self.check_recent_blockhash(&transaction.message)?;
+        }
+        let loaded_addresses = self.load_addresses(&transaction.message)?;
+        let reserved_account_keys = self.reserved_account_keys.active(&self.feature_set);
+        for pubkey in
+            demoted_accounts(&transaction.message, &loaded_addresses, &reserved_account_keys)
+        {
+            log::warn!(
+                "Transaction marks {pubkey} writable, but the runtime demotes it to readonly"
+            );
+        }
+        let instructions =
+            decompile_message(&transaction.message, &loaded_addresses, &reserved_account_keys);
+        let limits = compute_budget_limits(&instructions, &self.feature_set)?;
+        let mut writable_accounts: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|ixn| &ixn.accounts)
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .collect();
+        writable_accounts.sort_unstable();
+        writable_accounts.dedup();
+        let instruction_count = instructions.len();
+        let compute_budget = apply_limits(&limits, self.compute_budget);
+        let shared_unit_limit = Some(compute_budget.compute_unit_limit);
+        let mut result = self.process_chain(instructions, compute_budget, shared_unit_limit);
+        result.loaded_data_size.lookup_tables = transaction
+            .message
+            .address_table_lookups()
+            .map_or(0, |lookups| lookups.len());
+        let loaded_data_size_limit = limits.loaded_accounts_bytes.get();
+        if result.loaded_data_size.total() > loaded_data_size_limit as usize {
+            log::warn!(
+                "Transaction loads {} bytes of account data, over its limit of \
+                 {loaded_data_size_limit}; the runtime would reject it",
+                result.loaded_data_size.total()
+            );
+        }
+        if let Some(block_builder) = &self.block_builder {
+            block_builder.borrow_mut().add(&TransactionCost {
+                compute_units: result
+                    .results
+                    .iter()
+                    .map(|result| result.compute_units_consumed)
+                    .sum(),
+                signatures: u64::from(transaction.message.header().num_required_signatures),
+                instructions: instruction_count,
+                writable_accounts,
+            });
+        }
+        self.advance_clock();
+        Ok(result)
+    }
+
+    /// Like `process_transaction`, but first verifies the signature of every required signer
+    /// against the message and fails without running anything if one is missing or invalid.
+    pub fn process_transaction_signed(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<InstructionChainResult, SeashellError> {
+        let unverified = unverified_signers(transaction);
+        if !unverified.is_empty() {
+            return Err(SeashellError::MissingSignatures(unverified));
+        }
+        self.process_transaction(transaction)
+    }
+
+    /// Fails unless the recent blockhash of `message` is in the blockhash queue, telling an
+    /// expired blockhash apart from an unknown one. Messages starting with `AdvanceNonceAccount`
+    /// use a durable nonce instead, and always pass.
+    pub fn check_recent_blockhash(&self, message: &VersionedMessage) -> Result<(), SeashellError> {
+        let advances_nonce = message.instructions().first().is_some_and(|ixn| {
+            message
+                .static_account_keys()
+                .get(ixn.program_id_index as usize)
+                == Some(&solana_sdk_ids::system_program::id())
+                && ixn.data.starts_with(&4u32.to_le_bytes())
+        });
+        let blockhash = message.recent_blockhash();
+        let queue = self.accounts_db.sysvars.blockhash_queue();
+        if advances_nonce || queue.is_valid(blockhash) {
+            Ok(())
+        } else if queue.is_expired(blockhash) {
+            Err(SeashellError::BlockhashExpired(*blockhash))
+        } else {
+            Err(SeashellError::BlockhashNotFound(*blockhash))
+        }
+    }
+
+    /// Resolves the addresses `message` loads from address lookup tables at the current slot.
+    /// Legacy messages load none.
+    pub fn load_addresses(
+        &self,
+        message: &VersionedMessage,
+    ) -> Result<LoadedAddresses, SeashellError> {
+        let Some(lookups) = message.address_table_lookups() else {
+            return Ok(LoadedAddresses::default());
+        };
+
+        let current_slot = self.accounts_db.sysvars.clock().slot;
+        let slot_hashes = self.accounts_db.sysvars.slot_hashes();
+        lookups
+            .iter()
+            .map(|lookup| {
+                let account = match self.accounts_db.account_maybe(&lookup.account_key) {
+                    Some(account) => account,
+                    None => self.fetch_from_scenario_or_rpc(&lookup.account_key)?,
+                };
+                AddressLookupTable::from_account(&account)?.lookup(
+                    lookup,
+                    current_slot,
+                    &slot_hashes,
+                )
+            })
+            .collect()
+    }
+
+    /// Loads the instruction's accounts and programs, preferring `overlay` over the `AccountsDb`,
+    /// and runs it without committing anything.
+    pub(crate) fn execute(
+        &self,
+        ixn: Instruction,
+        overlay: &WorkingSet,
+        compute_budget: ComputeBudget,
+    ) -> Result<ExecutionOutput, InstructionProcessingError> {
+        let load_start = Instant::now();
+        let transaction_accounts = match self.accounts_db.try_accounts_for_instruction_with_overlay(
+            self.config.allow_uninitialized_accounts_local,
+            &ixn,
+            &overlay.accounts,
+            &overlay.processed,
+        ) {
+            Ok(accounts) => accounts,
+            Err(SeashellError::AccountNotFound(pubkey)) if self.config.strict_accounts => {
+                return Err(InstructionProcessingError::AccountNotFound(pubkey));
+            }
+            Err(e) => panic!("{e}"),
+        };
+
+        let sysvar_cache = self
+            .accounts_db
+            .sysvars_for_instruction(&transaction_accounts);
+        let load_accounts_us = load_start.elapsed().as_micros() as u64;
+
+        let mut programs = self.accounts_db.programs.read().clone();
+        for (program_id, entry) in &overlay.programs {
+            programs.replenish(*program_id, entry.clone());
+        }
+
+        let blockhash_queue = self.accounts_db.sysvars.blockhash_queue();
+        let input = ExecutionInput {
+            instruction: ixn,
+            transaction_accounts,
+            sysvar_cache,
+            programs,
+            feature_set: self.feature_set.clone(),
+            epoch_stakes: self.epoch_stakes.clone(),
+            compute_budget,
+            rent: self.accounts_db.sysvars.rent(),
+            record_logs: self.config.record_logs
+                || self.config.capture_vm_faults
+                || self.log_collector.is_some(),
+            capture_vm_fault: self.config.capture_vm_faults,
+            blockhash: blockhash_queue.latest(),
+            lamports_per_signature: blockhash_queue.lamports_per_signature(),
+            processed_siblings: overlay.processed.clone(),
+            instruction_datas: overlay.instruction_datas.clone(),
+        };
+
+        let mut output = match self.config.instruction_timeout {
+            Some(timeout) => execute_instruction_with_timeout(input, timeout)
+                .ok_or(InstructionProcessingError::Timeout(timeout))?,
+            None => execute_instruction(input),
+        };
+        output.timings.load_accounts_us = load_accounts_us;
+        output.loaded_data_size = self.loaded_data_size(&output.pre_accounts, overlay);
+
+        if let Some(log_collector) = &self.log_collector {
+            let mut log_collector = log_collector.borrow_mut();
+            for log in &self.config.log_filter.filter(&output.logs) {
+                log_collector.log(log);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// The account data loading `accounts` costs, with the programdata of upgradeable programs.
+    fn loaded_data_size(
+        &self,
+        accounts: &[(Pubkey, AccountSharedData)],
+        overlay: &WorkingSet,
+    ) -> LoadedDataSize {
+        let mut size = LoadedDataSize::default();
+        for (pubkey, account) in accounts {
+            if *pubkey == solana_sdk_ids::sysvar::instructions::id() {
+                continue;
+            }
+            size.extend([(*pubkey, account.data().len())]);
+
+            if account.owner() != &solana_sdk_ids::bpf_loader_upgradeable::id() {
+                continue;
+            }
+            let Ok(UpgradeableLoaderState::Program { programdata_address }) =
+                bincode::deserialize(account.data())
+            else {
+                continue;
+            };
+            let programdata = overlay
+                .accounts
+                .get(&programdata_address)
+                .cloned()
+                .or_else(|| self.accounts_db.account_maybe(&programdata_address));
+            if let Some(programdata) = programdata {
+                size.extend([(programdata_address, programdata.data().len())]);
+            }
+        }
+        size
+    }
+
+    /// Decimals of `mint` as the next instruction would see it.
+    fn mint_decimals(&self, mint: &Pubkey, overlay: &WorkingSet) -> Option<u8> {
+        match overlay.accounts.get(mint) {
+            Some(account) => spl::mint_decimals(account),
+            None => spl::mint_decimals(&self.accounts_db.account_maybe(mint)?),
+        }
+    }
+
+    fn commit_accounts(&self, accounts: impl IntoIterator<Item = (Pubkey, AccountSharedData)>) {
+        let accounts: Vec<_> = accounts.into_iter().collect();
+        self.watchlist.check(
+            &accounts,
+            &self.accounts_db.sysvars.rent(),
+            self.accounts_db.sysvars.clock().slot,
+        );
+        for (pubkey, account) in accounts {
+            self.set_account_from_account_shared_data(pubkey, account);
+        }
+    }
+
+    pub fn airdrop(&mut self, pubkey: Pubkey, amount: u64) {
+        let mut account = self
+            .accounts_db
+            .account_maybe(&pubkey)
+            .unwrap_or_else(|| AccountSharedData::new(0, 0, &solana_sdk_ids::system_program::id()));
+        account.set_lamports(account.lamports() + amount);
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+
+    pub fn account(&self, pubkey: &Pubkey) -> Account {
+        self.accounts_db.account_must(pubkey).into()
+    }
+
+    /// Like `account`, but returns `SeashellError::AccountNotFound` instead of panicking when the
+    /// account is neither local nor fetchable from the scenario's RPC.
+    pub fn try_account(&self, pubkey: &Pubkey) -> Result<Account, SeashellError> {
+        self.accounts_db.try_account(pubkey).map(Into::into)
+    }
+
+    /// Renders an account with fields decoded through the layout registry.
+    pub fn format_account(&self, pubkey: &Pubkey) -> String {
+        self.layouts
+            .format_account(pubkey, &self.accounts_db.account_must(pubkey))
+    }
+
+    /// Prints an account with fields decoded through the layout registry.
+    pub fn dump_account(&self, pubkey: &Pubkey) {
+        println!("{}", self.format_account(pubkey));
+    }
+
+    /// Records execution and RPC metrics to `sink`, see `crate::metrics`. Forks made afterwards
+    /// record to the same sink.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.accounts_db.metrics = Metrics::new(sink);
+    }
+
+    /// Registers a new keypair holding `lamports` and returns a wallet signing with it.
+    pub fn new_wallet(&self, lamports: u64) -> MockWallet {
+        let keypair = self.keypairs.new_keypair();
+        self.set_account(
+            keypair.pubkey(),
+            Account { lamports, owner: solana_sdk_ids::system_program::id(), ..Account::default() },
+        );
+        MockWallet::new(keypair)
+    }
+
+    /// A wallet for `pubkey`, if its keypair is registered.
+    pub fn wallet(&self, pubkey: &Pubkey) -> Option<MockWallet> {
+        self.keypairs.get(pubkey).map(MockWallet::new)
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.accounts_db.set_account(pubkey, account.into());
+    }
+
+    pub fn set_account_from_account_shared_data(&self, pubkey: Pubkey, account: AccountSharedData) {
+        self.accounts_db.set_account(pubkey, account);
+    }
+
+    /// Redirects every read and write of `address` to `replacement`, so instructions naming
+    /// `address` operate on `replacement`'s state while still seeing `address` as the key. Lets
+    /// forked-state tests swap a mainnet config for one with a local admin without editing
+    /// instruction account lists.
+    pub fn override_address(&self, address: Pubkey, replacement: Pubkey) {
+        self.accounts_db
+            .address_overrides
+            .write()
+            .insert(address, replacement);
+    }
+
+    /// Overrides the PDA of `seeds` under `program_id` with `replacement`, returning the PDA.
+    pub fn override_pda(
+        &self,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+        replacement: Pubkey,
+    ) -> Pubkey {
+        let (pda, _) = Pubkey::find_program_address(seeds, program_id);
+        self.override_address(pda, replacement);
+        pda
+    }
+
+    /// Removes the override of `address`, returning its replacement if there was one.
+    pub fn clear_address_override(&self, address: &Pubkey) -> Option<Pubkey> {
+        self.accounts_db.address_overrides.write().remove(address)
+    }
+
+    /// Applies typed field patches to an account's data, failing without side effects if any
+    /// patch falls outside the account data.
+    pub fn modify_account(
+        &mut self,
+        pubkey: &Pubkey,
+        patches: &[FieldPatch],
+    ) -> Result<(), SeashellError> {
+        self.accounts_db.patch_account(pubkey, patches)
+    }
+
+    /// The clock time-bearing fixtures are stamped with: the `Clock` sysvar shifted by
+    /// `Config::fixture_clock_offset`.
+    pub fn fixture_clock(&self) -> Clock {
+        self.config
+            .fixture_clock_offset
+            .apply(&self.accounts_db.sysvars.clock())
+    }
+
+    /// Sets a vote account materialized from `builder` as of the fixture clock.
+    pub fn set_vote_account(&self, pubkey: Pubkey, builder: &VoteAccountBuilder) {
+        let account = builder.build(&self.fixture_clock(), &self.accounts_db.sysvars.rent());
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+
+    /// Sets a rent-exempt address lookup table account holding `table`.
+    pub fn set_address_lookup_table(&self, pubkey: Pubkey, table: &AddressLookupTable) {
+        let account = table.to_account(&self.accounts_db.sysvars.rent());
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+
+    pub fn clear_non_program_accounts(&self) {
+        self.accounts_db.clear_non_program_accounts();
+    }
+
+    /// Saves the current accounts, programs, sysvars and epoch stakes, to come back to with
+    /// [`Seashell::restore`]. Cheap: account data is shared until it is next written.
+    pub fn checkpoint(&self) -> StateHandle {
+        StateHandle {
+            accounts_db: self.accounts_db.checkpoint(),
+            epoch_stakes: self.epoch_stakes.clone(),
+        }
+    }
+
+    /// Rewinds to `handle`. The handle stays valid, so one checkpoint can be restored before
+    /// trying each of several paths.
+    pub fn restore(&mut self, handle: &StateHandle) {
+        self.accounts_db.restore(&handle.accounts_db);
+        self.epoch_stakes = handle.epoch_stakes.clone();
+    }
+
+    /// Puts this `Seashell` back in the state it was constructed in, for reusing one instance
+    /// across tests: accounts other than programs and their programdata are dropped, and the
+    /// sysvars, epoch stakes and address overrides reset. Builtins and every program loaded so
+    /// far stay loaded, and the configuration and scenario are kept.
+    pub fn reset_to_genesis(&mut self) {
+        self.accounts_db.reset_to_genesis();
+        self.epoch_stakes = EpochStakes::default();
+        if let Some(log_collector) = &self.log_collector {
+            *log_collector.borrow_mut() = LogCollector::default();
+        }
+    }
+
+    pub fn warp(&self, slot: u64, timestamp: u64) {
+        self.accounts_db.warp(slot, timestamp as i64);
+    }
+
+    /// The blockhash transactions and durable nonces see as the most recent.
+    pub fn latest_blockhash(&self) -> Hash {
+        self.accounts_db.sysvars.blockhash_queue().latest()
+    }
+
+    /// Registers a new blockhash without moving the clock, e.g. so a durable nonce can be
+    /// advanced again. Returns the new blockhash.
+    pub fn expire_blockhash(&self) -> Hash {
+        self.accounts_db
+            .sysvars
+            .update_blockhash_queue(|queue| queue.advance(1))
+    }
+
+    /// Whether a transaction with `blockhash` as its recent blockhash would still be accepted.
+    pub fn is_blockhash_valid(&self, blockhash: &Hash) -> bool {
+        self.accounts_db
+            .sysvars
+            .blockhash_queue()
+            .is_valid(blockhash)
+    }
+
+    /// Sets the fee rate new blockhashes are registered with.
+    pub fn set_lamports_per_signature(&self, lamports_per_signature: u64) {
+        self.accounts_db.sysvars.update_blockhash_queue(|queue| {
+            queue.set_lamports_per_signature(lamports_per_signature)
+        });
+    }
+
+    /// Advances the clock to `slot` and keeps the other sysvars consistent with it: SlotHashes
+    /// gets an entry for every slot passed, the epoch and its start timestamp follow the
+    /// `EpochSchedule`, and StakeHistory gets an entry for every completed epoch. Unlike
+    /// [`Seashell::warp`], the timestamp is derived from the slot. Panics if `slot` is in the past.
+    pub fn warp_to_slot(&self, slot: u64) {
+        self.accounts_db.warp_to_slot(slot);
+    }
+}
+
+/// State saved by [`Seashell::checkpoint`].
+#[derive(Clone)]
+pub struct StateHandle {
+    pub(crate) accounts_db: AccountsDbCheckpoint,
+    pub(crate) epoch_stakes: EpochStakes,
+}
+
+/// Uncommitted state shared by the instructions of a chain.
+#[derive(Default)]
+pub(crate) struct WorkingSet {
+    pub(crate) accounts: IndexMap<Pubkey, AccountSharedData>,
+    programs: Vec<(Pubkey, Arc<ProgramCacheEntry>)>,
+    /// Top-level instructions the chain has run so far, in order.
+    processed: Vec<Instruction>,
+    /// Data of every instruction in the chain, including the ones still to run.
+    instruction_datas: Vec<Vec<u8>>,
+}
+
+pub struct InstructionProcessingResult {
+    pub compute_units_consumed: u64,
+    pub return_data: Vec<u8>,
+    /// The program that set `return_data`, if any data was set.
+    pub return_data_program_id: Option<Pubkey>,
+    pub error: Option<InstructionProcessingError>,
+    pub post_execution_accounts: Vec<(Pubkey, Account)>,
+    /// Per-account changes made by the instruction, in transaction order, leaving out accounts it
+    /// didn't change. Empty if the instruction failed.
+    pub account_diffs: Vec<AccountDiff>,
+    /// Every instruction the runtime executed, starting with the top-level instruction and
+    /// followed by its CPIs in invocation order. Recorded for failed executions too.
+    pub instruction_trace: Vec<TracedInstruction>,
+    /// Compute units broken down per invocation, in the same order as `instruction_trace`.
+    pub compute_unit_frames: Vec<ComputeUnitFrame>,
+    /// Program logs of this execution, recorded whether or not a log collector is enabled, see
+    /// `Config::record_logs`.
+    pub logs: Vec<String>,
+    pub timings: ExecutionTimings,
+    /// The access violation that failed the instruction, with `Config::capture_vm_faults`.
+    pub vm_fault: Option<VmFault>,
+    /// Balances of the SPL Token and Token-2022 accounts among the transaction accounts, before
+    /// and after execution, like the `preTokenBalances` and `postTokenBalances` of
+    /// `getTransaction`. A failed instruction changes nothing, so its post balances equal the pre
+    /// balances.
+    pub pre_token_balances: Vec<TokenBalance>,
+    pub post_token_balances: Vec<TokenBalance>,
+    /// Set when the instruction failed on live accounts that drifted from the pinned scenario.
+    pub pinning_suggestion: Option<PinningSuggestion>,
+    /// Account data loaded to run the instruction.
+    pub loaded_data_size: LoadedDataSize,
+    /// Known mainnet addresses the instruction signs with or writes to, with
+    /// `Config::audit_known_addresses`.
+    pub known_address_uses: Vec<KnownAddressUse>,
+    /// Accounts whose writes disagree with the mutability the IDL registered for the program
+    /// declares. See `crate::idl_audit`.
+    pub mutability_mismatches: Vec<MutabilityMismatch>,
+    /// Anchor events of programs with a registered IDL, decoded through it. See
+    /// `crate::anchor_error`.
+    pub decoded_events: Vec<DecodedEvent>,
+    /// The custom error the instruction failed with, named after the failing program's IDL.
+    pub anchor_error: Option<AnchorError>,
+    /// The CPI that ran into the stack depth or instruction trace length limit, if the
+    /// instruction failed on one. See `crate::invocation_limit`.
+    pub invocation_limit: Option<InvocationLimitError>,
+}
+
+impl InstructionProcessingResult {
+    /// `decimals_of` resolves the decimals of mints that aren't among the transaction accounts.
+    pub(crate) fn from_output(
+        output: ExecutionOutput,
+        decimals_of: impl Fn(&Pubkey) -> Option<u8>,
+    ) -> Self {
+        let ExecutionOutput {
+            result,
+            compute_units_consumed,
+            return_data,
+            return_data_program_id,
+            pre_accounts,
+            post_accounts,
+            instruction_trace,
+            logs,
+            timings,
+            modified_programs: _,
+            vm_fault,
+            loaded_data_size,
+        } = output;
+        let (error, post_accounts) = match result {
+            Ok(()) => (None, post_accounts),
+            Err(e) => (Some(InstructionProcessingError::InstructionError(e)), Vec::default()),
+        };
+        let account_diffs = account_diffs(&pre_accounts, &post_accounts);
+        let pre_token_balances = spl::token_balances(&pre_accounts, &decimals_of);
+        let post_token_balances = match error {
+            None => spl::token_balances(&post_accounts, &decimals_of),
+            Some(_) => pre_token_balances.clone(),
+        };
+        let post_execution_accounts = post_accounts
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect();
+        InstructionProcessingResult {
+            compute_units_consumed,
+            return_data,
+            return_data_program_id,
+            error,
+            post_execution_accounts,
+            account_diffs,
+            instruction_trace,
+            compute_unit_frames: compute_unit_frames(&logs),
+            logs,
+            timings,
+            vm_fault,
+            pre_token_balances,
+            post_token_balances,
+            pinning_suggestion: None,
+            loaded_data_size,
+            known_address_uses: Vec::new(),
+            mutability_mismatches: Vec::new(),
+            decoded_events: Vec::new(),
+            anchor_error: None,
+            invocation_limit: None,
+        }
+    }
+
+    pub(crate) fn from_error(error: InstructionProcessingError) -> Self {
+        InstructionProcessingResult {
+            compute_units_consumed: 0,
+            return_data: Vec::new(),
+            return_data_program_id: None,
+            error: Some(error),
+            post_execution_accounts: Vec::default(),
+            account_diffs: Vec::default(),
+            instruction_trace: Vec::default(),
+            compute_unit_frames: Vec::default(),
+            logs: Vec::default(),
+            timings: ExecutionTimings::default(),
+            vm_fault: None,
+            pre_token_balances: Vec::default(),
+            post_token_balances: Vec::default(),
+            pinning_suggestion: None,
+            loaded_data_size: LoadedDataSize::default(),
+            known_address_uses: Vec::new(),
+            mutability_mismatches: Vec::new(),
+            decoded_events: Vec::new(),
+            anchor_error: None,
+            invocation_limit: None,
+        }
+    }
+
+    /// The CPIs made during execution, i.e. the trace without top-level instructions.
+    pub fn inner_instructions(&self) -> impl Iterator<Item = &TracedInstruction> {
+        self.instruction_trace
+            .iter()
+            .filter(|instruction| instruction.is_cpi())
+    }
+
+    /// Anchor events emitted during execution, through `Program data:` logs or self-CPIs to the
+    /// program's event authority, in emission order.
+    pub fn events(&self) -> Vec<AnchorEvent> {
+        anchor_events(&self.instruction_trace, &self.logs)
+    }
+
+    /// Compute units each program spent itself, summed over all of its invocations.
+    pub fn compute_units_by_program(&self) -> IndexMap<Pubkey, u64> {
+        let mut units = IndexMap::new();
+        for frame in &self.compute_unit_frames {
+            *units.entry(frame.program_id).or_default() +=
+                frame.exclusive_units.unwrap_or_default();
+        }
+        units
+    }
+
+    /// How the instruction changed `pubkey`, if it changed it at all.
+    pub fn account_diff(&self, pubkey: &Pubkey) -> Option<&AccountDiff> {
+        self.account_diffs
+            .iter()
+            .find(|diff| diff.pubkey == *pubkey)
+    }
+
+    /// How much the token balance of `token_account` changed, in base units. Accounts created or
+    /// closed by the instruction count as holding zero on the other side.
+    pub fn token_balance_change(&self, token_account: &Pubkey) -> Option<i128> {
+        let amount = |balances: &[TokenBalance]| {
+            balances
+                .iter()
+                .find(|balance| balance.account == *token_account)
+                .map(|balance| i128::from(balance.amount))
+        };
+        let pre = amount(&self.pre_token_balances);
+        let post = amount(&self.post_token_balances);
+        if pre.is_none() && post.is_none() {
+            return None;
+        }
+        Some(post.unwrap_or_default() - pre.unwrap_or_default())
+    }
+
+    /// The post-execution state of `pubkey`, if it was one of the transaction accounts.
+    pub fn post_account(&self, pubkey: &Pubkey) -> Option<&Account> {
+        find_post_account(&self.post_execution_accounts, pubkey)
+    }
+
+    /// Borrows the post-execution data of `pubkey`.
+    #[track_caller]
+    pub fn account_data(&self, pubkey: &Pubkey) -> &[u8] {
+        &self.account(pubkey).data
+    }
+
+    /// Borrows the post-execution state of `pubkey`.
+    #[track_caller]
+    pub fn account(&self, pubkey: &Pubkey) -> &Account {
+        self.post_account(pubkey)
+            .unwrap_or_else(|| panic!("Account {pubkey} is not in the post-execution state"))
+    }
+}
+
+pub struct InstructionChainResult {
+    /// Results of the instructions that ran, in order. Stops at the first failure.
+    pub results: Vec<InstructionProcessingResult>,
+    /// Index and error of the instruction that aborted the chain.
+    pub error: Option<(usize, InstructionProcessingError)>,
+    /// Account data loaded by the instructions that ran, each account counted once. For
+    /// transactions, includes the lookup tables.
+    pub loaded_data_size: LoadedDataSize,
+    /// Final state of every account the chain touched; empty if the chain was rolled back.
+    pub post_execution_accounts: Vec<(Pubkey, Account)>,
+}
+
+impl InstructionChainResult {
+    /// The final state of `pubkey`, if the chain touched it.
+    pub fn post_account(&self, pubkey: &Pubkey) -> Option<&Account> {
+        find_post_account(&self.post_execution_accounts, pubkey)
+    }
+
+    /// Borrows the final data of `pubkey`.
+    #[track_caller]
+    pub fn account_data(&self, pubkey: &Pubkey) -> &[u8] {
+        &self.account(pubkey).data
+    }
+
+    /// Borrows the final state of `pubkey`.
+    #[track_caller]
+    pub fn account(&self, pubkey: &Pubkey) -> &Account {
+        self.post_account(pubkey)
+            .unwrap_or_else(|| panic!("Account {pubkey} is not in the post-execution state"))
+    }
+}
+
+fn chain_loaded_data_size(results: &[InstructionProcessingResult]) -> LoadedDataSize {
+    let mut size = LoadedDataSize::default();
+    for result in results {
+        size.extend(result.loaded_data_size.accounts.iter().copied());
+    }
+    size
+}
+
+/// Diffs the accounts present in both states, once per key.
+fn account_diffs(
+    pre_accounts: &[(Pubkey, AccountSharedData)],
+    post_accounts: &[(Pubkey, AccountSharedData)],
+) -> Vec<AccountDiff> {
+    let mut diffs: Vec<AccountDiff> = Vec::new();
+    for ((pubkey, pre), (_, post)) in pre_accounts.iter().zip(post_accounts) {
+        if diffs.iter().any(|diff| diff.pubkey == *pubkey) {
+            continue;
+        }
+        diffs.extend(AccountDiff::between(*pubkey, pre, post));
+    }
+    diffs
+}
+
+fn find_post_account<'a>(
+    accounts: &'a [(Pubkey, Account)],
+    pubkey: &Pubkey,
+) -> Option<&'a Account> {
+    accounts
+        .iter()
+        .find(|(key, _)| key == pubkey)
+        .map(|(_, account)| account)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionProcessingError {
+    InstructionError(InstructionError),
+    ProgramError,
+    /// Execution did not finish within `Config::instruction_timeout`.
+    Timeout(Duration),
+    /// The instruction uses an account that could not be found, with `Config::strict_accounts`.
+    AccountNotFound(Pubkey),
+}
+
+pub fn try_find_workspace_root() -> Option<PathBuf> {
+    let cargo = std::env::var("CARGO").unwrap_or("cargo".to_owned());
+    let output = std::process::Command::new(cargo)
+        .arg("locate-project")
+        .arg("--workspace")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let root = parsed["root"]
+        .as_str()
+        .unwrap()
+        .strip_suffix("Cargo.toml")?;
+
+    Some(PathBuf::from(root))
+}
+
+/// Where the scenario named `scenario_name` is stored in the workspace.
+fn scenario_path(scenario_name: &str) -> PathBuf {
+    let workspace_root = try_find_workspace_root().expect("Failed to locate workspace root");
+    workspace_root.join(format!("scenarios/{scenario_name}.json.gz"))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::check::Check;
+    use crate::layout::{AccountLayout, FieldType};
+    use crate::patch::DataPatch;
+    use crate::test_utils::{serve_rpc, system_transfer, transfer_accounts, RPC_SLOT};
+
+    #[test]
+    fn test_native_transfer() {
+        crate::set_log();
+        let mut seashell = Seashell::new();
+
+        let from = solana_pubkey::Pubkey::new_unique();
+        let to = solana_pubkey::Pubkey::new_unique();
+        seashell.airdrop(from, 1000);
+        seashell.accounts_db.set_account_mock(to);
+        println!("Airdropped 1000 lamports to {from}");
+
+        let mut data = Vec::with_capacity(12);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&500u64.to_le_bytes());
+
+        let ixn = Instruction {
+            program_id: solana_sdk_ids::system_program::id(),
+            accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+            data,
+        };
+
+        let result = seashell.process_instruction(ixn);
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.compute_units_consumed, 150);
 
-        let result = seashell.process_instruction(ixn);
-        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
-        assert_eq!(result.compute_units_consumed, 150);
-
         let post_from = result
             .post_execution_accounts
             .iter()
@@ -501,112 +1908,755 @@ mod tests {
     }
 
     #[test]
-    fn test_spl_transfer() {
-        crate::set_log();
-        let mut seashell = Seashell::new();
-        let from: Pubkey = solana_pubkey::Pubkey::new_unique();
-        let to = solana_pubkey::Pubkey::new_unique();
-        let from_authority = solana_pubkey::Pubkey::new_unique();
-        let mint = solana_pubkey::Pubkey::new_unique();
+    fn test_spl_transfer() {
+        crate::set_log();
+        let mut seashell = Seashell::new();
+        let from: Pubkey = solana_pubkey::Pubkey::new_unique();
+        let to = solana_pubkey::Pubkey::new_unique();
+        let from_authority = solana_pubkey::Pubkey::new_unique();
+        let mint = solana_pubkey::Pubkey::new_unique();
+
+        seashell.set_mint(mint, 0, None, 1000);
+        seashell.set_token_account(from, mint, from_authority, 1000);
+        seashell.set_token_account(to, mint, Pubkey::new_unique(), 0);
+        seashell.airdrop(from_authority, 1000);
+
+        let mut data = [0; 9];
+        data[0] = 3;
+        data[1..9].copy_from_slice(&500u64.to_le_bytes());
+
+        let ixn = Instruction {
+            program_id: crate::spl::TOKEN_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(from, true),
+                AccountMeta::new(to, false),
+                AccountMeta::new_readonly(from_authority, true),
+            ],
+            data: data.to_vec(),
+        };
+
+        let result = seashell.process_instruction(ixn);
+
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.compute_units_consumed, 4644);
+
+        let post_from = result
+            .post_execution_accounts
+            .iter()
+            .find(|(pubkey, _)| *pubkey == from)
+            .expect("Resulting account should exist")
+            .to_owned()
+            .1;
+        let post_from_balance = u64::from_le_bytes(post_from.data[64..72].try_into().unwrap());
+        assert_eq!(
+            post_from_balance, 500,
+            "Expected from token account to have 500 tokens after transfer"
+        );
+
+        let post_to = result
+            .post_execution_accounts
+            .iter()
+            .find(|(pubkey, _)| *pubkey == to)
+            .expect("Resulting account should exist")
+            .to_owned()
+            .1;
+        let post_to_balance = u64::from_le_bytes(post_to.data[64..72].try_into().unwrap());
+        assert_eq!(
+            post_to_balance, 500,
+            "Expected to token account to have 500 tokens after transfer"
+        );
+
+        assert!(
+            result.return_data.is_empty(),
+            "Expected no return data, got: {:?}",
+            result.return_data
+        );
+    }
+
+    #[test]
+    fn test_spl_transfer_token_balances() {
+        let mut seashell = Seashell::new();
+        let (from, to, from_authority) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = Pubkey::new_unique();
+        seashell.set_mint(mint, 0, None, 1000);
+        seashell.set_token_account(from, mint, from_authority, 1000);
+        seashell.set_token_account(to, mint, Pubkey::new_unique(), 0);
+        seashell.airdrop(from_authority, 1000);
+
+        let mut data = vec![3];
+        data.extend_from_slice(&500u64.to_le_bytes());
+        let result = seashell.process_instruction(Instruction {
+            program_id: crate::spl::TOKEN_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(from, false),
+                AccountMeta::new(to, false),
+                AccountMeta::new_readonly(from_authority, true),
+            ],
+            data,
+        });
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+
+        assert_eq!(result.pre_token_balances.len(), 2);
+        assert_eq!(result.token_balance_change(&from), Some(-500));
+        assert_eq!(result.token_balance_change(&to), Some(500));
+        assert_eq!(result.token_balance_change(&from_authority), None);
+        assert_eq!(result.token_balance(&from), 500);
+        result.assert_token_balance(&to, 500);
+        // Without `Config::memoize` the AccountsDb keeps the pre-execution balances.
+        seashell.assert_token_balance(&from, 1000);
+    }
+
+    #[test]
+    fn test_memoize() {
+        crate::set_log();
+        let mut seashell = Seashell::new_with_config(Config {
+            memoize: true,
+            allow_uninitialized_accounts_local: false,
+            allow_uninitialized_accounts_fetched: false,
+            ..Config::default()
+        });
+
+        let from = solana_pubkey::Pubkey::new_unique();
+        let to = solana_pubkey::Pubkey::new_unique();
+        seashell.airdrop(from, 1000);
+        seashell.accounts_db.set_account_mock(to);
+        println!("Airdropped 1000 lamports to {from}");
+
+        let mut data = Vec::with_capacity(12);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&500u64.to_le_bytes());
+
+        let ixn = Instruction {
+            program_id: solana_sdk_ids::system_program::id(),
+            accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+            data,
+        };
+
+        let result = seashell.process_instruction(ixn);
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.compute_units_consumed, 150);
+
+        let post_from = seashell.account(&from);
+        assert_eq!(
+            post_from.lamports(),
+            500,
+            "Expected from account to have 500 lamports after transfer"
+        );
+        let post_to = seashell.account(&to);
+        assert_eq!(
+            post_to.lamports(),
+            500,
+            "Expected to account to have 500 lamports after transfer"
+        );
+    }
+
+    #[test]
+    fn test_instruction_chain_rollback() {
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+
+        let (from, to) = transfer_accounts(&seashell);
+        let transfer = |lamports| system_transfer(from, to, lamports);
+
+        // The second transfer only fails because the first one already moved the lamports.
+        let result = seashell.process_instruction_chain(vec![transfer(600), transfer(600)]);
+        assert_eq!(result.results.len(), 2);
+        assert!(matches!(result.error, Some((1, _))));
+        assert_eq!(seashell.account(&from).lamports(), 1000);
+        assert_eq!(seashell.account(&to).lamports(), 0);
+
+        let result = seashell.process_instruction_chain(vec![transfer(600), transfer(400)]);
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.account(&to).lamports, 1000);
+        assert!(result.account_data(&from).is_empty());
+        assert_eq!(seashell.account(&from).lamports(), 0);
+        assert_eq!(seashell.account(&to).lamports(), 1000);
+    }
+
+    #[test]
+    fn test_instruction_chain_processed_siblings() {
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let accounts: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        for pubkey in &accounts {
+            seashell.set_account(*pubkey, Account { lamports: 1000, ..Account::default() });
+        }
+        let transfer = |from, to| system_transfer(from, to, 100);
+
+        // The second transfer runs with the first recorded as its sibling, whose accounts it
+        // doesn't load.
+        let result = seashell.process_instruction_chain(vec![
+            transfer(accounts[0], accounts[1]),
+            transfer(accounts[2], accounts[3]),
+        ]);
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(result.results[1].instruction_trace.len(), 1);
+        assert_eq!(result.results[1].post_execution_accounts.len(), 3);
+        let lamports: Vec<u64> = accounts
+            .iter()
+            .map(|pubkey| seashell.account(pubkey).lamports)
+            .collect();
+        assert_eq!(lamports, vec![900, 1100, 900, 1100]);
+    }
+
+    #[test]
+    fn test_versioned_transaction_with_lookup_table() {
+        use solana_message::{v0, AddressLookupTableAccount};
+
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+
+        let (from, to) = transfer_accounts(&seashell);
+
+        let table_key = solana_pubkey::Pubkey::new_unique();
+        seashell.set_address_lookup_table(table_key, &AddressLookupTable::new(None, vec![to]));
+
+        let transfer = system_transfer(from, to, 600);
+        let message = v0::Message::try_compile(
+            &from,
+            &[transfer],
+            &[AddressLookupTableAccount { key: table_key, addresses: vec![to] }],
+            seashell.latest_blockhash(),
+        )
+        .unwrap();
+        assert_eq!(message.address_table_lookups.len(), 1);
+
+        let mut transaction = VersionedTransaction {
+            signatures: vec![Default::default()],
+            message: VersionedMessage::V0(message),
+        };
+        let result = seashell.process_transaction(&transaction).unwrap();
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.loaded_data_size.lookup_tables, 1);
+        assert_eq!(seashell.account(&from).lamports(), 400);
+        assert_eq!(seashell.account(&to).lamports(), 600);
+
+        // Indexes past the end of the table are rejected before anything runs.
+        let VersionedMessage::V0(message) = &mut transaction.message else { unreachable!() };
+        message.address_table_lookups[0].writable_indexes = vec![1];
+        assert!(seashell.process_transaction(&transaction).is_err());
+    }
+
+    #[test]
+    fn test_expect_err() {
+        let mut seashell = Seashell::new();
+
+        let (from, to) = transfer_accounts(&seashell);
+
+        let transfer = system_transfer(from, to, 2000);
+
+        // SystemError::ResultWithNegativeLamports
+        let result = seashell.expect_err(transfer.clone(), InstructionError::Custom(1));
+        assert!(!result.logs.is_empty());
+
+        seashell.idls.insert(
+            solana_sdk_ids::system_program::id(),
+            Idl::from_json(r#"{"errors":[{"code":1,"name":"ResultWithNegativeLamports"}]}"#)
+                .unwrap(),
+        );
+        seashell.expect_anchor_err(transfer, "ResultWithNegativeLamports");
+    }
+
+    #[test]
+    fn test_record_logs() {
+        let mut seashell = Seashell::new();
+
+        let (from, to) = transfer_accounts(&seashell);
+
+        let transfer = system_transfer(from, to, 100);
+
+        assert!(!seashell
+            .process_instruction(transfer.clone())
+            .logs
+            .is_empty());
+        seashell.config.record_logs = false;
+        assert!(seashell
+            .process_instruction(transfer.clone())
+            .logs
+            .is_empty());
+
+        // An enabled log collector still gets every log.
+        seashell.enable_log_collector();
+        assert!(!seashell.process_instruction(transfer).logs.is_empty());
+        assert!(!seashell.logs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_process_and_validate() {
+        let seashell = Seashell::new();
+
+        let (from, to) = transfer_accounts(&seashell);
+
+        let transfer = system_transfer(from, to, 400);
+
+        let result = seashell.process_and_validate(
+            transfer,
+            &[
+                Check::success(),
+                Check::compute_units(150),
+                Check::account(&from).lamports(600),
+                Check::account(&to)
+                    .lamports(400)
+                    .owner(&solana_sdk_ids::system_program::id())
+                    .space(0),
+            ],
+        );
+
+        // Accounts the transfer didn't change, like the system program, are left out.
+        assert_eq!(result.account_diffs.len(), 2);
+        assert_eq!(result.account_diff(&from).unwrap().lamports, Some((1000, 600)));
+        assert!(result.account_diff(&to).unwrap().data_ranges.is_empty());
+    }
+
+    #[test]
+    fn test_assertion_failures_show_accounts_side_by_side() {
+        let mut seashell = Seashell::new();
+        let owner = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        seashell.layouts.register(
+            owner,
+            AccountLayout::new("Vault")
+                .field("authority", FieldType::Pubkey)
+                .field("amount", FieldType::U64),
+        );
+        let mut data = Pubkey::new_unique().to_bytes().to_vec();
+        data.extend_from_slice(&5u64.to_le_bytes());
+        let account = Account { lamports: 10, data: data.clone(), owner, ..Account::default() };
+        seashell.set_account(vault, account.clone());
+        seashell.assert_account(&vault, &account);
+
+        let panic_message = |f: &dyn Fn()| {
+            let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+            payload.downcast_ref::<String>().unwrap().clone()
+        };
+        data[32..].copy_from_slice(&7u64.to_le_bytes());
+        let message = panic_message(&|| {
+            seashell.assert_account(&vault, &Account { data: data.clone(), ..account.clone() })
+        });
+        assert!(message.contains("!   amount"), "{message}");
+        assert!(message.contains("    authority"), "{message}");
+        assert!(message.contains("  owner"), "{message}");
+        assert!(!message.contains("! owner"), "{message}");
+
+        let (from, to) = transfer_accounts(&seashell);
+        let transfer = system_transfer(from, to, 400);
+        let message = panic_message(&|| {
+            seashell.process_and_validate(transfer.clone(), &[Check::account(&to).lamports(500)]);
+        });
+        assert!(message.contains("expected") && message.contains("actual"), "{message}");
+        assert!(message.contains("! lamports"), "{message}");
+
+        // An instruction that unexpectedly succeeds shows what it changed.
+        let message = panic_message(&|| {
+            seashell.expect_err(transfer.clone(), InstructionError::Custom(1));
+        });
+        assert!(message.contains("before") && message.contains("after"), "{message}");
+        assert!(message.contains(&format!("Account {from}")), "{message}");
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let (from, to) = transfer_accounts(&seashell);
+        let handle = seashell.checkpoint();
+
+        let transfer = |lamports| system_transfer(from, to, lamports);
+
+        assert!(seashell.process_instruction(transfer(600)).error.is_none());
+        seashell.warp(100, 1_700_000_000);
+        assert_eq!(seashell.account(&to).lamports, 600);
+
+        seashell.restore(&handle);
+        assert_eq!(seashell.account(&from).lamports, 1000);
+        assert_eq!(seashell.account(&to).lamports, 0);
+        assert_eq!(seashell.accounts_db.sysvars.clock().slot, 0);
+
+        assert!(seashell.process_instruction(transfer(300)).error.is_none());
+        assert_eq!(seashell.account(&to).lamports, 300);
+    }
+
+    #[test]
+    fn test_reset_to_genesis() {
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let program_id = Pubkey::new_unique();
+        let elf = include_bytes!("spl/elfs/tokenkeg.so");
+        seashell.load_upgradeable_program_from_bytes(program_id, elf, None);
+        let accounts = seashell.accounts_db.accounts.read().len();
+        let from = Pubkey::new_unique();
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        seashell.warp_to_slot(1000);
+
+        seashell.reset_to_genesis();
+        assert_eq!(seashell.accounts_db.accounts.read().len(), accounts);
+        assert!(seashell.accounts_db.account_maybe(&from).is_none());
+        assert!(seashell.accounts_db.account_maybe(&program_id).is_some());
+        assert_eq!(seashell.accounts_db.sysvars.clock().slot, 0);
+    }
+
+    #[test]
+    fn test_warp_to_slot() {
+        let seashell = Seashell::new();
+        let sysvars = &seashell.accounts_db.sysvars;
+        let slots_per_epoch = sysvars.epoch_schedule().slots_per_epoch;
+        let start = sysvars.clock();
+
+        let slot = 2 * slots_per_epoch + 10;
+        seashell.warp_to_slot(slot);
+        let clock = sysvars.clock();
+        assert_eq!(clock.slot, slot);
+        assert_eq!(clock.epoch, 2);
+        assert_eq!(clock.unix_timestamp, start.unix_timestamp + (slot * 400 / 1000) as i64);
+        assert_eq!(clock.epoch_start_timestamp, clock.unix_timestamp - 4);
+
+        let slot_hashes = sysvars.slot_hashes();
+        assert_eq!(slot_hashes.first().map(|(slot, _)| *slot), Some(slot));
+        assert!(slot_hashes.get(&(slot - 1)).is_some());
+        let stake_history = sysvars.stake_history();
+        assert!(stake_history.get(0).is_some() && stake_history.get(1).is_some());
+        assert!(stake_history.get(2).is_none());
+    }
 
-        create_mint_account(&mut seashell, mint, 1000);
-        create_token_account(&mut seashell, from, mint, from_authority, 1000);
-        create_token_account(&mut seashell, to, mint, Pubkey::new_unique(), 0);
-        seashell.airdrop(from_authority, 1000);
+    #[test]
+    fn test_slots_per_instruction() {
+        let seashell =
+            Seashell::new_with_config(Config { slots_per_instruction: 5, ..Config::default() });
+        let payer = Pubkey::new_unique();
+        seashell.set_account(payer, Account { lamports: 1000, ..Account::default() });
+        let start = seashell.accounts_db.sysvars.clock();
+
+        for _ in 0..3 {
+            // An empty transfer to self, only here to move the clock.
+            seashell.process_instruction(system_transfer(payer, payer, 0));
+        }
 
-        let mut data = [0; 9];
-        data[0] = 3;
-        data[1..9].copy_from_slice(&500u64.to_le_bytes());
+        let clock = seashell.accounts_db.sysvars.clock();
+        assert_eq!(clock.slot, start.slot + 15);
+        assert_eq!(clock.unix_timestamp, start.unix_timestamp + 6);
+        assert!(seashell
+            .accounts_db
+            .sysvars
+            .slot_hashes()
+            .get(&clock.slot)
+            .is_some());
+    }
 
-        let ixn = Instruction {
-            program_id: crate::spl::TOKEN_PROGRAM_ID,
+    #[test]
+    fn test_durable_nonce() {
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let nonce = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        seashell.set_account(
+            nonce,
+            Account {
+                lamports: solana_rent::Rent::default().minimum_balance(80),
+                data: vec![0; 80],
+                owner: solana_sdk_ids::system_program::id(),
+                ..Account::default()
+            },
+        );
+        let recent_blockhashes = solana_sdk_ids::sysvar::recent_blockhashes::id();
+
+        let mut data = 6u32.to_le_bytes().to_vec();
+        data.extend_from_slice(authority.as_ref());
+        let initialize = Instruction {
+            program_id: solana_sdk_ids::system_program::id(),
             accounts: vec![
-                AccountMeta::new(from, true),
-                AccountMeta::new(to, false),
-                AccountMeta::new_readonly(from_authority, true),
+                AccountMeta::new(nonce, false),
+                AccountMeta::new_readonly(recent_blockhashes, false),
+                AccountMeta::new_readonly(solana_sdk_ids::sysvar::rent::id(), false),
             ],
-            data: data.to_vec(),
+            data,
         };
+        let result = seashell.process_instruction(initialize);
+        assert!(result.error.is_none(), "{:?}", result.error);
 
-        let result = seashell.process_instruction(ixn);
+        let advance = Instruction {
+            program_id: solana_sdk_ids::system_program::id(),
+            accounts: vec![
+                AccountMeta::new(nonce, false),
+                AccountMeta::new_readonly(recent_blockhashes, false),
+                AccountMeta::new_readonly(authority, true),
+            ],
+            data: 4u32.to_le_bytes().to_vec(),
+        };
+        // The nonce already holds the latest blockhash.
+        assert!(seashell
+            .process_instruction(advance.clone())
+            .error
+            .is_some());
+
+        let blockhash = seashell.latest_blockhash();
+        assert_ne!(seashell.expire_blockhash(), blockhash);
+        assert!(seashell.is_blockhash_valid(&blockhash));
+        let result = seashell.process_instruction(advance);
+        assert!(result.error.is_none(), "{:?}", result.error);
+
+        seashell.warp_to_slot(200);
+        assert!(!seashell.is_blockhash_valid(&blockhash));
+    }
 
-        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
-        assert_eq!(result.compute_units_consumed, 4644);
+    #[test]
+    fn test_recent_blockhash_validation() {
+        let mut seashell = Seashell::new();
+        let payer = Pubkey::new_unique();
+        let transaction = |blockhash: &solana_hash::Hash| VersionedTransaction {
+            signatures: vec![Default::default()],
+            message: VersionedMessage::Legacy(solana_message::Message::new_with_blockhash(
+                &[],
+                Some(&payer),
+                blockhash,
+            )),
+        };
 
-        let post_from = result
-            .post_execution_accounts
-            .iter()
-            .find(|(pubkey, _)| *pubkey == from)
-            .expect("Resulting account should exist")
-            .to_owned()
-            .1;
-        let post_from_balance = u64::from_le_bytes(post_from.data[64..72].try_into().unwrap());
-        assert_eq!(
-            post_from_balance, 500,
-            "Expected from token account to have 500 tokens after transfer"
-        );
+        let blockhash = seashell.latest_blockhash();
+        assert!(seashell
+            .process_transaction(&transaction(&blockhash))
+            .is_ok());
+        let unknown = solana_hash::Hash::new_unique();
+        assert!(matches!(
+            seashell.process_transaction(&transaction(&unknown)),
+            Err(SeashellError::BlockhashNotFound(hash)) if hash == unknown
+        ));
+
+        seashell.warp_to_slot(200);
+        assert!(matches!(
+            seashell.process_transaction(&transaction(&blockhash)),
+            Err(SeashellError::BlockhashExpired(hash)) if hash == blockhash
+        ));
+
+        seashell.config.check_recent_blockhash = false;
+        assert!(seashell
+            .process_transaction(&transaction(&blockhash))
+            .is_ok());
+    }
 
-        let post_to = result
-            .post_execution_accounts
-            .iter()
-            .find(|(pubkey, _)| *pubkey == to)
-            .expect("Resulting account should exist")
-            .to_owned()
-            .1;
-        let post_to_balance = u64::from_le_bytes(post_to.data[64..72].try_into().unwrap());
-        assert_eq!(
-            post_to_balance, 500,
-            "Expected to token account to have 500 tokens after transfer"
-        );
+    #[test]
+    fn test_process_instruction_signed() {
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let from = Keypair::new();
+        let to = Pubkey::new_unique();
+        seashell.airdrop(from.pubkey(), 1000);
+        seashell.set_account(to, Account::default());
+
+        let transfer = system_transfer(from.pubkey(), to, 400);
+        let Err(SeashellError::MissingSignatures(missing)) =
+            seashell.process_instruction_signed(transfer.clone(), &[&Keypair::new()])
+        else {
+            panic!("expected a missing signature");
+        };
+        assert_eq!(missing, vec![from.pubkey()]);
+        assert_eq!(seashell.account(&to).lamports, 0);
 
-        assert!(
-            result.return_data.is_empty(),
-            "Expected no return data, got: {:?}",
-            result.return_data
-        );
+        let result = seashell
+            .process_instruction_signed(transfer, &[&from])
+            .unwrap();
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(seashell.account(&to).lamports, 400);
     }
 
     #[test]
-    fn test_memoize() {
-        crate::set_log();
-        let mut seashell = Seashell::new_with_config(Config {
-            memoize: true,
-            allow_uninitialized_accounts_local: false,
-            allow_uninitialized_accounts_fetched: false,
-        });
+    fn test_fork() {
+        let base = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let (from, to) = transfer_accounts(&base);
+
+        let fork = base.fork();
+        let result = fork.process_instruction(system_transfer(from, to, 400));
+        assert!(result.error.is_none(), "{:?}", result.error);
+
+        assert_eq!(fork.account(&to).lamports, 400);
+        assert_eq!(base.account(&to).lamports, 0);
+        assert!(Arc::ptr_eq(
+            &base
+                .accounts_db
+                .programs
+                .read()
+                .find(&crate::spl::TOKEN_PROGRAM_ID)
+                .unwrap(),
+            &fork
+                .accounts_db
+                .programs
+                .read()
+                .find(&crate::spl::TOKEN_PROGRAM_ID)
+                .unwrap(),
+        ));
+    }
 
-        let from = solana_pubkey::Pubkey::new_unique();
-        let to = solana_pubkey::Pubkey::new_unique();
-        seashell.airdrop(from, 1000);
-        seashell.accounts_db.set_account_mock(to);
-        println!("Airdropped 1000 lamports to {from}");
+    #[test]
+    fn test_default_programs_are_shared() {
+        let token_program = |seashell: &Seashell| {
+            seashell
+                .accounts_db
+                .programs
+                .read()
+                .find(&crate::spl::TOKEN_PROGRAM_ID)
+                .unwrap()
+        };
+        let first = Seashell::new();
+        let mut second = Seashell::new();
+        assert!(Arc::ptr_eq(&token_program(&first), &token_program(&second)));
+
+        // Replacing a program only affects the instance it is loaded into.
+        second.use_p_token();
+        assert!(!Arc::ptr_eq(&token_program(&first), &token_program(&second)));
+        assert!(Arc::ptr_eq(&token_program(&first), &token_program(&Seashell::new())));
+    }
 
-        let mut data = Vec::with_capacity(12);
-        data.extend_from_slice(&2u32.to_le_bytes());
-        data.extend_from_slice(&500u64.to_le_bytes());
+    #[test]
+    fn test_upgradeable_program() {
+        let mut seashell = Seashell::new();
+        let authority = Pubkey::new_unique();
+        let elf = include_bytes!("spl/elfs/tokenkeg.so");
+        seashell.load_upgradeable_program_from_bytes(
+            crate::spl::TOKEN_PROGRAM_ID,
+            elf,
+            Some(authority),
+        );
 
-        let ixn = Instruction {
-            program_id: solana_sdk_ids::system_program::id(),
-            accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
-            data,
+        let program = seashell.account(&crate::spl::TOKEN_PROGRAM_ID);
+        assert!(program.executable);
+        assert_eq!(program.owner, solana_sdk_ids::bpf_loader_upgradeable::id());
+        let UpgradeableLoaderState::Program { programdata_address } =
+            bincode::deserialize(&program.data).unwrap()
+        else {
+            panic!("Expected a program account");
         };
 
-        let result = seashell.process_instruction(ixn);
+        let programdata = seashell.account(&programdata_address);
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        assert!(matches!(
+            bincode::deserialize(&programdata.data[..metadata_len]).unwrap(),
+            UpgradeableLoaderState::ProgramData { upgrade_authority_address: Some(a), .. }
+                if a == authority
+        ));
+        assert_eq!(&programdata.data[metadata_len..], elf);
+
+        // The program runs through the upgradeable loader: InitializeMint2.
+        let mint = Pubkey::new_unique();
+        seashell.set_account(
+            mint,
+            Account {
+                lamports: 1_000_000_000,
+                data: vec![0; crate::spl::MINT_ACCOUNT_SIZE],
+                owner: crate::spl::TOKEN_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+        let mut data = vec![20, 6];
+        data.extend_from_slice(authority.as_ref());
+        data.push(0);
+        let result = seashell.process_instruction(Instruction {
+            program_id: crate::spl::TOKEN_PROGRAM_ID,
+            accounts: vec![AccountMeta::new(mint, false)],
+            data,
+        });
         assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
-        assert_eq!(result.compute_units_consumed, 150);
 
-        let post_from = seashell.account(&from);
-        assert_eq!(
-            post_from.lamports(),
-            500,
-            "Expected from account to have 500 lamports after transfer"
+        // Truncated or foreign programdata is an error rather than a panic.
+        let metadata = programdata.data[..metadata_len].to_vec();
+        for data in [metadata[..8].to_vec(), metadata, programdata.data[metadata_len..].to_vec()] {
+            let loaded = seashell.accounts_db.load_upgradeable_program_from_accounts(
+                Pubkey::new_unique(),
+                program.clone().into(),
+                programdata_address,
+                AccountSharedData::from(Account { data, ..programdata.clone() }),
+                &seashell.feature_set,
+                &seashell.compute_budget,
+            );
+            assert!(loaded.is_err());
+        }
+    }
+
+    #[test]
+    fn test_upgrade_program_from_buffer() {
+        let mut seashell = Seashell::new();
+        seashell.config.memoize = true;
+        let authority = Pubkey::new_unique();
+        seashell.load_upgradeable_program_from_bytes(
+            crate::spl::TOKEN_PROGRAM_ID,
+            include_bytes!("spl/elfs/tokenkeg.so"),
+            Some(authority),
         );
-        let post_to = seashell.account(&to);
-        assert_eq!(
-            post_to.lamports(),
-            500,
-            "Expected to account to have 500 lamports after transfer"
+        let (programdata_address, _) = Pubkey::find_program_address(
+            &[crate::spl::TOKEN_PROGRAM_ID.as_ref()],
+            &solana_sdk_ids::bpf_loader_upgradeable::id(),
+        );
+        // A program can't be upgraded in the slot it was deployed in.
+        seashell.warp_to_slot(seashell.accounts_db.sysvars.clock().slot + 1);
+
+        let (buffer, spill) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let elf = include_bytes!("spl/elfs/ptoken.so");
+        seashell.set_program_buffer(buffer, Some(authority), elf);
+        seashell.airdrop(authority, 1_000_000_000);
+        seashell.airdrop(spill, 1);
+        let result = seashell.process_instruction(Instruction {
+            program_id: solana_sdk_ids::bpf_loader_upgradeable::id(),
+            accounts: vec![
+                AccountMeta::new(programdata_address, false),
+                AccountMeta::new(crate::spl::TOKEN_PROGRAM_ID, false),
+                AccountMeta::new(buffer, false),
+                AccountMeta::new(spill, false),
+                AccountMeta::new_readonly(solana_sdk_ids::sysvar::rent::id(), false),
+                AccountMeta::new_readonly(solana_sdk_ids::sysvar::clock::id(), false),
+                AccountMeta::new_readonly(authority, true),
+            ],
+            // UpgradeableLoaderInstruction::Upgrade
+            data: 3u32.to_le_bytes().to_vec(),
+        });
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        let programdata = seashell.account(&programdata_address);
+        assert_eq!(&programdata.data[metadata_len..metadata_len + elf.len()], elf);
+
+        // Transfers now run through p-token, at its compute cost.
+        seashell.warp_to_slot(seashell.accounts_db.sysvars.clock().slot + 1);
+        let (from, to, from_authority) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = Pubkey::new_unique();
+        seashell.set_mint(mint, 0, None, 1000);
+        seashell.set_token_account(from, mint, from_authority, 1000);
+        seashell.set_token_account(to, mint, Pubkey::new_unique(), 0);
+        seashell.airdrop(from_authority, 1000);
+        let mut data = vec![3];
+        data.extend_from_slice(&500u64.to_le_bytes());
+        let result = seashell.process_instruction(Instruction {
+            program_id: crate::spl::TOKEN_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(from, false),
+                AccountMeta::new(to, false),
+                AccountMeta::new_readonly(from_authority, true),
+            ],
+            data,
+        });
+        assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+        assert_eq!(result.compute_units_consumed, 82);
+        seashell.assert_token_balance(&to, 500);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_precompile_reads_other_instruction_data() {
+        use ed25519_dalek::Signer;
+        let seashell = Seashell::new();
+        let privkey = ed25519_dalek::Keypair::generate(&mut rand::thread_rng());
+        let message = b"signed elsewhere in the transaction";
+        let signed = solana_ed25519_program::new_ed25519_instruction_with_signature(
+            message,
+            &privkey.sign(message).to_bytes(),
+            &privkey.public.to_bytes(),
         );
+
+        // Offsets into `signed`, which lays out the public key, signature and message after a
+        // 16-byte header.
+        let mut data = vec![1, 0];
+        for offset in [48u16, 1, 16, 1, 112, message.len() as u16, 1] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        let referencing = Instruction {
+            program_id: solana_sdk_ids::ed25519_program::id(),
+            accounts: vec![],
+            data,
+        };
+
+        let result = seashell.process_instruction_chain(vec![referencing, signed]);
+        assert!(result.error.is_none(), "{:?}", result.error);
     }
 
     #[test]
@@ -710,6 +2760,27 @@ mod tests {
         assert!(reader.contains_key(&associated_token));
     }
 
+    #[test]
+    fn test_load_missing_program() {
+        let mut seashell = Seashell::new();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let directories = vec![temp_dir.path().join("missing"), temp_dir.path().to_path_buf()];
+
+        let err = seashell
+            .load_program_from_directories("nope", Pubkey::new_unique(), &directories)
+            .unwrap_err();
+        match err {
+            SeashellError::ProgramNotFound { program_name, searched } => {
+                assert_eq!(program_name, "nope");
+                assert_eq!(
+                    searched,
+                    vec![directories[0].join("nope.so"), directories[1].join("nope.so")]
+                );
+            }
+            err => panic!("Expected ProgramNotFound, got: {err:?}"),
+        }
+    }
+
     #[test]
     fn test_scenario_loading() {
         use std::fs;
@@ -724,6 +2795,7 @@ mod tests {
             memoize: false,
             allow_uninitialized_accounts_local: false,
             allow_uninitialized_accounts_fetched: false,
+            ..Config::default()
         });
 
         let pubkey1 = Pubkey::from_str_const("B91piBSfCBRs5rUxCMRdJEGv7tNEnFxweWcdQJHJoFpi");
@@ -753,6 +2825,274 @@ mod tests {
         unsafe { std::env::remove_var("RPC_URL") }
     }
 
+    #[test]
+    fn test_scenario_metadata_roundtrip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("metadata.json.gz");
+        let pubkey = Pubkey::new_unique();
+        let known = Pubkey::new_unique();
+
+        let mut scenario = Scenario::from_file(path.clone(), false);
+        scenario.insert(pubkey, AccountSharedData::new(7, 0, &Pubkey::default()));
+        scenario.add_known_accounts([pubkey, known]);
+        drop(scenario);
+
+        let scenario = Scenario::from_file(path, false);
+        assert_eq!(scenario.get(&pubkey).unwrap().lamports(), 7);
+        assert_eq!(scenario.metadata().accounts, [pubkey, known].into_iter().collect());
+    }
+
+    #[test]
+    fn test_embedded_scenario_roundtrip() {
+        let embedded = crate::embed_scenario!("embedded");
+        let pubkey = Pubkey::new_from_array([7; 32]);
+        let account = Account {
+            lamports: 7,
+            data: vec![1, 2],
+            owner: solana_sdk_ids::system_program::id(),
+            ..Account::default()
+        };
+
+        // The embedded fixture holds what saving the same scenario to a file writes.
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("embedded.json.gz");
+        let mut saved = Scenario::from_file(path.clone(), false);
+        saved.insert(pubkey, account.clone().into());
+        saved.add_known_accounts([pubkey]);
+        drop(saved);
+        let saved = std::fs::read(&path).unwrap();
+        for bytes in [embedded, saved.as_slice()] {
+            let scenario = Scenario::from_bytes(bytes, false);
+            assert_eq!(Account::from(scenario.get(&pubkey).unwrap()), account);
+            assert_eq!(scenario.metadata().accounts, BTreeSet::from([pubkey]));
+            assert!(scenario.path().is_none());
+        }
+
+        let mut seashell = Seashell::new();
+        seashell.load_scenario_from_bytes(embedded);
+        assert_eq!(seashell.account(&pubkey).data, account.data);
+    }
+
+    #[test]
+    fn test_scenario_bootstrap() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bootstrap.json.gz");
+        let admin = Pubkey::new_unique();
+        let config = Pubkey::new_unique();
+        let system_program = solana_sdk_ids::system_program::id();
+
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.accounts_db.scenario = Scenario::from_file(path.clone(), false);
+        seashell
+            .accounts_db
+            .scenario
+            .insert(admin, AccountSharedData::new(1000, 0, &system_program));
+        seashell.set_account(config, Account::default());
+        let result = seashell.add_bootstrap_instruction(system_transfer(admin, config, 400));
+        assert!(result.error.is_none(), "{:?}", result.error);
+        drop(seashell);
+
+        // A fresh instance replays the bootstrap on load; the scenario itself is unchanged.
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.set_account(config, Account::default());
+        seashell.load_scenario_from_bytes(&std::fs::read(&path).unwrap());
+        assert_eq!(seashell.account(&config).lamports, 400);
+        assert_eq!(seashell.account(&admin).lamports, 600);
+        let recorded = Scenario::from_bytes(&std::fs::read(&path).unwrap(), false);
+        assert_eq!(recorded.get(&admin).unwrap().lamports(), 1000);
+    }
+
+    #[test]
+    fn test_scenario_patches() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("patches.json.gz");
+        let token_account = Pubkey::new_unique();
+        let market = Pubkey::new_unique();
+        let seat = Pubkey::new_unique();
+
+        let mut scenario = Scenario::from_file(path.clone(), false);
+        scenario.insert(token_account, AccountSharedData::new(1, 165, &spl::TOKEN_PROGRAM_ID));
+        scenario.insert(market, AccountSharedData::new(1, 4096, &Pubkey::new_unique()));
+        let patches: Vec<DataPatch> = serde_json::from_value(serde_json::json!([
+            { "field": "amount", "value": 1_000_000 },
+            { "field": "delegate", "value": seat.to_string() }
+        ]))
+        .unwrap();
+        scenario.add_patch(token_account, patches);
+        let layouts = Seashell::new().layouts;
+        scenario
+            .patch_account(
+                &market,
+                &[FieldPatch::Pubkey { offset: 2048, value: seat }.into()],
+                &layouts,
+            )
+            .unwrap();
+        // An unknown field fails without writing anything.
+        assert!(scenario
+            .patch_account(
+                &token_account,
+                &[
+                    FieldPatch::U8 { offset: 108, value: 1 }.into(),
+                    DataPatch::Field { field: "seats".to_string(), value: 1.into() },
+                ],
+                &layouts,
+            )
+            .is_err());
+        drop(scenario);
+
+        let mut seashell = Seashell::new();
+        seashell.load_scenario_from_bytes(&std::fs::read(&path).unwrap());
+        let decoded = seashell
+            .layouts
+            .decode(&seashell.accounts_db.account_must(&token_account))
+            .unwrap();
+        assert_eq!(decoded.get("amount"), Some("1000000"));
+        assert_eq!(decoded.get("delegate"), Some(format!("Some({seat})").as_str()));
+        assert_eq!(decoded.get("state"), Some("0"));
+        assert_eq!(&seashell.account(&market).data[2048..2080], seat.as_ref());
+    }
+
+    #[test]
+    fn test_layered_scenarios() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let base_path = temp_dir.path().join("base.json.gz");
+        let overlay_path = temp_dir.path().join("overlay.json.gz");
+        let market = Pubkey::new_unique();
+        let seat = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+
+        let mut base = Scenario::from_file(base_path.clone(), false);
+        base.insert(market, AccountSharedData::new(1, 0, &Pubkey::default()));
+        base.insert(seat, AccountSharedData::new(1, 0, &Pubkey::default()));
+        drop(base);
+        let mut overlay = Scenario::from_file(overlay_path.clone(), false);
+        overlay.insert(seat, AccountSharedData::new(2, 0, &Pubkey::default()));
+        drop(overlay);
+        let base_bytes = std::fs::read(&base_path).unwrap();
+
+        let mut seashell = Seashell::new();
+        let mut scenario = Scenario::from_file(overlay_path.clone(), false);
+        scenario.add_base(Scenario::from_file(base_path.clone(), false));
+        seashell.accounts_db.scenario = scenario;
+        seashell.apply_scenario_metadata().unwrap();
+        assert_eq!(seashell.account(&market).lamports, 1);
+        assert_eq!(seashell.account(&seat).lamports, 2);
+
+        // New accounts land in the top layer only.
+        seashell
+            .accounts_db
+            .scenario
+            .pin(vault, AccountSharedData::new(3, 0, &Pubkey::default()));
+        drop(seashell);
+        assert_eq!(std::fs::read(&base_path).unwrap(), base_bytes);
+        let overlay = Scenario::from_file(overlay_path, false);
+        assert_eq!(overlay.get(&vault).unwrap().lamports(), 3);
+        assert!(overlay.get(&market).is_none());
+    }
+
+    #[test]
+    fn test_prefetched_accounts_skip_rpc() {
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (rpc_url, requests) = serve_rpc(HashMap::from([
+            (from, Account { lamports: 1000, ..Account::default() }),
+            (to, Account { lamports: 1, ..Account::default() }),
+        ]));
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.accounts_db.scenario = Scenario::rpc_only(rpc_url, false);
+        seashell.accounts_db.scenario.add_known_accounts([from, to]);
+
+        let mut progress = Vec::new();
+        seashell
+            .accounts_db
+            .scenario
+            .prefetch(|batch| progress.push(batch));
+        assert_eq!(progress, [PrefetchProgress { fetched: 2, total: 2 }]);
+        assert_eq!(*requests.lock(), ["getSlot", "getMultipleAccounts"]);
+        assert_eq!(seashell.accounts_db.scenario.slot(), Some(RPC_SLOT));
+
+        // Both accounts are in the scenario now, so executing against them asks the RPC nothing.
+        let result = seashell.process_instruction(system_transfer(from, to, 400));
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(seashell.account(&to).lamports, 401);
+        assert_eq!(requests.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_recording_replays_local_accounts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("recorded.json.gz");
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let transfer = |lamports| system_transfer(from, to, lamports);
+
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.accounts_db.scenario = Scenario::from_file(path.clone(), false);
+        seashell.start_recording();
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        seashell.set_account(to, Account::default());
+        assert!(seashell.process_instruction(transfer(400)).error.is_none());
+        assert!(seashell.process_instruction(transfer(100)).error.is_none());
+        drop(seashell);
+
+        // The accounts come back in the state the first transfer saw, and stay writable.
+        let mut replay = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        replay.accounts_db.scenario = Scenario::from_file(path, false);
+        replay.apply_scenario_metadata().unwrap();
+        assert_eq!(replay.account(&from).lamports, 1000);
+        assert!(replay.process_instruction(transfer(400)).error.is_none());
+        assert_eq!(replay.account(&from).lamports, 600);
+        assert_eq!(replay.account(&to).lamports, 400);
+    }
+
+    #[test]
+    fn test_ensure_runs_setup_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("ensure.json.gz");
+        let config = Pubkey::new_unique();
+        let setup = |seashell: &mut Seashell| {
+            seashell.set_account(config, Account { lamports: 7, ..Account::default() });
+        };
+
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.accounts_db.scenario = Scenario::from_file(path.clone(), false);
+        assert!(seashell.ensure(&("config", 7), setup).unwrap());
+        assert!(!seashell.ensure(&("config", 7), setup).unwrap());
+        drop(seashell);
+
+        let mut seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        seashell.load_scenario_from_bytes(&std::fs::read(&path).unwrap());
+        assert!(!seashell.ensure(&("config", 7), setup).unwrap());
+        assert_eq!(seashell.account(&config).lamports, 7);
+        assert!(seashell.ensure(&("config", 8), setup).unwrap());
+    }
+
+    #[test]
+    fn test_run_against_snapshots() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        for (label, lamports) in [("2024-01-01", 1000), ("2024-02-01", 1000), ("2024-03-01", 100)] {
+            let path = temp_dir.path().join(format!("{label}.json.gz"));
+            let mut scenario = Scenario::from_file(path, false);
+            let system_program = solana_sdk_ids::system_program::id();
+            scenario.insert(from, AccountSharedData::new(lamports, 0, &system_program));
+            scenario.insert(to, AccountSharedData::new(0, 0, &system_program));
+        }
+
+        let transfer = system_transfer(from, to, 500);
+
+        let series = Seashell::new()
+            .run_against_snapshots(temp_dir.path(), &transfer)
+            .unwrap();
+        let labels: Vec<&str> = series
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["2024-01-01", "2024-02-01", "2024-03-01"]);
+        assert_eq!(series.first_regression().unwrap().label, "2024-03-01");
+    }
+
     #[test]
     fn test_account_lookup_order() {
         let mut seashell = Seashell::new();
@@ -782,6 +3122,69 @@ mod tests {
         assert_eq!(seashell.account(&pubkey).lamports(), 2000);
     }
 
+    #[test]
+    fn test_strict_accounts() {
+        let seashell =
+            Seashell::new_with_config(Config { strict_accounts: true, ..Config::default() });
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        seashell.airdrop(from, 1000);
+        let ixn = system_transfer(from, to, 400);
+        let result = seashell.process_instruction(ixn.clone());
+        assert_eq!(result.error, Some(InstructionProcessingError::AccountNotFound(to)));
+        assert!(matches!(
+            seashell.try_process_instruction(ixn.clone()),
+            Err(SeashellError::AccountNotFound(pubkey)) if pubkey == to
+        ));
+        assert!(matches!(
+            seashell.try_account(&to),
+            Err(SeashellError::AccountNotFound(pubkey)) if pubkey == to
+        ));
+        assert_eq!(seashell.try_account(&from).unwrap().lamports, 1000);
+        assert!(seashell
+            .accounts_db
+            .try_accounts_for_instruction(false, &ixn)
+            .is_err());
+        assert_eq!(
+            seashell
+                .accounts_db
+                .try_accounts_for_instruction(true, &ixn)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_address_overrides() {
+        let seashell = Seashell::new_with_config(Config {
+            memoize: true,
+            allow_uninitialized_accounts_local: true,
+            ..Config::default()
+        });
+        let program_id = Pubkey::new_unique();
+        let local = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        seashell.set_account(
+            local,
+            Account {
+                lamports: 1000,
+                owner: solana_sdk_ids::system_program::id(),
+                ..Account::default()
+            },
+        );
+        let config = seashell.override_pda(&[b"config"], &program_id, local);
+        assert_eq!(seashell.account(&config).lamports, 1000);
+
+        let result = seashell.process_instruction(system_transfer(config, to, 400));
+        assert!(result.error.is_none());
+        assert_eq!(seashell.account(&local).lamports, 600);
+        assert_eq!(seashell.fork().account(&config).lamports, 600);
+
+        assert_eq!(seashell.clear_address_override(&config), Some(local));
+        assert!(seashell.try_account(&config).is_err());
+    }
+
     #[test]
     #[should_panic(expected = "Account not found")]
     fn test_missing_account_without_rpc() {
@@ -813,9 +3216,9 @@ mod tests {
         let from_authority = solana_pubkey::Pubkey::new_unique();
         let mint = solana_pubkey::Pubkey::new_unique();
 
-        create_mint_account(&mut seashell, mint, 1000);
-        create_token_account(&mut seashell, from, mint, from_authority, 1000);
-        create_token_account(&mut seashell, to, mint, Pubkey::new_unique(), 0);
+        seashell.set_mint(mint, 0, None, 1000);
+        seashell.set_token_account(from, mint, from_authority, 1000);
+        seashell.set_token_account(to, mint, Pubkey::new_unique(), 0);
         seashell.airdrop(from_authority, 1000);
 
         let mut data = [0; 9];
@@ -870,4 +3273,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_instruction_timeout_stops_loop_without_syscalls() {
+        use std::sync::atomic::AtomicU64;
+
+        use solana_program_runtime::loaded_programs::{
+            ProgramCacheEntryOwner, ProgramCacheEntryType,
+        };
+        use solana_program_runtime::solana_sbpf::assembler::assemble;
+
+        let mut seashell = Seashell::new_with_config(Config {
+            instruction_timeout: Some(Duration::from_millis(100)),
+            ..Config::default()
+        });
+        // Only the deadline can stop the loop.
+        seashell.compute_budget.compute_unit_limit = u64::MAX;
+
+        let environment = crate::agave::program_runtime_environment(
+            &seashell.feature_set,
+            &seashell.compute_budget,
+        );
+        let executable = assemble("entrypoint:\n    ja -1", environment).unwrap();
+        let program_id = Pubkey::new_unique();
+        let entry = ProgramCacheEntry {
+            program: ProgramCacheEntryType::Loaded(executable),
+            account_owner: ProgramCacheEntryOwner::LoaderV2,
+            account_size: 0,
+            deployment_slot: 0,
+            effective_slot: 0,
+            tx_usage_counter: Arc::default(),
+            latest_access_slot: AtomicU64::new(0),
+        };
+        seashell
+            .accounts_db
+            .programs
+            .get_mut()
+            .replenish(program_id, Arc::new(entry));
+        let mut program_account = AccountSharedData::new(1, 0, &solana_sdk_ids::bpf_loader::id());
+        program_account.set_executable(true);
+        seashell
+            .accounts_db
+            .set_account(program_id, program_account);
+
+        let start = Instant::now();
+        let result = seashell.process_instruction(Instruction {
+            program_id,
+            accounts: vec![],
+            data: vec![],
+        });
+        assert!(
+            matches!(result.error, Some(InstructionProcessingError::Timeout(_))),
+            "Expected a timeout, got: {:?}",
+            result.error
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
 }
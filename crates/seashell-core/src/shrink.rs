@@ -0,0 +1,183 @@
+//! Finds the accounts an instruction passes without needing them, to trim account lists that
+//! waste transaction space.
+//!
+//! Programs don't report which accounts they read, so each candidate is checked by running the
+//! instruction again: an account is removable when scrambling its lamports, owner and data
+//! changes nothing about the execution, and can be readonly when the instruction runs the same
+//! with its writable flag cleared.
+//!
+//! ```ignore
+//! let shrink = seashell.suggest_account_shrink(&ixn)?;
+//! println!("{shrink}");
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+
+use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+use crate::error::SeashellError;
+use crate::execute::ExecutionOutput;
+use crate::seashell::{Seashell, WorkingSet};
+
+/// Accounts an instruction could do without, in the order it passes them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountShrink {
+    /// Accounts whose state didn't affect the execution. A program may still expect them at
+    /// their position, so dropping one can shift the accounts after it.
+    pub removable: Vec<Pubkey>,
+    /// Writable accounts, not among `removable`, that the instruction never wrote and still runs
+    /// the same with as readonly.
+    pub readonly: Vec<Pubkey>,
+}
+
+impl AccountShrink {
+    pub fn is_empty(&self) -> bool {
+        self.removable.is_empty() && self.readonly.is_empty()
+    }
+}
+
+impl fmt::Display for AccountShrink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Every account is needed as passed");
+        }
+        for pubkey in &self.removable {
+            writeln!(f, "{pubkey}: unused, can be removed")?;
+        }
+        for pubkey in &self.readonly {
+            writeln!(f, "{pubkey}: never written, can be readonly")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `actual` ran like `expected`, apart from the post state of `ignored`.
+fn same_outcome(expected: &ExecutionOutput, actual: &ExecutionOutput, ignored: &Pubkey) -> bool {
+    expected.result == actual.result
+        && expected.compute_units_consumed == actual.compute_units_consumed
+        && expected.return_data == actual.return_data
+        && expected.logs == actual.logs
+        && expected.post_accounts.len() == actual.post_accounts.len()
+        && expected
+            .post_accounts
+            .iter()
+            .zip(&actual.post_accounts)
+            .all(|(expected, actual)| expected.0 == *ignored || expected == actual)
+}
+
+/// A copy of `account` with every field a program could look at changed.
+fn scrambled(account: &AccountSharedData) -> AccountSharedData {
+    let mut scrambled = account.clone();
+    scrambled.set_lamports(account.lamports().wrapping_add(1));
+    scrambled.set_owner(Pubkey::new_unique());
+    let data: Vec<u8> = account.data().iter().map(|byte| !byte).collect();
+    scrambled.set_data_from_slice(&data);
+    scrambled
+}
+
+impl Seashell {
+    /// Runs `ixn`, which must succeed, then reruns it once per account it neither wrote nor
+    /// passed on to a CPI, and once per writable account it didn't write, to find what it doesn't
+    /// need. Nothing is committed.
+    pub fn suggest_account_shrink(
+        &self,
+        ixn: &Instruction,
+    ) -> Result<AccountShrink, SeashellError> {
+        let run = |ixn: Instruction, overlay: &WorkingSet| {
+            self.execute(ixn, overlay, self.compute_budget)
+                .map_err(|e| SeashellError::Custom(format!("Instruction failed to run: {e:?}")))
+        };
+        let baseline = run(ixn.clone(), &WorkingSet::default())?;
+        if let Err(e) = &baseline.result {
+            return Err(SeashellError::Custom(format!("Instruction failed: {e:?}")));
+        }
+
+        let written: HashSet<Pubkey> = baseline
+            .pre_accounts
+            .iter()
+            .zip(&baseline.post_accounts)
+            .filter(|(pre, post)| pre != post)
+            .map(|(pre, _)| pre.0)
+            .collect();
+        let mut used: HashSet<Pubkey> = written.iter().copied().collect();
+        used.insert(ixn.program_id);
+        for cpi in baseline
+            .instruction_trace
+            .iter()
+            .filter(|traced| traced.is_cpi())
+        {
+            used.insert(cpi.program_id);
+            used.extend(cpi.accounts.iter().map(|meta| meta.pubkey));
+        }
+
+        let mut shrink = AccountShrink::default();
+        let mut checked = HashSet::new();
+        for meta in &ixn.accounts {
+            let pubkey = meta.pubkey;
+            if !checked.insert(pubkey) {
+                continue;
+            }
+            let removable = !used.contains(&pubkey)
+                && baseline
+                    .pre_accounts
+                    .iter()
+                    .find(|(account_pubkey, _)| *account_pubkey == pubkey)
+                    .is_some_and(|(_, account)| {
+                        let overlay = WorkingSet {
+                            accounts: [(pubkey, scrambled(account))].into_iter().collect(),
+                            ..WorkingSet::default()
+                        };
+                        run(ixn.clone(), &overlay)
+                            .is_ok_and(|output| same_outcome(&baseline, &output, &pubkey))
+                    });
+            if removable {
+                shrink.removable.push(pubkey);
+                continue;
+            }
+
+            if meta.is_writable && !written.contains(&pubkey) {
+                let mut readonly = ixn.clone();
+                for meta in &mut readonly.accounts {
+                    if meta.pubkey == pubkey {
+                        meta.is_writable = false;
+                    }
+                }
+                if run(readonly, &WorkingSet::default())
+                    .is_ok_and(|output| same_outcome(&baseline, &output, &pubkey))
+                {
+                    shrink.readonly.push(pubkey);
+                }
+            }
+        }
+        Ok(shrink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::test_utils::{system_transfer, transfer_accounts};
+
+    #[test]
+    fn test_suggest_account_shrink() {
+        let seashell = Seashell::new();
+        let (from, to) = transfer_accounts(&seashell);
+        let extra = Pubkey::new_unique();
+        seashell
+            .set_account(extra, Account { lamports: 5, data: vec![7; 8], ..Account::default() });
+
+        let mut transfer = system_transfer(from, to, 400);
+        transfer.accounts.push(AccountMeta::new(extra, false));
+        let shrink = seashell.suggest_account_shrink(&transfer).unwrap();
+        assert_eq!(shrink, AccountShrink { removable: vec![extra], readonly: Vec::new() });
+        assert_eq!(shrink.to_string(), format!("{extra}: unused, can be removed\n"));
+        // Nothing was committed.
+        assert_eq!(seashell.account(&from).lamports, 1000);
+    }
+}
@@ -0,0 +1,93 @@
+//! Signature checks for the `_signed` processing entry points.
+//!
+//! `process_instruction` trusts `AccountMeta::is_signer`, so a test can sign as any account.
+//! [`crate::Seashell::process_instruction_signed`] only runs an instruction when a keypair is
+//! given for every signer it declares, and [`crate::Seashell::process_transaction_signed`]
+//! verifies a transaction's signatures against its message like the runtime does.
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_transaction::versioned::VersionedTransaction;
+
+/// The accounts `ixns` mark as signers that aren't in `signers`, in order of first use.
+pub fn missing_signers(ixns: &[Instruction], signers: &[Pubkey]) -> Vec<Pubkey> {
+    let mut missing = Vec::new();
+    for meta in ixns.iter().flat_map(|ixn| &ixn.accounts) {
+        if meta.is_signer && !signers.contains(&meta.pubkey) && !missing.contains(&meta.pubkey) {
+            missing.push(meta.pubkey);
+        }
+    }
+    missing
+}
+
+/// The required signers of `transaction` whose signature is missing or doesn't verify against
+/// the message.
+pub fn unverified_signers(transaction: &VersionedTransaction) -> Vec<Pubkey> {
+    let message_bytes = transaction.message.serialize();
+    let required = usize::from(transaction.message.header().num_required_signatures);
+    transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .take(required)
+        .enumerate()
+        .filter(|(index, pubkey)| {
+            !transaction
+                .signatures
+                .get(*index)
+                .is_some_and(|signature| signature.verify(pubkey.as_ref(), &message_bytes))
+        })
+        .map(|(_, pubkey)| *pubkey)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_hash::Hash;
+    use solana_instruction::AccountMeta;
+    use solana_keypair::Keypair;
+    use solana_message::{Message, VersionedMessage};
+    use solana_signature::Signature;
+    use solana_signer::Signer;
+
+    use super::*;
+
+    #[test]
+    fn test_unverified_signers() {
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+            data: vec![],
+        };
+        assert_eq!(
+            missing_signers(std::slice::from_ref(&ixn), &[payer.pubkey()]),
+            vec![authority.pubkey()]
+        );
+        assert!(missing_signers(&[ixn.clone()], &[authority.pubkey()]).is_empty());
+
+        let message = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &[ixn],
+            Some(&payer.pubkey()),
+            &Hash::new_unique(),
+        ));
+        let message_bytes = message.serialize();
+        let mut transaction = VersionedTransaction {
+            signatures: vec![
+                payer.sign_message(&message_bytes),
+                authority.sign_message(&message_bytes),
+            ],
+            message,
+        };
+        assert!(unverified_signers(&transaction).is_empty());
+
+        transaction.signatures[1] = Signature::default();
+        assert_eq!(unverified_signers(&transaction), vec![authority.pubkey()]);
+        transaction.signatures.truncate(1);
+        assert_eq!(unverified_signers(&transaction), vec![authority.pubkey()]);
+    }
+}
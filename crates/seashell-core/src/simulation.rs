@@ -0,0 +1,231 @@
+//! Transaction results in the shape of the `simulateTransaction` RPC response, so client code
+//! written against RPC simulation can be unit tested against seashell:
+//!
+//! ```ignore
+//! let response = seashell.simulate_transaction_rpc(&tx, &[vault]);
+//! assert_eq!(my_client::parse_simulation(&response)?, expected);
+//! println!("{}", serde_json::to_string(&response)?);
+//! ```
+//!
+//! `err`, `logs`, `unitsConsumed`, `returnData` and `accounts` follow the runtime: instruction
+//! failures carry the index of the failing instruction, logs are cut off at the transaction's
+//! 10 KB limit with `Log truncated`, and return data is base64 with the program that set it.
+//! Seashell charges no fees and keeps no balances per transaction, so `fee`, the balances and
+//! `innerInstructions` are left out, as the RPC does when they aren't requested.
+
+use solana_instruction::error::InstructionError;
+use solana_pubkey::Pubkey;
+use solana_rpc_client_api::response::RpcSimulateTransactionResult;
+use solana_svm_log_collector::LogCollector;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_context::TransactionReturnData;
+use solana_transaction_error::TransactionError;
+
+use crate::cli_account::to_keyed_account;
+use crate::error::SeashellError;
+use crate::seashell::{InstructionChainResult, InstructionProcessingError, Seashell};
+
+/// The `TransactionError` the runtime fails a transaction with where Seashell rejected it before
+/// running anything.
+pub(crate) fn rejection_error(error: &SeashellError) -> TransactionError {
+    match error {
+        SeashellError::BlockhashNotFound(_) | SeashellError::BlockhashExpired(_) => {
+            TransactionError::BlockhashNotFound
+        }
+        SeashellError::MissingSignatures(_) => TransactionError::SignatureFailure,
+        SeashellError::AccountNotFound(_) => TransactionError::AccountNotFound,
+        _ => TransactionError::SanitizeFailure,
+    }
+}
+
+/// The `TransactionError` of instruction `index` failing with `error`. Failures the runtime has
+/// no error for, like timeouts, are reported as `ProgramFailedToComplete`.
+pub(crate) fn instruction_error(
+    index: usize,
+    error: &InstructionProcessingError,
+) -> TransactionError {
+    let index = index as u8;
+    match error {
+        InstructionProcessingError::InstructionError(error) => {
+            TransactionError::InstructionError(index, error.clone())
+        }
+        InstructionProcessingError::AccountNotFound(_) => TransactionError::AccountNotFound,
+        InstructionProcessingError::ProgramError | InstructionProcessingError::Timeout(_) => {
+            TransactionError::InstructionError(index, InstructionError::ProgramFailedToComplete)
+        }
+    }
+}
+
+impl InstructionChainResult {
+    /// The error the transaction failed with, as the runtime reports it.
+    pub fn transaction_error(&self) -> Option<TransactionError> {
+        self.error
+            .as_ref()
+            .map(|(index, error)| instruction_error(*index, error))
+    }
+
+    /// The logs of every instruction that ran, cut off where the runtime's per-transaction log
+    /// limit would cut them off.
+    pub fn transaction_logs(&self) -> Vec<String> {
+        let mut log_collector = LogCollector::default();
+        for log in self.results.iter().flat_map(|result| &result.logs) {
+            log_collector.log(log);
+        }
+        log_collector.into_messages()
+    }
+
+    pub fn units_consumed(&self) -> u64 {
+        self.results
+            .iter()
+            .map(|result| result.compute_units_consumed)
+            .sum()
+    }
+
+    /// The return data the transaction ends with: that of the last instruction that set any.
+    pub fn transaction_return_data(&self) -> Option<TransactionReturnData> {
+        self.results.iter().rev().find_map(|result| {
+            Some(TransactionReturnData {
+                program_id: result.return_data_program_id?,
+                data: result.return_data.clone(),
+            })
+        })
+    }
+
+    /// The result as `simulateTransaction` returns it, without `accounts`; see
+    /// [`Seashell::simulate_transaction_rpc`] for those.
+    pub fn to_rpc_simulation(&self) -> RpcSimulateTransactionResult {
+        RpcSimulateTransactionResult {
+            err: self.transaction_error().map(Into::into),
+            logs: Some(self.transaction_logs()),
+            accounts: None,
+            units_consumed: Some(self.units_consumed()),
+            loaded_accounts_data_size: Some(self.loaded_data_size.total() as u32),
+            return_data: self.transaction_return_data().map(Into::into),
+            inner_instructions: None,
+            replacement_blockhash: None,
+            fee: None,
+            pre_balances: None,
+            post_balances: None,
+            pre_token_balances: None,
+            post_token_balances: None,
+            loaded_addresses: None,
+        }
+    }
+}
+
+/// The `simulateTransaction` result of a transaction rejected with `err` before running.
+pub fn rejected_simulation(err: TransactionError) -> RpcSimulateTransactionResult {
+    RpcSimulateTransactionResult {
+        err: Some(err.into()),
+        logs: Some(Vec::new()),
+        accounts: None,
+        units_consumed: Some(0),
+        loaded_accounts_data_size: Some(0),
+        return_data: None,
+        inner_instructions: None,
+        replacement_blockhash: None,
+        fee: None,
+        pre_balances: None,
+        post_balances: None,
+        pre_token_balances: None,
+        post_token_balances: None,
+        loaded_addresses: None,
+    }
+}
+
+impl Seashell {
+    /// Simulates `tx` on a fork as `simulateTransaction` does with `sigVerify` off, with the
+    /// post-execution state of `accounts` in base64. Like the RPC, accounts the transaction
+    /// doesn't load are `null`, and so is every account if the transaction failed. Nothing is
+    /// committed.
+    pub fn simulate_transaction_rpc(
+        &self,
+        tx: &VersionedTransaction,
+        accounts: &[Pubkey],
+    ) -> RpcSimulateTransactionResult {
+        let mut fork = self.fork();
+        fork.config.memoize = false;
+        let (mut simulation, post_accounts) = match fork.process_transaction(tx) {
+            Ok(result) => (result.to_rpc_simulation(), result.post_execution_accounts),
+            Err(error) => (rejected_simulation(rejection_error(&error)), Vec::new()),
+        };
+        simulation.accounts = (!accounts.is_empty()).then(|| {
+            accounts
+                .iter()
+                .map(|pubkey| {
+                    let (_, account) = post_accounts.iter().find(|(key, _)| key == pubkey)?;
+                    Some(to_keyed_account(pubkey, account).account)
+                })
+                .collect()
+        });
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::Account;
+    use solana_hash::Hash;
+    use solana_instruction::Instruction;
+    use solana_keypair::Keypair;
+    use solana_message::Message;
+    use solana_signer::Signer;
+    use solana_transaction::Transaction;
+
+    use super::*;
+    use crate::test_utils::system_transfer;
+
+    #[test]
+    fn test_simulate_transaction_rpc() {
+        let seashell = Seashell::new();
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        seashell.set_account(payer.pubkey(), Account { lamports: 1000, ..Account::default() });
+        seashell.set_account(to, Account { lamports: 1, ..Account::default() });
+
+        let transfer = |lamports| system_transfer(payer.pubkey(), to, lamports);
+        let tx = |ixns: &[Instruction], blockhash| {
+            VersionedTransaction::from(Transaction::new(
+                &[&payer],
+                Message::new(ixns, Some(&payer.pubkey())),
+                blockhash,
+            ))
+        };
+        let system_program = solana_sdk_ids::system_program::id();
+
+        let simulation = seashell
+            .simulate_transaction_rpc(&tx(&[transfer(400)], seashell.latest_blockhash()), &[to]);
+        let json = serde_json::to_value(&simulation).unwrap();
+        assert_eq!(json["err"], serde_json::Value::Null);
+        assert_eq!(
+            json["logs"],
+            serde_json::json!([
+                format!("Program {system_program} invoke [1]"),
+                format!("Program {system_program} success")
+            ])
+        );
+        assert_eq!(json["unitsConsumed"], 150);
+        assert_eq!(json["returnData"], serde_json::Value::Null);
+        assert_eq!(simulation.accounts.unwrap()[0].as_ref().unwrap().lamports, 401);
+        assert_eq!(seashell.account(&to).lamports, 1);
+
+        let simulation = seashell.simulate_transaction_rpc(
+            &tx(&[transfer(1), transfer(4000)], seashell.latest_blockhash()),
+            &[to],
+        );
+        let json = serde_json::to_value(&simulation).unwrap();
+        // SystemError::ResultWithNegativeLamports
+        assert_eq!(json["err"], serde_json::json!({"InstructionError": [1, {"Custom": 1}]}));
+        assert_eq!(json["unitsConsumed"], 300);
+        assert_eq!(json["accounts"], serde_json::json!([null]));
+        assert_eq!(
+            json["logs"].as_array().unwrap().last().unwrap(),
+            &format!("Program {system_program} failed: custom program error: 0x1")
+        );
+
+        let simulation =
+            seashell.simulate_transaction_rpc(&tx(&[transfer(1)], Hash::new_unique()), &[to]);
+        assert_eq!(simulation.err, Some(TransactionError::BlockhashNotFound.into()));
+        assert_eq!(simulation.accounts, Some(vec![None]));
+    }
+}
@@ -0,0 +1,267 @@
+//! Results in a stable, serializable shape for snapshot tests, e.g. with `insta`:
+//!
+//! ```ignore
+//! let result = seashell.process_instruction(ixn);
+//! insta::assert_yaml_snapshot!(seashell::snapshot::redact(&result));
+//!
+//! let redactions = Redactions::new().label(market, "market").max_bytes(32);
+//! insta::assert_yaml_snapshot!(redactions.redact_chain(&chain_result));
+//! ```
+//!
+//! Addresses change between runs when tests use `Pubkey::new_unique` or fresh keypairs, so every
+//! address other than builtin programs, sysvars and the token programs is replaced by its label,
+//! or by `pubkey#N` in the order addresses first appear; the same address gets the same name
+//! throughout one redaction, logs included. Timings are left out, accounts are keyed by name in
+//! sorted maps, and data is hex, cut off after `max_bytes` if set.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use agave_feature_set::FeatureSet;
+use serde::Serialize;
+use solana_account::{Account, ReadableAccount};
+use solana_pubkey::Pubkey;
+
+use crate::reserved_keys::ReservedAccountKeys;
+use crate::seashell::{InstructionChainResult, InstructionProcessingResult};
+use crate::spl::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::trace::TracedInstruction;
+
+/// An instruction of the trace, with account flags spelled out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactedInstruction {
+    pub program_id: String,
+    /// Each account's name, followed by ` (signer)`, ` (writable)` or ` (signer, writable)`.
+    pub accounts: Vec<String>,
+    pub data: String,
+    pub stack_height: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactedAccount {
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactedResult {
+    pub error: Option<String>,
+    pub compute_units_consumed: u64,
+    pub return_data: String,
+    pub logs: Vec<String>,
+    pub instruction_trace: Vec<RedactedInstruction>,
+    /// The names of the accounts the instruction changed.
+    pub changed_accounts: Vec<String>,
+    pub post_execution_accounts: BTreeMap<String, RedactedAccount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactedChainResult {
+    /// Index and error of the instruction that aborted the chain.
+    pub error: Option<(usize, String)>,
+    pub results: Vec<RedactedResult>,
+    pub post_execution_accounts: BTreeMap<String, RedactedAccount>,
+}
+
+/// How results are redacted: names for addresses, and how much data to keep.
+#[derive(Debug, Clone, Default)]
+pub struct Redactions {
+    labels: HashMap<Pubkey, String>,
+    max_bytes: Option<usize>,
+}
+
+impl Redactions {
+    pub fn new() -> Self {
+        Redactions::default()
+    }
+
+    /// Names `pubkey` `label` instead of `pubkey#N`.
+    pub fn label(mut self, pubkey: Pubkey, label: impl Into<String>) -> Self {
+        self.labels.insert(pubkey, label.into());
+        self
+    }
+
+    /// Keeps only the first `max_bytes` bytes of data, noting how many were cut.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn redact(&self, result: &InstructionProcessingResult) -> RedactedResult {
+        Redactor::new(self).result(result)
+    }
+
+    /// Redacts every result of the chain with the same names.
+    pub fn redact_chain(&self, result: &InstructionChainResult) -> RedactedChainResult {
+        let mut redactor = Redactor::new(self);
+        let results: Vec<RedactedResult> = result
+            .results
+            .iter()
+            .map(|result| redactor.result(result))
+            .collect();
+        RedactedChainResult {
+            error: result
+                .error
+                .as_ref()
+                .map(|(index, error)| (*index, redactor.text(&format!("{error:?}")))),
+            results,
+            post_execution_accounts: redactor.accounts(&result.post_execution_accounts),
+        }
+    }
+}
+
+/// Redacts `result` with no labels and no truncation.
+pub fn redact(result: &InstructionProcessingResult) -> RedactedResult {
+    Redactions::default().redact(result)
+}
+
+/// Names addresses in the order they are first seen during one redaction.
+struct Redactor<'a> {
+    redactions: &'a Redactions,
+    stable: HashSet<Pubkey>,
+    names: Vec<(Pubkey, String)>,
+    unlabeled: usize,
+}
+
+impl<'a> Redactor<'a> {
+    fn new(redactions: &'a Redactions) -> Self {
+        let mut stable = ReservedAccountKeys::default().active(&FeatureSet::all_enabled());
+        stable.extend([TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID]);
+        Redactor { redactions, stable, names: Vec::new(), unlabeled: 0 }
+    }
+
+    fn name(&mut self, pubkey: &Pubkey) -> String {
+        if let Some((_, name)) = self.names.iter().find(|(key, _)| key == pubkey) {
+            return name.clone();
+        }
+        let name = match self.redactions.labels.get(pubkey) {
+            Some(label) => label.clone(),
+            None if self.stable.contains(pubkey) => pubkey.to_string(),
+            None => {
+                self.unlabeled += 1;
+                format!("pubkey#{}", self.unlabeled)
+            }
+        };
+        self.names.push((*pubkey, name.clone()));
+        name
+    }
+
+    /// `text` with every named address replaced by its name.
+    fn text(&self, text: &str) -> String {
+        self.names
+            .iter()
+            .fold(text.to_string(), |text, (pubkey, name)| text.replace(&pubkey.to_string(), name))
+    }
+
+    fn bytes(&self, bytes: &[u8]) -> String {
+        match self.redactions.max_bytes {
+            Some(max_bytes) if bytes.len() > max_bytes => format!(
+                "{}... ({} more bytes)",
+                hex::encode(&bytes[..max_bytes]),
+                bytes.len() - max_bytes
+            ),
+            _ => hex::encode(bytes),
+        }
+    }
+
+    fn instruction(&mut self, traced: &TracedInstruction) -> RedactedInstruction {
+        RedactedInstruction {
+            program_id: self.name(&traced.program_id),
+            accounts: traced
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let name = self.name(&meta.pubkey);
+                    match (meta.is_signer, meta.is_writable) {
+                        (true, true) => format!("{name} (signer, writable)"),
+                        (true, false) => format!("{name} (signer)"),
+                        (false, true) => format!("{name} (writable)"),
+                        (false, false) => name,
+                    }
+                })
+                .collect(),
+            data: self.bytes(&traced.data),
+            stack_height: traced.stack_height,
+        }
+    }
+
+    fn accounts(&mut self, accounts: &[(Pubkey, Account)]) -> BTreeMap<String, RedactedAccount> {
+        accounts
+            .iter()
+            .map(|(pubkey, account)| {
+                let account = RedactedAccount {
+                    lamports: account.lamports(),
+                    owner: self.name(account.owner()),
+                    executable: account.executable(),
+                    data: self.bytes(account.data()),
+                };
+                (self.name(pubkey), account)
+            })
+            .collect()
+    }
+
+    fn result(&mut self, result: &InstructionProcessingResult) -> RedactedResult {
+        let instruction_trace = result
+            .instruction_trace
+            .iter()
+            .map(|traced| self.instruction(traced))
+            .collect();
+        let post_execution_accounts = self.accounts(&result.post_execution_accounts);
+        let changed_accounts = result
+            .account_diffs
+            .iter()
+            .map(|diff| self.name(&diff.pubkey))
+            .collect();
+        RedactedResult {
+            error: result
+                .error
+                .as_ref()
+                .map(|error| self.text(&format!("{error:?}"))),
+            compute_units_consumed: result.compute_units_consumed,
+            return_data: self.bytes(&result.return_data),
+            logs: result.logs.iter().map(|log| self.text(log)).collect(),
+            instruction_trace,
+            changed_accounts,
+            post_execution_accounts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seashell::Seashell;
+    use crate::test_utils::system_transfer;
+
+    #[test]
+    fn test_redact() {
+        let seashell = Seashell::new();
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+        seashell.set_account(to, Account { lamports: 1, ..Account::default() });
+        let ixn = system_transfer(from, to, 400);
+        let system_program = solana_sdk_ids::system_program::id().to_string();
+
+        let redacted = redact(&seashell.process_instruction(ixn.clone()));
+        assert_eq!(redacted.instruction_trace[0].program_id, system_program);
+        assert_eq!(
+            redacted.instruction_trace[0].accounts,
+            ["pubkey#1 (signer, writable)", "pubkey#2 (writable)"]
+        );
+        assert_eq!(redacted.instruction_trace[0].data, "020000009001000000000000");
+        assert_eq!(redacted.changed_accounts, ["pubkey#1", "pubkey#2"]);
+        assert_eq!(redacted.post_execution_accounts["pubkey#2"].lamports, 401);
+        assert_eq!(redacted.post_execution_accounts["pubkey#2"].owner, system_program);
+
+        let redacted = Redactions::new()
+            .label(from, "payer")
+            .max_bytes(4)
+            .redact(&seashell.process_instruction(ixn));
+        assert_eq!(redacted.instruction_trace[0].accounts[0], "payer (signer, writable)");
+        assert_eq!(redacted.instruction_trace[0].data, "02000000... (8 more bytes)");
+        let json = serde_json::to_value(&redacted).unwrap();
+        assert_eq!(json["post_execution_accounts"]["payer"]["lamports"], 200);
+        assert!(!json.to_string().contains(&from.to_string()));
+    }
+}
@@ -0,0 +1,133 @@
+//! Token balances of SPL Token and Token-2022 accounts, extensions included, read from the
+//! `AccountsDb` or from the accounts an execution left behind:
+//!
+//! ```ignore
+//! let result = seashell.process_instruction(swap);
+//! assert_eq!(result.token_balance(&user_usdc), 900_000);
+//! seashell.assert_token_balance(&pool_usdc, 1_100_000);
+//! ```
+
+use solana_account::ReadableAccount;
+use solana_pubkey::Pubkey;
+
+use super::token_account_data;
+use crate::seashell::{InstructionChainResult, InstructionProcessingResult, Seashell};
+
+/// The amount held by `account`, if it is an initialized token account of either token program.
+pub fn token_amount(account: &impl ReadableAccount) -> Option<u64> {
+    token_account_data(account).map(|data| u64::from_le_bytes(data[64..72].try_into().unwrap()))
+}
+
+#[track_caller]
+fn amount_of(pubkey: &Pubkey, account: Option<&impl ReadableAccount>, state: &str) -> u64 {
+    let account = account.unwrap_or_else(|| panic!("Account {pubkey} is not in the {state}"));
+    token_amount(account).unwrap_or_else(|| panic!("Account {pubkey} is not a token account"))
+}
+
+#[track_caller]
+fn assert_amount(pubkey: &Pubkey, actual: u64, expected: u64) {
+    if actual != expected {
+        let difference = i128::from(actual) - i128::from(expected);
+        panic!(
+            "Token account {pubkey} holds {actual}, expected {expected} ({difference:+} from \
+             expected)"
+        );
+    }
+}
+
+impl Seashell {
+    /// The amount held by the token account `pubkey`. Panics if it doesn't exist or isn't a
+    /// token account.
+    #[track_caller]
+    pub fn token_balance(&self, pubkey: &Pubkey) -> u64 {
+        amount_of(pubkey, self.accounts_db.account_maybe(pubkey).as_ref(), "AccountsDb")
+    }
+
+    /// Panics unless the token account `pubkey` holds `expected`.
+    #[track_caller]
+    pub fn assert_token_balance(&self, pubkey: &Pubkey, expected: u64) {
+        assert_amount(pubkey, self.token_balance(pubkey), expected);
+    }
+}
+
+impl InstructionProcessingResult {
+    /// The post-execution amount held by the token account `pubkey`.
+    #[track_caller]
+    pub fn token_balance(&self, pubkey: &Pubkey) -> u64 {
+        amount_of(pubkey, self.post_account(pubkey), "post-execution state")
+    }
+
+    /// Panics unless the token account `pubkey` holds `expected` after execution.
+    #[track_caller]
+    pub fn assert_token_balance(&self, pubkey: &Pubkey, expected: u64) {
+        assert_amount(pubkey, self.token_balance(pubkey), expected);
+    }
+}
+
+impl InstructionChainResult {
+    /// The final amount held by the token account `pubkey`.
+    #[track_caller]
+    pub fn token_balance(&self, pubkey: &Pubkey) -> u64 {
+        amount_of(pubkey, self.post_account(pubkey), "post-execution state")
+    }
+
+    /// Panics unless the token account `pubkey` holds `expected` at the end of the chain.
+    #[track_caller]
+    pub fn assert_token_balance(&self, pubkey: &Pubkey, expected: u64) {
+        assert_amount(pubkey, self.token_balance(pubkey), expected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::error::InstructionError;
+
+    use super::*;
+    use crate::seashell::InstructionProcessingError;
+    use crate::spl::extensions::{ExtensionAccountBuilder, ExtensionMintBuilder};
+
+    #[test]
+    fn test_token_balance() {
+        let seashell = Seashell::new();
+        let (authority, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = seashell.create_mint(6, authority);
+        let account = seashell.create_token_account(mint, owner, 750);
+        assert_eq!(seashell.token_balance(&account), 750);
+        seashell.assert_token_balance(&account, 750);
+
+        let (extension_mint, extension_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        seashell.set_extension_mint(
+            extension_mint,
+            &ExtensionMintBuilder::new(6)
+                .transfer_fee(50, 1_000)
+                .supply(42),
+        );
+        seashell.set_extension_token_account(
+            extension_account,
+            &ExtensionAccountBuilder::new(extension_mint, owner, 42).immutable_owner(),
+        );
+        seashell.assert_token_balance(&extension_account, 42);
+
+        let result = InstructionProcessingResult {
+            post_execution_accounts: vec![(
+                account,
+                seashell.accounts_db.account_must(&account).into(),
+            )],
+            ..InstructionProcessingResult::from_error(InstructionProcessingError::InstructionError(
+                InstructionError::Custom(0),
+            ))
+        };
+        result.assert_token_balance(&account, 750);
+
+        let panic_message = |f: &dyn Fn()| {
+            let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+            payload.downcast_ref::<String>().unwrap().clone()
+        };
+        let message = panic_message(&|| seashell.assert_token_balance(&account, 700));
+        assert!(message.contains("holds 750, expected 700 (+50 from expected)"), "{message}");
+        let message = panic_message(&|| {
+            seashell.token_balance(&mint);
+        });
+        assert_eq!(message, format!("Account {mint} is not a token account"));
+    }
+}
@@ -0,0 +1,469 @@
+//! Scaffolding for Token-2022 confidential transfers, which otherwise need a full validator to
+//! exercise: keys, a mint and token accounts with room for the extension, and the
+//! configure-account, deposit, apply-pending-balance and withdraw flows with their proofs.
+//!
+//! Token-2022 reads proofs from context state accounts of the ZK ElGamal proof program. With
+//! [`ProofMode::Verify`] each proof is verified by the proof program in an instruction run
+//! before the token instruction, which writes the context account; with [`ProofMode::Stub`] the
+//! context account is written directly and nothing is verified, which is faster and keeps the
+//! proof program out of the compute units.
+//!
+//! ```ignore
+//! let keys = ConfidentialKeys::new_rand();
+//! seashell.create_confidential_mint(mint, authority, 6);
+//! seashell.create_confidential_token_account(account, mint, owner, 1_000);
+//! seashell.configure_confidential_account(account, mint, owner, &keys, ProofMode::Verify)?;
+//! seashell.confidential_deposit(account, mint, owner, 600, 6);
+//! seashell.apply_pending_balance(account, owner, &keys)?;
+//! seashell.confidential_withdraw(account, mint, owner, &keys, 250, 6, ProofMode::Stub)?;
+//! ```
+
+use solana_account::{Account, ReadableAccount};
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+use solana_zk_sdk::encryption::auth_encryption::{AeCiphertext, AeKey};
+use solana_zk_sdk::encryption::elgamal::{ElGamal, ElGamalCiphertext, ElGamalKeypair};
+use solana_zk_sdk::encryption::pedersen::Pedersen;
+use solana_zk_sdk::zk_elgamal_proof_program::instruction::{ContextStateInfo, ProofInstruction};
+use solana_zk_sdk::zk_elgamal_proof_program::proof_data::{
+    BatchedRangeProofU64Data, CiphertextCommitmentEqualityProofData, ProofType,
+    PubkeyValidityProofData, ZkProofData,
+};
+use solana_zk_sdk::zk_elgamal_proof_program::state::ProofContextState;
+
+use super::{
+    extension, ACCOUNT_TYPE_ACCOUNT, ACCOUNT_TYPE_MINT, ACCOUNT_TYPE_OFFSET, MINT_DECIMALS_OFFSET,
+    MINT_IS_INITIALIZED_OFFSET, TOKEN_2022_PROGRAM_ID, TOKEN_ACCOUNT_STATE_OFFSET,
+};
+use crate::error::SeashellError;
+use crate::seashell::{InstructionChainResult, InstructionProcessingResult, Seashell};
+
+/// `TokenInstruction::ConfidentialTransferExtension`.
+const CONFIDENTIAL_TRANSFER_EXTENSION: u8 = 27;
+const CONFIGURE_ACCOUNT: u8 = 2;
+const DEPOSIT: u8 = 5;
+const WITHDRAW: u8 = 6;
+const APPLY_PENDING_BALANCE: u8 = 8;
+
+/// `ExtensionType`s, and the sizes of their values.
+const CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+const CONFIDENTIAL_TRANSFER_ACCOUNT: u16 = 5;
+const CONFIDENTIAL_TRANSFER_MINT_SIZE: usize = 65;
+const CONFIDENTIAL_TRANSFER_ACCOUNT_SIZE: usize = 295;
+
+/// Offsets in `ConfidentialTransferAccount`.
+const PENDING_BALANCE_LO: usize = 33;
+const PENDING_BALANCE_HI: usize = 97;
+const AVAILABLE_BALANCE: usize = 161;
+const DECRYPTABLE_AVAILABLE_BALANCE: usize = 225;
+const PENDING_BALANCE_CREDIT_COUNTER: usize = 263;
+
+/// What the token CLI allows by default.
+const MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER: u64 = 65_536;
+const REMAINING_BALANCE_BIT_LENGTH: usize = 64;
+
+/// The encryption keys of one confidential token account.
+pub struct ConfidentialKeys {
+    pub elgamal: ElGamalKeypair,
+    pub aes: AeKey,
+}
+
+impl ConfidentialKeys {
+    pub fn new_rand() -> Self {
+        ConfidentialKeys { elgamal: ElGamalKeypair::new_rand(), aes: AeKey::new_rand() }
+    }
+}
+
+/// How the proofs of a confidential transfer instruction reach Token-2022.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofMode {
+    /// Verify each proof with the ZK ElGamal proof program.
+    Verify,
+    /// Write each proof's context account directly, without verifying it.
+    Stub,
+}
+
+/// The decrypted confidential balances of a token account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfidentialBalances {
+    /// Deposited and received, waiting for `apply_pending_balance`.
+    pub pending: u64,
+    pub available: u64,
+}
+
+fn proof_error(e: impl std::fmt::Display) -> SeashellError {
+    SeashellError::Custom(format!("Failed to generate proof: {e}"))
+}
+
+/// Writes the type and length of an extension at the start of the extension area of `data`,
+/// returning where its value starts.
+fn write_extension_header(data: &mut [u8], extension_type: u16, len: usize) -> usize {
+    let offset = ACCOUNT_TYPE_OFFSET + 1;
+    data[offset..offset + 2].copy_from_slice(&extension_type.to_le_bytes());
+    data[offset + 2..offset + 4].copy_from_slice(&(len as u16).to_le_bytes());
+    offset + 4
+}
+
+fn decrypt_balances(
+    extension: &[u8],
+    keys: &ConfidentialKeys,
+) -> Result<ConfidentialBalances, SeashellError> {
+    let decrypt = |offset: usize| {
+        ElGamalCiphertext::from_bytes(&extension[offset..offset + 64])
+            .and_then(|ciphertext| keys.elgamal.secret().decrypt_u32(&ciphertext))
+            .ok_or_else(|| SeashellError::Custom("Failed to decrypt the pending balance".into()))
+    };
+    let pending = decrypt(PENDING_BALANCE_LO)? + (decrypt(PENDING_BALANCE_HI)? << 16);
+    let available = AeCiphertext::from_bytes(
+        &extension[DECRYPTABLE_AVAILABLE_BALANCE..DECRYPTABLE_AVAILABLE_BALANCE + 36],
+    )
+    .and_then(|ciphertext| keys.aes.decrypt(&ciphertext))
+    .ok_or_else(|| SeashellError::Custom("Failed to decrypt the available balance".into()))?;
+    Ok(ConfidentialBalances { pending, available })
+}
+
+fn confidential_transfer_instruction(
+    kind: u8,
+    accounts: Vec<AccountMeta>,
+    args: &[u8],
+) -> Instruction {
+    let mut data = vec![CONFIDENTIAL_TRANSFER_EXTENSION, kind];
+    data.extend_from_slice(args);
+    Instruction { program_id: TOKEN_2022_PROGRAM_ID, accounts, data }
+}
+
+impl Seashell {
+    /// Writes a Token-2022 mint with the `ConfidentialTransferMint` extension that approves new
+    /// accounts on its own and has no auditor. `authority` is its mint authority and its
+    /// confidential transfer authority.
+    pub fn create_confidential_mint(&self, mint: Pubkey, authority: Pubkey, decimals: u8) {
+        let mut data = vec![0; ACCOUNT_TYPE_OFFSET + 1 + 4 + CONFIDENTIAL_TRANSFER_MINT_SIZE];
+        data[0..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(authority.as_ref());
+        data[MINT_DECIMALS_OFFSET] = decimals;
+        data[MINT_IS_INITIALIZED_OFFSET] = 1;
+        data[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_MINT;
+        let value = write_extension_header(
+            &mut data,
+            CONFIDENTIAL_TRANSFER_MINT,
+            CONFIDENTIAL_TRANSFER_MINT_SIZE,
+        );
+        data[value..value + 32].copy_from_slice(authority.as_ref());
+        data[value + 32] = 1; // auto_approve_new_accounts
+        self.set_token_2022_account(mint, data);
+    }
+
+    /// Writes an initialized Token-2022 account holding `amount` public tokens, with room for the
+    /// `ConfidentialTransferAccount` extension that `configure_confidential_account` adds.
+    pub fn create_confidential_token_account(
+        &self,
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    ) {
+        let mut data = vec![0; ACCOUNT_TYPE_OFFSET + 1 + 4 + CONFIDENTIAL_TRANSFER_ACCOUNT_SIZE];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[TOKEN_ACCOUNT_STATE_OFFSET] = 1; // AccountState::Initialized
+        data[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_ACCOUNT;
+        self.set_token_2022_account(account, data);
+    }
+
+    fn set_token_2022_account(&self, pubkey: Pubkey, data: Vec<u8>) {
+        let lamports = self.accounts_db.sysvars.rent().minimum_balance(data.len());
+        self.set_account(
+            pubkey,
+            Account { lamports, data, owner: TOKEN_2022_PROGRAM_ID, ..Account::default() },
+        );
+    }
+
+    /// Writes a context state account for a proof, owned by `authority`. With
+    /// `ProofMode::Verify` the account is left empty and `verify` builds the instruction that
+    /// verifies the proof into it; with `ProofMode::Stub` it holds `context_state` already.
+    fn proof_context_account(
+        &self,
+        mode: ProofMode,
+        authority: &Pubkey,
+        context_state: Vec<u8>,
+        verify: impl FnOnce(ContextStateInfo) -> Instruction,
+    ) -> (Pubkey, Option<Instruction>) {
+        let context_account = Pubkey::new_unique();
+        let len = context_state.len();
+        let (data, verify) = match mode {
+            ProofMode::Verify => (
+                vec![0; len],
+                Some(verify(ContextStateInfo {
+                    context_state_account: &context_account,
+                    context_state_authority: authority,
+                })),
+            ),
+            ProofMode::Stub => (context_state, None),
+        };
+        self.set_account(
+            context_account,
+            Account {
+                lamports: self.accounts_db.sysvars.rent().minimum_balance(len),
+                data,
+                owner: solana_sdk_ids::zk_elgamal_proof_program::id(),
+                ..Account::default()
+            },
+        );
+        (context_account, verify)
+    }
+
+    /// The `ConfidentialTransferAccount` extension of `account`.
+    fn confidential_transfer_account(&self, account: &Pubkey) -> Result<Vec<u8>, SeashellError> {
+        self.accounts_db
+            .account_maybe(account)
+            .and_then(|data| {
+                extension(data.data(), CONFIDENTIAL_TRANSFER_ACCOUNT)
+                    .filter(|value| value.len() == CONFIDENTIAL_TRANSFER_ACCOUNT_SIZE)
+                    .map(<[u8]>::to_vec)
+            })
+            .ok_or_else(|| {
+                SeashellError::Custom(format!(
+                    "{account} is not configured for confidential transfers"
+                ))
+            })
+    }
+
+    /// Decrypts the confidential balances of `account` with its `keys`.
+    pub fn confidential_balances(
+        &self,
+        account: &Pubkey,
+        keys: &ConfidentialKeys,
+    ) -> Result<ConfidentialBalances, SeashellError> {
+        decrypt_balances(&self.confidential_transfer_account(account)?, keys)
+    }
+
+    /// Runs `ConfigureAccount` on `account` with `keys`, proving that the ElGamal public key is
+    /// valid.
+    pub fn configure_confidential_account(
+        &self,
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        keys: &ConfidentialKeys,
+        mode: ProofMode,
+    ) -> Result<InstructionChainResult, SeashellError> {
+        let proof = PubkeyValidityProofData::new(&keys.elgamal).map_err(proof_error)?;
+        let (context_account, verify) = self.proof_context_account(
+            mode,
+            &owner,
+            ProofContextState::encode(&owner, ProofType::PubkeyValidity, proof.context_data()),
+            |info| ProofInstruction::VerifyPubkeyValidity.encode_verify_proof(Some(info), &proof),
+        );
+
+        let mut args = keys.aes.encrypt(0).to_bytes().to_vec();
+        args.extend_from_slice(&MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER.to_le_bytes());
+        args.push(0); // proof_instruction_offset: the proof is in a context account
+        let configure = confidential_transfer_instruction(
+            CONFIGURE_ACCOUNT,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(context_account, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+            &args,
+        );
+        Ok(self.process_instruction_chain(verify.into_iter().chain([configure]).collect()))
+    }
+
+    /// Runs `Deposit`, moving `amount` public tokens of `account` into its pending balance.
+    pub fn confidential_deposit(
+        &self,
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> InstructionProcessingResult {
+        let mut args = amount.to_le_bytes().to_vec();
+        args.push(decimals);
+        self.process_instruction(confidential_transfer_instruction(
+            DEPOSIT,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+            &args,
+        ))
+    }
+
+    /// Runs `ApplyPendingBalance`, moving the pending balance of `account` into its available
+    /// balance.
+    pub fn apply_pending_balance(
+        &self,
+        account: Pubkey,
+        owner: Pubkey,
+        keys: &ConfidentialKeys,
+    ) -> Result<InstructionProcessingResult, SeashellError> {
+        let extension = self.confidential_transfer_account(&account)?;
+        let balances = decrypt_balances(&extension, keys)?;
+        let mut args =
+            extension[PENDING_BALANCE_CREDIT_COUNTER..PENDING_BALANCE_CREDIT_COUNTER + 8].to_vec();
+        args.extend_from_slice(
+            &keys
+                .aes
+                .encrypt(balances.available + balances.pending)
+                .to_bytes(),
+        );
+        Ok(self.process_instruction(confidential_transfer_instruction(
+            APPLY_PENDING_BALANCE,
+            vec![AccountMeta::new(account, false), AccountMeta::new_readonly(owner, true)],
+            &args,
+        )))
+    }
+
+    /// Runs `Withdraw`, moving `amount` of the available balance of `account` back to its public
+    /// balance, proving that the remaining balance is what the ciphertext holds and not negative.
+    #[allow(clippy::too_many_arguments)]
+    pub fn confidential_withdraw(
+        &self,
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        keys: &ConfidentialKeys,
+        amount: u64,
+        decimals: u8,
+        mode: ProofMode,
+    ) -> Result<InstructionChainResult, SeashellError> {
+        let extension = self.confidential_transfer_account(&account)?;
+        let available = decrypt_balances(&extension, keys)?.available;
+        let remaining = available.checked_sub(amount).ok_or_else(|| {
+            SeashellError::Custom(format!(
+                "Can't withdraw {amount} from an available balance of {available}"
+            ))
+        })?;
+        let available_ciphertext =
+            ElGamalCiphertext::from_bytes(&extension[AVAILABLE_BALANCE..AVAILABLE_BALANCE + 64])
+                .ok_or_else(|| {
+                    SeashellError::Custom("Malformed available balance ciphertext".into())
+                })?;
+        let remaining_ciphertext = &available_ciphertext - &ElGamal::encode(amount);
+        let (commitment, opening) = Pedersen::new(remaining);
+
+        let equality = CiphertextCommitmentEqualityProofData::new(
+            &keys.elgamal,
+            &remaining_ciphertext,
+            &commitment,
+            &opening,
+            remaining,
+        )
+        .map_err(proof_error)?;
+        let range = BatchedRangeProofU64Data::new(
+            vec![&commitment],
+            vec![remaining],
+            vec![REMAINING_BALANCE_BIT_LENGTH],
+            vec![&opening],
+        )
+        .map_err(proof_error)?;
+        let (equality_account, verify_equality) = self.proof_context_account(
+            mode,
+            &owner,
+            ProofContextState::encode(
+                &owner,
+                ProofType::CiphertextCommitmentEquality,
+                equality.context_data(),
+            ),
+            |info| {
+                ProofInstruction::VerifyCiphertextCommitmentEquality
+                    .encode_verify_proof(Some(info), &equality)
+            },
+        );
+        let (range_account, verify_range) = self.proof_context_account(
+            mode,
+            &owner,
+            ProofContextState::encode(
+                &owner,
+                ProofType::BatchedRangeProofU64,
+                range.context_data(),
+            ),
+            |info| {
+                ProofInstruction::VerifyBatchedRangeProofU64.encode_verify_proof(Some(info), &range)
+            },
+        );
+
+        let mut args = amount.to_le_bytes().to_vec();
+        args.push(decimals);
+        args.extend_from_slice(&keys.aes.encrypt(remaining).to_bytes());
+        args.extend_from_slice(&[0, 0]); // both proofs are in context accounts
+        let withdraw = confidential_transfer_instruction(
+            WITHDRAW,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(equality_account, false),
+                AccountMeta::new_readonly(range_account, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+            &args,
+        );
+        Ok(self.process_instruction_chain(
+            verify_equality
+                .into_iter()
+                .chain(verify_range)
+                .chain([withdraw])
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seashell::Config;
+
+    #[test]
+    fn test_confidential_deposit_and_withdraw() {
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let mint = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        seashell.set_account(owner, Account { lamports: 1_000_000_000, ..Account::default() });
+        seashell.create_confidential_mint(mint, owner, 2);
+        seashell.create_confidential_token_account(account, mint, owner, 1_000);
+        let keys = ConfidentialKeys::new_rand();
+        assert!(seashell.confidential_balances(&account, &keys).is_err());
+
+        let result = seashell
+            .configure_confidential_account(account, mint, owner, &keys, ProofMode::Verify)
+            .unwrap();
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(
+            seashell.confidential_balances(&account, &keys).unwrap(),
+            ConfidentialBalances { pending: 0, available: 0 }
+        );
+
+        let result = seashell.confidential_deposit(account, mint, owner, 600, 2);
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(
+            seashell.confidential_balances(&account, &keys).unwrap(),
+            ConfidentialBalances { pending: 600, available: 0 }
+        );
+        let result = seashell
+            .apply_pending_balance(account, owner, &keys)
+            .unwrap();
+        assert!(result.error.is_none(), "{:?}", result.error);
+
+        for mode in [ProofMode::Stub, ProofMode::Verify] {
+            let result = seashell
+                .confidential_withdraw(account, mint, owner, &keys, 100, 2, mode)
+                .unwrap();
+            assert!(result.error.is_none(), "{mode:?}: {:?}", result.error);
+        }
+        assert_eq!(
+            seashell.confidential_balances(&account, &keys).unwrap(),
+            ConfidentialBalances { pending: 0, available: 400 }
+        );
+        let amount = &seashell.account(&account).data[64..72];
+        assert_eq!(u64::from_le_bytes(amount.try_into().unwrap()), 600);
+        assert!(seashell
+            .confidential_withdraw(account, mint, owner, &keys, 500, 2, ProofMode::Stub)
+            .is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! Token-2022 mints and token accounts with extensions, laid out as the program lays them out,
+//! so tests of extension-aware code don't need mainnet clones or hand-rolled TLV bytes:
+//!
+//! ```ignore
+//! let builder = ExtensionMintBuilder::new(6)
+//!     .mint_authority(authority)
+//!     .transfer_fee(50, 5_000)
+//!     .transfer_hook(hook_program);
+//! seashell.set_extension_mint(mint, &builder);
+//! seashell.set_extension_token_account(account, &ExtensionAccountBuilder::new(mint, owner, 100));
+//! ```
+//!
+//! [`Seashell::set_extension_token_account`] adds the account extensions the mint's extensions
+//! require, as `InitializeAccount` does. The confidential transfer extension of accounts is a
+//! placeholder: room for `ConfigureAccount` to fill in, as with
+//! [`Seashell::create_confidential_token_account`].
+
+use solana_account::{AccountSharedData, ReadableAccount};
+use solana_clock::Clock;
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+
+use super::{
+    extension, ACCOUNT_TYPE_ACCOUNT, ACCOUNT_TYPE_MINT, ACCOUNT_TYPE_OFFSET, MINT_DECIMALS_OFFSET,
+    MINT_IS_INITIALIZED_OFFSET, TOKEN_2022_PROGRAM_ID, TOKEN_ACCOUNT_STATE_OFFSET,
+};
+use crate::seashell::Seashell;
+
+/// `ExtensionType`s.
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const TRANSFER_FEE_AMOUNT: u16 = 2;
+const CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+const IMMUTABLE_OWNER: u16 = 7;
+const INTEREST_BEARING_CONFIG: u16 = 10;
+const PERMANENT_DELEGATE: u16 = 12;
+const TRANSFER_HOOK: u16 = 14;
+const TRANSFER_HOOK_ACCOUNT: u16 = 15;
+
+const CONFIDENTIAL_TRANSFER_ACCOUNT_SIZE: usize = 295;
+/// Token-2022 pads accounts of the multisig length so they can't be mistaken for one.
+const MULTISIG_SIZE: usize = 355;
+
+/// `OptionalNonZeroPubkey`: the default pubkey stands for none.
+fn optional_pubkey(pubkey: Option<Pubkey>) -> [u8; 32] {
+    pubkey.unwrap_or_default().to_bytes()
+}
+
+/// `base` followed by the account type and the TLV entries of `extensions`.
+fn with_extensions(base: &[u8], account_type: u8, extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut data = base.to_vec();
+    data.resize(ACCOUNT_TYPE_OFFSET, 0);
+    data.push(account_type);
+    for (extension_type, value) in extensions {
+        data.extend_from_slice(&extension_type.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+    if data.len() == MULTISIG_SIZE {
+        data.extend_from_slice(&[0; 2]);
+    }
+    data
+}
+
+fn token_2022_account(data: Vec<u8>, rent: &Rent) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        rent.minimum_balance(data.len()),
+        data.len(),
+        &TOKEN_2022_PROGRAM_ID,
+    );
+    account.set_data_from_slice(&data);
+    account
+}
+
+/// Builds Token-2022 mints with extensions.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionMintBuilder {
+    decimals: u8,
+    supply: u64,
+    mint_authority: Option<Pubkey>,
+    freeze_authority: Option<Pubkey>,
+    transfer_fee: Option<(u16, u64)>,
+    transfer_fee_authority: Option<Pubkey>,
+    transfer_hook: Option<Pubkey>,
+    interest_rate: Option<i16>,
+    permanent_delegate: Option<Pubkey>,
+    confidential_transfer: Option<Option<Pubkey>>,
+}
+
+impl ExtensionMintBuilder {
+    /// Creates a builder for a mint of `decimals` without supply, authorities or extensions.
+    pub fn new(decimals: u8) -> Self {
+        ExtensionMintBuilder { decimals, ..ExtensionMintBuilder::default() }
+    }
+
+    pub fn supply(mut self, supply: u64) -> Self {
+        self.supply = supply;
+        self
+    }
+
+    /// Sets the mint authority, which also becomes the authority of the extensions that have one
+    /// unless set otherwise.
+    pub fn mint_authority(mut self, authority: Pubkey) -> Self {
+        self.mint_authority = Some(authority);
+        self
+    }
+
+    pub fn freeze_authority(mut self, authority: Pubkey) -> Self {
+        self.freeze_authority = Some(authority);
+        self
+    }
+
+    /// Adds `TransferFeeConfig` charging `basis_points` of each transfer, up to `maximum_fee`,
+    /// in every epoch.
+    pub fn transfer_fee(mut self, basis_points: u16, maximum_fee: u64) -> Self {
+        self.transfer_fee = Some((basis_points, maximum_fee));
+        self
+    }
+
+    /// Sets the transfer fee config and withdraw withheld authority.
+    pub fn transfer_fee_authority(mut self, authority: Pubkey) -> Self {
+        self.transfer_fee_authority = Some(authority);
+        self
+    }
+
+    /// Adds `TransferHook`, calling `program_id` on every transfer.
+    pub fn transfer_hook(mut self, program_id: Pubkey) -> Self {
+        self.transfer_hook = Some(program_id);
+        self
+    }
+
+    /// Adds `InterestBearingConfig` accruing `rate` basis points a year from the build time.
+    pub fn interest_bearing(mut self, rate: i16) -> Self {
+        self.interest_rate = Some(rate);
+        self
+    }
+
+    pub fn permanent_delegate(mut self, delegate: Pubkey) -> Self {
+        self.permanent_delegate = Some(delegate);
+        self
+    }
+
+    /// Adds `ConfidentialTransferMint`, approving new accounts on its own, with `auditor` as the
+    /// ElGamal pubkey of the auditor if any.
+    pub fn confidential_transfer(mut self, auditor: Option<Pubkey>) -> Self {
+        self.confidential_transfer = Some(auditor);
+        self
+    }
+
+    /// The extensions in the order they are written, as `(ExtensionType, value)` pairs.
+    fn extensions(&self, clock: &Clock) -> Vec<(u16, Vec<u8>)> {
+        let authority = optional_pubkey(self.mint_authority);
+        let mut extensions = Vec::new();
+        if let Some((basis_points, maximum_fee)) = self.transfer_fee {
+            let fee_authority = match self.transfer_fee_authority {
+                Some(fee_authority) => fee_authority.to_bytes(),
+                None => authority,
+            };
+            let mut transfer_fee = clock.epoch.to_le_bytes().to_vec();
+            transfer_fee.extend_from_slice(&maximum_fee.to_le_bytes());
+            transfer_fee.extend_from_slice(&basis_points.to_le_bytes());
+            let mut value = [fee_authority, fee_authority].concat();
+            value.extend_from_slice(&0u64.to_le_bytes()); // withheld_amount
+            value.extend_from_slice(&transfer_fee); // older_transfer_fee
+            value.extend_from_slice(&transfer_fee); // newer_transfer_fee
+            extensions.push((TRANSFER_FEE_CONFIG, value));
+        }
+        if let Some(auditor) = self.confidential_transfer {
+            let mut value = authority.to_vec();
+            value.push(1); // auto_approve_new_accounts
+            value.extend_from_slice(&optional_pubkey(auditor));
+            extensions.push((CONFIDENTIAL_TRANSFER_MINT, value));
+        }
+        if let Some(rate) = self.interest_rate {
+            let mut value = authority.to_vec();
+            value.extend_from_slice(&clock.unix_timestamp.to_le_bytes()); // initialization
+            value.extend_from_slice(&rate.to_le_bytes()); // pre_update_average_rate
+            value.extend_from_slice(&clock.unix_timestamp.to_le_bytes()); // last_update
+            value.extend_from_slice(&rate.to_le_bytes()); // current_rate
+            extensions.push((INTEREST_BEARING_CONFIG, value));
+        }
+        if let Some(delegate) = self.permanent_delegate {
+            extensions.push((PERMANENT_DELEGATE, delegate.to_bytes().to_vec()));
+        }
+        if let Some(program_id) = self.transfer_hook {
+            extensions.push((TRANSFER_HOOK, [authority, program_id.to_bytes()].concat()));
+        }
+        extensions
+    }
+
+    /// Materializes the mint, with timestamps and the transfer fee epoch taken from `clock`.
+    pub fn build(&self, clock: &Clock, rent: &Rent) -> AccountSharedData {
+        let mut base = [0; 82];
+        if let Some(authority) = self.mint_authority {
+            base[0..4].copy_from_slice(&1u32.to_le_bytes());
+            base[4..36].copy_from_slice(authority.as_ref());
+        }
+        base[36..44].copy_from_slice(&self.supply.to_le_bytes());
+        base[MINT_DECIMALS_OFFSET] = self.decimals;
+        base[MINT_IS_INITIALIZED_OFFSET] = 1;
+        if let Some(authority) = self.freeze_authority {
+            base[46..50].copy_from_slice(&1u32.to_le_bytes());
+            base[50..82].copy_from_slice(authority.as_ref());
+        }
+        let data = with_extensions(&base, ACCOUNT_TYPE_MINT, &self.extensions(clock));
+        token_2022_account(data, rent)
+    }
+}
+
+/// Builds Token-2022 token accounts with extensions.
+#[derive(Debug, Clone)]
+pub struct ExtensionAccountBuilder {
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    immutable_owner: bool,
+    withheld_amount: Option<u64>,
+    transfer_hook_account: bool,
+    confidential_transfer: bool,
+}
+
+impl ExtensionAccountBuilder {
+    pub fn new(mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        ExtensionAccountBuilder {
+            mint,
+            owner,
+            amount,
+            immutable_owner: false,
+            withheld_amount: None,
+            transfer_hook_account: false,
+            confidential_transfer: false,
+        }
+    }
+
+    /// Adds `ImmutableOwner`, as associated token accounts have.
+    pub fn immutable_owner(mut self) -> Self {
+        self.immutable_owner = true;
+        self
+    }
+
+    /// Adds `TransferFeeAmount` with `withheld_amount` fees withheld in the account.
+    pub fn transfer_fee_amount(mut self, withheld_amount: u64) -> Self {
+        self.withheld_amount = Some(withheld_amount);
+        self
+    }
+
+    pub fn transfer_hook_account(mut self) -> Self {
+        self.transfer_hook_account = true;
+        self
+    }
+
+    /// Reserves room for `ConfidentialTransferAccount`, for `ConfigureAccount` to fill in.
+    pub fn confidential_transfer(mut self) -> Self {
+        self.confidential_transfer = true;
+        self
+    }
+
+    /// Adds the account extensions that the extensions in `mint_data` require.
+    pub fn required_by(mut self, mint_data: &[u8]) -> Self {
+        if extension(mint_data, TRANSFER_FEE_CONFIG).is_some() {
+            self.withheld_amount.get_or_insert(0);
+        }
+        self.transfer_hook_account |= extension(mint_data, TRANSFER_HOOK).is_some();
+        self
+    }
+
+    pub fn build(&self, rent: &Rent) -> AccountSharedData {
+        let mut base = [0; ACCOUNT_TYPE_OFFSET];
+        base[0..32].copy_from_slice(self.mint.as_ref());
+        base[32..64].copy_from_slice(self.owner.as_ref());
+        base[64..72].copy_from_slice(&self.amount.to_le_bytes());
+        base[TOKEN_ACCOUNT_STATE_OFFSET] = 1; // AccountState::Initialized
+
+        let mut extensions = Vec::new();
+        if let Some(withheld_amount) = self.withheld_amount {
+            extensions.push((TRANSFER_FEE_AMOUNT, withheld_amount.to_le_bytes().to_vec()));
+        }
+        if self.immutable_owner {
+            extensions.push((IMMUTABLE_OWNER, Vec::new()));
+        }
+        if self.transfer_hook_account {
+            extensions.push((TRANSFER_HOOK_ACCOUNT, vec![0])); // transferring
+        }
+        let mut data = with_extensions(&base, ACCOUNT_TYPE_ACCOUNT, &extensions);
+        if self.confidential_transfer {
+            // Left uninitialized; `ConfigureAccount` writes the header and the value.
+            data.resize(data.len() + 4 + CONFIDENTIAL_TRANSFER_ACCOUNT_SIZE, 0);
+        }
+        token_2022_account(data, rent)
+    }
+}
+
+impl Seashell {
+    /// Sets a Token-2022 mint materialized from `builder` as of the fixture clock.
+    pub fn set_extension_mint(&self, pubkey: Pubkey, builder: &ExtensionMintBuilder) {
+        let account = builder.build(&self.fixture_clock(), &self.accounts_db.sysvars.rent());
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+
+    /// Sets a Token-2022 account materialized from `builder`, with the extensions its mint
+    /// requires if the mint exists.
+    pub fn set_extension_token_account(&self, pubkey: Pubkey, builder: &ExtensionAccountBuilder) {
+        let builder = match self.accounts_db.account_maybe(&builder.mint) {
+            Some(mint) => builder.clone().required_by(mint.data()),
+            None => builder.clone(),
+        };
+        let account = builder.build(&self.accounts_db.sysvars.rent());
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spl::{mint_decimals, token_balances};
+
+    #[test]
+    fn test_extension_builders() {
+        let seashell = Seashell::new();
+        let (mint, account, authority, owner, hook) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        seashell.set_extension_mint(
+            mint,
+            &ExtensionMintBuilder::new(6)
+                .mint_authority(authority)
+                .supply(1_000)
+                .transfer_fee(50, 5_000)
+                .transfer_hook(hook)
+                .interest_bearing(-25)
+                .permanent_delegate(authority)
+                .confidential_transfer(None),
+        );
+        let mint_account = seashell.accounts_db.account_must(&mint);
+        let data = mint_account.data();
+        assert_eq!(mint_decimals(&mint_account), Some(6));
+        assert_eq!(data[ACCOUNT_TYPE_OFFSET], ACCOUNT_TYPE_MINT);
+        let transfer_fee = extension(data, TRANSFER_FEE_CONFIG).unwrap();
+        assert_eq!(transfer_fee.len(), 108);
+        assert_eq!(&transfer_fee[..32], authority.as_ref());
+        assert_eq!(transfer_fee[106..108], 50u16.to_le_bytes());
+        assert_eq!(&extension(data, TRANSFER_HOOK).unwrap()[32..], hook.as_ref());
+        let interest = extension(data, INTEREST_BEARING_CONFIG).unwrap();
+        assert_eq!(interest.len(), 52);
+        assert_eq!(interest[50..52], (-25i16).to_le_bytes());
+        assert_eq!(extension(data, PERMANENT_DELEGATE).unwrap(), authority.as_ref());
+        assert_eq!(extension(data, CONFIDENTIAL_TRANSFER_MINT).unwrap().len(), 65);
+
+        seashell.set_extension_token_account(
+            account,
+            &ExtensionAccountBuilder::new(mint, owner, 100).immutable_owner(),
+        );
+        let token_account = seashell.accounts_db.account_must(&account);
+        let data = token_account.data();
+        assert_eq!(data[ACCOUNT_TYPE_OFFSET], ACCOUNT_TYPE_ACCOUNT);
+        assert_eq!(extension(data, TRANSFER_FEE_AMOUNT).unwrap(), 0u64.to_le_bytes());
+        assert_eq!(extension(data, IMMUTABLE_OWNER).unwrap(), &[] as &[u8]);
+        assert_eq!(extension(data, TRANSFER_HOOK_ACCOUNT).unwrap(), [0]);
+        let balances = token_balances(
+            &[(mint, mint_account.clone()), (account, token_account.clone())],
+            |_| None,
+        );
+        assert_eq!((balances[0].owner, balances[0].amount), (owner, 100));
+
+        let placeholder = ExtensionAccountBuilder::new(mint, owner, 0)
+            .confidential_transfer()
+            .build(&Rent::default());
+        assert_eq!(placeholder.data().len(), ACCOUNT_TYPE_OFFSET + 1 + 4 + 295);
+        // Not `ConfidentialTransferAccount` until configured.
+        assert!(extension(placeholder.data(), 5).is_none());
+    }
+}
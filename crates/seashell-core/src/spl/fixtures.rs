@@ -0,0 +1,227 @@
+//! Mints and token accounts for tests, written straight into the `AccountsDb` without running
+//! the token programs:
+//!
+//! ```ignore
+//! let mint = seashell.create_mint(6, authority);
+//! let vault = seashell.create_ata(vault_authority, mint);
+//! let user = seashell.create_token_account(mint, user_wallet, 1_000_000);
+//! seashell.mint_to(mint, vault, 5_000_000)?;
+//! ```
+//!
+//! Accounts are rent-exempt and owned by the token program of their mint, so the same calls
+//! work for Token-2022 mints set up some other way. `create_*` helpers pick fresh addresses;
+//! `set_*` helpers write to a given one.
+
+use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_pubkey::Pubkey;
+
+use super::{
+    associated_token_address, mint_data, token_account_data, MINT_ACCOUNT_SIZE,
+    MINT_DECIMALS_OFFSET, MINT_IS_INITIALIZED_OFFSET, TOKEN_2022_PROGRAM_ID, TOKEN_ACCOUNT_SIZE,
+    TOKEN_ACCOUNT_STATE_OFFSET, TOKEN_PROGRAM_ID,
+};
+use crate::error::SeashellError;
+use crate::seashell::Seashell;
+use crate::spl::extensions::ExtensionAccountBuilder;
+
+const MINT_SUPPLY_OFFSET: usize = 36;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// Writes `value` as the `COption<Pubkey>` at the start of `data`.
+fn write_coption_pubkey(data: &mut [u8], value: Option<Pubkey>) {
+    if let Some(pubkey) = value {
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(pubkey.as_ref());
+    }
+}
+
+impl Seashell {
+    /// The token program owning `mint`, or SPL Token if the mint doesn't exist yet.
+    fn token_program_of(&self, mint: &Pubkey) -> Pubkey {
+        self.accounts_db
+            .account_maybe(mint)
+            .map(|account| *account.owner())
+            .unwrap_or(TOKEN_PROGRAM_ID)
+    }
+
+    fn set_token_state(&self, pubkey: Pubkey, token_program: &Pubkey, data: Vec<u8>) {
+        let rent = self.accounts_db.sysvars.rent();
+        let mut account =
+            AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), token_program);
+        account.set_data_from_slice(&data);
+        self.set_account_from_account_shared_data(pubkey, account);
+    }
+
+    /// Sets an SPL Token mint with `supply` and `authority` as its mint authority, if any.
+    pub fn set_mint(&self, pubkey: Pubkey, decimals: u8, authority: Option<Pubkey>, supply: u64) {
+        let mut data = vec![0; MINT_ACCOUNT_SIZE];
+        write_coption_pubkey(&mut data, authority);
+        data[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8].copy_from_slice(&supply.to_le_bytes());
+        data[MINT_DECIMALS_OFFSET] = decimals;
+        data[MINT_IS_INITIALIZED_OFFSET] = 1;
+        self.set_token_state(pubkey, &TOKEN_PROGRAM_ID, data);
+    }
+
+    /// Sets a token account of `mint` holding `amount`, leaving the mint's supply as it is.
+    pub fn set_token_account(&self, pubkey: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) {
+        let mut data = vec![0; TOKEN_ACCOUNT_SIZE];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&amount.to_le_bytes());
+        data[TOKEN_ACCOUNT_STATE_OFFSET] = 1; // AccountState::Initialized
+        self.set_token_state(pubkey, &self.token_program_of(&mint), data);
+    }
+
+    /// Creates an SPL Token mint with no supply, minted by `authority`.
+    pub fn create_mint(&self, decimals: u8, authority: Pubkey) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.set_mint(mint, decimals, Some(authority), 0);
+        mint
+    }
+
+    /// Creates a token account of `mint` for `owner`, with `amount` minted to it.
+    pub fn create_token_account(&self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let pubkey = Pubkey::new_unique();
+        self.set_token_account(pubkey, mint, owner, 0);
+        if let Err(error) = self.mint_to(mint, pubkey, amount) {
+            panic!("Failed to mint to a new token account: {error}");
+        }
+        pubkey
+    }
+
+    /// Creates the empty associated token account of `owner` for `mint`.
+    pub fn create_ata(&self, owner: Pubkey, mint: Pubkey) -> Pubkey {
+        let ata = associated_token_address(&owner, &mint, &self.token_program_of(&mint));
+        self.set_token_account(ata, mint, owner, 0);
+        ata
+    }
+
+    /// The associated token account of `owner` for `mint` under `token_program`, created empty
+    /// as the associated token program would if it doesn't exist yet: Token-2022 accounts get
+    /// `ImmutableOwner` and the extensions the mint requires.
+    pub fn get_or_create_ata(&self, owner: Pubkey, mint: Pubkey, token_program: Pubkey) -> Pubkey {
+        let ata = associated_token_address(&owner, &mint, &token_program);
+        if self.accounts_db.account_maybe(&ata).is_some() {
+            return ata;
+        }
+        if token_program == TOKEN_2022_PROGRAM_ID {
+            let builder = ExtensionAccountBuilder::new(mint, owner, 0).immutable_owner();
+            self.set_extension_token_account(ata, &builder);
+        } else {
+            let mut data = vec![0; TOKEN_ACCOUNT_SIZE];
+            data[..32].copy_from_slice(mint.as_ref());
+            data[32..64].copy_from_slice(owner.as_ref());
+            data[TOKEN_ACCOUNT_STATE_OFFSET] = 1; // AccountState::Initialized
+            self.set_token_state(ata, &token_program, data);
+        }
+        ata
+    }
+
+    /// Adds `amount` to `token_account` and to the supply of `mint`, as a `MintTo` would, without
+    /// checking the mint authority.
+    pub fn mint_to(
+        &self,
+        mint: Pubkey,
+        token_account: Pubkey,
+        amount: u64,
+    ) -> Result<(), SeashellError> {
+        let mut mint_account = self.accounts_db.try_account(&mint)?;
+        let mut account = self.accounts_db.try_account(&token_account)?;
+        let supply = mint_data(&mint_account)
+            .map(|data| u64_at(data, MINT_SUPPLY_OFFSET))
+            .ok_or_else(|| SeashellError::Custom(format!("{mint} is not a mint")))?;
+        let balance = token_account_data(&account)
+            .filter(|data| data[..32] == mint.to_bytes())
+            .map(|data| u64_at(data, TOKEN_ACCOUNT_AMOUNT_OFFSET))
+            .ok_or_else(|| {
+                SeashellError::Custom(format!("{token_account} is not a token account of {mint}"))
+            })?;
+        let overflow = || SeashellError::Custom(format!("Minting {amount} of {mint} overflows"));
+        let supply = supply.checked_add(amount).ok_or_else(overflow)?;
+        let balance = balance.checked_add(amount).ok_or_else(overflow)?;
+
+        mint_account.data_as_mut_slice()[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8]
+            .copy_from_slice(&supply.to_le_bytes());
+        account.data_as_mut_slice()[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&balance.to_le_bytes());
+        self.set_account_from_account_shared_data(mint, mint_account);
+        self.set_account_from_account_shared_data(token_account, account);
+        Ok(())
+    }
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spl::extensions::ExtensionMintBuilder;
+    use crate::spl::ASSOCIATED_TOKEN_PROGRAM_ID;
+
+    #[test]
+    fn test_token_fixtures() {
+        let seashell = Seashell::new();
+        let (authority, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = seashell.create_mint(6, authority);
+        let account = seashell.create_token_account(mint, owner, 1_500_000);
+        let ata = seashell.create_ata(owner, mint);
+        assert_eq!(
+            ata,
+            Pubkey::find_program_address(
+                &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+                &ASSOCIATED_TOKEN_PROGRAM_ID
+            )
+            .0
+        );
+        seashell.mint_to(mint, ata, 500_000).unwrap();
+
+        let mint_account = seashell.accounts_db.account_must(&mint);
+        assert_eq!(mint_account.owner(), &TOKEN_PROGRAM_ID);
+        assert_eq!(&mint_account.data()[4..36], authority.as_ref());
+        assert_eq!(u64_at(mint_account.data(), MINT_SUPPLY_OFFSET), 2_000_000);
+        let balances = crate::spl::token_balances(
+            &[
+                (account, seashell.accounts_db.account_must(&account)),
+                (ata, seashell.accounts_db.account_must(&ata)),
+            ],
+            |_| Some(6),
+        );
+        assert_eq!(balances[0].amount, 1_500_000);
+        assert_eq!((balances[1].owner, balances[1].amount), (owner, 500_000));
+
+        assert_eq!(seashell.get_or_create_ata(owner, mint, TOKEN_PROGRAM_ID), ata);
+        assert_eq!(seashell.token_balance(&ata), 500_000);
+
+        assert!(seashell.mint_to(Pubkey::new_unique(), ata, 1).is_err());
+        assert!(seashell.mint_to(account, ata, 1).is_err());
+        assert!(seashell.mint_to(mint, ata, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_get_or_create_ata() {
+        let seashell = Seashell::new();
+        let owner = Pubkey::new_unique();
+        let mint = seashell.create_mint(6, Pubkey::new_unique());
+        let ata = seashell.get_or_create_ata(owner, mint, TOKEN_PROGRAM_ID);
+        assert_eq!(ata, associated_token_address(&owner, &mint, &TOKEN_PROGRAM_ID));
+        let account = seashell.accounts_db.account_must(&ata);
+        assert_eq!(
+            (account.owner(), account.data().len()),
+            (&TOKEN_PROGRAM_ID, TOKEN_ACCOUNT_SIZE)
+        );
+        assert_eq!(seashell.token_balance(&ata), 0);
+
+        let mint = Pubkey::new_unique();
+        seashell.set_extension_mint(mint, &ExtensionMintBuilder::new(6).transfer_fee(50, 1_000));
+        let ata = seashell.get_or_create_ata(owner, mint, TOKEN_2022_PROGRAM_ID);
+        assert_eq!(ata, associated_token_address(&owner, &mint, &TOKEN_2022_PROGRAM_ID));
+        let account = seashell.accounts_db.account_must(&ata);
+        assert_eq!(account.owner(), &TOKEN_2022_PROGRAM_ID);
+        assert!(crate::spl::extension(account.data(), 7).is_some()); // ImmutableOwner
+        assert!(crate::spl::extension(account.data(), 2).is_some()); // TransferFeeAmount
+        assert_eq!(seashell.token_balance(&ata), 0);
+    }
+}
@@ -1,5 +1,13 @@
+pub mod balance;
+#[cfg(feature = "confidential")]
+pub mod confidential;
+pub mod extensions;
+pub mod fixtures;
+
+use solana_account::{AccountSharedData, ReadableAccount};
 use solana_pubkey::{pubkey, Pubkey};
 
+use crate::layout::{AccountLayout, FieldType, LayoutRegistry};
 use crate::Seashell;
 
 pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
@@ -7,6 +15,17 @@ pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
     pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
 pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
 
+pub const MINT_ACCOUNT_SIZE: usize = 82;
+pub const TOKEN_ACCOUNT_SIZE: usize = 165;
+
+/// Offset of the Token-2022 `AccountType` byte in mints and token accounts with extensions.
+const ACCOUNT_TYPE_OFFSET: usize = TOKEN_ACCOUNT_SIZE;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+
 pub fn load(seashell: &mut Seashell) {
     seashell.load_program_from_bytes(TOKEN_PROGRAM_ID, include_bytes!("elfs/tokenkeg.so"));
     seashell.load_program_from_bytes(
@@ -21,4 +40,204 @@ pub fn load_p_token(seashell: &mut Seashell) {
         TOKEN_PROGRAM_ID,
         include_bytes!("elfs/ptoken.so"),
     );
-}
\ No newline at end of file
+}
+
+/// Registers the base `Mint` and `Account` layouts for both token programs.
+pub fn register_layouts(registry: &mut LayoutRegistry) {
+    for program_id in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        registry.register(
+            program_id,
+            AccountLayout::new("Mint")
+                .data_len(MINT_ACCOUNT_SIZE)
+                .field("mint_authority", FieldType::COptionPubkey)
+                .field("supply", FieldType::U64)
+                .field("decimals", FieldType::U8)
+                .field("is_initialized", FieldType::Bool)
+                .field("freeze_authority", FieldType::COptionPubkey),
+        );
+        registry.register(
+            program_id,
+            AccountLayout::new("TokenAccount")
+                .data_len(TOKEN_ACCOUNT_SIZE)
+                .field("mint", FieldType::Pubkey)
+                .field("owner", FieldType::Pubkey)
+                .field("amount", FieldType::U64)
+                .field("delegate", FieldType::COptionPubkey)
+                .field("state", FieldType::U8)
+                .field("is_native", FieldType::COptionU64)
+                .field("delegated_amount", FieldType::U64)
+                .field("close_authority", FieldType::COptionPubkey),
+        );
+    }
+}
+
+/// The associated token account of `owner` for `mint` under `token_program`.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// The value of the `extension_type` extension in the data of a Token-2022 account.
+pub(crate) fn extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + 4 <= data.len() {
+        let found = u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+        let len = u16::from_le_bytes(data[offset + 2..offset + 4].try_into().unwrap()) as usize;
+        if found == 0 {
+            return None;
+        }
+        let value = data.get(offset + 4..offset + 4 + len)?;
+        if found == extension_type {
+            return Some(value);
+        }
+        offset += 4 + len;
+    }
+    None
+}
+
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == TOKEN_PROGRAM_ID || *program_id == TOKEN_2022_PROGRAM_ID
+}
+
+/// The data of `account` if it is an initialized mint of either token program, extensions
+/// included.
+fn mint_data(account: &AccountSharedData) -> Option<&[u8]> {
+    let data = account.data();
+    let is_mint = data.len() == MINT_ACCOUNT_SIZE
+        || (data.len() > ACCOUNT_TYPE_OFFSET && data[ACCOUNT_TYPE_OFFSET] == ACCOUNT_TYPE_MINT);
+    (is_token_program(account.owner()) && is_mint && data[MINT_IS_INITIALIZED_OFFSET] != 0)
+        .then_some(data)
+}
+
+/// The data of `account` if it is an initialized token account of either token program,
+/// extensions included.
+fn token_account_data(account: &impl ReadableAccount) -> Option<&[u8]> {
+    let data = account.data();
+    let is_token_account = data.len() == TOKEN_ACCOUNT_SIZE
+        || (data.len() > ACCOUNT_TYPE_OFFSET && data[ACCOUNT_TYPE_OFFSET] == ACCOUNT_TYPE_ACCOUNT);
+    (is_token_program(account.owner()) && is_token_account && data[TOKEN_ACCOUNT_STATE_OFFSET] != 0)
+        .then_some(data)
+}
+
+pub fn mint_decimals(account: &AccountSharedData) -> Option<u8> {
+    mint_data(account).map(|data| data[MINT_DECIMALS_OFFSET])
+}
+
+/// The token balance of one account, as reported in the `preTokenBalances` and
+/// `postTokenBalances` of `getTransaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalance {
+    /// Index of the account among the transaction accounts.
+    pub account_index: usize,
+    pub account: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub program_id: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl TokenBalance {
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(i32::from(self.decimals))
+    }
+
+    /// The amount in whole tokens with trailing zeros trimmed, like RPC's `uiAmountString`.
+    pub fn ui_amount_string(&self) -> String {
+        let decimals = usize::from(self.decimals);
+        if decimals == 0 {
+            return self.amount.to_string();
+        }
+        let digits = format!("{:0>width$}", self.amount, width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            whole.to_string()
+        } else {
+            format!("{whole}.{fraction}")
+        }
+    }
+}
+
+/// Decodes every token account among `accounts`. Decimals come from a mint among `accounts` or
+/// from `decimals_of`; like RPC, accounts whose mint can't be found are left out.
+pub fn token_balances(
+    accounts: &[(Pubkey, AccountSharedData)],
+    decimals_of: impl Fn(&Pubkey) -> Option<u8>,
+) -> Vec<TokenBalance> {
+    accounts
+        .iter()
+        .enumerate()
+        .filter_map(|(account_index, (pubkey, account))| {
+            let data = token_account_data(account)?;
+            let mint = Pubkey::try_from(&data[..32]).unwrap();
+            let decimals = accounts
+                .iter()
+                .find(|(key, _)| *key == mint)
+                .and_then(|(_, mint)| mint_decimals(mint))
+                .or_else(|| decimals_of(&mint))?;
+            Some(TokenBalance {
+                account_index,
+                account: *pubkey,
+                mint,
+                owner: Pubkey::try_from(&data[32..64]).unwrap(),
+                program_id: *account.owner(),
+                amount: u64::from_le_bytes(data[64..72].try_into().unwrap()),
+                decimals,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::WritableAccount;
+
+    use super::*;
+
+    #[test]
+    fn test_token_balances() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut mint_account = AccountSharedData::new(1, MINT_ACCOUNT_SIZE, &TOKEN_PROGRAM_ID);
+        mint_account.data_as_mut_slice()[MINT_DECIMALS_OFFSET] = 6;
+        mint_account.data_as_mut_slice()[MINT_IS_INITIALIZED_OFFSET] = 1;
+
+        let mut data = vec![0; TOKEN_ACCOUNT_SIZE];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&1_500_000u64.to_le_bytes());
+        data[TOKEN_ACCOUNT_STATE_OFFSET] = 1;
+        let mut token_account = AccountSharedData::new(1, TOKEN_ACCOUNT_SIZE, &TOKEN_PROGRAM_ID);
+        token_account.set_data_from_slice(&data);
+        let account = Pubkey::new_unique();
+
+        // Without the mint among the accounts, decimals come from the fallback.
+        let accounts = vec![(account, token_account)];
+        assert!(token_balances(&accounts, |_| None).is_empty());
+        let balances = token_balances(&accounts, |key| (*key == mint).then_some(6));
+        assert_eq!(balances.len(), 1);
+
+        let accounts = vec![(mint, mint_account), accounts[0].clone()];
+        let balances = token_balances(&accounts, |_| None);
+        assert_eq!(
+            balances,
+            vec![TokenBalance {
+                account_index: 1,
+                account,
+                mint,
+                owner,
+                program_id: TOKEN_PROGRAM_ID,
+                amount: 1_500_000,
+                decimals: 6,
+            }]
+        );
+        assert_eq!(balances[0].ui_amount(), 1.5);
+        assert_eq!(balances[0].ui_amount_string(), "1.5");
+        let small = TokenBalance { amount: 5, ..balances[0].clone() };
+        assert_eq!(small.ui_amount_string(), "0.000005");
+    }
+}
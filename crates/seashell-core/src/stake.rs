@@ -0,0 +1,291 @@
+//! Stake accounts, and canned stake states for protocols computing stake-derived values such as
+//! LST exchange rates or reward estimates.
+//!
+//! How much of a delegation is effective depends on the cluster-wide totals in StakeHistory, so
+//! an activating or deactivating stake account means little without a matching history.
+//! [`Seashell::set_stake_edge_case`] writes the stake account, its vote account and a
+//! StakeHistory that together reproduce one [`StakeEdgeCase`] at the current epoch:
+//!
+//! ```ignore
+//! seashell.warp_to_slot(10 * DEFAULT_SLOTS_PER_EPOCH);
+//! let status =
+//!     seashell.set_stake_edge_case(StakeEdgeCase::DeactivatedWhileActivating, stake, vote);
+//! // Only the part that had activated is cooling down; the rest never became effective.
+//! assert!(status.deactivating < STAKE_EDGE_CASE_LAMPORTS);
+//! ```
+
+use solana_account::AccountSharedData;
+use solana_clock::Epoch;
+use solana_native_token::LAMPORTS_PER_SOL;
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_stake_interface::stake_flags::StakeFlags;
+use solana_stake_interface::stake_history::{StakeHistory, StakeHistoryEntry, MAX_ENTRIES};
+use solana_stake_interface::state::{
+    Authorized, Delegation, Meta, Stake, StakeActivationStatus, StakeStateV2,
+};
+
+use crate::seashell::Seashell;
+use crate::vote::VoteAccountBuilder;
+
+/// The stake delegated by every [`StakeEdgeCase`].
+pub const STAKE_EDGE_CASE_LAMPORTS: u64 = 1_000 * LAMPORTS_PER_SOL;
+
+/// Effective stake of the cluster in the histories [`StakeEdgeCase`]s write.
+const CLUSTER_STAKE: u64 = 400_000_000 * LAMPORTS_PER_SOL;
+
+/// Credits the vote account of a [`StakeEdgeCase`] earns per epoch, unless it didn't vote.
+const CREDITS_PER_EPOCH: u64 = 400_000;
+
+/// Builds delegated stake accounts.
+#[derive(Debug, Clone)]
+pub struct StakeAccountBuilder {
+    staker: Pubkey,
+    withdrawer: Pubkey,
+    delegation: Delegation,
+    credits_observed: u64,
+    lamports: Option<u64>,
+}
+
+impl StakeAccountBuilder {
+    /// Creates a builder for `stake` lamports delegated to `voter_pubkey` since genesis, with
+    /// `authority` as staker and withdrawer.
+    pub fn new(authority: Pubkey, voter_pubkey: Pubkey, stake: u64) -> Self {
+        StakeAccountBuilder {
+            staker: authority,
+            withdrawer: authority,
+            delegation: Delegation::new(&voter_pubkey, stake, 0),
+            credits_observed: 0,
+            lamports: None,
+        }
+    }
+
+    pub fn withdrawer(mut self, withdrawer: Pubkey) -> Self {
+        self.withdrawer = withdrawer;
+        self
+    }
+
+    pub fn activation_epoch(mut self, epoch: Epoch) -> Self {
+        self.delegation.activation_epoch = epoch;
+        self
+    }
+
+    pub fn deactivation_epoch(mut self, epoch: Epoch) -> Self {
+        self.delegation.deactivation_epoch = epoch;
+        self
+    }
+
+    /// The vote account credits rewards were last paid up to.
+    pub fn credits_observed(mut self, credits_observed: u64) -> Self {
+        self.credits_observed = credits_observed;
+        self
+    }
+
+    /// Overrides the account balance; defaults to the rent-exempt reserve plus the stake.
+    pub fn lamports(mut self, lamports: u64) -> Self {
+        self.lamports = Some(lamports);
+        self
+    }
+
+    pub fn delegation(&self) -> Delegation {
+        self.delegation
+    }
+
+    pub fn build(&self, rent: &Rent) -> AccountSharedData {
+        let space = StakeStateV2::size_of();
+        let rent_exempt_reserve = rent.minimum_balance(space);
+        let meta = Meta {
+            rent_exempt_reserve,
+            authorized: Authorized { staker: self.staker, withdrawer: self.withdrawer },
+            ..Meta::default()
+        };
+        let stake = Stake { delegation: self.delegation, credits_observed: self.credits_observed };
+        let lamports = self
+            .lamports
+            .unwrap_or_else(|| rent_exempt_reserve.saturating_add(self.delegation.stake));
+        AccountSharedData::new_data_with_space(
+            lamports,
+            &StakeStateV2::Stake(meta, stake, StakeFlags::empty()),
+            space,
+            &solana_sdk_ids::stake::id(),
+        )
+        .expect("Failed to serialize stake state")
+    }
+}
+
+/// Stake states that are hard to set up by hand because they depend on StakeHistory. Each
+/// delegates [`STAKE_EDGE_CASE_LAMPORTS`] and needs the clock at epoch 3 or later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StakeEdgeCase {
+    /// Delegated last epoch while the cluster was warming up far more stake than it could
+    /// activate at once, and deactivated this epoch. Only the part that had become effective
+    /// cools down; the rest is neither effective nor activating anymore.
+    DeactivatedWhileActivating,
+    /// Delegated two epochs ago into a warmup queue twice the size of the cluster's effective
+    /// stake, so it is still mostly activating.
+    WarmupQueue,
+    /// Deactivated two epochs ago while half the cluster was cooling down, so it is still
+    /// mostly deactivating.
+    CooldownQueue,
+    /// Fully active, delegated to a vote account that earned no credits in the last two
+    /// epochs, so it earns no rewards for them.
+    ZeroCreditEpochs,
+}
+
+impl StakeEdgeCase {
+    pub const ALL: [StakeEdgeCase; 4] = [
+        StakeEdgeCase::DeactivatedWhileActivating,
+        StakeEdgeCase::WarmupQueue,
+        StakeEdgeCase::CooldownQueue,
+        StakeEdgeCase::ZeroCreditEpochs,
+    ];
+
+    /// The stake account of the case at `epoch`, owned by `authority`.
+    pub fn stake_account(
+        &self,
+        authority: Pubkey,
+        vote_account: Pubkey,
+        epoch: Epoch,
+    ) -> StakeAccountBuilder {
+        let builder = StakeAccountBuilder::new(authority, vote_account, STAKE_EDGE_CASE_LAMPORTS);
+        match self {
+            StakeEdgeCase::DeactivatedWhileActivating => builder
+                .activation_epoch(epoch - 1)
+                .deactivation_epoch(epoch),
+            StakeEdgeCase::WarmupQueue => builder.activation_epoch(epoch - 2),
+            StakeEdgeCase::CooldownQueue => builder.deactivation_epoch(epoch - 2),
+            StakeEdgeCase::ZeroCreditEpochs => {
+                builder.credits_observed(CREDITS_PER_EPOCH * (epoch - 1))
+            }
+        }
+    }
+
+    /// The vote account of the case at `epoch`, voting every epoch except the last two for
+    /// [`StakeEdgeCase::ZeroCreditEpochs`].
+    pub fn vote_account(&self, node_pubkey: Pubkey, epoch: Epoch) -> VoteAccountBuilder {
+        let mut credits = vec![CREDITS_PER_EPOCH; epoch as usize + 1];
+        if *self == StakeEdgeCase::ZeroCreditEpochs {
+            credits[epoch as usize - 1..].fill(0);
+        }
+        VoteAccountBuilder::new(node_pubkey).credits_per_epoch(0, &credits)
+    }
+
+    /// A history of every epoch before `epoch`: a cluster of 400M SOL activating and
+    /// deactivating 1% of it per epoch, with the anomaly of the case in the epochs it spans.
+    pub fn stake_history(&self, epoch: Epoch) -> StakeHistory {
+        let usual = StakeHistoryEntry {
+            effective: CLUSTER_STAKE,
+            activating: CLUSTER_STAKE / 100,
+            deactivating: CLUSTER_STAKE / 100,
+        };
+        let (anomaly, anomalous_epochs) = match self {
+            StakeEdgeCase::DeactivatedWhileActivating => {
+                (StakeHistoryEntry { activating: CLUSTER_STAKE, ..usual.clone() }, 1)
+            }
+            StakeEdgeCase::WarmupQueue => {
+                (StakeHistoryEntry { activating: 2 * CLUSTER_STAKE, ..usual.clone() }, 2)
+            }
+            StakeEdgeCase::CooldownQueue => {
+                (StakeHistoryEntry { deactivating: CLUSTER_STAKE / 2, ..usual.clone() }, 2)
+            }
+            StakeEdgeCase::ZeroCreditEpochs => (usual.clone(), 0),
+        };
+
+        let mut stake_history = StakeHistory::default();
+        for past in epoch.saturating_sub(MAX_ENTRIES as u64)..epoch {
+            let entry = if past + anomalous_epochs >= epoch { &anomaly } else { &usual };
+            stake_history.add(past, entry.clone());
+        }
+        stake_history
+    }
+}
+
+impl Seashell {
+    /// Sets a stake account delegated to `vote_account` as in `case` at the current epoch,
+    /// along with the vote account and a StakeHistory to match, replacing the current one.
+    /// Returns the activation status the stake program computes for the stake account.
+    ///
+    /// Panics if the clock is before epoch 3.
+    pub fn set_stake_edge_case(
+        &self,
+        case: StakeEdgeCase,
+        stake_account: Pubkey,
+        vote_account: Pubkey,
+    ) -> StakeActivationStatus {
+        let sysvars = &self.accounts_db.sysvars;
+        let epoch = sysvars.clock().epoch;
+        assert!(epoch >= 3, "Stake edge cases need the clock at epoch 3 or later, not {epoch}");
+
+        let stake_history = case.stake_history(epoch);
+        let builder = case.stake_account(stake_account, vote_account, epoch);
+        self.set_account_from_account_shared_data(stake_account, builder.build(&sysvars.rent()));
+        self.set_vote_account(vote_account, &case.vote_account(vote_account, epoch));
+
+        let new_rate_activation_epoch = self
+            .feature_set
+            .new_warmup_cooldown_rate_epoch(&sysvars.epoch_schedule());
+        let status = builder.delegation().stake_activating_and_deactivating(
+            epoch,
+            &stake_history,
+            new_rate_activation_epoch,
+        );
+        sysvars.set_stake_history(stake_history);
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::ReadableAccount;
+    use solana_clock::DEFAULT_SLOTS_PER_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn test_stake_edge_cases() {
+        let seashell = Seashell::new();
+        seashell.warp_to_slot(10 * DEFAULT_SLOTS_PER_EPOCH);
+        let stake_account = Pubkey::new_unique();
+        let vote_account = Pubkey::new_unique();
+
+        let status = seashell.set_stake_edge_case(
+            StakeEdgeCase::DeactivatedWhileActivating,
+            stake_account,
+            vote_account,
+        );
+        assert_eq!(status.activating, 0);
+        assert!(status.deactivating > 0 && status.deactivating < STAKE_EDGE_CASE_LAMPORTS / 2);
+        let account = seashell.account(&stake_account);
+        let state: StakeStateV2 = bincode::deserialize(account.data()).unwrap();
+        let delegation = state.delegation().unwrap();
+        assert_eq!((delegation.activation_epoch, delegation.deactivation_epoch), (9, 10));
+        assert_eq!(delegation.voter_pubkey, vote_account);
+        assert!(seashell
+            .accounts_db
+            .sysvars
+            .stake_history()
+            .get(9)
+            .is_some());
+
+        let status =
+            seashell.set_stake_edge_case(StakeEdgeCase::WarmupQueue, stake_account, vote_account);
+        assert!(status.effective < status.activating);
+
+        let status =
+            seashell.set_stake_edge_case(StakeEdgeCase::CooldownQueue, stake_account, vote_account);
+        assert!(status.deactivating > STAKE_EDGE_CASE_LAMPORTS / 2);
+
+        let status = seashell.set_stake_edge_case(
+            StakeEdgeCase::ZeroCreditEpochs,
+            stake_account,
+            vote_account,
+        );
+        assert_eq!(status, StakeActivationStatus::with_effective(STAKE_EDGE_CASE_LAMPORTS));
+        let vote_state = solana_vote_interface::state::VoteStateV3::deserialize(
+            seashell.account(&vote_account).data(),
+        )
+        .unwrap();
+        let credits = vote_state.epoch_credits.last().unwrap();
+        assert_eq!(credits.1, CREDITS_PER_EPOCH * 9);
+    }
+}
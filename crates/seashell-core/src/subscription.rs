@@ -0,0 +1,161 @@
+//! `accountSubscribe` websocket subscriptions that keep scenario accounts close to the chain, for
+//! simulations re-run against near-real-time state without refetching it every time.
+//!
+//! Updates are buffered as they arrive and only applied by [`Seashell::refresh`], so state never
+//! changes in the middle of a simulation:
+//!
+//! ```ignore
+//! seashell.load_temporary_scenario();
+//! seashell.subscribe_accounts([pool, oracle])?;
+//! loop {
+//!     seashell.refresh();
+//!     let quote = seashell.process_instruction(swap.clone());
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use solana_account::{Account, AccountSharedData};
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+use solana_pubsub_client::pubsub_client::{AccountSubscription, PubsubClient};
+use solana_rpc_client_api::config::RpcAccountInfoConfig;
+
+use crate::error::SeashellError;
+use crate::seashell::Seashell;
+
+/// The websocket URL an RPC node serves next to `rpc_url`: the same host with the `ws` scheme,
+/// and the port after the RPC port if one is given, as `solana-test-validator` does.
+pub fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{host}:{}", port.saturating_add(1)),
+            Err(_) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+    format!("{scheme}://{authority}{path}")
+}
+
+/// Live `accountSubscribe` subscriptions and the updates they received since the last drain.
+pub struct AccountSubscriptions {
+    url: String,
+    commitment: CommitmentConfig,
+    subscriptions: HashMap<Pubkey, AccountSubscription>,
+}
+
+impl AccountSubscriptions {
+    pub fn new(url: String, commitment: CommitmentConfig) -> Self {
+        AccountSubscriptions { url, commitment, subscriptions: HashMap::new() }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Subscribes to `pubkey` unless already subscribed.
+    pub fn subscribe(&mut self, pubkey: Pubkey) -> Result<(), SeashellError> {
+        if self.subscriptions.contains_key(&pubkey) {
+            return Ok(());
+        }
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.commitment),
+            ..RpcAccountInfoConfig::default()
+        };
+        let subscription = PubsubClient::account_subscribe(&self.url, &pubkey, Some(config))
+            .map_err(|e| {
+                SeashellError::Custom(format!(
+                    "Failed to subscribe to {pubkey} on {}: {e}",
+                    self.url
+                ))
+            })?;
+        self.subscriptions.insert(pubkey, subscription);
+        Ok(())
+    }
+
+    pub fn is_subscribed(&self, pubkey: &Pubkey) -> bool {
+        self.subscriptions.contains_key(pubkey)
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// The latest update of every account that changed since the last drain. Updates that
+    /// can't be decoded are skipped.
+    pub fn drain(&self) -> HashMap<Pubkey, AccountSharedData> {
+        let mut latest = HashMap::new();
+        for (pubkey, (_, receiver)) in &self.subscriptions {
+            if let Some(response) = receiver.try_iter().last() {
+                match response.value.decode::<Account>() {
+                    Some(account) => {
+                        latest.insert(*pubkey, account.into());
+                    }
+                    None => log::warn!("Failed to decode the update of {pubkey}"),
+                }
+            }
+        }
+        latest
+    }
+}
+
+impl Drop for AccountSubscriptions {
+    fn drop(&mut self) {
+        for (pubkey, (mut subscription, _)) in self.subscriptions.drain() {
+            if subscription.shutdown().is_err() {
+                log::warn!("Failed to unsubscribe from {pubkey}");
+            }
+        }
+    }
+}
+
+impl Seashell {
+    /// Subscribes to updates of `pubkeys` on the websocket of the scenario's RPC, or
+    /// `RpcOptions::websocket_url` if set. Updates are applied to the scenario by
+    /// [`Seashell::refresh`].
+    pub fn subscribe_accounts(
+        &mut self,
+        pubkeys: impl IntoIterator<Item = Pubkey>,
+    ) -> Result<(), SeashellError> {
+        self.accounts_db.scenario.subscribe(pubkeys)
+    }
+
+    /// Subscribes to every account the scenario holds.
+    pub fn subscribe_scenario_accounts(&mut self) -> Result<(), SeashellError> {
+        let pubkeys = self.accounts_db.scenario.pubkeys();
+        self.subscribe_accounts(pubkeys)
+    }
+
+    /// Swaps the latest state of every subscribed account that changed into the scenario,
+    /// returning the accounts that did.
+    pub fn refresh(&self) -> Vec<Pubkey> {
+        self.accounts_db.scenario.refresh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8900");
+        assert_eq!(
+            websocket_url("https://mainnet.helius-rpc.com/?api-key=abc"),
+            "wss://mainnet.helius-rpc.com/?api-key=abc"
+        );
+        assert_eq!(websocket_url("http://[::1]:8899/rpc"), "ws://[::1]:8900/rpc");
+    }
+}
@@ -1,17 +1,20 @@
 use parking_lot::RwLock;
 use solana_account::{Account, AccountSharedData, ReadableAccount};
-use solana_clock::Clock;
+use solana_clock::{Clock, DEFAULT_MS_PER_SLOT};
 use solana_epoch_rewards::EpochRewards;
 use solana_epoch_schedule::EpochSchedule;
 use solana_hash::Hash;
 use solana_instruction::{BorrowedAccountMeta, BorrowedInstruction, Instruction};
 use solana_pubkey::Pubkey;
 use solana_rent::Rent;
+use solana_sdk_ids::sysvar::recent_blockhashes;
 use solana_slot_hashes::{SlotHashes, MAX_ENTRIES};
 use solana_stake_interface::stake_history::{StakeHistory, StakeHistoryEntry};
 use solana_sysvar::last_restart_slot::LastRestartSlot;
 use solana_sysvar_id::{SysvarId, ID as SYSVAR};
 
+use crate::blockhash::BlockhashQueue;
+
 pub struct Sysvars {
     clock: RwLock<Clock>,
     epoch_schedule: RwLock<EpochSchedule>,
@@ -20,6 +23,7 @@ pub struct Sysvars {
     slot_hashes: RwLock<SlotHashes>,
     stake_history: RwLock<StakeHistory>,
     last_restart_slot: RwLock<LastRestartSlot>,
+    blockhash_queue: RwLock<BlockhashQueue>,
 }
 
 impl Default for Sysvars {
@@ -47,6 +51,22 @@ impl Default for Sysvars {
             rent: RwLock::new(rent),
             slot_hashes: RwLock::new(slot_hashes),
             stake_history: RwLock::new(stake_history),
+            blockhash_queue: RwLock::new(BlockhashQueue::default()),
+        }
+    }
+}
+
+impl Clone for Sysvars {
+    fn clone(&self) -> Self {
+        Self {
+            clock: RwLock::new(self.clock()),
+            epoch_rewards: RwLock::new(self.epoch_rewards()),
+            epoch_schedule: RwLock::new(self.epoch_schedule()),
+            last_restart_slot: RwLock::new(self.last_restart_slot()),
+            rent: RwLock::new(self.rent()),
+            slot_hashes: RwLock::new(self.slot_hashes()),
+            stake_history: RwLock::new(self.stake_history()),
+            blockhash_queue: RwLock::new(self.blockhash_queue()),
         }
     }
 }
@@ -76,10 +96,36 @@ impl Sysvars {
         self.stake_history.read().clone()
     }
 
+    pub fn set_stake_history(&self, stake_history: StakeHistory) {
+        *self.stake_history.write() = stake_history;
+    }
+
     pub fn last_restart_slot(&self) -> LastRestartSlot {
         self.last_restart_slot.read().clone()
     }
 
+    pub fn blockhash_queue(&self) -> BlockhashQueue {
+        self.blockhash_queue.read().clone()
+    }
+
+    pub fn update_blockhash_queue<T>(&self, f: impl FnOnce(&mut BlockhashQueue) -> T) -> T {
+        f(&mut self.blockhash_queue.write())
+    }
+
+    /// Every sysvar `get` and `set` support.
+    pub fn ids() -> [Pubkey; 8] {
+        [
+            Clock::id(),
+            EpochSchedule::id(),
+            EpochRewards::id(),
+            Rent::id(),
+            SlotHashes::id(),
+            StakeHistory::id(),
+            LastRestartSlot::id(),
+            recent_blockhashes::id(),
+        ]
+    }
+
     pub fn is_sysvar(&self, sysvar: &Pubkey) -> bool {
         sysvar == &Clock::id()
             || sysvar == &EpochSchedule::id()
@@ -88,6 +134,7 @@ impl Sysvars {
             || sysvar == &SlotHashes::id()
             || sysvar == &StakeHistory::id()
             || sysvar == &LastRestartSlot::id()
+            || sysvar == &recent_blockhashes::id()
     }
 
     pub fn set(&self, sysvar: &Pubkey, account: AccountSharedData) {
@@ -113,6 +160,11 @@ impl Sysvars {
             _ if sysvar == &LastRestartSlot::id() => {
                 *self.last_restart_slot.write() = bincode::deserialize(account.data()).unwrap();
             }
+            _ if sysvar == &recent_blockhashes::id() => {
+                self.blockhash_queue
+                    .write()
+                    .set_from_recent_blockhashes(account.data());
+            }
             _ => panic!("Unknown sysvar: {sysvar}"),
         }
     }
@@ -143,6 +195,9 @@ impl Sysvars {
             _ if sysvar == &LastRestartSlot::id() => {
                 AccountSharedData::new_data(0, &*self.last_restart_slot.read(), &SYSVAR).unwrap()
             }
+            _ if sysvar == &recent_blockhashes::id() => {
+                self.blockhash_queue.read().recent_blockhashes_account()
+            }
             _ => panic!("Unknown sysvar: {sysvar}"),
         }
     }
@@ -152,27 +207,81 @@ impl Sysvars {
         clock.slot = slot;
         clock.unix_timestamp = timestamp;
     }
+
+    /// Advances to `slot` as a validator would: the clock moves by [`DEFAULT_MS_PER_SLOT`] per
+    /// slot and rolls over epochs per the `EpochSchedule`, SlotHashes and the blockhash queue gain
+    /// an entry for every skipped slot, and StakeHistory gains one for every completed epoch,
+    /// carrying the latest entry forward.
+    pub fn warp_to_slot(&self, slot: u64) {
+        let epoch_schedule = self.epoch_schedule();
+        let mut clock = self.clock.write();
+        assert!(slot >= clock.slot, "Cannot warp back from slot {} to {slot}", clock.slot);
+
+        let seconds_since = |from: u64| ((slot - from) * DEFAULT_MS_PER_SLOT / 1000) as i64;
+        let epoch = epoch_schedule.get_epoch(slot);
+        let unix_timestamp = clock.unix_timestamp + seconds_since(clock.slot);
+        if epoch != clock.epoch {
+            clock.epoch_start_timestamp =
+                unix_timestamp - seconds_since(epoch_schedule.get_first_slot_in_epoch(epoch));
+        }
+
+        let mut slot_hashes = self.slot_hashes.write();
+        let first_new_slot = (clock.slot + 1).max(slot.saturating_sub(MAX_ENTRIES as u64 - 1));
+        for slot in first_new_slot..=slot {
+            let mut hash = [0; 32];
+            hash[..8].copy_from_slice(&slot.to_le_bytes());
+            slot_hashes.add(slot, Hash::new_from_array(hash));
+        }
+
+        self.blockhash_queue.write().advance(slot - clock.slot);
+
+        let mut stake_history = self.stake_history.write();
+        let latest = stake_history
+            .first()
+            .map(|(_, entry)| entry.clone())
+            .unwrap_or_default();
+        for completed in clock.epoch..epoch {
+            if stake_history.get(completed).is_none() {
+                stake_history.add(completed, latest.clone());
+            }
+        }
+
+        clock.slot = slot;
+        clock.epoch = epoch;
+        clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(slot);
+        clock.unix_timestamp = unix_timestamp;
+    }
 }
 
 pub struct SysvarInstructions;
 
 impl SysvarInstructions {
-    pub fn construct_instructions_account(instruction: &Instruction) -> AccountSharedData {
-        let borrowed_accounts = instruction.accounts.iter().map(|meta| {
-            BorrowedAccountMeta {
-                pubkey: &meta.pubkey,
-                is_signer: meta.is_signer,
-                is_writable: meta.is_writable,
-            }
-        }).collect::<Vec<_>>();
-
-        let borrowed_instruction = BorrowedInstruction {
-            program_id: &instruction.program_id,
-            accounts: borrowed_accounts,
-            data: &instruction.data,
-        };
+    /// The instructions sysvar of a message made of the top-level instructions `processed` before
+    /// `instruction`, then `instruction` itself.
+    pub fn construct_instructions_account(
+        processed: &[Instruction],
+        instruction: &Instruction,
+    ) -> AccountSharedData {
+        let instructions: Vec<_> = processed
+            .iter()
+            .chain(std::iter::once(instruction))
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
 
-        let sysvar_instructions_data = solana_instructions_sysvar::construct_instructions_data(&[borrowed_instruction]);
+        let sysvar_instructions_data =
+            solana_instructions_sysvar::construct_instructions_data(&instructions);
 
         AccountSharedData::from(Account {
             data: sysvar_instructions_data,
@@ -180,4 +289,4 @@ impl SysvarInstructions {
             ..Account::default()
         })
     }
-}
\ No newline at end of file
+}
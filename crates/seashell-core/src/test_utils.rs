@@ -0,0 +1,113 @@
+//! Fixtures shared by the unit tests.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use crate::cli_account::to_keyed_account;
+use crate::seashell::Seashell;
+
+/// The slot [`serve_rpc`] reports.
+pub(crate) const RPC_SLOT: u64 = 100;
+
+/// A System Program transfer of `lamports` from `from` to `to`.
+pub(crate) fn system_transfer(from: Pubkey, to: Pubkey, lamports: u64) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction {
+        program_id: solana_sdk_ids::system_program::id(),
+        accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+        data,
+    }
+}
+
+/// A new account holding 1000 lamports and a new empty one, to transfer between.
+pub(crate) fn transfer_accounts(seashell: &Seashell) -> (Pubkey, Pubkey) {
+    let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+    seashell.set_account(from, Account { lamports: 1000, ..Account::default() });
+    seashell.set_account(to, Account::default());
+    (from, to)
+}
+
+/// A JSON-RPC server on localhost answering `getSlot`, `getAccountInfo` and
+/// `getMultipleAccounts` from `accounts`. Returns its URL and the method of every request served.
+pub(crate) fn serve_rpc(accounts: HashMap<Pubkey, Account>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let accounts = Arc::new(accounts);
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let served = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (accounts, served) = (accounts.clone(), served.clone());
+            std::thread::spawn(move || serve_connection(stream.unwrap(), &accounts, &served));
+        }
+    });
+    (url, requests)
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    accounts: &HashMap<Pubkey, Account>,
+    served: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    // Clients keep the connection alive, so serve requests until they close it.
+    loop {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let request: Value = serde_json::from_slice(&body).unwrap();
+
+        let account = |pubkey: &Value| {
+            let pubkey: Pubkey = pubkey.as_str().unwrap().parse().unwrap();
+            accounts
+                .get(&pubkey)
+                .map(|account| to_keyed_account(&pubkey, account).account)
+        };
+        let params = &request["params"];
+        let context = json!({ "slot": RPC_SLOT });
+        let method = request["method"].as_str().unwrap().to_string();
+        let result = match method.as_str() {
+            "getSlot" => json!(RPC_SLOT),
+            "getAccountInfo" => json!({ "context": context, "value": account(&params[0]) }),
+            "getMultipleAccounts" => {
+                let value: Vec<_> = params[0].as_array().unwrap().iter().map(account).collect();
+                json!({ "context": context, "value": value })
+            }
+            _ => Value::Null,
+        };
+        served.lock().push(method);
+
+        let response = json!({ "jsonrpc": "2.0", "result": result, "id": request["id"] });
+        let response = response.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: \
+             {}\r\n\r\n{response}",
+            response.len()
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+/// The stack height of top-level instructions; CPIs start at 2.
+pub const TRANSACTION_LEVEL_STACK_HEIGHT: usize = 1;
+
+/// One entry of the runtime's instruction trace, either the top-level instruction or a CPI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracedInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+    /// 1 for top-level instructions, 2 for the CPIs they make, and so on.
+    pub stack_height: usize,
+}
+
+impl TracedInstruction {
+    pub fn is_cpi(&self) -> bool {
+        self.stack_height > TRANSACTION_LEVEL_STACK_HEIGHT
+    }
+
+    pub fn to_instruction(&self) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: self.accounts.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+/// Compute units spent in one program invocation, top-level or CPI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputeUnitFrame {
+    pub program_id: Pubkey,
+    pub stack_height: usize,
+    /// Units consumed by the invocation including its CPIs. `None` for builtins, which don't
+    /// report their usage; their cost shows up in the caller's `exclusive_units` instead.
+    pub inclusive_units: Option<u64>,
+    /// Units consumed by the program itself, excluding the CPIs it made.
+    pub exclusive_units: Option<u64>,
+}
+
+/// Rebuilds per-invocation compute usage from the runtime's `invoke [n]` and `consumed` logs.
+/// Frames are returned in invocation order, matching the instruction trace.
+pub(crate) fn compute_unit_frames(logs: &[String]) -> Vec<ComputeUnitFrame> {
+    let mut frames: Vec<ComputeUnitFrame> = Vec::new();
+    // Indices into `frames` of the open invocations, with the units their children consumed.
+    let mut stack: Vec<(usize, u64)> = Vec::new();
+
+    for log in logs {
+        let Some(rest) = log.strip_prefix("Program ") else {
+            continue;
+        };
+        let Some((program_id, event)) = rest.split_once(' ') else {
+            continue;
+        };
+        let Ok(program_id) = program_id.parse::<Pubkey>() else {
+            continue;
+        };
+
+        if let Some(stack_height) = event
+            .strip_prefix("invoke [")
+            .and_then(|height| height.strip_suffix(']'))
+            .and_then(|height| height.parse().ok())
+        {
+            stack.push((frames.len(), 0));
+            frames.push(ComputeUnitFrame {
+                program_id,
+                stack_height,
+                inclusive_units: None,
+                exclusive_units: None,
+            });
+        } else if let Some(consumed) = event
+            .strip_prefix("consumed ")
+            .and_then(|consumed| consumed.split_once(' '))
+            .and_then(|(consumed, _)| consumed.parse::<u64>().ok())
+        {
+            if let Some((index, children)) = stack.last() {
+                frames[*index].inclusive_units = Some(consumed);
+                frames[*index].exclusive_units = Some(consumed.saturating_sub(*children));
+            }
+        } else if event == "success" || event.starts_with("failed") {
+            let Some((index, _)) = stack.pop() else {
+                continue;
+            };
+            if let (Some(units), Some((_, parent_children))) =
+                (frames[index].inclusive_units, stack.last_mut())
+            {
+                *parent_children += units;
+            }
+        }
+    }
+
+    frames
+}
+
+/// Where the time of one execution went, in microseconds.
+///
+/// The VM breakdown covers the top-level invocation; the runtime doesn't report it for CPIs, whose
+/// time is part of the caller's `execute_us`. Programs are verified and compiled once when they are
+/// loaded, not per execution, so that cost never shows up here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionTimings {
+    /// Reading the instruction's accounts and sysvars out of the `AccountsDb`.
+    pub load_accounts_us: u64,
+    /// Serializing account data into the VM's input region.
+    pub serialize_us: u64,
+    pub create_vm_us: u64,
+    /// Time spent running program code in the VM.
+    pub execute_us: u64,
+    /// Copying account data back out of the VM.
+    pub deserialize_us: u64,
+    /// Wall-clock time of the whole runtime call, including builtins and precompiles.
+    pub total_us: u64,
+}
+
+/// Bytes every loaded account counts on top of its data.
+pub const TRANSACTION_ACCOUNT_BASE_SIZE: usize = 64;
+/// Bytes every address lookup table a transaction uses counts.
+pub const ADDRESS_LOOKUP_TABLE_BASE_SIZE: usize = 8248;
+/// Loaded account data a transaction may use without a `SetLoadedAccountsDataSizeLimit`.
+pub const MAX_LOADED_ACCOUNTS_DATA_SIZE_BYTES: u32 = 64 * 1024 * 1024;
+
+/// The account data loaded to run an instruction or transaction, as capped by
+/// `SetLoadedAccountsDataSizeLimit` and priced by loaded-accounts-data-size fees.
+///
+/// Upgradeable programs count their programdata account too. The instructions sysvar is built
+/// on the fly rather than loaded, so it doesn't count.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadedDataSize {
+    /// Data length of every loaded account, in load order.
+    pub accounts: Vec<(Pubkey, usize)>,
+    pub lookup_tables: usize,
+}
+
+impl LoadedDataSize {
+    /// Bytes charged, with [`TRANSACTION_ACCOUNT_BASE_SIZE`] per account and
+    /// [`ADDRESS_LOOKUP_TABLE_BASE_SIZE`] per lookup table.
+    pub fn total(&self) -> usize {
+        self.accounts
+            .iter()
+            .map(|(_, data_len)| data_len + TRANSACTION_ACCOUNT_BASE_SIZE)
+            .sum::<usize>()
+            + self.lookup_tables * ADDRESS_LOOKUP_TABLE_BASE_SIZE
+    }
+
+    /// Adds the accounts that aren't counted yet; an account loaded twice is charged once.
+    pub fn extend(&mut self, accounts: impl IntoIterator<Item = (Pubkey, usize)>) {
+        for (pubkey, data_len) in accounts {
+            if !self.accounts.iter().any(|(counted, _)| *counted == pubkey) {
+                self.accounts.push((pubkey, data_len));
+            }
+        }
+    }
+
+    /// The largest accounts first, to see where an account size optimization pays off.
+    pub fn largest_accounts(&self) -> Vec<(Pubkey, usize)> {
+        let mut accounts = self.accounts.clone();
+        accounts.sort_by(|a, b| b.1.cmp(&a.1));
+        accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_unit_frames() {
+        let outer = Pubkey::new_unique();
+        let inner = Pubkey::new_unique();
+        let system = solana_sdk_ids::system_program::id();
+        let logs: Vec<String> = [
+            format!("Program {outer} invoke [1]"),
+            "Program log: hello".to_string(),
+            format!("Program {inner} invoke [2]"),
+            format!("Program {system} invoke [3]"),
+            format!("Program {system} success"),
+            format!("Program {inner} consumed 3000 of 190000 compute units"),
+            format!("Program {inner} success"),
+            format!("Program {inner} invoke [2]"),
+            format!("Program {inner} consumed 1000 of 180000 compute units"),
+            format!("Program {inner} failed: custom program error: 0x1"),
+            format!("Program {outer} consumed 10000 of 200000 compute units"),
+            format!("Program {outer} failed: custom program error: 0x1"),
+        ]
+        .into();
+
+        let frames = compute_unit_frames(&logs);
+        let units: Vec<_> = frames
+            .iter()
+            .map(|frame| (frame.program_id, frame.stack_height, frame.exclusive_units))
+            .collect();
+        assert_eq!(
+            units,
+            vec![
+                (outer, 1, Some(6000)),
+                (inner, 2, Some(3000)),
+                (system, 3, None),
+                (inner, 2, Some(1000)),
+            ]
+        );
+        assert_eq!(frames[0].inclusive_units, Some(10000));
+    }
+
+    #[test]
+    fn test_loaded_data_size() {
+        let program = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let mut size = LoadedDataSize::default();
+        size.extend([(program, 1000), (account, 165)]);
+        size.extend([(account, 165)]);
+        size.lookup_tables = 1;
+
+        assert_eq!(size.accounts.len(), 2);
+        assert_eq!(size.total(), 1165 + 2 * TRANSACTION_ACCOUNT_BASE_SIZE + 8248);
+        assert_eq!(size.largest_accounts()[0], (program, 1000));
+    }
+}
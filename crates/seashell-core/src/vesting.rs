@@ -0,0 +1,298 @@
+//! Vesting schedules in wall-clock terms, and fixtures of the Bonfida token-vesting program, so
+//! claim logic can be tested at each unlock without re-deriving slot math:
+//!
+//! ```ignore
+//! let start = seashell.fixture_clock().unix_timestamp;
+//! let schedule = VestingSchedule::linear(start, 1_200, "1y").cliff("3mo").period("1mo");
+//! let vesting = seashell.set_token_vesting([7; 31], destination, mint, &schedule);
+//! seashell.warp_forward("3mo");
+//! claim(&mut seashell, &vesting);
+//! assert_eq!(token_amount(&seashell, &destination), schedule.vested_at(now(&seashell)));
+//! ```
+//!
+//! Durations are a [`Duration`] or text like `"90d"` or `"1y 6mo"`; see [`parse_duration`].
+//! [`Seashell::warp_forward`] moves the clock by whole slots, as a validator would, so the
+//! clock lands on or just past the requested time.
+
+use std::time::Duration;
+
+use solana_account::AccountSharedData;
+use solana_clock::DEFAULT_MS_PER_SLOT;
+use solana_pubkey::{pubkey, Pubkey};
+
+use crate::error::SeashellError;
+use crate::seashell::Seashell;
+use crate::spl::{associated_token_address, TOKEN_PROGRAM_ID};
+
+pub const TOKEN_VESTING_PROGRAM_ID: Pubkey =
+    pubkey!("CChTq6PthWU82YZkbveA3WDf7s97BWhBK4Vx9bmsT743");
+
+/// Length of the token-vesting header: destination, mint and `is_initialized`.
+const VESTING_HEADER_LEN: usize = 65;
+const VESTING_SCHEDULE_LEN: usize = 16;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Parses a duration written as numbers with units, optionally separated by spaces: `s`, `m`,
+/// `h`, `d`, `w`, `mo` (30 days) and `y` (365 days), e.g. `"90d"`, `"1y 6mo"` or `"1h30m"`.
+pub fn parse_duration(text: &str) -> Result<Duration, SeashellError> {
+    let error = || SeashellError::Custom(format!("Invalid duration {text:?}"));
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(error());
+    }
+    let mut seconds = 0u64;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount: u64 = rest[..digits].parse().map_err(|_| error())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "s" => 1,
+            "m" => MINUTE,
+            "h" => HOUR,
+            "d" => DAY,
+            "w" => 7 * DAY,
+            "mo" => 30 * DAY,
+            "y" => 365 * DAY,
+            _ => return Err(error()),
+        };
+        seconds = amount
+            .checked_mul(unit)
+            .and_then(|amount| seconds.checked_add(amount))
+            .ok_or_else(error)?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// A duration given as a [`Duration`] or as text [`parse_duration`] reads. Invalid text panics,
+/// as a typo in a test fixture should.
+pub trait VestingDuration {
+    fn to_duration(&self) -> Duration;
+}
+
+impl VestingDuration for Duration {
+    fn to_duration(&self) -> Duration {
+        *self
+    }
+}
+
+impl VestingDuration for &str {
+    fn to_duration(&self) -> Duration {
+        parse_duration(self).unwrap()
+    }
+}
+
+fn seconds(duration: impl VestingDuration) -> i64 {
+    duration.to_duration().as_secs() as i64
+}
+
+/// Tokens unlocking linearly from `start` over `duration`, in steps of `period`, with nothing
+/// unlocked before the cliff. With neither a cliff nor a period, every second unlocks its share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VestingSchedule {
+    pub start: i64,
+    pub total: u64,
+    pub duration: i64,
+    pub cliff: i64,
+    pub period: i64,
+}
+
+impl VestingSchedule {
+    pub fn linear(start: i64, total: u64, duration: impl VestingDuration) -> Self {
+        VestingSchedule { start, total, duration: seconds(duration).max(1), cliff: 0, period: 1 }
+    }
+
+    /// Everything unlocks at once after `duration`.
+    pub fn cliff_only(start: i64, total: u64, duration: impl VestingDuration) -> Self {
+        let schedule = Self::linear(start, total, duration);
+        schedule
+            .cliff_at(schedule.duration)
+            .period_of(schedule.duration)
+    }
+
+    /// Unlocks nothing until `cliff` after the start, then what has vested by then.
+    pub fn cliff(self, cliff: impl VestingDuration) -> Self {
+        self.cliff_at(seconds(cliff))
+    }
+
+    /// Unlocks in steps of `period`, e.g. monthly.
+    pub fn period(self, period: impl VestingDuration) -> Self {
+        self.period_of(seconds(period))
+    }
+
+    fn cliff_at(self, cliff: i64) -> Self {
+        VestingSchedule { cliff: cliff.min(self.duration), ..self }
+    }
+
+    fn period_of(self, period: i64) -> Self {
+        VestingSchedule { period: period.clamp(1, self.duration), ..self }
+    }
+
+    pub fn cliff_timestamp(&self) -> i64 {
+        self.start + self.cliff
+    }
+
+    pub fn end_timestamp(&self) -> i64 {
+        self.start + self.duration
+    }
+
+    /// The amount unlocked by `timestamp`. Rounds down, leaving the remainder to the end.
+    pub fn vested_at(&self, timestamp: i64) -> u64 {
+        let elapsed = timestamp - self.start;
+        if elapsed < self.cliff {
+            return 0;
+        }
+        if elapsed >= self.duration {
+            return self.total;
+        }
+        let elapsed = elapsed - elapsed % self.period;
+        (u128::from(self.total) * elapsed as u128 / self.duration as u128) as u64
+    }
+
+    /// Each time the unlocked amount grows, with the amount it grows by, ending with the
+    /// remainder at the end. The amounts add up to `total`.
+    pub fn unlocks(&self) -> Vec<(i64, u64)> {
+        let mut unlocks = Vec::new();
+        let mut vested = 0;
+        let mut elapsed = self.cliff;
+        loop {
+            let timestamp = self.start + elapsed;
+            let now_vested = self.vested_at(timestamp);
+            if now_vested > vested {
+                unlocks.push((timestamp, now_vested - vested));
+                vested = now_vested;
+            }
+            if elapsed >= self.duration {
+                return unlocks;
+            }
+            elapsed = (elapsed - elapsed % self.period + self.period).min(self.duration);
+        }
+    }
+}
+
+/// The accounts of a token-vesting contract set by [`Seashell::set_token_vesting`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenVesting {
+    /// The seeds the program's instructions take: the given seed, then the bump.
+    pub seeds: [u8; 32],
+    pub vesting_account: Pubkey,
+    /// The token account of the vesting account holding the unclaimed tokens.
+    pub vault: Pubkey,
+}
+
+/// The vesting account of `seed` and its seeds, the bump appended.
+pub fn token_vesting_address(seed: [u8; 31]) -> (Pubkey, [u8; 32]) {
+    let (address, bump) = Pubkey::find_program_address(&[&seed], &TOKEN_VESTING_PROGRAM_ID);
+    let mut seeds = [0; 32];
+    seeds[..31].copy_from_slice(&seed);
+    seeds[31] = bump;
+    (address, seeds)
+}
+
+impl Seashell {
+    /// Warps forward by `duration`, in as many slots as it takes, as [`Seashell::warp_to_slot`]
+    /// does. The clock ends up at least `duration` later.
+    pub fn warp_forward(&self, duration: impl VestingDuration) {
+        let millis = duration.to_duration().as_millis() as u64;
+        let slots = millis.div_ceil(DEFAULT_MS_PER_SLOT);
+        self.warp_to_slot(self.accounts_db.sysvars.clock().slot + slots);
+    }
+
+    /// Warps forward until the clock reaches `timestamp`. Does nothing if it already has.
+    pub fn warp_to_timestamp(&self, timestamp: i64) {
+        let now = self.accounts_db.sysvars.clock().unix_timestamp;
+        if timestamp > now {
+            self.warp_forward(Duration::from_secs((timestamp - now) as u64));
+        }
+    }
+
+    /// Sets a Bonfida token-vesting contract releasing `schedule` to the token account
+    /// `destination`, with its vault holding the whole total. The vesting account is that of
+    /// `seed`. Each of [`VestingSchedule::unlocks`] is an entry, so give the schedule a period.
+    pub fn set_token_vesting(
+        &self,
+        seed: [u8; 31],
+        destination: Pubkey,
+        mint: Pubkey,
+        schedule: &VestingSchedule,
+    ) -> TokenVesting {
+        let (vesting_account, seeds) = token_vesting_address(seed);
+        let vault = associated_token_address(&vesting_account, &mint, &TOKEN_PROGRAM_ID);
+        let rent = self.accounts_db.sysvars.rent();
+
+        let unlocks = schedule.unlocks();
+        let mut data =
+            Vec::with_capacity(VESTING_HEADER_LEN + unlocks.len() * VESTING_SCHEDULE_LEN);
+        data.extend_from_slice(destination.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.push(1); // is_initialized
+        for (release_time, amount) in &unlocks {
+            data.extend_from_slice(&(*release_time as u64).to_le_bytes());
+            data.extend_from_slice(&amount.to_le_bytes());
+        }
+        let mut account = AccountSharedData::new(
+            rent.minimum_balance(data.len()),
+            data.len(),
+            &TOKEN_VESTING_PROGRAM_ID,
+        );
+        account.set_data_from_slice(&data);
+        self.set_account_from_account_shared_data(vesting_account, account);
+
+        self.set_token_account(vault, mint, vesting_account, schedule.total);
+
+        TokenVesting { seeds, vesting_account, vault }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vesting_schedule() {
+        assert_eq!(parse_duration("1y 6mo").unwrap(), Duration::from_secs(545 * DAY));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(90 * MINUTE));
+        assert!(parse_duration("3 days").is_err());
+        assert!(parse_duration("").is_err());
+
+        let schedule = VestingSchedule::linear(1_000, 1_200, "360d")
+            .cliff("90d")
+            .period("30d");
+        assert_eq!(schedule.vested_at(1_000 + 89 * DAY as i64), 0);
+        assert_eq!(schedule.vested_at(schedule.cliff_timestamp()), 300);
+        assert_eq!(schedule.vested_at(schedule.cliff_timestamp() + 29 * DAY as i64), 300);
+        assert_eq!(schedule.vested_at(schedule.end_timestamp()), 1_200);
+        let unlocks = schedule.unlocks();
+        assert_eq!(unlocks.len(), 10);
+        assert_eq!(unlocks[0], (schedule.cliff_timestamp(), 300));
+        assert_eq!(unlocks.iter().map(|(_, amount)| amount).sum::<u64>(), 1_200);
+
+        let cliff = VestingSchedule::cliff_only(0, 7, "1w");
+        assert_eq!(cliff.unlocks(), [(7 * DAY as i64, 7)]);
+
+        let seashell = Seashell::new();
+        let start = seashell.fixture_clock().unix_timestamp;
+        let schedule = VestingSchedule::linear(start, 100, "10d").period("1d");
+        let (destination, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let vesting = seashell.set_token_vesting([3; 31], destination, mint, &schedule);
+        let data = seashell.account(&vesting.vesting_account).data;
+        assert_eq!(data.len(), VESTING_HEADER_LEN + 10 * VESTING_SCHEDULE_LEN);
+        assert_eq!(&data[..32], destination.as_ref());
+        assert_eq!(seashell.account(&vesting.vault).data[64..72], 100u64.to_le_bytes());
+
+        seashell.warp_forward("2d 1s");
+        let now = seashell.fixture_clock().unix_timestamp;
+        assert!(now >= start + 2 * DAY as i64 + 1);
+        assert_eq!(schedule.vested_at(now), 20);
+        seashell.warp_to_timestamp(schedule.end_timestamp());
+        assert_eq!(schedule.vested_at(seashell.fixture_clock().unix_timestamp), 100);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use solana_account::{AccountSharedData, WritableAccount};
+use solana_clock::{Clock, Epoch};
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_vote_interface::state::{
+    BlockTimestamp, VoteInit, VoteStateV3, VoteStateVersions, MAX_EPOCH_CREDITS_HISTORY,
+};
+
+use crate::error::SeashellError;
+
+/// Builds vote accounts with a synthetic credit and commission history.
+///
+/// The builder describes a validator across many epochs; `build` materializes the vote state as
+/// it would look at the epoch of the given clock, so warping and rebuilding replays the history.
+#[derive(Debug, Clone)]
+pub struct VoteAccountBuilder {
+    node_pubkey: Pubkey,
+    authorized_voter: Pubkey,
+    authorized_withdrawer: Pubkey,
+    /// (epoch, commission) pairs sorted by epoch; the latest entry at or before the clock epoch
+    /// wins.
+    commission_changes: Vec<(Epoch, u8)>,
+    /// (epoch, credits earned in that epoch) pairs sorted by epoch.
+    earned_credits: Vec<(Epoch, u64)>,
+    lamports: Option<u64>,
+}
+
+impl VoteAccountBuilder {
+    /// Creates a builder where the node identity is also the authorized voter and withdrawer.
+    pub fn new(node_pubkey: Pubkey) -> Self {
+        VoteAccountBuilder {
+            node_pubkey,
+            authorized_voter: node_pubkey,
+            authorized_withdrawer: node_pubkey,
+            commission_changes: vec![(0, 0)],
+            earned_credits: Vec::new(),
+            lamports: None,
+        }
+    }
+
+    pub fn authorized_voter(mut self, authorized_voter: Pubkey) -> Self {
+        self.authorized_voter = authorized_voter;
+        self
+    }
+
+    pub fn authorized_withdrawer(mut self, authorized_withdrawer: Pubkey) -> Self {
+        self.authorized_withdrawer = authorized_withdrawer;
+        self
+    }
+
+    /// Sets the commission in effect from genesis onwards, discarding any recorded changes.
+    pub fn commission(mut self, commission: u8) -> Self {
+        self.commission_changes = vec![(0, commission)];
+        self
+    }
+
+    /// Records a commission change taking effect at `epoch`.
+    pub fn commission_change(mut self, epoch: Epoch, commission: u8) -> Self {
+        self.commission_changes.retain(|(e, _)| *e != epoch);
+        self.commission_changes.push((epoch, commission));
+        self.commission_changes.sort_by_key(|(e, _)| *e);
+        self
+    }
+
+    /// Records the credits earned in each epoch starting at `start_epoch`.
+    pub fn credits_per_epoch(mut self, start_epoch: Epoch, credits: &[u64]) -> Self {
+        for (epoch, earned) in (start_epoch..).zip(credits) {
+            self.earned_credits.retain(|(e, _)| *e != epoch);
+            self.earned_credits.push((epoch, *earned));
+        }
+        self.earned_credits.sort_by_key(|(e, _)| *e);
+        self
+    }
+
+    /// Overrides the account balance; defaults to the rent-exempt minimum.
+    pub fn lamports(mut self, lamports: u64) -> Self {
+        self.lamports = Some(lamports);
+        self
+    }
+
+    /// The commission in effect at `epoch`.
+    pub fn commission_at(&self, epoch: Epoch) -> u8 {
+        self.commission_changes
+            .iter()
+            .rev()
+            .find(|(e, _)| *e <= epoch)
+            .map(|(_, commission)| *commission)
+            .unwrap_or_default()
+    }
+
+    /// The `(epoch, credits, prev_credits)` history as the vote program would have recorded it by
+    /// `epoch`, capped at `MAX_EPOCH_CREDITS_HISTORY` entries. Epochs that earned nothing have no
+    /// entry: the vote program overwrites an entry that earned no credits with the next epoch's.
+    pub fn epoch_credits_at(&self, epoch: Epoch) -> Vec<(Epoch, u64, u64)> {
+        let mut total = 0u64;
+        let mut epoch_credits: Vec<(Epoch, u64, u64)> = self
+            .earned_credits
+            .iter()
+            .filter(|(e, earned)| *e <= epoch && *earned > 0)
+            .map(|(e, earned)| {
+                let prev_credits = total;
+                total = total.saturating_add(*earned);
+                (*e, total, prev_credits)
+            })
+            .collect();
+        if epoch_credits.len() > MAX_EPOCH_CREDITS_HISTORY {
+            epoch_credits.drain(..epoch_credits.len() - MAX_EPOCH_CREDITS_HISTORY);
+        }
+        epoch_credits
+    }
+
+    /// Materializes the vote account as of `clock.epoch`, with its last vote timestamp at `clock`.
+    pub fn build(&self, clock: &Clock, rent: &Rent) -> AccountSharedData {
+        let mut vote_state = VoteStateV3::new(
+            &VoteInit {
+                node_pubkey: self.node_pubkey,
+                authorized_voter: self.authorized_voter,
+                authorized_withdrawer: self.authorized_withdrawer,
+                commission: self.commission_at(clock.epoch),
+            },
+            clock,
+        );
+        vote_state.epoch_credits = self.epoch_credits_at(clock.epoch);
+        vote_state.last_timestamp =
+            BlockTimestamp { slot: clock.slot, timestamp: clock.unix_timestamp };
+
+        let space = VoteStateV3::size_of();
+        let lamports = self.lamports.unwrap_or_else(|| rent.minimum_balance(space));
+        let mut account = AccountSharedData::new(lamports, space, &solana_sdk_ids::vote::id());
+        VoteStateV3::serialize(
+            &VoteStateVersions::V3(Box::new(vote_state)),
+            account.data_as_mut_slice(),
+        )
+        .expect("Failed to serialize vote state");
+        account
+    }
+}
+
+/// The stake table behind the `sol_get_epoch_stake` syscall.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochStakes {
+    /// Stake delegated to each vote account for the current epoch.
+    #[serde_as(as = "HashMap<serde_with::DisplayFromStr, _>")]
+    pub vote_accounts: HashMap<Pubkey, u64>,
+}
+
+impl EpochStakes {
+    /// Fetches the activated stake of every current and delinquent vote account.
+    pub fn from_rpc(rpc_client: &RpcClient) -> Result<Self, SeashellError> {
+        let vote_accounts = rpc_client
+            .get_vote_accounts()
+            .map_err(|e| SeashellError::Custom(format!("Failed to fetch vote accounts: {e}")))?;
+        let vote_accounts = vote_accounts
+            .current
+            .iter()
+            .chain(&vote_accounts.delinquent)
+            .filter_map(|info| Some((info.vote_pubkey.parse().ok()?, info.activated_stake)))
+            .collect();
+        Ok(EpochStakes { vote_accounts })
+    }
+
+    pub fn set(&mut self, vote_account: Pubkey, stake: u64) {
+        self.vote_accounts.insert(vote_account, stake);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.vote_accounts
+            .values()
+            .fold(0u64, |total, stake| total.saturating_add(*stake))
+    }
+
+    pub fn get(&self, vote_account: &Pubkey) -> u64 {
+        self.vote_accounts
+            .get(vote_account)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::ReadableAccount;
+
+    use super::*;
+
+    #[test]
+    fn test_vote_account_history() {
+        let node = Pubkey::new_unique();
+        let builder = VoteAccountBuilder::new(node)
+            .commission(5)
+            .commission_change(3, 10)
+            .credits_per_epoch(0, &[100, 200, 0, 300]);
+
+        let mut clock = Clock { epoch: 2, ..Clock::default() };
+        let account = builder.build(&clock, &Rent::default());
+        let vote_state = VoteStateV3::deserialize(account.data()).unwrap();
+        assert_eq!(vote_state.node_pubkey, node);
+        assert_eq!(vote_state.commission, 5);
+        // Epoch 2 earned nothing, so it has no entry.
+        assert_eq!(vote_state.epoch_credits, vec![(0, 100, 0), (1, 300, 100)]);
+
+        clock.epoch = 3;
+        let account = builder.build(&clock, &Rent::default());
+        let vote_state = VoteStateV3::deserialize(account.data()).unwrap();
+        assert_eq!(vote_state.commission, 10);
+        assert_eq!(vote_state.epoch_credits, vec![(0, 100, 0), (1, 300, 100), (3, 600, 300)]);
+    }
+}
@@ -0,0 +1,168 @@
+//! A mock wallet for driving client code written against wallet signing traits through Seashell.
+//!
+//! ```ignore
+//! let wallet = seashell.new_wallet(LAMPORTS_PER_SOL);
+//! let transaction = client.build_deposit(wallet.pubkey(), seashell.latest_blockhash());
+//! let result = wallet.sign_and_process_transaction(&seashell, transaction)?;
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::{Signer, SignerError};
+use solana_transaction::versioned::VersionedTransaction;
+
+use crate::error::SeashellError;
+use crate::seashell::{InstructionChainResult, Seashell};
+
+/// Keypairs created through a `Seashell`, so tests and wallets can sign as the accounts they set
+/// up. Forks share the keypairs registered before the fork.
+#[derive(Clone, Default)]
+pub struct KeypairRegistry {
+    keypairs: RefCell<HashMap<Pubkey, Rc<Keypair>>>,
+}
+
+impl KeypairRegistry {
+    pub fn new_keypair(&self) -> Rc<Keypair> {
+        self.insert(Keypair::new())
+    }
+
+    pub fn insert(&self, keypair: Keypair) -> Rc<Keypair> {
+        let keypair = Rc::new(keypair);
+        self.keypairs
+            .borrow_mut()
+            .insert(keypair.pubkey(), keypair.clone());
+        keypair
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<Rc<Keypair>> {
+        self.keypairs.borrow().get(pubkey).cloned()
+    }
+
+    pub fn pubkeys(&self) -> Vec<Pubkey> {
+        self.keypairs.borrow().keys().copied().collect()
+    }
+}
+
+/// A wallet backed by a registered keypair. It signs whatever it is asked to without prompting,
+/// and only ever fills in its own signature.
+#[derive(Clone)]
+pub struct MockWallet {
+    keypair: Rc<Keypair>,
+}
+
+impl MockWallet {
+    pub fn new(keypair: Rc<Keypair>) -> Self {
+        MockWallet { keypair }
+    }
+
+    /// Adds this wallet's signature to `transaction`, leaving other signers' signatures as they
+    /// are. Fails if the wallet isn't one of the transaction's required signers.
+    pub fn sign_transaction(
+        &self,
+        mut transaction: VersionedTransaction,
+    ) -> Result<VersionedTransaction, SignerError> {
+        let pubkey = self.keypair.pubkey();
+        let required = usize::from(transaction.message.header().num_required_signatures);
+        let index = transaction.message.static_account_keys()[..required]
+            .iter()
+            .position(|key| *key == pubkey)
+            .ok_or(SignerError::KeypairPubkeyMismatch)?;
+        transaction
+            .signatures
+            .resize(required, Signature::default());
+        transaction.signatures[index] = self.keypair.sign_message(&transaction.message.serialize());
+        Ok(transaction)
+    }
+
+    pub fn sign_all_transactions(
+        &self,
+        transactions: Vec<VersionedTransaction>,
+    ) -> Result<Vec<VersionedTransaction>, SignerError> {
+        transactions
+            .into_iter()
+            .map(|transaction| self.sign_transaction(transaction))
+            .collect()
+    }
+
+    /// Signs `transaction` and runs it with `Seashell::process_transaction_signed`, the mock
+    /// equivalent of a wallet's sign-and-send.
+    pub fn sign_and_process_transaction(
+        &self,
+        seashell: &Seashell,
+        transaction: VersionedTransaction,
+    ) -> Result<InstructionChainResult, SeashellError> {
+        let transaction = self
+            .sign_transaction(transaction)
+            .map_err(|e| SeashellError::Custom(format!("Wallet failed to sign: {e}")))?;
+        seashell.process_transaction_signed(&transaction)
+    }
+}
+
+impl Signer for MockWallet {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.keypair.pubkey())
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        Ok(self.keypair.sign_message(message))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::Instruction;
+    use solana_message::{Message, VersionedMessage};
+
+    use super::*;
+    use crate::seashell::Config;
+    use crate::test_utils::system_transfer;
+
+    #[test]
+    fn test_mock_wallet_end_to_end() {
+        let seashell = Seashell::new_with_config(Config { memoize: true, ..Config::default() });
+        let wallet = seashell.new_wallet(1000);
+        let cosigner = seashell.new_wallet(1000);
+        let to = Pubkey::new_unique();
+        seashell.set_account(to, Default::default());
+
+        let transfer = |from| system_transfer(from, to, 400);
+        let transaction = |ixns: &[Instruction]| VersionedTransaction {
+            signatures: vec![],
+            message: VersionedMessage::Legacy(Message::new_with_blockhash(
+                ixns,
+                Some(&wallet.pubkey()),
+                &seashell.latest_blockhash(),
+            )),
+        };
+
+        let result = wallet
+            .sign_and_process_transaction(&seashell, transaction(&[transfer(wallet.pubkey())]))
+            .unwrap();
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(seashell.account(&to).lamports, 400);
+
+        // The wallet can't sign for the cosigner's transfer.
+        let both = transaction(&[transfer(wallet.pubkey()), transfer(cosigner.pubkey())]);
+        assert!(wallet
+            .sign_and_process_transaction(&seashell, both.clone())
+            .is_err());
+        let signed = cosigner
+            .sign_transaction(wallet.sign_transaction(both).unwrap())
+            .unwrap();
+        let result = seashell.process_transaction_signed(&signed).unwrap();
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(seashell.account(&to).lamports, 1200);
+
+        assert!(seashell.wallet(&cosigner.pubkey()).is_some());
+        assert!(seashell.wallet(&to).is_none());
+    }
+}
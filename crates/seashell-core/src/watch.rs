@@ -0,0 +1,198 @@
+//! Lamport thresholds checked after every committed write, so long sessions and backtests collect
+//! violations as structured alerts instead of checking balances after every step.
+//!
+//! ```ignore
+//! let floor = 10 * LAMPORTS_PER_SOL;
+//! seashell.watchlist.add(Watch::lamports_below("insurance fund", insurance_fund, floor));
+//! seashell.watchlist.add(Watch::rent_exemption("vault PDAs", WatchTarget::Owner(program_id)));
+//! // ... run the session ...
+//! assert!(seashell.watchlist.alerts().is_empty(), "{:#?}", seashell.watchlist.alerts());
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+
+use solana_account::{AccountSharedData, ReadableAccount};
+use solana_pubkey::Pubkey;
+use solana_rent::Rent;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchTarget {
+    Account(Pubkey),
+    /// Every account owned by the program, e.g. all of its PDAs.
+    Owner(Pubkey),
+}
+
+impl WatchTarget {
+    fn matches(&self, pubkey: &Pubkey, account: &AccountSharedData) -> bool {
+        match self {
+            WatchTarget::Account(watched) => watched == pubkey,
+            WatchTarget::Owner(owner) => owner == account.owner(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchCondition {
+    LamportsBelow(u64),
+    /// Below the rent-exempt minimum for the account's data length. Closed accounts don't count.
+    BelowRentExemption,
+}
+
+/// A named threshold on the lamports of one or more accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub name: String,
+    pub target: WatchTarget,
+    pub condition: WatchCondition,
+}
+
+impl Watch {
+    pub fn lamports_below(name: impl Into<String>, pubkey: Pubkey, lamports: u64) -> Self {
+        Watch {
+            name: name.into(),
+            target: WatchTarget::Account(pubkey),
+            condition: WatchCondition::LamportsBelow(lamports),
+        }
+    }
+
+    pub fn rent_exemption(name: impl Into<String>, target: WatchTarget) -> Self {
+        Watch { name: name.into(), target, condition: WatchCondition::BelowRentExemption }
+    }
+
+    /// The threshold `account` is below, if it violates this watch.
+    fn violated_threshold(&self, account: &AccountSharedData, rent: &Rent) -> Option<u64> {
+        let threshold = match self.condition {
+            WatchCondition::LamportsBelow(lamports) => lamports,
+            WatchCondition::BelowRentExemption => {
+                if account.lamports() == 0 && account.data().is_empty() {
+                    return None;
+                }
+                rent.minimum_balance(account.data().len())
+            }
+        };
+        (account.lamports() < threshold).then_some(threshold)
+    }
+}
+
+/// An account crossing below a watch's threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub watch: String,
+    pub pubkey: Pubkey,
+    pub lamports: u64,
+    pub threshold: u64,
+    /// The clock slot when the write was committed.
+    pub slot: u64,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} has {} lamports, below {} (slot {})",
+            self.watch, self.pubkey, self.lamports, self.threshold, self.slot
+        )
+    }
+}
+
+/// The watches of a `Seashell` and the alerts they raised.
+///
+/// An account raises an alert when a write takes it below a threshold, not on every write that
+/// leaves it there; it can alert again once it has recovered.
+#[derive(Debug, Clone, Default)]
+pub struct Watchlist {
+    watches: Vec<Watch>,
+    /// `(watch index, account)` pairs currently below their threshold.
+    violating: RefCell<HashSet<(usize, Pubkey)>>,
+    alerts: RefCell<Vec<Alert>>,
+}
+
+impl Watchlist {
+    pub fn add(&mut self, watch: Watch) -> &mut Self {
+        self.watches.push(watch);
+        self
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn alerts(&self) -> Vec<Alert> {
+        self.alerts.borrow().clone()
+    }
+
+    /// Returns the alerts raised so far and clears them.
+    pub fn take_alerts(&self) -> Vec<Alert> {
+        std::mem::take(&mut self.alerts.borrow_mut())
+    }
+
+    pub(crate) fn check(&self, accounts: &[(Pubkey, AccountSharedData)], rent: &Rent, slot: u64) {
+        if self.watches.is_empty() {
+            return;
+        }
+        let mut violating = self.violating.borrow_mut();
+        let mut alerts = self.alerts.borrow_mut();
+        for (index, watch) in self.watches.iter().enumerate() {
+            for (pubkey, account) in accounts {
+                if !watch.target.matches(pubkey, account) {
+                    violating.remove(&(index, *pubkey));
+                    continue;
+                }
+                let Some(threshold) = watch.violated_threshold(account, rent) else {
+                    violating.remove(&(index, *pubkey));
+                    continue;
+                };
+                if violating.insert((index, *pubkey)) {
+                    let alert = Alert {
+                        watch: watch.name.clone(),
+                        pubkey: *pubkey,
+                        lamports: account.lamports(),
+                        threshold,
+                        slot,
+                    };
+                    log::warn!("{alert}");
+                    alerts.push(alert);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account::WritableAccount;
+
+    use super::*;
+
+    #[test]
+    fn test_watchlist_alerts_on_crossing() {
+        let rent = Rent::default();
+        let fund = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let pda = Pubkey::new_unique();
+        let mut watchlist = Watchlist::default();
+        watchlist
+            .add(Watch::lamports_below("insurance fund", fund, 1000))
+            .add(Watch::rent_exemption("pdas", WatchTarget::Owner(program_id)));
+
+        let fund_with = |lamports| (fund, AccountSharedData::new(lamports, 0, &Pubkey::default()));
+        watchlist.check(&[fund_with(500)], &rent, 1);
+        watchlist.check(&[fund_with(400)], &rent, 2);
+        watchlist.check(&[fund_with(2000)], &rent, 3);
+        watchlist.check(&[fund_with(0)], &rent, 4);
+
+        let mut underfunded = AccountSharedData::new(1, 100, &program_id);
+        watchlist.check(&[(pda, underfunded.clone())], &rent, 5);
+        underfunded.set_lamports(0);
+        underfunded.set_data_from_slice(&[]);
+        watchlist.check(&[(pda, underfunded)], &rent, 6);
+
+        let alerts = watchlist.take_alerts();
+        let slots: Vec<u64> = alerts.iter().map(|alert| alert.slot).collect();
+        assert_eq!(slots, vec![1, 4, 5]);
+        assert_eq!(alerts[2].threshold, rent.minimum_balance(100));
+        assert!(watchlist.alerts().is_empty());
+    }
+}
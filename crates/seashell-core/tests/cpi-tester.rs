@@ -0,0 +1,34 @@
+use seashell::cpi_tester::{self, CPI_TESTER_PROGRAM_ID};
+use seashell::Seashell;
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+fn transfer(from: Pubkey, to: Pubkey, lamports: u64) -> Instruction {
+    let mut data = Vec::with_capacity(12);
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction {
+        program_id: solana_sdk_ids::system_program::id(),
+        accounts: vec![AccountMeta::new(from, true), AccountMeta::new(to, false)],
+        data,
+    }
+}
+
+#[test]
+fn test_cpi_tester_signed_transfer() {
+    let mut seashell = Seashell::new();
+    seashell.load_cpi_tester();
+
+    let (vault, bump) = Pubkey::find_program_address(&[b"vault"], &CPI_TESTER_PROGRAM_ID);
+    let to = Pubkey::new_unique();
+    seashell.set_account(vault, Account { lamports: 1000, ..Account::default() });
+    seashell.set_account(to, Account::default());
+
+    let ixn = cpi_tester::invoke(&transfer(vault, to, 600), &[&[b"vault", &[bump]]]);
+    let result = seashell.process_instruction(ixn);
+
+    assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+    assert_eq!(result.inner_instructions().count(), 1);
+    assert_eq!(result.account(&to).lamports, 600);
+}
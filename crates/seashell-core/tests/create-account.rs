@@ -9,6 +9,7 @@ fn test_create_account() {
         memoize: true,
         allow_uninitialized_accounts_local: true,
         allow_uninitialized_accounts_fetched: true,
+        ..Default::default()
     });
     let account_loader_out_dir = try_find_workspace_root()
         .unwrap()
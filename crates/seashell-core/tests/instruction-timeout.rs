@@ -0,0 +1,55 @@
+#![cfg(target_os = "linux")]
+
+use std::time::Duration;
+
+use seashell::{Config, InstructionProcessingError, Seashell};
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+/// Threads of this process. The test is alone in its binary, so only the harness and the test
+/// itself are running.
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+#[test]
+fn test_instruction_timeout() {
+    let mut seashell = Seashell::new_with_config(Config {
+        instruction_timeout: Some(Duration::ZERO),
+        ..Config::default()
+    });
+    let (from, to, authority, mint) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    seashell.set_mint(mint, 0, None, 1000);
+    seashell.set_token_account(from, mint, authority, 1000);
+    seashell.set_token_account(to, mint, Pubkey::new_unique(), 0);
+    seashell.airdrop(authority, 1000);
+
+    let mut data = vec![3];
+    data.extend_from_slice(&500u64.to_le_bytes());
+    let ixn = Instruction {
+        program_id: seashell::spl::TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(from, false),
+            AccountMeta::new(to, false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+        data,
+    };
+
+    // The token program logs through a syscall, where the VM is stopped.
+    let threads = thread_count();
+    let result = seashell.process_instruction(ixn.clone());
+    assert!(
+        matches!(result.error, Some(InstructionProcessingError::Timeout(Duration::ZERO))),
+        "Expected a timeout, got: {:?}",
+        result.error
+    );
+    assert_eq!(thread_count(), threads);
+    assert_eq!(seashell.token_balance(&from), 1000);
+
+    seashell.config.instruction_timeout = None;
+    let result = seashell.process_instruction(ixn);
+    assert!(result.error.is_none(), "Expected no error, got: {:?}", result.error);
+    seashell.assert_token_balance(&to, 500);
+}
@@ -0,0 +1,80 @@
+//! Invokes an arbitrary program with caller-provided metas, data and signer seeds.
+//!
+//! Accounts: `[target_program, ...cpi_accounts]`.
+//!
+//! Instruction data:
+//! - `u8` number of CPI accounts, then one flags byte per account (`0x1` signer, `0x2` writable)
+//! - `u8` number of signers, then per signer a `u8` number of seeds and per seed a `u8` length
+//!   followed by the seed bytes (including the bump)
+//! - the rest is passed through as the CPI instruction data
+
+#[cfg(feature = "bpf-entrypoint")]
+mod entrypoint {
+    use pinocchio::account_info::AccountInfo;
+    use pinocchio::cpi::slice_invoke_signed;
+    use pinocchio::instruction::{AccountMeta, Instruction, Seed, Signer};
+    use pinocchio::program_error::ProgramError;
+    use pinocchio::pubkey::Pubkey;
+    use pinocchio::{entrypoint, ProgramResult};
+
+    entrypoint!(process_instruction);
+
+    const SIGNER: u8 = 0x1;
+    const WRITABLE: u8 = 0x2;
+
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], ProgramError> {
+        if data.len() < len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (head, tail) = data.split_at(len);
+        *data = tail;
+        Ok(head)
+    }
+
+    fn take_u8(data: &mut &[u8]) -> Result<u8, ProgramError> {
+        Ok(take(data, 1)?[0])
+    }
+
+    pub fn process_instruction(
+        _: &Pubkey,
+        accounts: &[AccountInfo],
+        mut data: &[u8],
+    ) -> ProgramResult {
+        let [target, cpi_accounts @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        let num_accounts = take_u8(&mut data)? as usize;
+        let flags = take(&mut data, num_accounts)?;
+        let cpi_accounts = cpi_accounts
+            .get(..num_accounts)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let metas: Vec<AccountMeta> = cpi_accounts
+            .iter()
+            .zip(flags)
+            .map(|(account, flags)| {
+                AccountMeta::new(account.key(), flags & WRITABLE != 0, flags & SIGNER != 0)
+            })
+            .collect();
+
+        let num_signers = take_u8(&mut data)?;
+        let mut signer_seeds: Vec<Vec<Seed>> = Vec::with_capacity(num_signers as usize);
+        for _ in 0..num_signers {
+            let num_seeds = take_u8(&mut data)?;
+            let mut seeds = Vec::with_capacity(num_seeds as usize);
+            for _ in 0..num_seeds {
+                let len = take_u8(&mut data)? as usize;
+                seeds.push(Seed::from(take(&mut data, len)?));
+            }
+            signer_seeds.push(seeds);
+        }
+        let signers: Vec<Signer> = signer_seeds
+            .iter()
+            .map(|seeds| Signer::from(seeds.as_slice()))
+            .collect();
+
+        let instruction = Instruction { program_id: target.key(), accounts: &metas, data };
+        let account_infos: Vec<&AccountInfo> = cpi_accounts.iter().collect();
+        slice_invoke_signed(&instruction, &account_infos, &signers)
+    }
+}
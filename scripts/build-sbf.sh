@@ -35,4 +35,9 @@ fi
 
 # Run the cargo build command
 echo "Building SBF program: $PROGRAM"
-cargo build-sbf --tools-version v1.50 --manifest-path "$MANIFEST_PATH" --features bpf-entrypoint
\ No newline at end of file
+cargo build-sbf --tools-version v1.50 --manifest-path "$MANIFEST_PATH" --features bpf-entrypoint || exit 1
+
+# The CPI tester is embedded in seashell next to the SPL programs
+if [ "$PROGRAM" = "cpi-tester" ]; then
+    cp "./programs/${PROGRAM}/target/deploy/cpi_tester.so" ./crates/seashell-core/src/spl/elfs/
+fi
\ No newline at end of file